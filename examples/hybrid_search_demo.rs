@@ -2,11 +2,11 @@
 //!
 //! Run with: cargo run --example hybrid_search_demo -- /path/to/folder
 
-use file_search::{
+use khoj::{
     config::PrivacyConfig,
     embedding::EmbeddingModel,
     extractors::text,
-    indexer::{metadata, walker},
+    indexer::{chunker::Chunker, metadata, walker},
     search::HybridSearch,
     storage::{Database, TantivyIndex, VectorStore},
 };
@@ -104,17 +104,16 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     &content.text,
                 )?;
 
-                // Generate embedding for semantic search
-                let text_for_embedding = if content.text.len() > 5000 {
-                    // Truncate very long texts to first 5000 chars for embedding
-                    &content.text[..5000]
-                } else {
-                    &content.text
-                };
+                // Generate one embedding per chunk for semantic search
+                let chunks: Vec<&str> = Chunker::default()
+                    .chunk(&content.text)
+                    .iter()
+                    .map(|chunk| chunk.text(&content.text))
+                    .collect();
 
-                match embedding_model.embed(text_for_embedding) {
-                    Ok(embedding) => {
-                        vector_store.upsert(file_id, &embedding)?;
+                match embedding_model.embed_batch(&chunks) {
+                    Ok(embeddings) => {
+                        vector_store.upsert_chunks(file_id, &embeddings)?;
                         println!("   ✓ {} ({}, embedded)", metadata.filename, disc_file.file_type.as_str());
                         indexed_count += 1;
                     }
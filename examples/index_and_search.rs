@@ -2,7 +2,7 @@
 //!
 //! Run with: cargo run --example index_and_search
 
-use file_search::{
+use khoj::{
     config::PrivacyConfig,
     extractors::text,
     indexer::{metadata, walker},
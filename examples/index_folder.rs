@@ -2,12 +2,11 @@
 //!
 //! Run with: cargo run --example index_folder -- /path/to/folder
 
-use file_search::{
+use khoj::{
     config::PrivacyConfig,
-    extractors::text,
-    indexer::{metadata, walker},
+    indexer::{IndexEvent, Indexer},
     search::HybridSearch,
-    storage::{Database, TantivyIndex, VectorStore},
+    storage::{TantivyIndex, VectorStore},
 };
 use std::env;
 use std::path::PathBuf;
@@ -37,88 +36,44 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let index_base = env::temp_dir().join("file-search-demo");
     std::fs::create_dir_all(&index_base)?;
 
-    let db_path = index_base.join("db.sqlite");
-    let tantivy_path = index_base.join("tantivy");
-
     println!("💾 Index location: {}", index_base.display());
     println!();
 
-    // Initialize storage components
-    println!("🔧 Initializing storage...");
-    let db = Database::new(&db_path).await?;
-    let mut tantivy_index = TantivyIndex::new(&tantivy_path)?;
-    let vector_store = VectorStore::new(384)?; // 384-dim for all-MiniLM-L6-v2
-
-    // Configure file walker with privacy settings
-    let privacy_config = PrivacyConfig::default();
-    let walker = walker::FileWalker::new(privacy_config);
-
-    // Discover files
-    println!("🔎 Discovering files...");
-    let discovered = walker.walk(&folder_path)?;
-    println!("   Found {} files to index", discovered.len());
-    println!();
-
-    // Index each file
+    // Open the index and run the indexing pipeline
     println!("📚 Indexing files...");
-    let mut indexed_count = 0;
-    let mut skipped_count = 0;
-
-    for disc_file in discovered {
-        // Extract metadata
-        let metadata = match metadata::extract_metadata(&disc_file.path, disc_file.file_type) {
-            Ok(m) => m,
-            Err(e) => {
-                println!("   ⚠️  Skipped {}: {}", disc_file.path.display(), e);
-                skipped_count += 1;
-                continue;
+    let mut indexer = Indexer::new(&index_base, PrivacyConfig::default()).await?;
+    let summary = indexer
+        .index_directory(&folder_path, |event| match event {
+            IndexEvent::Discovered { files, .. } => {
+                println!("   Found {} files to index", files);
             }
-        };
-
-        // Store in database
-        let file_id = db.upsert_file(&metadata).await?;
-
-        // Extract and index text content
-        match text::extract_text(&disc_file.path, disc_file.file_type) {
-            Ok(content) => {
-                // Store content in database
-                db.upsert_content(file_id, &content).await?;
-
-                // Index in Tantivy for keyword search
-                tantivy_index.upsert_document(
-                    file_id,
-                    &disc_file.path.to_string_lossy(),
-                    &metadata.filename,
-                    &content.text,
-                )?;
-
-                println!(
-                    "   ✓ Indexed: {} ({} bytes, {} words)",
-                    metadata.filename,
-                    metadata.size,
-                    content.word_count
-                );
-                indexed_count += 1;
+            IndexEvent::Added { path, file_type } | IndexEvent::Updated { path, file_type } => {
+                println!("   ✓ Indexed: {} ({})", path.display(), file_type.as_str());
             }
-            Err(_) => {
-                // For files we can't extract text from (images, etc.), just store metadata
-                println!("   ○ Metadata only: {} ({})", metadata.filename, disc_file.file_type.as_str());
-                skipped_count += 1;
+            IndexEvent::Unchanged { .. } => {}
+            IndexEvent::Removed { path } => {
+                println!("   - Removed: {}", path.display());
             }
-        }
-    }
-
-    // Commit the index
-    println!();
-    println!("💾 Committing index...");
-    tantivy_index.commit()?;
+            IndexEvent::Skipped { path, reason } => {
+                println!("   ○ Skipped: {} ({})", path.display(), reason);
+            }
+        })
+        .await?;
 
     println!();
     println!("✅ Indexing complete!");
-    println!("   {} files indexed", indexed_count);
-    println!("   {} files skipped", skipped_count);
+    println!("   {} files indexed", summary.indexed());
+    println!("   {} files unchanged", summary.unchanged);
+    println!("   {} files removed", summary.removed);
+    println!("   {} files skipped", summary.skipped);
     println!();
 
+    let paths = indexer.paths().clone();
+    drop(indexer);
+
+    let tantivy_index = TantivyIndex::open_read_only(&paths.tantivy)?;
+    let vector_store = VectorStore::new(384)?; // 384-dim for all-MiniLM-L6-v2
+
     // Create search engine
     let search_engine = HybridSearch::new(tantivy_index, vector_store);
 
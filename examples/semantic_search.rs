@@ -2,11 +2,11 @@
 //!
 //! Run with: cargo run --example semantic_search -- ~/Documents
 
-use file_search::{
+use khoj::{
     config::PrivacyConfig,
     embedding::EmbeddingModel,
     extractors::text,
-    indexer::{metadata, walker},
+    indexer::{chunker::Chunker, metadata, walker},
     search::HybridSearch,
     storage::{Database, TantivyIndex, VectorStore},
 };
@@ -39,7 +39,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let mut embedding_model = EmbeddingModel::new("models/model.onnx", "models/tokenizer.json")?;
 
     let db = Database::new(&index_base.join("db.sqlite")).await?;
-    let mut tantivy_index = TantivyIndex::new(&index_base.join("tantivy"))?;
+    let mut tantivy_index = TantivyIndex::new(index_base.join("tantivy"))?;
     let vector_store = VectorStore::new(384)?;
 
     // Index files
@@ -61,14 +61,14 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                     &content.text,
                 )?;
 
-                let text_chunk = if content.text.len() > 5000 {
-                    &content.text[..5000]
-                } else {
-                    &content.text
-                };
+                let chunks: Vec<&str> = Chunker::default()
+                    .chunk(&content.text)
+                    .iter()
+                    .map(|chunk| chunk.text(&content.text))
+                    .collect();
 
-                if let Ok(embedding) = embedding_model.embed(text_chunk) {
-                    vector_store.upsert(file_id, &embedding)?;
+                if let Ok(embeddings) = embedding_model.embed_batch(&chunks) {
+                    vector_store.upsert_chunks(file_id, &embeddings)?;
                     count += 1;
                     if count % 10 == 0 {
                         print!(".");
@@ -2,7 +2,7 @@
 //!
 //! Run with: cargo run --example simple_search
 
-use file_search::{
+use khoj::{
     indexer::{metadata, walker},
     storage::{Database, TantivyIndex, VectorStore},
     search::HybridSearch,
@@ -9,6 +9,8 @@ pub struct Config {
     pub storage: StorageConfig,
     pub search: SearchConfig,
     pub privacy: PrivacyConfig,
+    #[serde(default)]
+    pub indexing: IndexingConfig,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -29,9 +31,12 @@ pub struct SearchConfig {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PrivacyConfig {
-    /// Patterns to exclude from indexing
+    /// Patterns to exclude from indexing, in gitignore syntax relative to
+    /// each indexed folder
     pub exclude_patterns: Vec<String>,
-    /// Respect these ignore files
+    /// Ignore files honored while walking, in gitignore syntax; later
+    /// names take precedence. `.gitignore` only counts inside git
+    /// repositories, the others apply everywhere.
     pub respect_ignore_files: Vec<String>,
     /// Maximum file size to index (in bytes)
     pub max_file_size: u64,
@@ -50,12 +55,98 @@ impl Default for PrivacyConfig {
                 "**/*.key".to_string(),
                 "**/*.pem".to_string(),
             ],
-            respect_ignore_files: vec![".gitignore".to_string(), ".searchignore".to_string()],
+            respect_ignore_files: vec![
+                ".gitignore".to_string(),
+                ".ignore".to_string(),
+                ".searchignore".to_string(),
+            ],
             max_file_size: 100 * 1024 * 1024, // 100MB
         }
     }
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct IndexingConfig {
+    /// Number of parallel hashing and extraction workers
+    pub workers: usize,
+    /// Capacity of each queue between pipeline stages
+    pub queue_capacity: usize,
+    /// Number of documents, and of chunks, embedded per model call
+    pub embedding_batch_size: usize,
+    /// Maximum number of words in an embedded chunk of text
+    pub chunk_words: usize,
+    /// Number of words shared by consecutive chunks
+    pub chunk_overlap_words: usize,
+    /// Maximum number of chunks embedded per file
+    pub max_chunks_per_file: usize,
+    /// Hash every file, even when its size and modification time match
+    /// the index
+    pub paranoid: bool,
+    /// Commit all stores after this many written files
+    pub checkpoint_files: usize,
+    /// Commit all stores after this many seconds of writing
+    pub checkpoint_secs: u64,
+    /// Index the text output of Jupyter notebook cells along with their code
+    pub notebook_outputs: bool,
+    /// Limits for indexing the members of archives
+    pub archives: ArchiveConfig,
+}
+
+impl Default for IndexingConfig {
+    fn default() -> Self {
+        Self {
+            workers: std::thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(4),
+            queue_capacity: 64,
+            embedding_batch_size: 16,
+            chunk_words: 200,
+            chunk_overlap_words: 40,
+            max_chunks_per_file: 1000,
+            paranoid: false,
+            checkpoint_files: 1000,
+            checkpoint_secs: 60,
+            notebook_outputs: true,
+            archives: ArchiveConfig::default(),
+        }
+    }
+}
+
+/// Limits for indexing the members of zip, tar and 7z archives
+///
+/// Archives are untrusted input: a few kilobytes can expand to terabytes.
+/// Members larger than `max_member_size` are skipped; an archive that
+/// expands beyond `max_total_size` or `max_ratio` times its own size is not
+/// expanded at all.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ArchiveConfig {
+    /// Maximum nesting depth of archives inside archives; 0 disables
+    /// indexing the members of archives
+    pub max_depth: usize,
+    /// Maximum uncompressed size of a member (in bytes)
+    pub max_member_size: u64,
+    /// Maximum uncompressed size of all members of an archive (in bytes)
+    pub max_total_size: u64,
+    /// Maximum number of members read from an archive
+    pub max_members: usize,
+    /// Maximum ratio of uncompressed to compressed size
+    pub max_ratio: u64,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        Self {
+            max_depth: 3,
+            max_member_size: 64 * 1024 * 1024, // 64MB
+            max_total_size: 1024 * 1024 * 1024, // 1GB
+            max_members: 10_000,
+            max_ratio: 100,
+        }
+    }
+}
+
 impl Default for Config {
     fn default() -> Self {
         Self {
@@ -70,6 +161,7 @@ impl Default for Config {
                 fuzzy_distance: 2,
             },
             privacy: PrivacyConfig::default(),
+            indexing: IndexingConfig::default(),
         }
     }
 }
@@ -85,5 +177,7 @@ mod tests {
         assert_eq!(config.search.fuzzy_distance, 2);
         assert!(!config.storage.encrypt);
         assert!(!config.privacy.exclude_patterns.is_empty());
+        assert!(config.indexing.workers > 0);
+        assert!(config.indexing.queue_capacity > 0);
     }
 }
\ No newline at end of file
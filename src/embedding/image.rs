@@ -38,7 +38,7 @@ impl ImageEmbedding {
     pub fn embed_image(&mut self, image_path: &Path) -> Result<Vec<f32>> {
         // Load and preprocess image
         let img = image::open(image_path)
-            .map_err(|e| crate::Error::Io(std::io::Error::new(std::io::ErrorKind::Other, e)))?;
+            .map_err(|e| crate::Error::Io(std::io::Error::other(e)))?;
 
         self.embed_dynamic_image(&img)
     }
@@ -61,7 +61,7 @@ impl ImageEmbedding {
         let (_emb_shape, emb_data) = outputs[0].try_extract_tensor::<f32>()?;
 
         // Convert to Vec (typically CLIP image encoder outputs 512-dim)
-        let embedding_vec: Vec<f32> = emb_data.iter().copied().collect();
+        let embedding_vec: Vec<f32> = emb_data.to_vec();
 
         // Drop outputs to release mutable borrow before calling normalize
         drop(outputs);
@@ -88,6 +88,7 @@ impl ImageEmbedding {
 
         // ImageNet normalization parameters (used by CLIP)
         let mean = [0.48145466, 0.4578275, 0.40821073];
+        #[allow(clippy::excessive_precision)]
         let std = [0.26862954, 0.26130258, 0.27577711];
 
         // Convert to ndarray and normalize
@@ -119,6 +120,7 @@ impl ImageEmbedding {
 }
 
 #[cfg(test)]
+#[allow(clippy::items_after_test_module)]
 mod tests {
     use super::*;
     use image::{ImageBuffer, Rgb};
@@ -198,7 +200,7 @@ impl ClipTextEmbedding {
         let (_emb_shape, emb_data) = outputs[0].try_extract_tensor::<f32>()?;
 
         // Convert to Vec (CLIP text encoder outputs 512-dim)
-        let embedding_vec: Vec<f32> = emb_data.iter().copied().collect();
+        let embedding_vec: Vec<f32> = emb_data.to_vec();
 
         // Drop outputs to release mutable borrow
         drop(outputs);
@@ -7,9 +7,44 @@ use crate::types::Embedding;
 use crate::Result;
 use ort::session::{builder::GraphOptimizationLevel, Session};
 use ort::value::Value;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use tokenizer::Tokenizer;
 
+/// Dimension of text embeddings produced by all-MiniLM-L6-v2
+pub const TEXT_EMBEDDING_DIM: usize = 384;
+
+/// Dimension of CLIP ViT-B/32 image and text embeddings
+pub const IMAGE_EMBEDDING_DIM: usize = 512;
+
+/// Find a model file in common locations
+///
+/// Looks in `./models`, `../models`, `~/.khoj/models` and next to the
+/// running executable (walking up to three parent directories).
+pub fn find_model_path(filename: &str) -> Option<PathBuf> {
+    let exe_dir = std::env::current_exe()
+        .ok()
+        .and_then(|p| p.parent().map(|p| p.to_path_buf()));
+
+    let mut possible_paths = vec![
+        // Current directory
+        Some(PathBuf::from(format!("models/{}", filename))),
+        // Project directory (if running from subdirectory)
+        Some(PathBuf::from(format!("../models/{}", filename))),
+        // Home directory
+        dirs::home_dir().map(|h| h.join(".khoj/models").join(filename)),
+    ];
+
+    // Add executable directory paths
+    if let Some(ref d) = exe_dir {
+        possible_paths.push(Some(d.join("models").join(filename)));
+        possible_paths.push(d.parent().map(|p| p.join("models").join(filename)));
+        possible_paths.push(d.parent().and_then(|p| p.parent()).map(|p| p.join("models").join(filename)));
+        possible_paths.push(d.parent().and_then(|p| p.parent()).and_then(|p| p.parent()).map(|p| p.join("models").join(filename)));
+    }
+
+    possible_paths.into_iter().flatten().find(|path| path.exists())
+}
+
 /// Text embedding model using ONNX Runtime
 pub struct EmbeddingModel {
     session: Session,
@@ -71,17 +106,41 @@ impl EmbeddingModel {
     /// # Returns
     /// 384-dimensional embedding vector for all-MiniLM-L6-v2
     pub fn embed(&mut self, text: &str) -> Result<Embedding> {
-        // Tokenize input text
-        let tokens = self.tokenizer.encode(text, self.max_length)?;
+        self.embed_batch(&[text])?
+            .pop()
+            .ok_or_else(|| crate::Error::Embedding("Model returned no embedding".to_string()))
+    }
+
+    /// Generate embeddings for several texts in a single inference call
+    ///
+    /// # Arguments
+    /// * `texts` - Input texts to embed
+    ///
+    /// # Returns
+    /// One embedding per input text, in the same order
+    pub fn embed_batch(&mut self, texts: &[&str]) -> Result<Vec<Embedding>> {
+        if texts.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Tokenize input texts (every sequence is padded to max_length)
+        let mut input_ids = Vec::with_capacity(texts.len() * self.max_length);
+        let mut attention_mask = Vec::with_capacity(texts.len() * self.max_length);
+        let mut token_type_ids = Vec::with_capacity(texts.len() * self.max_length);
+        for text in texts {
+            let tokens = self.tokenizer.encode(text, self.max_length)?;
+            input_ids.extend(tokens.input_ids);
+            attention_mask.extend(tokens.attention_mask);
+            token_type_ids.extend(tokens.token_type_ids);
+        }
 
         // Prepare input tensors as ort Values
         // ort 2.0 expects (shape, data) tuples
-        let seq_len = tokens.input_ids.len();
-        let shape = vec![1, seq_len];
+        let shape = vec![texts.len(), self.max_length];
 
-        let input_ids_value = Value::from_array((shape.clone(), tokens.input_ids.clone()))?;
-        let attention_mask_value = Value::from_array((shape.clone(), tokens.attention_mask.clone()))?;
-        let token_type_ids_value = Value::from_array((shape.clone(), tokens.token_type_ids.clone()))?;
+        let input_ids_value = Value::from_array((shape.clone(), input_ids))?;
+        let attention_mask_value = Value::from_array((shape.clone(), attention_mask.clone()))?;
+        let token_type_ids_value = Value::from_array((shape.clone(), token_type_ids))?;
 
         // Run inference with proper input format
         let outputs = self.session.run(ort::inputs![
@@ -99,45 +158,44 @@ impl EmbeddingModel {
         let embeddings = ndarray::ArrayView3::from_shape(
             (shape[0] as usize, shape[1] as usize, shape[2] as usize),
             data
-        ).unwrap().to_owned();
+        ).map_err(|e| crate::Error::Embedding(e.to_string()))?.to_owned();
 
         // Drop outputs to release the mutable borrow
         drop(outputs);
 
-        // Mean pooling: average over sequence length (dim 1)
-        let pooled = self.mean_pool(&embeddings, &tokens.attention_mask)?;
-
-        // Normalize the embedding
-        let normalized = self.normalize(&pooled);
-
-        Ok(normalized)
+        // Mean pooling: average over sequence length (dim 1), then normalize
+        (0..texts.len())
+            .map(|i| {
+                let mask = &attention_mask[i * self.max_length..(i + 1) * self.max_length];
+                let pooled = self.mean_pool(&embeddings, i, mask)?;
+                Ok(self.normalize(&pooled))
+            })
+            .collect()
     }
 
     /// Mean pooling over sequence dimension with attention mask
-    fn mean_pool(&self, embeddings: &ndarray::Array3<f32>, attention_mask: &[i64]) -> Result<Vec<f32>> {
-        let batch_size = embeddings.shape()[0];
+    fn mean_pool(&self, embeddings: &ndarray::Array3<f32>, batch_index: usize, attention_mask: &[i64]) -> Result<Vec<f32>> {
         let seq_len = embeddings.shape()[1];
         let hidden_size = embeddings.shape()[2];
 
-        assert_eq!(batch_size, 1, "Only batch size 1 is supported");
-
         let mut pooled = vec![0.0f32; hidden_size];
         let mut mask_sum = 0i64;
 
-        for i in 0..seq_len {
-            let mask_value = attention_mask[i];
+        for (i, &mask_value) in attention_mask.iter().enumerate().take(seq_len) {
             mask_sum += mask_value;
 
             if mask_value > 0 {
-                for j in 0..hidden_size {
-                    pooled[j] += embeddings[[0, i, j]] * mask_value as f32;
+                for (j, value) in pooled.iter_mut().enumerate() {
+                    *value += embeddings[[batch_index, i, j]] * mask_value as f32;
                 }
             }
         }
 
         // Average
-        for val in &mut pooled {
-            *val /= mask_sum as f32;
+        if mask_sum > 0 {
+            for val in &mut pooled {
+                *val /= mask_sum as f32;
+            }
         }
 
         Ok(pooled)
@@ -39,6 +39,25 @@ pub enum Error {
     Other(#[from] anyhow::Error),
 }
 
+impl Error {
+    /// Check whether the operation may succeed if tried again later
+    ///
+    /// Only I/O errors such as a locked or briefly unavailable file count;
+    /// unsupported, corrupt or oversized files fail the same way every time.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Io(e) => !matches!(
+                e.kind(),
+                std::io::ErrorKind::InvalidData
+                    | std::io::ErrorKind::UnexpectedEof
+                    | std::io::ErrorKind::Unsupported
+                    | std::io::ErrorKind::Other
+            ),
+            _ => false,
+        }
+    }
+}
+
 // Convert tantivy errors to our Error type
 impl From<tantivy::TantivyError> for Error {
     fn from(err: tantivy::TantivyError) -> Self {
@@ -56,7 +75,7 @@ impl From<ort::Error> for Error {
 // Convert ignore errors to our Error type
 impl From<ignore::Error> for Error {
     fn from(err: ignore::Error) -> Self {
-        Error::Io(std::io::Error::new(std::io::ErrorKind::Other, err))
+        Error::Io(std::io::Error::other(err))
     }
 }
 
@@ -72,4 +91,10 @@ impl From<serde_json::Error> for Error {
     fn from(err: serde_json::Error) -> Self {
         Error::Other(err.into())
     }
-}
\ No newline at end of file
+}
+// Convert notify errors to our Error type
+impl From<notify::Error> for Error {
+    fn from(err: notify::Error) -> Self {
+        Error::Io(std::io::Error::other(err))
+    }
+}
@@ -0,0 +1,631 @@
+//! Source code extractor
+//!
+//! Code files are read as text like any other, and for languages with a
+//! tree-sitter grammar also parsed to find what they define. Functions,
+//! methods, types and constants are kept in [`ExtractedContent::symbols`]
+//! with the line they are defined on; the comments of the file go into the
+//! `comments` metadata entry and its identifiers into `identifiers`, where
+//! the index picks them up as fields of their own.
+//!
+//! Identifiers are matched by their parts: [`identifier_parts`] splits
+//! `parseQuery`, `ParseQuery` and `parse_query` alike into `parse` and
+//! `query`.
+
+use super::text::PlainTextExtractor;
+use super::{ExtractedContent, Extractor};
+use crate::types::FileType;
+use crate::Result;
+use std::collections::HashSet;
+use std::path::Path;
+use tree_sitter::{Language, Node, Parser};
+
+/// Files larger than this are indexed as text only; they are mostly
+/// generated or minified code
+const MAX_ANALYZED_SIZE: usize = 2 * 1024 * 1024;
+
+/// Kind of a definition in source code
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SymbolKind {
+    Function,
+    /// Function defined in a type, impl block or class
+    Method,
+    /// Struct, class, enum, interface, trait or type alias
+    Type,
+    Constant,
+}
+
+impl SymbolKind {
+    /// Parse a symbol kind from its name
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "function" => Some(Self::Function),
+            "method" => Some(Self::Method),
+            "type" => Some(Self::Type),
+            "constant" => Some(Self::Constant),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Function => "function",
+            Self::Method => "method",
+            Self::Type => "type",
+            Self::Constant => "constant",
+        }
+    }
+}
+
+/// A definition found in source code
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    /// Line of the name, starting at 1
+    pub line: usize,
+}
+
+/// Code files, with their definitions, comments and identifiers
+pub struct CodeExtractor;
+
+impl Extractor for CodeExtractor {
+    fn name(&self) -> &str {
+        "code"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Code]
+    }
+
+    fn extract(&self, path: &Path, file_type: FileType) -> Result<ExtractedContent> {
+        let mut content = PlainTextExtractor.extract(path, file_type)?;
+        if let Some(language) = content.language.clone() {
+            if content.text.len() <= MAX_ANALYZED_SIZE {
+                analyze(&mut content, &language);
+            }
+        }
+        Ok(content)
+    }
+}
+
+/// Find the definitions, comments and identifiers of source code
+///
+/// Languages without a grammar are left as they are.
+///
+/// # Arguments
+/// * `content` - Content holding the source code
+/// * `language` - Language of the code, as detected by the text extractor
+pub fn analyze(content: &mut ExtractedContent, language: &str) {
+    let Some(grammar) = Grammar::for_language(language) else {
+        return;
+    };
+    let mut parser = Parser::new();
+    if parser.set_language(&grammar.language()).is_err() {
+        return;
+    }
+    let Some(tree) = parser.parse(&content.text, None) else {
+        return;
+    };
+
+    let source = content.text.as_bytes();
+    let mut symbols = Vec::new();
+    let mut comments = Vec::new();
+    let mut identifiers = Vec::new();
+    let mut seen = HashSet::new();
+
+    // Depth-first walk, not descending into comments
+    let mut cursor = tree.walk();
+    'walk: loop {
+        let node = cursor.node();
+        let mut descend = true;
+        if node.kind().ends_with("comment") || grammar.is_docstring(node) {
+            descend = false;
+            let text = node.utf8_text(source).unwrap_or_default();
+            let text = strip_comment(text);
+            if !text.is_empty() {
+                comments.push(text);
+            }
+        } else if node.is_named() && node.kind().ends_with("identifier") {
+            let text = node.utf8_text(source).unwrap_or_default();
+            if seen.insert(text) {
+                identifiers.push(text);
+            }
+        } else {
+            symbols.extend(grammar.definitions(node, source));
+        }
+
+        if descend && cursor.goto_first_child() {
+            continue;
+        }
+        while !cursor.goto_next_sibling() {
+            if !cursor.goto_parent() {
+                break 'walk;
+            }
+        }
+    }
+
+    if !comments.is_empty() {
+        content.metadata.insert("comments".to_string(), comments.join("\n"));
+    }
+    if !identifiers.is_empty() {
+        content.metadata.insert("identifiers".to_string(), identifiers.join(" "));
+    }
+    content.symbols = symbols;
+}
+
+/// Languages with a grammar
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Grammar {
+    Rust,
+    Python,
+    JavaScript,
+    TypeScript,
+    Go,
+    Java,
+    C,
+    Cpp,
+}
+
+impl Grammar {
+    fn for_language(language: &str) -> Option<Self> {
+        Some(match language {
+            "rust" => Self::Rust,
+            "python" => Self::Python,
+            "javascript" => Self::JavaScript,
+            "typescript" => Self::TypeScript,
+            "go" => Self::Go,
+            "java" => Self::Java,
+            "c" => Self::C,
+            "cpp" => Self::Cpp,
+            _ => return None,
+        })
+    }
+
+    fn language(self) -> Language {
+        match self {
+            Self::Rust => tree_sitter_rust::LANGUAGE.into(),
+            Self::Python => tree_sitter_python::LANGUAGE.into(),
+            Self::JavaScript => tree_sitter_javascript::LANGUAGE.into(),
+            Self::TypeScript => tree_sitter_typescript::LANGUAGE_TSX.into(),
+            Self::Go => tree_sitter_go::LANGUAGE.into(),
+            Self::Java => tree_sitter_java::LANGUAGE.into(),
+            Self::C => tree_sitter_c::LANGUAGE.into(),
+            Self::Cpp => tree_sitter_cpp::LANGUAGE.into(),
+        }
+    }
+
+    /// Whether a node is a Python docstring, a string standing first in a
+    /// module, class or function
+    fn is_docstring(self, node: Node) -> bool {
+        self == Self::Python
+            && node.kind() == "string"
+            && node.parent().is_some_and(|statement| {
+                statement.kind() == "expression_statement"
+                    && statement.named_child_count() == 1
+                    && statement.prev_named_sibling().is_none()
+                    && statement.parent().is_some_and(|body| matches!(body.kind(), "module" | "block"))
+            })
+    }
+
+    /// Definitions made by a node
+    fn definitions(self, node: Node, source: &[u8]) -> Vec<Symbol> {
+        let kind = match (self, node.kind()) {
+            (
+                Self::Rust,
+                "struct_item" | "enum_item" | "union_item" | "trait_item" | "type_item",
+            )
+            | (Self::Python, "class_definition")
+            | (Self::JavaScript | Self::TypeScript, "class_declaration" | "class")
+            | (
+                Self::TypeScript,
+                "abstract_class_declaration"
+                | "interface_declaration"
+                | "type_alias_declaration"
+                | "enum_declaration",
+            )
+            | (Self::Go, "type_spec")
+            | (
+                Self::Java,
+                "class_declaration"
+                | "interface_declaration"
+                | "enum_declaration"
+                | "record_declaration"
+                | "annotation_type_declaration",
+            )
+            | (Self::Cpp, "alias_declaration") => SymbolKind::Type,
+            (Self::C | Self::Cpp, "struct_specifier" | "union_specifier" | "enum_specifier")
+            | (Self::Cpp, "class_specifier") => {
+                // Only the definition, not every use of the type
+                if node.child_by_field_name("body").is_none() {
+                    return Vec::new();
+                }
+                SymbolKind::Type
+            }
+            (Self::C | Self::Cpp, "type_definition") => {
+                return node
+                    .children_by_field_name("declarator", &mut node.walk())
+                    .filter(|declarator| declarator.kind() == "type_identifier")
+                    .filter_map(|declarator| symbol(declarator, SymbolKind::Type, source))
+                    .collect();
+            }
+            (Self::Rust, "function_item" | "function_signature_item")
+            | (Self::Python, "function_definition")
+            | (Self::JavaScript | Self::TypeScript, "function_declaration" | "generator_function_declaration")
+            | (Self::TypeScript, "function_signature") => self.function_kind(node),
+            (Self::Rust, "macro_definition") | (Self::Go, "function_declaration") | (Self::C | Self::Cpp, "preproc_function_def") => {
+                SymbolKind::Function
+            }
+            (Self::JavaScript | Self::TypeScript, "method_definition")
+            | (Self::TypeScript, "method_signature" | "abstract_method_signature")
+            | (Self::Go, "method_declaration")
+            | (Self::Java, "method_declaration" | "constructor_declaration") => SymbolKind::Method,
+            (Self::C | Self::Cpp, "function_definition") => {
+                return function_name(node.child_by_field_name("declarator"), source).into_iter().collect();
+            }
+            (Self::Rust, "const_item" | "static_item")
+            | (Self::Go, "const_spec")
+            | (Self::C | Self::Cpp, "preproc_def" | "enumerator") => SymbolKind::Constant,
+            (Self::JavaScript | Self::TypeScript, "variable_declarator") => {
+                let name = node.child_by_field_name("name").filter(|name| name.kind() == "identifier");
+                let value = node.child_by_field_name("value").map(|value| value.kind());
+                let kind = match value {
+                    Some("arrow_function" | "function_expression" | "function") => SymbolKind::Function,
+                    // Top-level constants named in capitals
+                    _ if node.parent().is_some_and(|declaration| {
+                        declaration.kind() == "lexical_declaration"
+                            && declaration.child(0).is_some_and(|keyword| keyword.kind() == "const")
+                            && declaration
+                                .parent()
+                                .is_some_and(|parent| matches!(parent.kind(), "program" | "export_statement"))
+                    }) =>
+                    {
+                        SymbolKind::Constant
+                    }
+                    _ => return Vec::new(),
+                };
+                return name
+                    .and_then(|name| symbol(name, kind, source))
+                    .filter(|symbol| kind != SymbolKind::Constant || is_constant_name(&symbol.name))
+                    .into_iter()
+                    .collect();
+            }
+            (Self::Python, "assignment") => {
+                // Module-level constants named in capitals
+                let top_level = node
+                    .parent()
+                    .filter(|statement| statement.kind() == "expression_statement")
+                    .and_then(|statement| statement.parent())
+                    .is_some_and(|module| module.kind() == "module");
+                return node
+                    .child_by_field_name("left")
+                    .filter(|left| top_level && left.kind() == "identifier")
+                    .and_then(|left| symbol(left, SymbolKind::Constant, source))
+                    .filter(|symbol| is_constant_name(&symbol.name))
+                    .into_iter()
+                    .collect();
+            }
+            (Self::Java, "field_declaration") => {
+                let modifiers = node
+                    .named_child(0)
+                    .filter(|modifiers| modifiers.kind() == "modifiers")
+                    .and_then(|modifiers| modifiers.utf8_text(source).ok())
+                    .unwrap_or_default();
+                let words: Vec<&str> = modifiers.split_whitespace().collect();
+                if !(words.contains(&"static") && words.contains(&"final")) {
+                    return Vec::new();
+                }
+                return node
+                    .children_by_field_name("declarator", &mut node.walk())
+                    .filter_map(|declarator| declarator.child_by_field_name("name"))
+                    .filter_map(|name| symbol(name, SymbolKind::Constant, source))
+                    .collect();
+            }
+            _ => return Vec::new(),
+        };
+
+        // Go declares several constants at once
+        node.children_by_field_name("name", &mut node.walk())
+            .filter_map(|name| symbol(name, kind, source))
+            .collect()
+    }
+
+    /// Whether a function is a method, being defined in an impl block,
+    /// trait or class
+    fn function_kind(self, node: Node) -> SymbolKind {
+        let containers: &[&str] = match self {
+            Self::Rust => &["impl_item", "trait_item"],
+            Self::Python => &["class_definition"],
+            _ => &[],
+        };
+        let mut ancestor = node.parent();
+        while let Some(parent) = ancestor {
+            if containers.contains(&parent.kind()) {
+                return SymbolKind::Method;
+            }
+            if matches!(parent.kind(), "function_item" | "function_definition") {
+                break;
+            }
+            ancestor = parent.parent();
+        }
+        SymbolKind::Function
+    }
+}
+
+/// Symbol named by a node
+fn symbol(name: Node, kind: SymbolKind, source: &[u8]) -> Option<Symbol> {
+    let text = name.utf8_text(source).ok()?.trim();
+    (!text.is_empty()).then(|| Symbol {
+        name: text.to_string(),
+        kind,
+        line: name.start_position().row + 1,
+    })
+}
+
+/// Name of a C or C++ function from its declarator
+///
+/// Functions named with a class (`Parser::parse`) or defined in a class
+/// body are methods.
+fn function_name(declarator: Option<Node>, source: &[u8]) -> Option<Symbol> {
+    let mut declarator = declarator?;
+    loop {
+        match declarator.kind() {
+            "identifier" => return symbol(declarator, SymbolKind::Function, source),
+            "field_identifier" | "destructor_name" | "operator_name" => {
+                return symbol(declarator, SymbolKind::Method, source)
+            }
+            "qualified_identifier" => {
+                let mut symbol = symbol(declarator, SymbolKind::Method, source)?;
+                if let Some((_, name)) = symbol.name.rsplit_once("::") {
+                    symbol.name = name.to_string();
+                }
+                return Some(symbol);
+            }
+            _ => {
+                declarator = declarator
+                    .child_by_field_name("declarator")
+                    .or_else(|| declarator.named_child(0))?;
+            }
+        }
+    }
+}
+
+/// Whether a name is written in capitals, like `MAX_SIZE`
+fn is_constant_name(name: &str) -> bool {
+    name.chars().any(|c| c.is_alphabetic()) && !name.chars().any(|c| c.is_lowercase())
+}
+
+/// Text of a comment without its markers
+fn strip_comment(comment: &str) -> String {
+    let comment = comment.trim();
+    let comment = comment
+        .strip_prefix("\"\"\"")
+        .or_else(|| comment.strip_prefix("'''"))
+        .map(|docstring| docstring.trim_end_matches(['"', '\'']))
+        .unwrap_or(comment);
+    comment
+        .lines()
+        .map(|line| {
+            let line = line.trim();
+            let line = line.strip_suffix("*/").unwrap_or(line);
+            line.trim_start_matches(['/', '*', '!', '#']).trim()
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Byte ranges of the parts of the words in a text
+///
+/// Words are split on anything but letters and digits, and identifiers on
+/// case changes: `parseHTTPRequest_v2` has the parts `parse`, `HTTP`,
+/// `Request` and `v2`.
+pub fn identifier_parts(text: &str) -> Vec<(usize, usize)> {
+    let mut parts = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut chars = text.char_indices().peekable();
+    let mut previous: Option<char> = None;
+
+    while let Some((i, c)) = chars.next() {
+        if !c.is_alphanumeric() {
+            if let Some(start) = start.take() {
+                parts.push((start, i));
+            }
+            previous = None;
+            continue;
+        }
+        let boundary = match previous {
+            // parse|Query, utf8|Decode
+            Some(p) if (p.is_lowercase() || p.is_ascii_digit()) && c.is_uppercase() => true,
+            // HTTP|Request
+            Some(p) if p.is_uppercase() && c.is_uppercase() => {
+                chars.peek().is_some_and(|&(_, next)| next.is_lowercase())
+            }
+            _ => false,
+        };
+        match start {
+            Some(s) if boundary => {
+                parts.push((s, i));
+                start = Some(i);
+            }
+            Some(_) => {}
+            None => start = Some(i),
+        }
+        previous = Some(c);
+    }
+    if let Some(start) = start {
+        parts.push((start, text.len()));
+    }
+    parts
+}
+
+/// Lowercase parts of an identifier, see [`identifier_parts`]
+pub fn split_identifier(identifier: &str) -> Vec<String> {
+    identifier_parts(identifier)
+        .into_iter()
+        .map(|(start, end)| identifier[start..end].to_lowercase())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn symbols(language: &str, code: &str) -> Vec<(String, SymbolKind, usize)> {
+        let mut content = ExtractedContent::new(code.to_string());
+        analyze(&mut content, language);
+        content.symbols.into_iter().map(|s| (s.name, s.kind, s.line)).collect()
+    }
+
+    fn names(language: &str, code: &str) -> Vec<(String, SymbolKind)> {
+        symbols(language, code).into_iter().map(|(name, kind, _)| (name, kind)).collect()
+    }
+
+    use SymbolKind::*;
+
+    #[test]
+    fn test_rust_symbols() {
+        let code = "/// Maximum depth\nconst MAX_DEPTH: usize = 3;\n\npub struct Query;\n\nimpl Query {\n    fn parse_query(text: &str) -> Self {\n        fn helper() {}\n        Query\n    }\n}\n\ntrait Search {\n    fn search(&self);\n}\n\nfn main() {}\n";
+        assert_eq!(
+            symbols("rust", code),
+            vec![
+                ("MAX_DEPTH".to_string(), Constant, 2),
+                ("Query".to_string(), Type, 4),
+                ("parse_query".to_string(), Method, 7),
+                ("helper".to_string(), Function, 8),
+                ("Search".to_string(), Type, 13),
+                ("search".to_string(), Method, 14),
+                ("main".to_string(), Function, 17),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_python_symbols() {
+        let code = "\"\"\"Search helpers.\"\"\"\nDEFAULT_LIMIT = 10\nlimit = 5\n\nclass Index:\n    def search(self, query):\n        \"\"\"Find matches.\"\"\"\n        return []\n\ndef load(path):  # open the index\n    pass\n";
+        let mut content = ExtractedContent::new(code.to_string());
+        analyze(&mut content, "python");
+        assert_eq!(
+            content.symbols.iter().map(|s| (s.name.as_str(), s.kind)).collect::<Vec<_>>(),
+            vec![("DEFAULT_LIMIT", Constant), ("Index", Type), ("search", Method), ("load", Function)]
+        );
+        assert_eq!(content.metadata["comments"], "Search helpers.\nFind matches.\nopen the index");
+        assert_eq!(content.metadata["identifiers"], "DEFAULT_LIMIT limit Index search self query load path");
+    }
+
+    #[test]
+    fn test_javascript_symbols() {
+        let code = "// Entry points\nconst API_URL = 'https://example.com';\nconst client = makeClient();\nexport const fetchUser = async (id) => id;\nfunction render() {}\nclass View {\n  update() {}\n}\n";
+        assert_eq!(
+            names("javascript", code),
+            vec![
+                ("API_URL".to_string(), Constant),
+                ("fetchUser".to_string(), Function),
+                ("render".to_string(), Function),
+                ("View".to_string(), Type),
+                ("update".to_string(), Method),
+            ]
+        );
+
+        let code = "interface User { name: string }\ntype Id = number;\nenum Color { Red }\nexport function load(): User { return null; }\nclass Store { get(id: Id) { return 1; } }\n";
+        assert_eq!(
+            names("typescript", code),
+            vec![
+                ("User".to_string(), Type),
+                ("Id".to_string(), Type),
+                ("Color".to_string(), Type),
+                ("load".to_string(), Function),
+                ("Store".to_string(), Type),
+                ("get".to_string(), Method),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_go_and_java_symbols() {
+        let code = "package main\n\nconst (\n\tMaxSize = 10\n)\n\ntype Server struct{}\n\nfunc (s *Server) Start() {}\n\nfunc main() {}\n";
+        assert_eq!(
+            names("go", code),
+            vec![
+                ("MaxSize".to_string(), Constant),
+                ("Server".to_string(), Type),
+                ("Start".to_string(), Method),
+                ("main".to_string(), Function),
+            ]
+        );
+
+        let code = "public class Parser {\n  static final int LIMIT = 3;\n  private int count;\n  Parser() {}\n  public void parse() {}\n}\ninterface Visitor {}\n";
+        assert_eq!(
+            names("java", code),
+            vec![
+                ("Parser".to_string(), Type),
+                ("LIMIT".to_string(), Constant),
+                ("Parser".to_string(), Method),
+                ("parse".to_string(), Method),
+                ("Visitor".to_string(), Type),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_c_symbols() {
+        let code = "#define BUFFER_SIZE 64\ntypedef struct node { int value; } Node;\nenum color { RED };\nstatic char *read_line(FILE *file) { return 0; }\nstruct node *next(struct node *n);\n";
+        assert_eq!(
+            names("c", code),
+            vec![
+                ("BUFFER_SIZE".to_string(), Constant),
+                // The typedef comes before the struct it names
+                ("Node".to_string(), Type),
+                ("node".to_string(), Type),
+                ("color".to_string(), Type),
+                ("RED".to_string(), Constant),
+                ("read_line".to_string(), Function),
+            ]
+        );
+
+        let code = "class Lexer {\n  int peek() { return 0; }\n};\nint Lexer::next() { return 1; }\nint main() { return 0; }\n";
+        assert_eq!(
+            names("cpp", code),
+            vec![
+                ("Lexer".to_string(), Type),
+                ("peek".to_string(), Method),
+                ("next".to_string(), Method),
+                ("main".to_string(), Function),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unsupported_language() {
+        let mut content = ExtractedContent::new("def hello\n  puts 'hi'\nend\n".to_string());
+        analyze(&mut content, "ruby");
+        assert!(content.symbols.is_empty());
+        assert!(content.metadata.is_empty());
+    }
+
+    #[test]
+    fn test_identifier_parts() {
+        assert_eq!(split_identifier("parseQuery"), vec!["parse", "query"]);
+        assert_eq!(split_identifier("parse_query"), vec!["parse", "query"]);
+        assert_eq!(split_identifier("ParseQuery"), vec!["parse", "query"]);
+        assert_eq!(split_identifier("parseHTTPRequest_v2"), vec!["parse", "http", "request", "v2"]);
+        assert_eq!(split_identifier("utf8Decode MAX_SIZE"), vec!["utf8", "decode", "max", "size"]);
+        assert_eq!(split_identifier("Écrire"), vec!["écrire"]);
+        assert!(split_identifier("__").is_empty());
+    }
+
+    #[test]
+    fn test_extractor() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("lib.rs");
+        fs::write(&path, "// Tokenizer entry point\npub fn tokenize() {}\n").unwrap();
+
+        let registry = super::super::ExtractorRegistry::builtin();
+        assert_eq!(registry.find(&path, FileType::Code).unwrap().name(), "code");
+        let content = registry.extract(&path, FileType::Code).unwrap();
+        assert_eq!(content.language.as_deref(), Some("rust"));
+        assert_eq!(content.symbols, vec![Symbol { name: "tokenize".to_string(), kind: Function, line: 2 }]);
+        assert_eq!(content.metadata["comments"], "Tokenizer entry point");
+    }
+}
@@ -0,0 +1,241 @@
+//! Word document extractor
+//!
+//! DOCX files are zip packages of WordprocessingML parts. The whole
+//! document tree is read, so that text in tables, text boxes and content
+//! controls is found as well as top-level paragraphs, followed by the
+//! footnotes, endnotes, comments, headers and footers. Table rows become one
+//! line each, with their cells separated by tabs. Text deleted with tracked
+//! changes and field codes are left out. The document properties are kept
+//! under their own names, the last editor as `modified_by`.
+
+use super::office::{local_name, open_package, read_part, read_properties, relationships, CORE_PROPERTIES};
+use super::{Capabilities, ExtractedContent, Extractor};
+use crate::types::FileType;
+use crate::Result;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::fs::File;
+use std::path::Path;
+use zip::ZipArchive;
+
+/// Parts related to the main document, by relationship type, in the order
+/// their text follows the body
+const RELATED_PARTS: &[&str] = &["footnotes", "endnotes", "comments", "header", "footer"];
+
+/// Elements whose content is not text of the document: deleted and moved
+/// text of tracked changes, fallbacks repeating the content of an
+/// alternative, and paragraph properties such as tab stops
+const SKIPPED_ELEMENTS: &[&str] = &["del", "moveFrom", "Fallback", "pPr"];
+
+/// Word documents
+pub struct DocxExtractor;
+
+impl Extractor for DocxExtractor {
+    fn name(&self) -> &str {
+        "docx"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Docx]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            metadata: true,
+            ..Capabilities::TEXT
+        }
+    }
+
+    fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+        let mut package = open_package(path)?;
+        let main = main_part(&mut package)?;
+        let xml = read_part(&mut package, &main)?
+            .ok_or_else(|| crate::Error::Extraction("DOCX extraction failed: no document part".to_string()))?;
+
+        let mut sections = vec![wordml_text(&xml)];
+        let related = relationships(&mut package, &main)?;
+        for kind in RELATED_PARTS {
+            for rel in related.iter().filter(|rel| rel.kind.rsplit('/').next() == Some(kind)) {
+                let Some(xml) = read_part(&mut package, &rel.target)? else {
+                    continue;
+                };
+                // Sections often repeat the same header and footer
+                let text = wordml_text(&xml);
+                if !text.is_empty() && !sections.contains(&text) {
+                    sections.push(text);
+                }
+            }
+        }
+
+        let mut content = ExtractedContent::new(sections.join("\n\n"));
+        content.metadata.extend(read_properties(&mut package, "docProps/core.xml", CORE_PROPERTIES)?);
+        Ok(content)
+    }
+}
+
+/// Name of the main document part, from the package relationships
+fn main_part(package: &mut ZipArchive<File>) -> Result<String> {
+    Ok(relationships(package, "")?
+        .into_iter()
+        .find(|rel| rel.kind.ends_with("/officeDocument"))
+        .map(|rel| rel.target)
+        .unwrap_or_else(|| "word/document.xml".to_string()))
+}
+
+/// Read the text of a WordprocessingML part
+///
+/// Paragraphs end with a new line, except in table cells, where they are
+/// separated by spaces.
+fn wordml_text(xml: &[u8]) -> String {
+    let mut reader = Reader::from_reader(xml);
+    let mut text = String::new();
+    let mut in_text = false;
+    // Depth inside skipped elements, and inside table cells
+    let mut skipped = 0;
+    let mut cells = 0;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(element)) => {
+                let name = local_name(&element);
+                if skipped > 0 || SKIPPED_ELEMENTS.contains(&name.as_str()) {
+                    skipped += 1;
+                } else if name == "t" {
+                    in_text = true;
+                } else if name == "tc" {
+                    cells += 1;
+                }
+            }
+            Ok(Event::Empty(element)) if skipped == 0 => match local_name(&element).as_str() {
+                "tab" => text.push('\t'),
+                "br" | "cr" => text.push('\n'),
+                "p" => end_paragraph(&mut text, cells > 0),
+                _ => {}
+            },
+            Ok(Event::Text(t)) if in_text && skipped == 0 => text.push_str(&t.unescape().unwrap_or_default()),
+            Ok(Event::End(_)) if skipped > 0 => skipped -= 1,
+            Ok(Event::End(element)) => match String::from_utf8_lossy(element.local_name().as_ref()).as_ref() {
+                "t" => in_text = false,
+                "p" => end_paragraph(&mut text, cells > 0),
+                "tc" => {
+                    cells -= 1;
+                    text.truncate(text.trim_end_matches(' ').len());
+                    text.push('\t');
+                }
+                "tr" => {
+                    if text.ends_with('\t') {
+                        text.pop();
+                    }
+                    text.push('\n');
+                }
+                _ => {}
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    text.trim().to_string()
+}
+
+/// End a paragraph, with a space inside table cells and a new line elsewhere
+fn end_paragraph(text: &mut String, in_cell: bool) {
+    if !in_cell {
+        text.push('\n');
+    } else if !text.is_empty() && !text.ends_with(char::is_whitespace) {
+        text.push(' ');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::office::tests::write_package;
+    use tempfile::TempDir;
+
+    const NS: &str = r#"xmlns:w="http://schemas.openxmlformats.org/wordprocessingml/2006/main" xmlns:mc="http://schemas.openxmlformats.org/markup-compatibility/2006" xmlns:wps="http://schemas.microsoft.com/office/word/2010/wordprocessingShape""#;
+
+    fn part(root: &str, body: &str) -> String {
+        format!(r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?><w:{root} {NS}>{body}</w:{root}>"#)
+    }
+
+    #[test]
+    fn test_wordml_text() {
+        let body = r#"<w:body>
+            <w:p><w:pPr><w:tabs><w:tab w:val="left" w:pos="720"/></w:tabs></w:pPr><w:r><w:t>Service</w:t></w:r><w:r><w:t xml:space="preserve"> agreement</w:t></w:r></w:p>
+            <w:tbl>
+              <w:tr><w:tc><w:p><w:r><w:t>Term</w:t></w:r></w:p></w:tc><w:tc><w:p><w:r><w:t>12 months</w:t></w:r></w:p><w:p><w:r><w:t>renewable</w:t></w:r></w:p></w:tc></w:tr>
+              <w:tr><w:tc><w:p><w:r><w:t>Fee</w:t></w:r></w:p></w:tc><w:tc><w:p/></w:tc></w:tr>
+            </w:tbl>
+            <w:p><w:r><w:t>Signed</w:t></w:r><w:r><w:tab/><w:t>Paid</w:t></w:r><w:del><w:r><w:delText>Draft</w:delText></w:r></w:del><w:r><w:br/><w:t>Done</w:t></w:r></w:p>
+            <w:p><w:r><w:fldChar w:fldCharType="begin"/></w:r><w:r><w:instrText>PAGE</w:instrText></w:r><w:r><w:t>3</w:t></w:r></w:p>
+            <w:p><w:r><mc:AlternateContent><mc:Choice Requires="wps"><w:drawing><wps:txbx><w:txbxContent><w:p><w:r><w:t>Text box</w:t></w:r></w:p></w:txbxContent></wps:txbx></w:drawing></mc:Choice>
+              <mc:Fallback><w:pict><w:txbxContent><w:p><w:r><w:t>Text box</w:t></w:r></w:p></w:txbxContent></w:pict></mc:Fallback></mc:AlternateContent></w:r></w:p>
+            <w:sdt><w:sdtContent><w:p><w:r><w:t>Tom &amp; Jerry</w:t></w:r></w:p></w:sdtContent></w:sdt>
+            </w:body>"#;
+        assert_eq!(
+            wordml_text(part("document", body).as_bytes()),
+            "Service agreement\nTerm\t12 months renewable\nFee\t\nSigned\tPaid\nDone\n3\nText box\n\nTom & Jerry"
+        );
+    }
+
+    #[test]
+    fn test_extract_docx() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("contract.docx");
+        let package_rels = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/>
+            </Relationships>"#;
+        let rels = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/header" Target="header1.xml"/>
+            <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/header" Target="header2.xml"/>
+            <Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/footer" Target="footer1.xml"/>
+            <Relationship Id="rId4" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/footnotes" Target="footnotes.xml"/>
+            <Relationship Id="rId5" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/comments" Target="comments.xml"/>
+            <Relationship Id="rId6" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/>
+            </Relationships>"#;
+        let paragraph = |text: &str| format!("<w:p><w:r><w:t>{}</w:t></w:r></w:p>", text);
+        let footnotes = format!(
+            r#"<w:footnote w:type="separator" w:id="-1"><w:p><w:r><w:separator/></w:r></w:p></w:footnote><w:footnote w:id="1">{}</w:footnote>"#,
+            paragraph("Excluding public holidays")
+        );
+        let core = r#"<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/">
+            <dc:title>Support contract</dc:title><dc:creator>Alice Martin</dc:creator><cp:lastModifiedBy>Bob Stone</cp:lastModifiedBy>
+            <dcterms:created>2024-02-01T10:00:00Z</dcterms:created></cp:coreProperties>"#;
+        write_package(
+            &path,
+            &[
+                ("_rels/.rels", package_rels),
+                ("word/document.xml", &part("document", &format!("<w:body>{}</w:body>", paragraph("Response within 4 hours")))),
+                ("word/_rels/document.xml.rels", rels),
+                ("word/header1.xml", &part("hdr", &paragraph("Confidential"))),
+                ("word/header2.xml", &part("hdr", &paragraph("Confidential"))),
+                ("word/footer1.xml", &part("ftr", &paragraph("Acme Corp"))),
+                ("word/footnotes.xml", &part("footnotes", &footnotes)),
+                ("word/comments.xml", &part("comments", &format!("<w:comment w:id=\"0\">{}</w:comment>", paragraph("Check with legal")))),
+                ("docProps/core.xml", core),
+            ],
+        );
+
+        let content = DocxExtractor.extract(&path, FileType::Docx).unwrap();
+        assert_eq!(
+            content.text,
+            "Response within 4 hours\n\nExcluding public holidays\n\nCheck with legal\n\nConfidential\n\nAcme Corp"
+        );
+        let metadata = |name: &str| content.metadata.get(name).map(String::as_str);
+        assert_eq!(metadata("title"), Some("Support contract"));
+        assert_eq!(metadata("author"), Some("Alice Martin"));
+        assert_eq!(metadata("modified_by"), Some("Bob Stone"));
+        assert_eq!(metadata("created"), Some("2024-02-01T10:00:00Z"));
+    }
+
+    #[test]
+    fn test_missing_document() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("empty.docx");
+        write_package(&path, &[("docProps/core.xml", "<coreProperties/>")]);
+        assert!(DocxExtractor.extract(&path, FileType::Docx).is_err());
+    }
+}
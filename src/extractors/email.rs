@@ -0,0 +1,258 @@
+//! Email message extractor
+//!
+//! Messages are parsed as MIME with mail-parser, which decodes
+//! quoted-printable and base64 bodies and encoded header words. The
+//! extracted text starts with the From, To, Cc, Subject and Date headers,
+//! followed by the text bodies of the message; HTML-only bodies go through
+//! the [HTML extractor](super::markup).
+//!
+//! The sender, recipients, subject and date are also kept in
+//! [`ExtractedContent::metadata`] under `from`, `to`, `subject` and `date`
+//! (RFC 3339), where the index picks them up as fields of their own.
+//! Attachments are not extracted here: the indexer reads messages like
+//! archives, each attachment becoming a file of its own (see
+//! [`archive`](crate::indexer::archive)), and so do the messages of mbox
+//! mailboxes.
+
+use super::markup::extract_html;
+use super::{Capabilities, ExtractedContent, Extractor};
+use crate::types::FileType;
+use crate::Result;
+use mail_parser::{Address, Message, MessageParser, MimeHeaders};
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+/// Headers identifying a message, at least one of which must be present
+/// besides `From` for text to be taken as a message
+const MESSAGE_HEADERS: &[&str] = &["date", "subject", "message-id", "received", "to"];
+
+/// Email messages (`.eml` files and Maildir messages)
+pub struct EmailExtractor;
+
+impl Extractor for EmailExtractor {
+    fn name(&self) -> &str {
+        "email"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Email]
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["message/rfc822"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            metadata: true,
+            ..Capabilities::TEXT
+        }
+    }
+
+    fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+        let bytes = fs::read(path)?;
+        let message = parse_message(&bytes)?;
+        Ok(extract_message(&message))
+    }
+}
+
+/// Parse a MIME message
+pub fn parse_message(bytes: &[u8]) -> Result<Message<'_>> {
+    MessageParser::default()
+        .parse(bytes)
+        .ok_or_else(|| crate::Error::Extraction("Email extraction failed: not a MIME message".to_string()))
+}
+
+/// Extract the headers and text bodies of a message
+pub fn extract_message(message: &Message) -> ExtractedContent {
+    let from = message.from().map(format_addresses);
+    let to = message.to().map(format_addresses);
+    let cc = message.cc().map(format_addresses);
+    let subject = message.subject().map(|subject| subject.trim().to_string());
+    let date = message.date().filter(|date| date.is_valid());
+
+    let mut text = String::new();
+    for (name, value) in [("From", &from), ("To", &to), ("Cc", &cc), ("Subject", &subject)] {
+        if let Some(value) = value.as_deref().filter(|value| !value.is_empty()) {
+            let _ = writeln!(text, "{}: {}", name, value);
+        }
+    }
+    if let Some(date) = date {
+        let _ = writeln!(text, "Date: {}", date.to_rfc822());
+    }
+
+    for part in message.text_bodies() {
+        let body = match part.text_contents() {
+            Some(html) if part.is_text_html() => extract_html(html).text,
+            Some(body) => body.trim().to_string(),
+            None => continue,
+        };
+        if !body.is_empty() {
+            text.push('\n');
+            text.push_str(&body);
+            text.push('\n');
+        }
+    }
+
+    let mut content = ExtractedContent::new(text.trim_end().to_string());
+    let attachments: Vec<&str> = message.attachments().filter_map(|part| part.attachment_name()).collect();
+    let properties = [
+        ("from", from),
+        ("to", to),
+        ("subject", subject),
+        ("date", date.map(|date| date.to_rfc3339())),
+        ("attachments", (!attachments.is_empty()).then(|| attachments.join(", "))),
+    ];
+    for (name, value) in properties {
+        if let Some(value) = value.filter(|value| !value.is_empty()) {
+            content.metadata.insert(name.to_string(), value);
+        }
+    }
+    content
+}
+
+/// Format addresses as `Name <address>`, separated by commas
+fn format_addresses(addresses: &Address) -> String {
+    addresses
+        .iter()
+        .map(|addr| match (addr.name(), addr.address()) {
+            (Some(name), Some(address)) => format!("{} <{}>", name, address),
+            (name, address) => name.or(address).unwrap_or_default().to_string(),
+        })
+        .filter(|addr| !addr.is_empty())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Check whether text starts with the header of an email message
+///
+/// The first lines must all be header fields, among them `From` and one
+/// of [`MESSAGE_HEADERS`], up to an empty line or the end of the sample.
+pub fn is_message(head: &[u8]) -> bool {
+    let mut names = Vec::new();
+    let mut offset = 0;
+    for line in head.split(|&b| b == b'\n') {
+        let last = offset + line.len() == head.len();
+        offset += line.len() + 1;
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        if line.is_empty() {
+            break;
+        }
+        if line[0] == b' ' || line[0] == b'\t' {
+            // Folded continuation of the previous field
+            if names.is_empty() {
+                return false;
+            }
+            continue;
+        }
+        let Some(colon) = line.iter().position(|&b| b == b':') else {
+            // Line cut off at the end of the sample
+            if last && !names.is_empty() {
+                break;
+            }
+            return false;
+        };
+        let name = &line[..colon];
+        if name.is_empty() || !name.iter().all(|&b| b.is_ascii_graphic()) {
+            return false;
+        }
+        names.push(String::from_utf8_lossy(name).to_lowercase());
+    }
+
+    names.iter().any(|name| name == "from")
+        && names.iter().any(|name| MESSAGE_HEADERS.contains(&name.as_str()))
+}
+
+/// Check whether text starts like an mbox mailbox: a `From ` separator
+/// line followed by the header of a message
+pub fn is_mbox(head: &[u8]) -> bool {
+    head.starts_with(b"From ")
+        && head
+            .iter()
+            .position(|&b| b == b'\n')
+            .is_some_and(|end| is_message(&head[end + 1..]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const MESSAGE: &str = "From: Alice Martin <alice@example.com>\r\n\
+        To: Bob <bob@example.com>, carol@example.com\r\n\
+        Subject: =?UTF-8?Q?Caf=C3=A9_budget?=\r\n\
+        Date: Tue, 14 May 2024 09:30:00 +0200\r\n\
+        Message-ID: <1@example.com>\r\n\
+        MIME-Version: 1.0\r\n\
+        Content-Type: multipart/mixed; boundary=\"outer\"\r\n\
+        \r\n\
+        --outer\r\n\
+        Content-Type: text/plain; charset=utf-8\r\n\
+        Content-Transfer-Encoding: quoted-printable\r\n\
+        \r\n\
+        The caf=C3=A9 budget for Q3 is attached. It needs sign-off before =\r\n\
+        Friday.\r\n\
+        --outer\r\n\
+        Content-Type: text/plain; name=\"budget.txt\"\r\n\
+        Content-Disposition: attachment; filename=\"budget.txt\"\r\n\
+        Content-Transfer-Encoding: base64\r\n\
+        \r\n\
+        Q29mZmVlIGJlYW5zOiAxMjAwIEVVUg==\r\n\
+        --outer--\r\n";
+
+    #[test]
+    fn test_extract_message() {
+        let content = extract_message(&parse_message(MESSAGE.as_bytes()).unwrap());
+
+        assert_eq!(
+            content.text,
+            "From: Alice Martin <alice@example.com>\n\
+             To: Bob <bob@example.com>, carol@example.com\n\
+             Subject: Café budget\n\
+             Date: Tue, 14 May 2024 09:30:00 +0200\n\
+             \n\
+             The café budget for Q3 is attached. It needs sign-off before Friday."
+        );
+        assert_eq!(content.metadata["from"], "Alice Martin <alice@example.com>");
+        assert_eq!(content.metadata["to"], "Bob <bob@example.com>, carol@example.com");
+        assert_eq!(content.metadata["subject"], "Café budget");
+        assert_eq!(content.metadata["date"], "2024-05-14T09:30:00+02:00");
+        assert_eq!(content.metadata["attachments"], "budget.txt");
+        // Attachments are indexed as files of their own
+        assert!(!content.text.contains("Coffee"));
+    }
+
+    #[test]
+    fn test_extract_html_message() {
+        let message = "From: news@example.com\nSubject: Weekly\nContent-Type: text/html; charset=utf-8\nContent-Transfer-Encoding: base64\n\n\
+            PHN0eWxlPnAge2NvbG9yOiByZWR9PC9zdHlsZT48cD5OZXcgPGI+b2ZmaWNlPC9iPiBob3VyczwvcD4=\n";
+        let content = extract_message(&parse_message(message.as_bytes()).unwrap());
+        assert_eq!(content.text, "From: news@example.com\nSubject: Weekly\n\nNew office hours");
+        assert!(!content.metadata.contains_key("to"));
+    }
+
+    #[test]
+    fn test_is_message() {
+        assert!(is_message(MESSAGE.as_bytes()));
+        assert!(is_message(b"Return-Path: <a@b.c>\nReceived: from mx\n  by host\nFrom: a@b.c\nSubject: hi\n\nbody"));
+        assert!(!is_message(b"From: a@b.c\n\nNo other header"));
+        assert!(!is_message(b"from: yesterday\nto: tomorrow\nand some prose\n"));
+        assert!(!is_message(b" From: a@b.c\nDate: today\n"));
+
+        assert!(is_mbox(b"From alice@example.com Tue May 14 09:30:00 2024\nFrom: alice@example.com\nDate: x\n\nHi\n"));
+        assert!(!is_mbox(b"From the desk of the director\nPlease read on."));
+    }
+
+    #[test]
+    fn test_extractor() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("budget.eml");
+        fs::write(&path, MESSAGE).unwrap();
+
+        let registry = super::super::ExtractorRegistry::builtin();
+        assert_eq!(registry.find(&path, FileType::Email).unwrap().name(), "email");
+        let content = registry.extract(&path, FileType::Email).unwrap();
+        assert_eq!(content.metadata["subject"], "Café budget");
+    }
+}
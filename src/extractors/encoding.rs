@@ -0,0 +1,124 @@
+//! Character encoding detection for text files
+//!
+//! Text is decoded by its byte order mark if it has one. Without one, UTF-16
+//! is recognized by its NUL bytes, valid UTF-8 is taken as UTF-8, and
+//! anything else is decoded in the legacy encoding chardetng guesses from
+//! its byte statistics, such as Windows-1252 or Shift_JIS.
+
+use chardetng::EncodingDetector;
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8};
+
+/// Text decoded to UTF-8
+#[derive(Debug, Clone)]
+pub struct Decoded {
+    pub text: String,
+    /// Encoding the text was decoded from
+    pub encoding: &'static Encoding,
+}
+
+/// Decode text in an unknown encoding
+///
+/// # Arguments
+/// * `bytes` - Raw content of a text file
+///
+/// # Returns
+/// The text without its byte order mark, malformed sequences replaced by
+/// U+FFFD, and the detected encoding
+pub fn decode(bytes: &[u8]) -> Decoded {
+    let (encoding, bom_length) = Encoding::for_bom(bytes).unwrap_or_else(|| (detect(bytes), 0));
+    let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+    Decoded {
+        text: text.into_owned(),
+        encoding,
+    }
+}
+
+/// Detect the encoding of text without a byte order mark
+fn detect(bytes: &[u8]) -> &'static Encoding {
+    // ASCII in UTF-16 is valid UTF-8 too, NUL bytes included
+    if let Some(encoding) = utf16_without_bom(bytes) {
+        return encoding;
+    }
+    if std::str::from_utf8(bytes).is_ok() {
+        return UTF_8;
+    }
+
+    let mut detector = EncodingDetector::new();
+    detector.feed(bytes, true);
+    detector.guess(None, true)
+}
+
+/// Recognize UTF-16 without a byte order mark
+///
+/// Latin text in UTF-16 has a NUL byte in every other position: the high
+/// byte of each code unit, which comes second in little endian and first in
+/// big endian.
+///
+/// # Returns
+/// UTF-16LE or UTF-16BE, or `None` if the bytes don't look like UTF-16
+pub(crate) fn utf16_without_bom(bytes: &[u8]) -> Option<&'static Encoding> {
+    let units = bytes.len() / 2;
+    if units < 2 {
+        return None;
+    }
+
+    let zeros = |offset: usize| bytes.iter().skip(offset).step_by(2).filter(|&&b| b == 0).count();
+    let (even, odd) = (zeros(0), zeros(1));
+    // Mostly NUL bytes on one side and none on the other
+    if even == 0 && odd * 10 >= units * 8 {
+        Some(UTF_16LE)
+    } else if odd == 0 && even * 10 >= units * 8 {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use encoding_rs::{SHIFT_JIS, WINDOWS_1252};
+
+    #[test]
+    fn test_unicode() {
+        let decoded = decode("naïve café".as_bytes());
+        assert_eq!((decoded.text.as_str(), decoded.encoding), ("naïve café", UTF_8));
+
+        // Byte order marks select the encoding and are dropped
+        let decoded = decode(b"\xEF\xBB\xBFplain");
+        assert_eq!((decoded.text.as_str(), decoded.encoding), ("plain", UTF_8));
+        let decoded = decode(b"\xFF\xFEl\x00o\x00g\x00");
+        assert_eq!((decoded.text.as_str(), decoded.encoding), ("log", UTF_16LE));
+        let decoded = decode(b"\xFE\xFF\x00l\x00o\x00g");
+        assert_eq!((decoded.text.as_str(), decoded.encoding), ("log", UTF_16BE));
+
+        // UTF-16 without a byte order mark
+        let decoded = decode(b"E\x00r\x00r\x00o\x00r\x00:\x00 \x00\xE9\x00");
+        assert_eq!((decoded.text.as_str(), decoded.encoding), ("Error: é", UTF_16LE));
+        let decoded = decode(b"\x00O\x00K");
+        assert_eq!((decoded.text.as_str(), decoded.encoding), ("OK", UTF_16BE));
+    }
+
+    #[test]
+    fn test_legacy_encodings() {
+        let text = "Le café était déjà très bon. Nous avons goûté la crème brûlée à côté de la fenêtre.";
+        let (bytes, _, _) = WINDOWS_1252.encode(text);
+        let decoded = decode(&bytes);
+        assert_eq!((decoded.text.as_str(), decoded.encoding), (text, WINDOWS_1252));
+
+        let text = "これは古いテキストファイルです。日本語の文字化けを防ぎます。";
+        let (bytes, _, _) = SHIFT_JIS.encode(text);
+        let decoded = decode(&bytes);
+        assert_eq!((decoded.text.as_str(), decoded.encoding), (text, SHIFT_JIS));
+    }
+
+    #[test]
+    fn test_utf16_without_bom() {
+        assert_eq!(utf16_without_bom(b"a\x00b\x00c\x00"), Some(UTF_16LE));
+        assert_eq!(utf16_without_bom(b"\x00a\x00b\x00c"), Some(UTF_16BE));
+        assert_eq!(utf16_without_bom(b"abcdef"), None);
+        assert_eq!(utf16_without_bom(b"\x00\x00\x00\x00"), None);
+        assert_eq!(utf16_without_bom(b"\x7FELF\x02\x01\x01\x00\x00\x00"), None);
+        assert_eq!(utf16_without_bom(b"a\x00"), None);
+    }
+}
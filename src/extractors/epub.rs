@@ -0,0 +1,380 @@
+//! EPUB e-book extractor
+//!
+//! An EPUB is a zip package whose `META-INF/container.xml` points to the
+//! package document (OPF). The chapters listed in its spine are XHTML
+//! documents, extracted with the [HTML extractor](super::markup) in reading
+//! order. Chapter titles come from the table of contents, the EPUB 3
+//! navigation document or the EPUB 2 NCX, and are kept in the `headings`
+//! metadata entry; the title and author of the book come from the OPF.
+
+use super::markup::extract_html;
+use super::office::{attributes, local_name, open_package, read_part, resolve, xml_properties};
+use super::{Capabilities, ExtractedContent, Extractor};
+use crate::types::FileType;
+use crate::Result;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Book properties of the OPF metadata, by element name
+const OPF_PROPERTIES: &[(&str, &str)] = &[
+    ("title", "title"),
+    ("creator", "author"),
+    ("subject", "subject"),
+    ("description", "description"),
+];
+
+/// EPUB e-books
+pub struct EpubExtractor;
+
+impl Extractor for EpubExtractor {
+    fn name(&self) -> &str {
+        "epub"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Epub]
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["application/epub+zip"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            metadata: true,
+            ..Capabilities::TEXT
+        }
+    }
+
+    fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+        let mut package = open_package(path)?;
+        let not_epub = |reason: &str| crate::Error::Extraction(format!("EPUB extraction failed: {}", reason));
+
+        let container = read_part(&mut package, "META-INF/container.xml")?.ok_or_else(|| not_epub("no container"))?;
+        let opf_path = rootfile(&container).ok_or_else(|| not_epub("no package document"))?;
+        let opf = read_part(&mut package, &opf_path)?.ok_or_else(|| not_epub("missing package document"))?;
+        let opf_dir = opf_path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+        let book = Book::parse(&opf, opf_dir);
+
+        // Chapter titles by document, from the navigation document or NCX
+        let mut toc = HashMap::new();
+        for toc_path in [&book.nav, &book.ncx].into_iter().flatten() {
+            let Some(xml) = read_part(&mut package, toc_path)? else {
+                continue;
+            };
+            let toc_dir = toc_path.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+            for (href, label) in toc_entries(&xml) {
+                let target = href.split('#').next().unwrap_or_default();
+                toc.entry(resolve(toc_dir, &percent_decode(target))).or_insert(label);
+            }
+            if !toc.is_empty() {
+                break;
+            }
+        }
+
+        let mut text = String::new();
+        let mut chapters = Vec::new();
+        for document in &book.spine {
+            let Some(bytes) = read_part(&mut package, document)? else {
+                continue;
+            };
+            let chapter = extract_html(&String::from_utf8_lossy(&bytes));
+            let title = toc.get(document).cloned().or_else(|| {
+                let headings = chapter.metadata.get("headings")?;
+                headings.lines().next().map(str::to_string)
+            });
+            chapters.extend(title);
+            if chapter.text.is_empty() {
+                continue;
+            }
+            if !text.is_empty() {
+                text.push_str("\n\n");
+            }
+            text.push_str(&chapter.text);
+        }
+
+        let mut content = ExtractedContent::new(text);
+        content.metadata.extend(xml_properties(&opf, OPF_PROPERTIES));
+        if !chapters.is_empty() {
+            content.metadata.insert("headings".to_string(), chapters.join("\n"));
+        }
+        Ok(content)
+    }
+}
+
+/// Path of the package document named by `META-INF/container.xml`
+fn rootfile(xml: &[u8]) -> Option<String> {
+    let mut reader = Reader::from_reader(xml);
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(element) | Event::Empty(element)) if local_name(&element) == "rootfile" => {
+                if let Some(path) = attributes(&reader, &element).remove("full-path") {
+                    return Some(resolve("", &percent_decode(&path)));
+                }
+            }
+            Ok(Event::Eof) | Err(_) => return None,
+            _ => {}
+        }
+        buf.clear();
+    }
+}
+
+/// Documents of a book, from its package document
+#[derive(Debug, Default)]
+struct Book {
+    /// Content documents in reading order
+    spine: Vec<String>,
+    /// EPUB 3 navigation document
+    nav: Option<String>,
+    /// EPUB 2 table of contents
+    ncx: Option<String>,
+}
+
+impl Book {
+    fn parse(opf: &[u8], dir: &str) -> Self {
+        let mut reader = Reader::from_reader(opf);
+        // Manifest items by id: path, media type and properties
+        let mut manifest = HashMap::new();
+        let mut spine = Vec::new();
+        let mut ncx_id = None;
+        let mut buf = Vec::new();
+
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(element) | Event::Empty(element)) => {
+                    let mut attributes = attributes(&reader, &element);
+                    match local_name(&element).as_str() {
+                        "item" => {
+                            if let (Some(id), Some(href)) = (attributes.remove("id"), attributes.remove("href")) {
+                                let item = (
+                                    resolve(dir, &percent_decode(&href)),
+                                    attributes.remove("media-type").unwrap_or_default(),
+                                    attributes.remove("properties").unwrap_or_default(),
+                                );
+                                manifest.insert(id, item);
+                            }
+                        }
+                        "spine" => ncx_id = attributes.remove("toc"),
+                        "itemref" => spine.extend(attributes.remove("idref")),
+                        _ => {}
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+
+        let is_nav = |properties: &str| properties.split_whitespace().any(|p| p == "nav");
+        let nav = manifest.values().find(|(_, _, properties)| is_nav(properties)).map(|(path, _, _)| path.clone());
+        let ncx = ncx_id
+            .and_then(|id| manifest.get(&id))
+            .or_else(|| manifest.values().find(|(_, media_type, _)| media_type == "application/x-dtbncx+xml"))
+            .map(|(path, _, _)| path.clone());
+        let spine = spine
+            .iter()
+            .filter_map(|id| manifest.get(id))
+            // The navigation document is a table of contents, not a chapter
+            .filter(|(_, media_type, properties)| {
+                matches!(media_type.as_str(), "application/xhtml+xml" | "text/html") && !is_nav(properties)
+            })
+            .map(|(path, _, _)| path.clone())
+            .collect();
+
+        Self { spine, nav, ncx }
+    }
+}
+
+/// Links and labels of a table of contents, in order
+///
+/// Reads the `toc` navigation of an EPUB 3 navigation document, or the
+/// navigation points of an NCX.
+fn toc_entries(xml: &[u8]) -> Vec<(String, String)> {
+    let mut reader = Reader::from_reader(xml);
+    let mut entries = Vec::new();
+    // Depth within the table of contents navigation, if inside it
+    let mut toc_depth: Option<usize> = None;
+    // Link being read, and its label
+    let mut link: Option<(String, String)> = None;
+    // NCX label being read, and the last one read
+    let mut label: Option<String> = None;
+    let mut last_label = String::new();
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(element)) => {
+                let name = local_name(&element);
+                if let Some(depth) = &mut toc_depth {
+                    *depth += 1;
+                }
+                let mut attributes = attributes(&reader, &element);
+                match name.as_str() {
+                    "nav" if toc_depth.is_none()
+                        && attributes.get("type").is_some_and(|t| t.split_whitespace().any(|t| t == "toc")) =>
+                    {
+                        toc_depth = Some(0);
+                    }
+                    "a" if toc_depth.is_some() => link = attributes.remove("href").map(|href| (href, String::new())),
+                    "text" => label = Some(String::new()),
+                    _ => {}
+                }
+            }
+            Ok(Event::Empty(element)) if local_name(&element) == "content" => {
+                if let Some(src) = attributes(&reader, &element).remove("src") {
+                    entries.push((src, std::mem::take(&mut last_label)));
+                }
+            }
+            Ok(Event::Text(text)) => {
+                let text = text.unescape().unwrap_or_default();
+                if let Some((_, label)) = &mut link {
+                    label.push_str(&text);
+                } else if let Some(label) = &mut label {
+                    label.push_str(&text);
+                }
+            }
+            Ok(Event::End(element)) => {
+                match String::from_utf8_lossy(element.local_name().as_ref()).as_ref() {
+                    "a" => entries.extend(link.take()),
+                    "text" => last_label = label.take().unwrap_or_default(),
+                    _ => {}
+                }
+                toc_depth = match toc_depth {
+                    Some(0) => None,
+                    depth => depth.map(|depth| depth - 1),
+                };
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    entries
+        .into_iter()
+        .map(|(href, label)| (href, label.split_whitespace().collect::<Vec<_>>().join(" ")))
+        .filter(|(_, label)| !label.is_empty())
+        .collect()
+}
+
+/// Decode `%XX` escapes of a URL path
+pub(crate) fn percent_decode(path: &str) -> String {
+    let bytes = path.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let escaped = (bytes[i] == b'%')
+            .then(|| bytes.get(i + 1..i + 3))
+            .flatten()
+            .and_then(|hex| u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok());
+        match escaped {
+            Some(byte) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            None => {
+                decoded.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::office::tests::write_package;
+    use tempfile::TempDir;
+
+    const CONTAINER: &str = r#"<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+        <rootfiles><rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/></rootfiles></container>"#;
+
+    fn chapter(heading: &str, body: &str) -> String {
+        format!(r#"<html xmlns="http://www.w3.org/1999/xhtml"><head><title>Book</title></head><body><h1>{heading}</h1><p>{body}</p></body></html>"#)
+    }
+
+    #[test]
+    fn test_extract_epub3() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("book.epub");
+        let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="3.0">
+            <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>The Harbour</dc:title><dc:creator>Jane Doe</dc:creator></metadata>
+            <manifest>
+              <item id="nav" href="nav.xhtml" media-type="application/xhtml+xml" properties="nav"/>
+              <item id="c1" href="text/chapter%201.xhtml" media-type="application/xhtml+xml"/>
+              <item id="c2" href="text/chapter2.xhtml" media-type="application/xhtml+xml"/>
+              <item id="css" href="style.css" media-type="text/css"/>
+            </manifest>
+            <spine><itemref idref="nav"/><itemref idref="c2"/><itemref idref="c1"/></spine></package>"#;
+        let nav = r#"<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops"><body>
+            <nav epub:type="landmarks"><ol><li><a href="text/chapter2.xhtml">Start</a></li></ol></nav>
+            <nav epub:type="toc"><ol><li><a href="text/chapter%201.xhtml#top">Arrival at <em>dawn</em></a></li></ol></nav></body></html>"#;
+        write_package(
+            &path,
+            &[
+                ("mimetype", "application/epub+zip"),
+                ("META-INF/container.xml", CONTAINER),
+                ("OEBPS/content.opf", opf),
+                ("OEBPS/nav.xhtml", nav),
+                ("OEBPS/text/chapter 1.xhtml", &chapter("One", "The boat came in.")),
+                ("OEBPS/text/chapter2.xhtml", &chapter("Two", "It left again.")),
+            ],
+        );
+
+        let content = EpubExtractor.extract(&path, FileType::Epub).unwrap();
+        assert_eq!(content.text, "Two\nIt left again.\n\nOne\nThe boat came in.");
+        assert_eq!(content.metadata["title"], "The Harbour");
+        assert_eq!(content.metadata["author"], "Jane Doe");
+        // Chapter 2 is not in the table of contents and keeps its heading
+        assert_eq!(content.metadata["headings"], "Two\nArrival at dawn");
+    }
+
+    #[test]
+    fn test_extract_epub2() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("old.epub");
+        let opf = r#"<package xmlns="http://www.idpf.org/2007/opf" version="2.0">
+            <metadata xmlns:dc="http://purl.org/dc/elements/1.1/"><dc:title>Old Book</dc:title></metadata>
+            <manifest>
+              <item id="ncx" href="toc.ncx" media-type="application/x-dtbncx+xml"/>
+              <item id="c1" href="c1.html" media-type="application/xhtml+xml"/>
+            </manifest>
+            <spine toc="ncx"><itemref idref="c1"/></spine></package>"#;
+        let ncx = r#"<ncx xmlns="http://www.daisy.org/z3986/2005/ncx/"><navMap>
+            <navPoint id="p1" playOrder="1"><navLabel><text>Prologue</text></navLabel><content src="c1.html"/></navPoint>
+            </navMap></ncx>"#;
+        write_package(
+            &path,
+            &[
+                ("META-INF/container.xml", CONTAINER),
+                ("OEBPS/content.opf", opf),
+                ("OEBPS/toc.ncx", ncx),
+                ("OEBPS/c1.html", &chapter("Before", "Once upon a time.")),
+            ],
+        );
+
+        let content = EpubExtractor.extract(&path, FileType::Epub).unwrap();
+        assert_eq!(content.text, "Before\nOnce upon a time.");
+        assert_eq!(content.metadata["title"], "Old Book");
+        assert_eq!(content.metadata["headings"], "Prologue");
+        assert!(!content.metadata.contains_key("author"));
+    }
+
+    #[test]
+    fn test_missing_container() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("bad.epub");
+        write_package(&path, &[("mimetype", "application/epub+zip")]);
+        assert!(EpubExtractor.extract(&path, FileType::Epub).is_err());
+    }
+
+    #[test]
+    fn test_percent_decode() {
+        assert_eq!(percent_decode("chapter%201.xhtml"), "chapter 1.xhtml");
+        assert_eq!(percent_decode("caf%C3%A9%2"), "café%2");
+    }
+}
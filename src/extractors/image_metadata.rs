@@ -0,0 +1,384 @@
+//! Camera and capture properties of photos, from EXIF and XMP
+//!
+//! EXIF is read from JPEG, TIFF, PNG, WebP and HEIF files. XMP packets are
+//! found by scanning the file for `<x:xmpmeta>`, which covers every format
+//! embedding them as plain XML, and fill in what EXIF lacks. Dimensions are
+//! read from the image header when the format is supported.
+
+use crate::Result;
+use chrono::{DateTime, NaiveDate, NaiveDateTime};
+use exif::{In, Tag, Value};
+use quick_xml::events::{BytesStart, Event};
+use std::fs;
+use std::path::Path;
+
+/// Camera and capture properties of a photo
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ImageMetadata {
+    pub camera_make: Option<String>,
+    pub camera_model: Option<String>,
+    pub lens: Option<String>,
+    /// Date taken, as a Unix timestamp; times without a zone are read as UTC
+    pub taken_at: Option<i64>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    /// EXIF orientation, from 1 (upright) to 8
+    pub orientation: Option<u16>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+}
+
+impl ImageMetadata {
+    /// Make and model of the camera, e.g. `FUJIFILM X100V`
+    ///
+    /// The make is left out when the model already starts with it, as in
+    /// `Canon` and `Canon EOS R5`.
+    pub fn camera(&self) -> Option<String> {
+        match (&self.camera_make, &self.camera_model) {
+            (Some(make), Some(model)) if model.to_lowercase().starts_with(&make.to_lowercase()) => {
+                Some(model.clone())
+            }
+            (Some(make), Some(model)) => Some(format!("{} {}", make, model)),
+            (make, model) => make.clone().or_else(|| model.clone()),
+        }
+    }
+
+    /// Whether nothing is known about the photo
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Fill in the properties this metadata lacks from another source
+    fn merge(&mut self, other: ImageMetadata) {
+        self.camera_make = self.camera_make.take().or(other.camera_make);
+        self.camera_model = self.camera_model.take().or(other.camera_model);
+        self.lens = self.lens.take().or(other.lens);
+        self.taken_at = self.taken_at.or(other.taken_at);
+        self.width = self.width.or(other.width);
+        self.height = self.height.or(other.height);
+        self.orientation = self.orientation.or(other.orientation);
+        self.latitude = self.latitude.or(other.latitude);
+        self.longitude = self.longitude.or(other.longitude);
+    }
+}
+
+/// Read the EXIF and XMP properties of an image
+///
+/// # Arguments
+/// * `path` - Path to the image
+///
+/// # Returns
+/// The properties found, empty for images without metadata
+pub fn read_image_metadata(path: &Path) -> Result<ImageMetadata> {
+    let bytes = fs::read(path)?;
+
+    let mut metadata = exif::Reader::new()
+        .read_from_container(&mut std::io::Cursor::new(&bytes))
+        .map(|exif| from_exif(&exif))
+        .unwrap_or_default();
+    if let Some(xmp) = find_xmp(&bytes) {
+        metadata.merge(parse_xmp(xmp));
+    }
+
+    // The header is authoritative for the size of the pixels
+    if let Ok((width, height)) = image::ImageReader::new(std::io::Cursor::new(&bytes))
+        .with_guessed_format()
+        .map_err(image::ImageError::from)
+        .and_then(|reader| reader.into_dimensions())
+    {
+        metadata.width = Some(width);
+        metadata.height = Some(height);
+    }
+
+    Ok(metadata)
+}
+
+/// Properties of parsed EXIF data
+fn from_exif(exif: &exif::Exif) -> ImageMetadata {
+    let field = |tag| exif.get_field(tag, In::PRIMARY).map(|f| &f.value);
+    let text = |tag| field(tag).and_then(ascii);
+    let uint = |tag| field(tag).and_then(|v| v.get_uint(0));
+
+    let taken_at = [
+        (Tag::DateTimeOriginal, Tag::OffsetTimeOriginal),
+        (Tag::DateTimeDigitized, Tag::OffsetTimeDigitized),
+        (Tag::DateTime, Tag::OffsetTime),
+    ]
+    .into_iter()
+    .find_map(|(tag, offset_tag)| {
+        let Some(Value::Ascii(values)) = field(tag) else {
+            return None;
+        };
+        let mut date = exif::DateTime::from_ascii(values.first()?).ok()?;
+        if let Some(Value::Ascii(offset)) = field(offset_tag) {
+            let _ = date.parse_offset(offset.first()?);
+        }
+        let naive = NaiveDate::from_ymd_opt(date.year.into(), date.month.into(), date.day.into())?
+            .and_hms_opt(date.hour.into(), date.minute.into(), date.second.into())?;
+        Some(naive.and_utc().timestamp() - i64::from(date.offset.unwrap_or(0)) * 60)
+    });
+
+    let coordinate = |tag, reference_tag, negative: &str| {
+        let Some(Value::Rational(parts)) = field(tag) else {
+            return None;
+        };
+        let degrees = parts.iter().zip([1.0, 60.0, 3600.0]).map(|(part, unit)| part.to_f64() / unit).sum::<f64>();
+        let sign = if text(reference_tag).is_some_and(|r| r.eq_ignore_ascii_case(negative)) { -1.0 } else { 1.0 };
+        degrees.is_finite().then_some(sign * degrees)
+    };
+
+    ImageMetadata {
+        camera_make: text(Tag::Make),
+        camera_model: text(Tag::Model),
+        lens: text(Tag::LensModel),
+        taken_at,
+        width: uint(Tag::PixelXDimension).or_else(|| uint(Tag::ImageWidth)),
+        height: uint(Tag::PixelYDimension).or_else(|| uint(Tag::ImageLength)),
+        orientation: uint(Tag::Orientation).and_then(|o| u16::try_from(o).ok()),
+        latitude: coordinate(Tag::GPSLatitude, Tag::GPSLatitudeRef, "S"),
+        longitude: coordinate(Tag::GPSLongitude, Tag::GPSLongitudeRef, "W"),
+    }
+}
+
+/// First string of an ASCII value, without padding
+fn ascii(value: &Value) -> Option<String> {
+    let Value::Ascii(values) = value else {
+        return None;
+    };
+    let text = String::from_utf8_lossy(values.first()?);
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Find the XMP packet embedded in a file
+fn find_xmp(bytes: &[u8]) -> Option<&[u8]> {
+    const START: &[u8] = b"<x:xmpmeta";
+    const END: &[u8] = b"</x:xmpmeta>";
+    let start = bytes.windows(START.len()).position(|w| w == START)?;
+    let end = bytes[start..].windows(END.len()).position(|w| w == END)?;
+    Some(&bytes[start..start + end + END.len()])
+}
+
+/// Properties of an XMP packet
+///
+/// Properties are written either as attributes of `rdf:Description` or as
+/// elements, possibly wrapping an `rdf:Alt` or `rdf:Seq` list of values.
+fn parse_xmp(xmp: &[u8]) -> ImageMetadata {
+    let mut metadata = ImageMetadata::default();
+    let mut set = |name: &[u8], value: &str| {
+        let value = value.trim();
+        if value.is_empty() {
+            return;
+        }
+        match name {
+            b"tiff:Make" => fill(&mut metadata.camera_make, Some(value.to_string())),
+            b"tiff:Model" => fill(&mut metadata.camera_model, Some(value.to_string())),
+            b"exifEX:LensModel" | b"aux:Lens" => fill(&mut metadata.lens, Some(value.to_string())),
+            b"exif:DateTimeOriginal" | b"photoshop:DateCreated" | b"xmp:CreateDate" => {
+                fill(&mut metadata.taken_at, parse_xmp_date(value))
+            }
+            b"exif:PixelXDimension" | b"tiff:ImageWidth" => fill(&mut metadata.width, value.parse().ok()),
+            b"exif:PixelYDimension" | b"tiff:ImageLength" => fill(&mut metadata.height, value.parse().ok()),
+            b"tiff:Orientation" => fill(&mut metadata.orientation, value.parse().ok()),
+            b"exif:GPSLatitude" => fill(&mut metadata.latitude, parse_xmp_coordinate(value)),
+            b"exif:GPSLongitude" => fill(&mut metadata.longitude, parse_xmp_coordinate(value)),
+            _ => {}
+        }
+    };
+
+    let mut reader = quick_xml::Reader::from_reader(xmp);
+    let mut buf = Vec::new();
+    let mut elements: Vec<Vec<u8>> = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(e)) => {
+                for (name, value) in attribute_properties(&e, &reader) {
+                    set(&name, &value);
+                }
+                elements.push(e.name().as_ref().to_vec());
+            }
+            Ok(Event::Empty(e)) => {
+                for (name, value) in attribute_properties(&e, &reader) {
+                    set(&name, &value);
+                }
+            }
+            Ok(Event::End(_)) => {
+                elements.pop();
+            }
+            Ok(Event::Text(e)) => {
+                // The innermost property around the text, skipping rdf:Alt and rdf:li
+                if let (Some(name), Ok(value)) = (elements.iter().rev().find(|n| !n.starts_with(b"rdf:")), e.unescape()) {
+                    set(name, &value);
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    metadata
+}
+
+/// Properties written as attributes of an `rdf:Description` element
+fn attribute_properties(element: &BytesStart, reader: &quick_xml::Reader<&[u8]>) -> Vec<(Vec<u8>, String)> {
+    if element.name().as_ref() != b"rdf:Description" {
+        return Vec::new();
+    }
+    element
+        .attributes()
+        .flatten()
+        .filter_map(|attribute| {
+            let value = attribute.decode_and_unescape_value(reader).ok()?;
+            Some((attribute.key.as_ref().to_vec(), value.into_owned()))
+        })
+        .collect()
+}
+
+/// Set a property unless it is already known
+fn fill<T>(property: &mut Option<T>, value: Option<T>) {
+    if property.is_none() {
+        *property = value;
+    }
+}
+
+/// Parse an XMP date, e.g. `2023-05-14T10:22:01+02:00` or `2023-05-14`
+fn parse_xmp_date(value: &str) -> Option<i64> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.timestamp());
+    }
+    // Without seconds or zone
+    if let Ok(date) = DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M%:z") {
+        return Some(date.timestamp());
+    }
+    ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%dT%H:%M:%S", "%Y-%m-%dT%H:%M"]
+        .into_iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| NaiveDate::parse_from_str(value, "%Y-%m-%d").ok()?.and_hms_opt(0, 0, 0))
+        .map(|date| date.and_utc().timestamp())
+}
+
+/// Parse an XMP GPS coordinate, `DDD,MM.mmk` or `DDD,MM,SSk` with `k` one of
+/// `N`, `S`, `E` or `W`
+fn parse_xmp_coordinate(value: &str) -> Option<f64> {
+    let direction = value.chars().last()?.to_ascii_uppercase();
+    let sign = match direction {
+        'N' | 'E' => 1.0,
+        'S' | 'W' => -1.0,
+        _ => return None,
+    };
+    let degrees = value[..value.len() - 1]
+        .split(',')
+        .zip([1.0, 60.0, 3600.0])
+        .map(|(part, unit)| part.trim().parse::<f64>().map(|v| v / unit))
+        .sum::<std::result::Result<f64, _>>()
+        .ok()?;
+    Some(sign * degrees)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use exif::experimental::Writer;
+    use exif::{Field, Rational};
+    use tempfile::TempDir;
+
+    fn ascii_field(tag: Tag, value: &str) -> Field {
+        Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+        }
+    }
+
+    /// A 2x1 JPEG with an EXIF segment holding the given fields
+    fn jpeg_with_exif(fields: &[Field]) -> Vec<u8> {
+        let mut writer = Writer::new();
+        for field in fields {
+            writer.push_field(field);
+        }
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let tiff = tiff.into_inner();
+
+        let mut jpeg = Vec::new();
+        image::RgbImage::new(2, 1)
+            .write_to(&mut std::io::Cursor::new(&mut jpeg), image::ImageFormat::Jpeg)
+            .unwrap();
+
+        // APP1 segment right after the start of image marker
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&((tiff.len() + 8) as u16).to_be_bytes());
+        segment.extend_from_slice(b"Exif\0\0");
+        segment.extend_from_slice(&tiff);
+        jpeg.splice(2..2, segment);
+        jpeg
+    }
+
+    #[test]
+    fn test_read_exif() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("photo.jpg");
+        let gps = |degrees, minutes| {
+            Value::Rational(vec![
+                Rational { num: degrees, denom: 1 },
+                Rational { num: minutes, denom: 1 },
+                Rational { num: 0, denom: 1 },
+            ])
+        };
+        let fields = [
+            ascii_field(Tag::Make, "FUJIFILM"),
+            ascii_field(Tag::Model, "X100V"),
+            ascii_field(Tag::LensModel, "23mm F2"),
+            ascii_field(Tag::DateTimeOriginal, "2023:05:14 10:22:01"),
+            ascii_field(Tag::OffsetTimeOriginal, "+02:00"),
+            Field { tag: Tag::Orientation, ifd_num: In::PRIMARY, value: Value::Short(vec![6]) },
+            Field { tag: Tag::GPSLatitude, ifd_num: In::PRIMARY, value: gps(48, 30) },
+            ascii_field(Tag::GPSLatitudeRef, "N"),
+            Field { tag: Tag::GPSLongitude, ifd_num: In::PRIMARY, value: gps(2, 15) },
+            ascii_field(Tag::GPSLongitudeRef, "W"),
+        ];
+        fs::write(&path, jpeg_with_exif(&fields)).unwrap();
+
+        let metadata = read_image_metadata(&path).unwrap();
+        assert_eq!(metadata.camera().as_deref(), Some("FUJIFILM X100V"));
+        assert_eq!(metadata.lens.as_deref(), Some("23mm F2"));
+        // 08:22:01 UTC
+        assert_eq!(metadata.taken_at, Some(1684052521));
+        assert_eq!((metadata.width, metadata.height), (Some(2), Some(1)));
+        assert_eq!(metadata.orientation, Some(6));
+        assert_eq!(metadata.latitude, Some(48.5));
+        assert_eq!(metadata.longitude, Some(-2.25));
+    }
+
+    #[test]
+    fn test_read_without_metadata() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("plain.png");
+        image::RgbImage::new(3, 4).save(&path).unwrap();
+
+        let metadata = read_image_metadata(&path).unwrap();
+        assert_eq!(metadata.camera(), None);
+        assert_eq!((metadata.width, metadata.height), (Some(3), Some(4)));
+    }
+
+    #[test]
+    fn test_parse_xmp() {
+        let xmp = br#"<x:xmpmeta xmlns:x="adobe:ns:meta/"><rdf:RDF>
+            <rdf:Description tiff:Make="Canon" tiff:Model="Canon EOS R5" exif:GPSLatitude="48,30.0S"
+                xmp:CreateDate="2021-12-31T23:00:00-01:00">
+              <exifEX:LensModel>RF24-105mm F4 L IS USM</exifEX:LensModel>
+              <exif:GPSLongitude>2,15,0E</exif:GPSLongitude>
+              <photoshop:DateCreated>2020-01-01</photoshop:DateCreated>
+            </rdf:Description>
+        </rdf:RDF></x:xmpmeta>"#;
+
+        let metadata = parse_xmp(find_xmp(&[b"junk".as_slice(), xmp, b"junk"].concat()).unwrap());
+        assert_eq!(metadata.camera().as_deref(), Some("Canon EOS R5"));
+        assert_eq!(metadata.lens.as_deref(), Some("RF24-105mm F4 L IS USM"));
+        // The first date found wins: 2022-01-01T00:00:00Z
+        assert_eq!(metadata.taken_at, Some(1640995200));
+        assert_eq!(metadata.latitude, Some(-48.5));
+        assert_eq!(metadata.longitude, Some(2.25));
+    }
+}
@@ -0,0 +1,388 @@
+//! Markdown extractor
+//!
+//! Notes are indexed as written, with their structure picked up on the way:
+//! YAML (`---`) or TOML (`+++`) front matter gives the `title`,
+//! `description`, `tags` and `date` metadata, headings go into `headings`,
+//! and `#tags` in the text are added to the front matter tags. Wiki-links
+//! (`[[Note]]`, `[[Note#Heading|alias]]`, `![[embed.png]]`) and links to
+//! relative paths are returned as [`Link`]s, from which the index answers
+//! which notes link to a file.
+
+use super::epub::percent_decode;
+use super::structured::toml_to_json;
+use super::text::PlainTextExtractor;
+use super::{Capabilities, ExtractedContent, Extractor};
+use crate::types::FileType;
+use crate::Result;
+use pulldown_cmark::{LinkType, MetadataBlockKind, Options, Parser, Tag, TagEnd};
+use serde_json::Value;
+use std::path::{Component, Path, PathBuf};
+
+/// Kind of a link between files
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LinkKind {
+    /// `[[Note]]`, naming a note by its file name, without `.md`
+    Wiki,
+    /// `[text](../notes/note.md)`, a path relative to the linking file
+    Path,
+}
+
+impl LinkKind {
+    /// Parse a kind from its name, see [`LinkKind::as_str`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "wiki" => Some(Self::Wiki),
+            "path" => Some(Self::Path),
+            _ => None,
+        }
+    }
+
+    /// Name of the kind, as stored in the index
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Wiki => "wiki",
+            Self::Path => "path",
+        }
+    }
+}
+
+/// A link from a note to another note or file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Link {
+    /// Note name of a wiki-link, or path of a path link
+    pub target: String,
+    pub kind: LinkKind,
+    /// Line of the link, starting at 1
+    pub line: usize,
+}
+
+impl Link {
+    /// The link with a relative path resolved against the linking file
+    ///
+    /// Paths are joined without touching the file system, so that links
+    /// between archive members resolve as well. Wiki-links are returned
+    /// unchanged.
+    pub fn resolve(&self, source: &str) -> Link {
+        if self.kind != LinkKind::Path {
+            return self.clone();
+        }
+
+        let joined = Path::new(source).parent().unwrap_or(Path::new("")).join(&self.target);
+        let mut resolved = PathBuf::new();
+        for component in joined.components() {
+            match component {
+                Component::CurDir => {}
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                component => resolved.push(component),
+            }
+        }
+        Link {
+            target: resolved.to_string_lossy().into_owned(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Markdown notes
+pub struct MarkdownExtractor;
+
+impl Extractor for MarkdownExtractor {
+    fn name(&self) -> &str {
+        "markdown"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Markdown]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            metadata: true,
+            ..Capabilities::TEXT
+        }
+    }
+
+    fn extract(&self, path: &Path, file_type: FileType) -> Result<ExtractedContent> {
+        let mut content = PlainTextExtractor.extract(path, file_type)?;
+        analyze(&mut content);
+        Ok(content)
+    }
+}
+
+/// Add the front matter, headings, tags and links of a note to its content
+pub fn analyze(content: &mut ExtractedContent) {
+    let options = Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS
+        | Options::ENABLE_WIKILINKS
+        | Options::ENABLE_TABLES;
+    let text = &content.text;
+    let line_starts: Vec<usize> = std::iter::once(0)
+        .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let line_at = |offset: usize| line_starts.partition_point(|&start| start <= offset);
+
+    let mut front_matter = None;
+    let mut headings = Vec::new();
+    let mut first_title = None;
+    let mut tags = Vec::new();
+    let mut links = Vec::new();
+    // Kind of the metadata block, and the level of the heading being read
+    let mut metadata_block = None;
+    let mut heading: Option<(pulldown_cmark::HeadingLevel, String)> = None;
+    let mut in_code = false;
+
+    for (event, range) in Parser::new_ext(text, options).into_offset_iter() {
+        match event {
+            pulldown_cmark::Event::Start(Tag::MetadataBlock(kind)) => metadata_block = Some(kind),
+            pulldown_cmark::Event::End(TagEnd::MetadataBlock(_)) => metadata_block = None,
+            pulldown_cmark::Event::Start(Tag::Heading { level, .. }) => heading = Some((level, String::new())),
+            pulldown_cmark::Event::End(TagEnd::Heading(_)) => {
+                if let Some((level, title)) = heading.take() {
+                    let title = title.trim().to_string();
+                    if !title.is_empty() {
+                        if level == pulldown_cmark::HeadingLevel::H1 && first_title.is_none() {
+                            first_title = Some(title.clone());
+                        }
+                        headings.push(title);
+                    }
+                }
+            }
+            pulldown_cmark::Event::Start(Tag::CodeBlock(_)) => in_code = true,
+            pulldown_cmark::Event::End(TagEnd::CodeBlock) => in_code = false,
+            pulldown_cmark::Event::Start(Tag::Link { link_type, dest_url, .. } | Tag::Image { link_type, dest_url, .. }) => {
+                if let Some(link) = link(link_type, &dest_url, line_at(range.start)) {
+                    links.push(link);
+                }
+            }
+            pulldown_cmark::Event::Text(value) => {
+                if let Some(kind) = metadata_block {
+                    front_matter = parse_front_matter(kind, &value);
+                } else if let Some((_, title)) = &mut heading {
+                    title.push_str(&value);
+                } else if !in_code {
+                    inline_tags(&value, &mut tags);
+                }
+            }
+            pulldown_cmark::Event::Code(value) => {
+                if let Some((_, title)) = &mut heading {
+                    title.push_str(&value);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut front_matter = front_matter.unwrap_or_default();
+    let mut front_tags = match front_matter.get("tags").or_else(|| front_matter.get("tag")) {
+        Some(Value::Array(values)) => values.iter().filter_map(scalar).collect(),
+        Some(value) => scalar(value)
+            .map(|tags| tags.split([',', ' ']).map(str::to_string).collect())
+            .unwrap_or_default(),
+        None => Vec::new(),
+    };
+    front_tags.append(&mut tags);
+    let mut all_tags: Vec<String> = Vec::new();
+    for tag in front_tags {
+        let tag = tag.trim().trim_start_matches('#').to_string();
+        if !tag.is_empty() && !all_tags.iter().any(|seen| seen.eq_ignore_ascii_case(&tag)) {
+            all_tags.push(tag);
+        }
+    }
+
+    let metadata = &mut content.metadata;
+    if let Some(title) = front_matter.get("title").and_then(scalar).or(first_title) {
+        metadata.insert("title".to_string(), title);
+    }
+    if let Some(description) = ["description", "summary"].iter().find_map(|key| front_matter.get(*key).and_then(scalar)) {
+        metadata.insert("description".to_string(), description);
+    }
+    if let Some(date) = ["date", "created"].iter().find_map(|key| front_matter.get_mut(*key).map(Value::take)) {
+        if let Some(date) = scalar(&date).and_then(|date| normalize_date(&date)) {
+            metadata.insert("date".to_string(), date);
+        }
+    }
+    if !all_tags.is_empty() {
+        metadata.insert("tags".to_string(), all_tags.join(", "));
+    }
+    if !headings.is_empty() {
+        metadata.insert("headings".to_string(), headings.join("\n"));
+    }
+    content.links = links;
+}
+
+/// A link to another note or a relative path, if the destination is one
+fn link(link_type: LinkType, destination: &str, line: usize) -> Option<Link> {
+    // Links to a heading or block of a note point at the note
+    let (target, kind) = match link_type {
+        LinkType::WikiLink { .. } => (destination.split(['#', '^']).next()?.trim().to_string(), LinkKind::Wiki),
+        LinkType::Autolink | LinkType::Email => return None,
+        _ => {
+            let has_scheme = destination
+                .split_once(':')
+                .is_some_and(|(scheme, _)| !scheme.is_empty() && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c)));
+            if has_scheme || destination.starts_with('/') {
+                return None;
+            }
+            let path = destination.split(['#', '?']).next()?;
+            (percent_decode(path), LinkKind::Path)
+        }
+    };
+    (!target.is_empty()).then_some(Link { target, kind, line })
+}
+
+/// Collect `#tags` from text
+///
+/// A tag starts with `#` at the start of a word and is made of letters,
+/// digits, `_`, `-` and `/`, with at least one character not a digit, so
+/// that `#1` is not a tag.
+fn inline_tags(text: &str, tags: &mut Vec<String>) {
+    let mut previous = ' ';
+    for (i, c) in text.char_indices() {
+        if c == '#' && previous.is_whitespace() {
+            let rest = &text[i + 1..];
+            let end = rest
+                .find(|c: char| !(c.is_alphanumeric() || "_-/".contains(c)))
+                .unwrap_or(rest.len());
+            let tag = &rest[..end];
+            if tag.chars().any(|c| !c.is_ascii_digit()) {
+                tags.push(tag.to_string());
+            }
+        }
+        previous = c;
+    }
+}
+
+/// Parse YAML or TOML front matter into a map of its top-level keys
+fn parse_front_matter(kind: MetadataBlockKind, source: &str) -> Option<serde_json::Map<String, Value>> {
+    let value = match kind {
+        MetadataBlockKind::YamlStyle => serde_yaml::from_str(source).ok()?,
+        MetadataBlockKind::PlusesStyle => toml_to_json(toml::from_str(source).ok()?),
+    };
+    match value {
+        Value::Object(map) => Some(map),
+        _ => None,
+    }
+}
+
+/// Text of a string, number or boolean
+fn scalar(value: &Value) -> Option<String> {
+    let text = match value {
+        Value::String(text) => text.trim().to_string(),
+        Value::Number(number) => number.to_string(),
+        Value::Bool(value) => value.to_string(),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+/// Convert a front matter date to RFC 3339
+///
+/// Accepts RFC 3339 dates as well as `2024-03-01` and `2024-03-01 09:30`;
+/// dates without a time zone are taken as UTC.
+fn normalize_date(date: &str) -> Option<String> {
+    if let Ok(date) = chrono::DateTime::parse_from_rfc3339(date) {
+        return Some(date.to_rfc3339());
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"] {
+        if let Ok(time) = chrono::NaiveDateTime::parse_from_str(date, format) {
+            return Some(time.and_utc().to_rfc3339());
+        }
+    }
+    let date = chrono::NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc().to_rfc3339())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn analyzed(text: &str) -> ExtractedContent {
+        let mut content = ExtractedContent::new(text.to_string());
+        analyze(&mut content);
+        content
+    }
+
+    #[test]
+    fn test_yaml_front_matter() {
+        let content = analyzed(
+            "---\ntitle: Weekly review\ntags: [planning, \"#work\"]\ndate: 2024-03-01\nsummary: What went well\n---\n\n# Wins\n\nShipped the #release/v2 on time. Issue #42 is done.\n\n```\n#not-a-tag\n```\n",
+        );
+        let metadata = |name: &str| content.metadata.get(name).map(String::as_str);
+        assert_eq!(metadata("title"), Some("Weekly review"));
+        assert_eq!(metadata("description"), Some("What went well"));
+        assert_eq!(metadata("tags"), Some("planning, work, release/v2"));
+        assert_eq!(metadata("date"), Some("2024-03-01T00:00:00+00:00"));
+        assert_eq!(metadata("headings"), Some("Wins"));
+        // The text is indexed as written
+        assert!(content.text.starts_with("---\ntitle: Weekly review"));
+    }
+
+    #[test]
+    fn test_toml_front_matter() {
+        let content = analyzed("+++\ntitle = \"Garden log\"\ntags = \"plants, soil\"\ndate = 2024-05-14T09:30:00+02:00\n+++\n\n## Tomatoes `v2`\n\nWatered.\n");
+        let metadata = |name: &str| content.metadata.get(name).map(String::as_str);
+        assert_eq!(metadata("title"), Some("Garden log"));
+        assert_eq!(metadata("tags"), Some("plants, soil"));
+        assert_eq!(metadata("date"), Some("2024-05-14T09:30:00+02:00"));
+        assert_eq!(metadata("headings"), Some("Tomatoes v2"));
+    }
+
+    #[test]
+    fn test_title_from_heading() {
+        let content = analyzed("Intro\n\n# Project Alpha\n\n## Goals\n\n# Appendix\n");
+        assert_eq!(content.metadata.get("title").map(String::as_str), Some("Project Alpha"));
+        assert_eq!(content.metadata.get("headings").map(String::as_str), Some("Project Alpha\nGoals\nAppendix"));
+        assert!(!content.metadata.contains_key("tags"));
+        assert!(!content.metadata.contains_key("date"));
+
+        // Broken front matter is ignored
+        let content = analyzed("---\ntitle: [unclosed\n---\n\nBody\n");
+        assert!(!content.metadata.contains_key("title"));
+    }
+
+    #[test]
+    fn test_links() {
+        let content = analyzed(
+            "See [[Project Alpha]] and [[Meeting notes#Decisions|the decisions]].\n\
+             ![[diagram.png]]\n\
+             [Budget](../finance/budget%202024.md#q1) [Site](https://example.com) <https://example.org>\n\
+             [Top](#top) [mail](mailto:a@example.com) [root](/etc/hosts)\n\n\
+             `[[not a link]]`\n",
+        );
+        let links: Vec<_> = content.links.iter().map(|l| (l.target.as_str(), l.kind, l.line)).collect();
+        assert_eq!(
+            links,
+            vec![
+                ("Project Alpha", LinkKind::Wiki, 1),
+                ("Meeting notes", LinkKind::Wiki, 1),
+                ("diagram.png", LinkKind::Wiki, 2),
+                ("../finance/budget 2024.md", LinkKind::Path, 3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_resolve() {
+        let link = |target: &str, kind| Link {
+            target: target.to_string(),
+            kind,
+            line: 1,
+        };
+        let resolved = link("../finance/budget.md", LinkKind::Path).resolve("/vault/notes/weekly.md");
+        assert_eq!(resolved.target, "/vault/finance/budget.md");
+        let resolved = link("./todo.md", LinkKind::Path).resolve("/backup.zip!/notes/weekly.md");
+        assert_eq!(resolved.target, "/backup.zip!/notes/todo.md");
+        let resolved = link("Project Alpha", LinkKind::Wiki).resolve("/vault/notes/weekly.md");
+        assert_eq!(resolved.target, "Project Alpha");
+    }
+
+    #[test]
+    fn test_normalize_date() {
+        assert_eq!(normalize_date("2024-03-01").as_deref(), Some("2024-03-01T00:00:00+00:00"));
+        assert_eq!(normalize_date("2024-03-01 09:30").as_deref(), Some("2024-03-01T09:30:00+00:00"));
+        assert_eq!(normalize_date("2024-03-01T09:30:00-05:00").as_deref(), Some("2024-03-01T09:30:00-05:00"));
+        assert_eq!(normalize_date("last week"), None);
+    }
+}
@@ -0,0 +1,436 @@
+//! HTML and XML extractors
+//!
+//! Markup is reduced to the text a reader would see: tags are dropped,
+//! entities decoded, whitespace collapsed, and block elements end a line.
+//! Scripts, style sheets and other content that is never displayed are
+//! left out, so that they neither match queries nor end up in snippets.
+//!
+//! The title, description and headings of a page are kept in
+//! [`ExtractedContent::metadata`] under `title`, `description` and
+//! `headings` (one heading per line), where the index picks them up as
+//! fields of their own.
+
+use super::{Capabilities, ExtractedContent, Extractor};
+use crate::types::FileType;
+use crate::Result;
+use quick_xml::events::Event;
+use quick_xml::Reader;
+use scraper::{ElementRef, Html, Node, Selector};
+use std::fs;
+use std::path::Path;
+
+/// Elements whose content is not displayed
+const HIDDEN_ELEMENTS: &[&str] = &["head", "script", "style", "noscript", "template"];
+
+/// Elements that start and end a line
+const BLOCK_ELEMENTS: &[&str] = &[
+    "address", "article", "aside", "blockquote", "br", "caption", "dd", "details", "div", "dl", "dt",
+    "fieldset", "figcaption", "figure", "footer", "form", "h1", "h2", "h3", "h4", "h5", "h6", "header",
+    "hr", "li", "main", "nav", "ol", "p", "pre", "section", "summary", "table", "tr", "ul",
+];
+
+/// HTML pages, including saved web pages and XHTML
+pub struct HtmlExtractor;
+
+impl Extractor for HtmlExtractor {
+    fn name(&self) -> &str {
+        "html"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Html]
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["text/html", "application/xhtml+xml"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            metadata: true,
+            ..Capabilities::TEXT
+        }
+    }
+
+    fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+        let bytes = fs::read(path)?;
+        Ok(extract_html(&String::from_utf8_lossy(&bytes)))
+    }
+}
+
+/// XML documents such as feeds, SVG drawings or configuration
+pub struct XmlExtractor;
+
+impl Extractor for XmlExtractor {
+    fn name(&self) -> &str {
+        "xml"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Xml]
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["text/xml", "application/xml", "image/svg+xml", "application/rss+xml", "application/atom+xml"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            metadata: true,
+            ..Capabilities::TEXT
+        }
+    }
+
+    fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+        extract_xml(&fs::read(path)?)
+    }
+}
+
+/// Extract the text and properties of an HTML page
+pub fn extract_html(html: &str) -> ExtractedContent {
+    let document = Html::parse_document(html);
+
+    let mut text = TextBuffer::default();
+    text.append_children(document.root_element());
+    let mut content = ExtractedContent::new(text.finish());
+
+    let headings: Vec<String> = document
+        .select(&selector("h1, h2, h3, h4, h5, h6"))
+        .map(|heading| collapse_whitespace(&heading.text().collect::<String>()))
+        .filter(|heading| !heading.is_empty())
+        .collect();
+
+    let title = document
+        .select(&selector("title"))
+        .next()
+        .map(|title| collapse_whitespace(&title.text().collect::<String>()))
+        .filter(|title| !title.is_empty())
+        .or_else(|| meta_content(&document, "og:title"))
+        .or_else(|| headings.first().cloned());
+    let properties = [
+        ("title", title),
+        (
+            "description",
+            meta_content(&document, "description").or_else(|| meta_content(&document, "og:description")),
+        ),
+        ("author", meta_content(&document, "author")),
+        ("keywords", meta_content(&document, "keywords")),
+        ("headings", (!headings.is_empty()).then(|| headings.join("\n"))),
+    ];
+    for (name, value) in properties {
+        if let Some(value) = value {
+            content.metadata.insert(name.to_string(), value);
+        }
+    }
+
+    content
+}
+
+/// Extract the text of an XML document
+///
+/// The first `title` and `description` elements, if any, give the
+/// document's properties, as in RSS and Atom feeds or SVG drawings.
+/// Malformed documents yield the text read up to the error.
+pub fn extract_xml(bytes: &[u8]) -> Result<ExtractedContent> {
+    let mut reader = Reader::from_reader(bytes);
+    let mut text = TextBuffer::default();
+    let mut properties: Vec<(&str, String)> = Vec::new();
+    // Property being read, and the depth of hidden elements being skipped
+    let mut property: Option<(&str, String)> = None;
+    let mut hidden = 0;
+    let mut buf = Vec::new();
+
+    loop {
+        let event = match reader.read_event_into(&mut buf) {
+            Ok(event) => event,
+            Err(e) if text.is_empty() => {
+                return Err(crate::Error::Extraction(format!("XML extraction failed: {}", e)));
+            }
+            Err(e) => {
+                tracing::debug!("Stopping at malformed XML: {}", e);
+                break;
+            }
+        };
+
+        match event {
+            Event::Start(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_lowercase();
+                if hidden > 0 || HIDDEN_ELEMENTS.contains(&name.as_str()) {
+                    hidden += 1;
+                } else if property.is_none() {
+                    property = ["title", "description"]
+                        .into_iter()
+                        .find(|p| *p == name && properties.iter().all(|(seen, _)| seen != p))
+                        .map(|p| (p, String::new()));
+                }
+            }
+            Event::End(_) if hidden > 0 => hidden -= 1,
+            Event::End(element) => {
+                let name = String::from_utf8_lossy(element.local_name().as_ref()).to_lowercase();
+                if let Some((p, value)) = property.take_if(|(p, _)| *p == name) {
+                    properties.push((p, collapse_whitespace(&value)));
+                }
+                text.break_line();
+            }
+            Event::Text(_) | Event::CData(_) if hidden > 0 => {}
+            Event::Text(content) => {
+                let content = match content.unescape() {
+                    Ok(content) => content.into_owned(),
+                    // Entities declared in a DTD
+                    Err(_) => String::from_utf8_lossy(&content).into_owned(),
+                };
+                if let Some((_, value)) = &mut property {
+                    value.push_str(&content);
+                }
+                text.push(&content);
+            }
+            Event::CData(content) => {
+                let content = String::from_utf8_lossy(&content);
+                if let Some((_, value)) = &mut property {
+                    value.push_str(&content);
+                }
+                text.push(&content);
+            }
+            Event::Eof => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    let mut content = ExtractedContent::new(text.finish());
+    for (name, value) in properties {
+        if !value.is_empty() {
+            content.metadata.insert(name.to_string(), value);
+        }
+    }
+    Ok(content)
+}
+
+/// Compile a selector known to be valid
+fn selector(selectors: &str) -> Selector {
+    Selector::parse(selectors).expect("valid selector")
+}
+
+/// Content of the `<meta>` element with the given name or property
+fn meta_content(document: &Html, name: &str) -> Option<String> {
+    document
+        .select(&selector("meta[content]"))
+        .find(|meta| {
+            let element = meta.value();
+            [element.attr("name"), element.attr("property")]
+                .into_iter()
+                .flatten()
+                .any(|n| n.eq_ignore_ascii_case(name))
+        })
+        .and_then(|meta| meta.value().attr("content"))
+        .map(collapse_whitespace)
+        .filter(|content| !content.is_empty())
+}
+
+/// Replace runs of whitespace with a single space and trim the ends
+fn collapse_whitespace(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Text being assembled from markup, with collapsed whitespace
+#[derive(Default)]
+struct TextBuffer {
+    text: String,
+    /// Whitespace seen since the last word
+    pending_space: bool,
+}
+
+impl TextBuffer {
+    /// Append the displayed text of an HTML element's children
+    fn append_children(&mut self, element: ElementRef) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => self.push(text),
+                Node::Element(_) => {
+                    if let Some(element) = ElementRef::wrap(child) {
+                        self.append_element(element);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Append the displayed text of an HTML element
+    fn append_element(&mut self, element: ElementRef) {
+        let name = element.value().name();
+        if HIDDEN_ELEMENTS.contains(&name) {
+            return;
+        }
+
+        let block = BLOCK_ELEMENTS.contains(&name);
+        if block {
+            self.break_line();
+        }
+        match name {
+            "pre" => self.push_preformatted(&element.text().collect::<String>()),
+            "img" => self.push(element.value().attr("alt").unwrap_or_default()),
+            "td" | "th" => {
+                self.pending_space = true;
+                self.append_children(element);
+                self.pending_space = true;
+            }
+            _ => self.append_children(element),
+        }
+        if block {
+            self.break_line();
+        }
+    }
+
+    /// Append text, collapsing its whitespace
+    fn push(&mut self, text: &str) {
+        if text.starts_with(char::is_whitespace) {
+            self.pending_space = true;
+        }
+        for word in text.split_whitespace() {
+            self.push_word(word);
+            self.pending_space = true;
+        }
+        if !text.ends_with(char::is_whitespace) && !text.trim().is_empty() {
+            self.pending_space = false;
+        }
+    }
+
+    /// Append text as is, keeping its line breaks and indentation
+    fn push_preformatted(&mut self, text: &str) {
+        self.text.push_str(text.trim_matches('\n'));
+        self.pending_space = false;
+        self.break_line();
+    }
+
+    fn push_word(&mut self, word: &str) {
+        if self.pending_space && !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push(' ');
+        }
+        self.text.push_str(word);
+        self.pending_space = false;
+    }
+
+    /// End the current line, unless it is empty
+    fn break_line(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+        self.pending_space = false;
+    }
+
+    fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    fn finish(self) -> String {
+        self.text.trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_extract_html() {
+        let content = extract_html(
+            r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+  <title>  Deployment
+    Runbook </title>
+  <meta name="Description" content="How we ship the billing service">
+  <meta property="og:title" content="Ignored">
+  <style>body { color: red; }</style>
+  <script>var tracking = "pixel";</script>
+</head>
+<body>
+  <h1>Runbook</h1>
+  <p>Run <code>make deploy</code> from the&nbsp;release branch &amp; wait.</p>
+  <script>console.log("hidden");</script>
+  <ul><li>Check <b>logs</b></li><li>Ping on-call</li></ul>
+  <h2>Rollback</h2>
+  <table><tr><th>Step</th><th>Owner</th></tr><tr><td>Revert</td><td>SRE</td></tr></table>
+  <pre>git revert HEAD
+  git push</pre>
+  <img src="diagram.png" alt="Architecture diagram">
+</body>
+</html>"#,
+        );
+
+        assert_eq!(
+            content.text,
+            "Runbook\n\
+             Run make deploy from the release branch & wait.\n\
+             Check logs\n\
+             Ping on-call\n\
+             Rollback\n\
+             Step Owner\n\
+             Revert SRE\n\
+             git revert HEAD\n  git push\n\
+             Architecture diagram"
+        );
+        assert!(!content.text.contains("tracking") && !content.text.contains("color"));
+        assert_eq!(content.metadata["title"], "Deployment Runbook");
+        assert_eq!(content.metadata["description"], "How we ship the billing service");
+        assert_eq!(content.metadata["headings"], "Runbook\nRollback");
+        assert!(!content.metadata.contains_key("author"));
+    }
+
+    #[test]
+    fn test_extract_html_fallback_title() {
+        let content = extract_html("<h1>Release notes</h1><p>Fixed <i>everything</i>.</p>");
+        assert_eq!(content.text, "Release notes\nFixed everything.");
+        assert_eq!(content.metadata["title"], "Release notes");
+        assert_eq!(content.word_count, 4);
+    }
+
+    #[test]
+    fn test_extract_xml() {
+        let content = extract_xml(
+            br#"<?xml version="1.0" encoding="UTF-8"?>
+<rss version="2.0">
+  <channel>
+    <title>Team &amp; Company News</title>
+    <description>Weekly updates</description>
+    <item>
+      <title>Office move</title>
+      <description><![CDATA[We move to <b>building C</b> on Monday]]></description>
+    </item>
+  </channel>
+</rss>"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            content.text,
+            "Team & Company News\nWeekly updates\nOffice move\nWe move to <b>building C</b> on Monday"
+        );
+        assert_eq!(content.metadata["title"], "Team & Company News");
+        assert_eq!(content.metadata["description"], "Weekly updates");
+    }
+
+    #[test]
+    fn test_extract_malformed_xml() {
+        let content = extract_xml(b"<notes><note>Keep this</note></wrong>").unwrap();
+        assert_eq!(content.text, "Keep this");
+        assert!(extract_xml(b"<a></b>").is_err());
+    }
+
+    #[test]
+    fn test_extractor_dispatch() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("drawing.svg");
+        fs::write(&path, r#"<svg xmlns="http://www.w3.org/2000/svg"><title>Floor plan</title><style>.a{}</style><text>Kitchen</text></svg>"#).unwrap();
+
+        let registry = super::super::ExtractorRegistry::builtin();
+        assert_eq!(registry.find(&path, FileType::Xml).unwrap().name(), "xml");
+        assert_eq!(registry.find(Path::new("page.htm"), FileType::Html).unwrap().name(), "html");
+
+        let content = registry.extract(&path, FileType::Xml).unwrap();
+        assert_eq!(content.text, "Floor plan\nKitchen");
+        assert_eq!(content.metadata["title"], "Floor plan");
+    }
+}
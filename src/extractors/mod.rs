@@ -1,5 +1,38 @@
 //! Content extractors for different file types
+//!
+//! Extraction is dispatched through an [`ExtractorRegistry`]; see
+//! [`registry`] for adding extractors for new formats.
 
+pub mod code;
+pub mod docx;
+pub mod email;
+pub mod encoding;
+pub mod epub;
+pub mod image_metadata;
+pub mod markdown;
+pub mod markup;
+pub mod notebook;
+pub mod ocr;
+pub mod office;
+pub mod registry;
+pub mod rtf;
+pub mod spreadsheet;
+pub mod structured;
 pub mod text;
 
-pub use text::{extract_text, extract_snippet, ExtractedContent};
\ No newline at end of file
+pub use code::{CodeExtractor, Symbol, SymbolKind};
+pub use docx::DocxExtractor;
+pub use email::EmailExtractor;
+pub use encoding::Decoded;
+pub use epub::EpubExtractor;
+pub use image_metadata::{read_image_metadata, ImageMetadata};
+pub use markdown::{Link, LinkKind, MarkdownExtractor};
+pub use markup::{HtmlExtractor, XmlExtractor};
+pub use notebook::NotebookExtractor;
+pub use ocr::{OcrExtractor, OcrModel};
+pub use office::{OpenDocumentExtractor, PptxExtractor};
+pub use registry::{Capabilities, Extractor, ExtractorRegistry};
+pub use rtf::RtfExtractor;
+pub use spreadsheet::SpreadsheetExtractor;
+pub use structured::{KeyTerm, KeyValue, StructuredExtractor};
+pub use text::{extract_text, extract_snippet, match_offset, ExtractedContent, Page};
@@ -0,0 +1,274 @@
+//! Jupyter notebook extractor
+//!
+//! Notebooks are read cell by cell rather than as the JSON they are stored
+//! in. Each cell becomes a page of the content, so that search results name
+//! the cell of a match. Code cells are analyzed like source code in the
+//! kernel's language, and markdown cells give the notebook its title and
+//! headings. Text outputs follow the code that printed them, unless turned
+//! off; images and HTML outputs are left out.
+
+use super::{code, markdown, Capabilities, ExtractedContent, Extractor};
+use crate::types::FileType;
+use crate::Result;
+use serde_json::Value;
+use std::path::Path;
+
+/// Maximum number of characters of output kept per cell, so that long
+/// training logs don't drown the code
+const MAX_OUTPUT_CHARS: usize = 10_000;
+
+/// Jupyter notebooks (nbformat 4)
+pub struct NotebookExtractor {
+    /// Index the text output of code cells
+    pub outputs: bool,
+}
+
+impl Default for NotebookExtractor {
+    fn default() -> Self {
+        Self { outputs: true }
+    }
+}
+
+impl Extractor for NotebookExtractor {
+    fn name(&self) -> &str {
+        "notebook"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ipynb"]
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["application/x-ipynb+json"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            text: true,
+            metadata: true,
+            pages: true,
+        }
+    }
+
+    fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+        let notebook: Value = serde_json::from_slice(&std::fs::read(path)?)
+            .map_err(|e| crate::Error::Extraction(format!("Notebook extraction failed: {}", e)))?;
+        let cells = notebook["cells"]
+            .as_array()
+            .ok_or_else(|| crate::Error::Extraction("Notebook extraction failed: no cells, nbformat 4 expected".to_string()))?;
+
+        let mut texts = Vec::with_capacity(cells.len());
+        let mut markdown_text = String::new();
+        let mut code_cells = Vec::new();
+        for (i, cell) in cells.iter().enumerate() {
+            let source = multiline(&cell["source"]);
+            let mut text = source.clone();
+            match cell["cell_type"].as_str() {
+                Some("markdown") => {
+                    markdown_text.push_str(&source);
+                    markdown_text.push_str("\n\n");
+                }
+                Some("code") => {
+                    code_cells.push((i, source));
+                    if self.outputs {
+                        let output = outputs(&cell["outputs"]);
+                        if !output.is_empty() {
+                            if !text.is_empty() && !text.ends_with('\n') {
+                                text.push('\n');
+                            }
+                            text.push_str(&output);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            texts.push(text);
+        }
+
+        let mut content = ExtractedContent::from_pages(texts);
+        let metadata = &notebook["metadata"];
+        content.language = metadata["kernelspec"]["language"]
+            .as_str()
+            .or_else(|| metadata["language_info"]["name"].as_str())
+            .map(str::to_lowercase);
+
+        let mut notes = ExtractedContent::new(markdown_text);
+        markdown::analyze(&mut notes);
+        for name in ["title", "headings"] {
+            if let Some(value) = notes.metadata.remove(name) {
+                content.metadata.insert(name.to_string(), value);
+            }
+        }
+        if let Some(language) = content.language.clone() {
+            analyze_code(&mut content, &code_cells, &language);
+        }
+        Ok(content)
+    }
+}
+
+/// Analyze the code cells of a notebook as one source file
+///
+/// Definitions get the line numbers they have in the content text.
+///
+/// # Arguments
+/// * `content` - Content of the notebook, with a page per cell
+/// * `code_cells` - Index and source of each code cell
+/// * `language` - Language of the notebook's kernel
+fn analyze_code(content: &mut ExtractedContent, code_cells: &[(usize, String)], language: &str) {
+    // First line of each cell in the joined code and in the content text
+    let mut starts = Vec::with_capacity(code_cells.len());
+    let mut code = String::new();
+    for (index, source) in code_cells {
+        let page_start = content.pages[*index].start;
+        let content_line = content.text[..page_start].matches('\n').count() + 1;
+        starts.push((code.matches('\n').count() + 1, content_line));
+        code.push_str(source);
+        code.push('\n');
+    }
+
+    let mut analyzed = ExtractedContent::new(code);
+    code::analyze(&mut analyzed, language);
+    content.metadata.extend(analyzed.metadata);
+    content.symbols = analyzed
+        .symbols
+        .into_iter()
+        .map(|mut symbol| {
+            let cell = starts.partition_point(|&(code_line, _)| code_line <= symbol.line) - 1;
+            let (code_line, content_line) = starts[cell];
+            symbol.line = content_line + symbol.line - code_line;
+            symbol
+        })
+        .collect();
+}
+
+/// Text of a notebook string, stored as a string or a list of lines
+fn multiline(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Array(lines) => lines.iter().filter_map(Value::as_str).collect(),
+        _ => String::new(),
+    }
+}
+
+/// Text of the outputs of a code cell: printed text, plain text results and
+/// errors
+fn outputs(outputs: &Value) -> String {
+    let mut text = String::new();
+    for output in outputs.as_array().into_iter().flatten() {
+        let part = match output["output_type"].as_str() {
+            Some("stream") => multiline(&output["text"]),
+            Some("execute_result" | "display_data") => multiline(&output["data"]["text/plain"]),
+            Some("error") => format!(
+                "{}: {}",
+                output["ename"].as_str().unwrap_or_default(),
+                output["evalue"].as_str().unwrap_or_default()
+            ),
+            _ => continue,
+        };
+        if part.is_empty() {
+            continue;
+        }
+        text.push_str(&part);
+        if !text.ends_with('\n') {
+            text.push('\n');
+        }
+    }
+
+    match text.char_indices().nth(MAX_OUTPUT_CHARS) {
+        Some((end, _)) => text[..end].to_string(),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::extractors::SymbolKind;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    /// Write a notebook with a Python kernel
+    pub(crate) fn write_notebook(path: &Path, cells: Value) {
+        let notebook = json!({
+            "cells": cells,
+            "metadata": {
+                "kernelspec": {"display_name": "Python 3", "language": "python", "name": "python3"},
+                "language_info": {"name": "python"}
+            },
+            "nbformat": 4,
+            "nbformat_minor": 5
+        });
+        std::fs::write(path, notebook.to_string()).unwrap();
+    }
+
+    fn analysis() -> Value {
+        json!([
+            {"cell_type": "markdown", "metadata": {}, "source": ["# Churn analysis\n", "\n", "## Loading data"]},
+            {"cell_type": "code", "execution_count": 1, "metadata": {}, "source": "import pandas as pd\ndf = pd.read_csv('churn.csv')\ndf.shape",
+             "outputs": [{"output_type": "execute_result", "execution_count": 1, "metadata": {}, "data": {"text/plain": ["(7043, 21)"]}}]},
+            {"cell_type": "code", "execution_count": 2, "metadata": {}, "source": ["# Share of customers lost\n", "def churn_rate(frame):\n", "    return frame.churned.mean()\n", "print(churn_rate(df))"],
+             "outputs": [
+                 {"output_type": "stream", "name": "stdout", "text": ["0.265\n"]},
+                 {"output_type": "display_data", "metadata": {}, "data": {"image/png": "iVBORw0KGgoAAAANSUhEUgAAAAEAAAAB", "text/plain": ["<Figure size 640x480 with 1 Axes>"]}}
+             ]},
+            {"cell_type": "raw", "metadata": {}, "source": "Draft"},
+            {"cell_type": "code", "execution_count": 3, "metadata": {}, "source": "df.missing()",
+             "outputs": [{"output_type": "error", "ename": "AttributeError", "evalue": "'DataFrame' object has no attribute 'missing'", "traceback": ["\u{1b}[0;31mAttributeError"]}]}
+        ])
+    }
+
+    #[test]
+    fn test_extract_notebook() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("churn.ipynb");
+        write_notebook(&path, analysis());
+
+        let content = NotebookExtractor::default().extract(&path, FileType::Unknown).unwrap();
+        assert_eq!(content.pages.len(), 5);
+        assert_eq!(content.page_text(1), Some("# Churn analysis\n\n## Loading data"));
+        assert_eq!(
+            content.page_text(2),
+            Some("import pandas as pd\ndf = pd.read_csv('churn.csv')\ndf.shape\n(7043, 21)\n")
+        );
+        assert_eq!(content.page_text(4), Some("Draft"));
+        assert!(content.page_text(3).unwrap().ends_with("print(churn_rate(df))\n0.265\n<Figure size 640x480 with 1 Axes>\n"));
+        assert!(content.page_text(5).unwrap().contains("AttributeError: 'DataFrame' object has no attribute 'missing'"));
+        assert!(!content.text.contains("iVBOR"));
+        assert!(!content.text.contains("\"cell_type\""));
+
+        assert_eq!(content.language.as_deref(), Some("python"));
+        let metadata = |name: &str| content.metadata.get(name).map(String::as_str);
+        assert_eq!(metadata("title"), Some("Churn analysis"));
+        assert_eq!(metadata("headings"), Some("Churn analysis\nLoading data"));
+        assert_eq!(metadata("comments"), Some("Share of customers lost"));
+        assert!(metadata("identifiers").unwrap().contains("read_csv"));
+
+        // Definitions point at their line in the text
+        assert_eq!(content.symbols.len(), 1);
+        let symbol = &content.symbols[0];
+        assert_eq!((symbol.name.as_str(), symbol.kind), ("churn_rate", SymbolKind::Function));
+        assert_eq!(content.text.lines().nth(symbol.line - 1), Some("def churn_rate(frame):"));
+    }
+
+    #[test]
+    fn test_without_outputs() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("churn.ipynb");
+        write_notebook(&path, analysis());
+
+        let content = NotebookExtractor { outputs: false }.extract(&path, FileType::Unknown).unwrap();
+        assert_eq!(content.page_text(2), Some("import pandas as pd\ndf = pd.read_csv('churn.csv')\ndf.shape"));
+        assert!(!content.text.contains("0.265"));
+        assert!(!content.text.contains("AttributeError"));
+    }
+
+    #[test]
+    fn test_invalid_notebook() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("old.ipynb");
+        std::fs::write(&path, r#"{"worksheets": [], "nbformat": 3}"#).unwrap();
+        assert!(NotebookExtractor::default().extract(&path, FileType::Unknown).is_err());
+        std::fs::write(&path, "not json").unwrap();
+        assert!(NotebookExtractor::default().extract(&path, FileType::Unknown).is_err());
+    }
+}
@@ -0,0 +1,544 @@
+//! Optical character recognition for images and scanned PDF pages
+//!
+//! Runs a text detection and a text recognition model in ONNX format, as
+//! exported from PaddleOCR. The detection model outputs, for every pixel,
+//! the probability that it belongs to text; each connected region of likely
+//! text is cropped from the image and read by the recognition model, whose
+//! output is decoded with CTC against a character dictionary. Recognized
+//! words are arranged back into lines in reading order.
+//!
+//! OCR is not part of the built-in extractors since it needs the models;
+//! register an [`OcrExtractor`] to enable it.
+
+use super::text::{extract_pdf_pages, pdf_metadata, ExtractedContent};
+use super::{Capabilities, Extractor};
+use crate::embedding::find_model_path;
+use crate::types::FileType;
+use crate::Result;
+use image::{DynamicImage, GenericImageView, GrayImage, RgbImage};
+use ort::session::{builder::GraphOptimizationLevel, Session};
+use ort::value::Value;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+/// File name of the text detection model
+pub const DETECTION_MODEL: &str = "ocr_det.onnx";
+
+/// File name of the text recognition model
+pub const RECOGNITION_MODEL: &str = "ocr_rec.onnx";
+
+/// File name of the character dictionary of the recognition model
+pub const DICTIONARY: &str = "ocr_keys.txt";
+
+/// Longest side of the image fed to the detection model
+const MAX_DETECTION_SIDE: u32 = 960;
+
+/// Probability above which a pixel of the detection map is text
+const PIXEL_THRESHOLD: f32 = 0.3;
+
+/// Mean probability above which a detected region is kept
+const BOX_THRESHOLD: f32 = 0.6;
+
+/// Growth of detected regions, which the model outputs shrunk
+const UNCLIP_RATIO: f32 = 1.5;
+
+/// Height of the text lines fed to the recognition model
+const RECOGNITION_HEIGHT: u32 = 48;
+
+/// Mean confidence below which a recognized word is dropped
+const MIN_CONFIDENCE: f32 = 0.5;
+
+/// Text detection and recognition models
+pub struct OcrModel {
+    detection: Session,
+    recognition: Session,
+    /// Characters by class index of the recognition model, without the
+    /// CTC blank at index 0
+    dictionary: Vec<String>,
+}
+
+/// Region of an image containing a word or a line of text
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct TextBox {
+    x0: u32,
+    y0: u32,
+    x1: u32,
+    y1: u32,
+}
+
+impl OcrModel {
+    /// Load the OCR models
+    ///
+    /// # Arguments
+    /// * `detection_path` - Path to the text detection model
+    /// * `recognition_path` - Path to the text recognition model
+    /// * `dictionary_path` - Path to the character dictionary, one character per line
+    pub fn new<P: AsRef<Path>>(detection_path: P, recognition_path: P, dictionary_path: P) -> Result<Self> {
+        let mut dictionary: Vec<String> = fs::read_to_string(dictionary_path.as_ref())?
+            .lines()
+            .map(|line| line.trim_end_matches('\r').to_string())
+            .collect();
+        // PaddleOCR models have an extra class for spaces after the dictionary
+        dictionary.push(" ".to_string());
+
+        Ok(Self {
+            detection: load_session(detection_path.as_ref())?,
+            recognition: load_session(recognition_path.as_ref())?,
+            dictionary,
+        })
+    }
+
+    /// Load the OCR models from the usual model locations
+    ///
+    /// # Returns
+    /// `None` if any of the model files is missing, see
+    /// [`find_model_path`]
+    pub fn find() -> Option<Result<Self>> {
+        let detection = find_model_path(DETECTION_MODEL)?;
+        let recognition = find_model_path(RECOGNITION_MODEL)?;
+        let dictionary = find_model_path(DICTIONARY)?;
+        Some(Self::new(detection, recognition, dictionary))
+    }
+
+    /// Recognize the text of an image
+    ///
+    /// # Returns
+    /// The recognized text, one line of the image per line
+    pub fn recognize(&mut self, img: &DynamicImage) -> Result<String> {
+        let mut words = Vec::new();
+        for text_box in self.detect(img)? {
+            let crop = img.crop_imm(text_box.x0, text_box.y0, text_box.x1 - text_box.x0, text_box.y1 - text_box.y0);
+            if let Some((word, confidence)) = self.read(&crop)? {
+                if confidence >= MIN_CONFIDENCE && !word.trim().is_empty() {
+                    words.push((text_box, word.trim().to_string()));
+                }
+            }
+        }
+        Ok(arrange_lines(words))
+    }
+
+    /// Find the regions of an image containing text
+    fn detect(&mut self, img: &DynamicImage) -> Result<Vec<TextBox>> {
+        let (width, height) = img.dimensions();
+        if width == 0 || height == 0 {
+            return Ok(Vec::new());
+        }
+
+        // The model needs sides that are multiples of 32
+        let scale = (MAX_DETECTION_SIDE as f32 / width.max(height) as f32).min(1.0);
+        let map_width = round_to_32(width as f32 * scale);
+        let map_height = round_to_32(height as f32 * scale);
+        let resized = img
+            .resize_exact(map_width, map_height, image::imageops::FilterType::Triangle)
+            .to_rgb8();
+
+        // ImageNet normalization, on BGR channels like PaddleOCR
+        let mean = [0.485, 0.456, 0.406];
+        let std = [0.229, 0.224, 0.225];
+        let plane = (map_width * map_height) as usize;
+        let mut data = vec![0.0f32; 3 * plane];
+        for (x, y, pixel) in resized.enumerate_pixels() {
+            let offset = (y * map_width + x) as usize;
+            for c in 0..3 {
+                data[c * plane + offset] = (pixel[2 - c] as f32 / 255.0 - mean[c]) / std[c];
+            }
+        }
+
+        let input = Value::from_array((vec![1, 3, map_height as usize, map_width as usize], data))?;
+        let outputs = self.detection.run(ort::inputs![input])?;
+        let (_shape, probabilities) = outputs[0].try_extract_tensor::<f32>()?;
+
+        let boxes = find_text_boxes(probabilities, map_width, map_height);
+        drop(outputs);
+
+        // Back to the coordinates of the original image
+        let (sx, sy) = (width as f32 / map_width as f32, height as f32 / map_height as f32);
+        Ok(boxes
+            .into_iter()
+            .map(|b| TextBox {
+                x0: ((b.x0 as f32 * sx) as u32).min(width - 1),
+                y0: ((b.y0 as f32 * sy) as u32).min(height - 1),
+                x1: ((b.x1 as f32 * sx).ceil() as u32).clamp(1, width),
+                y1: ((b.y1 as f32 * sy).ceil() as u32).clamp(1, height),
+            })
+            .filter(|b| b.x1 > b.x0 && b.y1 > b.y0)
+            .collect())
+    }
+
+    /// Read the text of a cropped region
+    ///
+    /// # Returns
+    /// The text and the mean confidence of its characters, or `None` if
+    /// nothing was read
+    fn read(&mut self, crop: &DynamicImage) -> Result<Option<(String, f32)>> {
+        let (width, height) = crop.dimensions();
+        let line_width = ((RECOGNITION_HEIGHT as f32 * width as f32 / height as f32).ceil() as u32).clamp(16, 3200);
+        let resized = crop
+            .resize_exact(line_width, RECOGNITION_HEIGHT, image::imageops::FilterType::Triangle)
+            .to_rgb8();
+
+        let plane = (line_width * RECOGNITION_HEIGHT) as usize;
+        let mut data = vec![0.0f32; 3 * plane];
+        for (x, y, pixel) in resized.enumerate_pixels() {
+            let offset = (y * line_width + x) as usize;
+            for c in 0..3 {
+                data[c * plane + offset] = (pixel[2 - c] as f32 / 255.0 - 0.5) / 0.5;
+            }
+        }
+
+        let input = Value::from_array((vec![1, 3, RECOGNITION_HEIGHT as usize, line_width as usize], data))?;
+        let outputs = self.recognition.run(ort::inputs![input])?;
+        let (shape, probabilities) = outputs[0].try_extract_tensor::<f32>()?;
+
+        // Output is (batch, time steps, classes)
+        let classes = shape.last().copied().unwrap_or_default() as usize;
+        Ok(ctc_decode(probabilities, classes, &self.dictionary))
+    }
+}
+
+/// Load an ONNX model
+fn load_session(path: &Path) -> Result<Session> {
+    let model_bytes = fs::read(path)?;
+    Ok(Session::builder()?
+        .with_optimization_level(GraphOptimizationLevel::Level3)?
+        .with_intra_threads(4)?
+        .commit_from_memory(&model_bytes)?)
+}
+
+/// Round a side length to the nearest positive multiple of 32
+fn round_to_32(side: f32) -> u32 {
+    ((side / 32.0).round() as u32).max(1) * 32
+}
+
+/// Find the connected regions of likely text in a detection map
+///
+/// # Arguments
+/// * `probabilities` - Probability of text for each pixel, row by row
+/// * `width` - Width of the map
+/// * `height` - Height of the map
+///
+/// # Returns
+/// Bounding boxes of the regions, grown back to the size of the text
+fn find_text_boxes(probabilities: &[f32], width: u32, height: u32) -> Vec<TextBox> {
+    let (w, h) = (width as usize, height as usize);
+    let mut visited = vec![false; w * h];
+    let mut boxes = Vec::new();
+    let mut stack = Vec::new();
+
+    for start in 0..w * h {
+        if visited[start] || probabilities[start] <= PIXEL_THRESHOLD {
+            continue;
+        }
+
+        // Flood fill the region, tracking its bounds and mean probability
+        visited[start] = true;
+        stack.push(start);
+        let (mut x0, mut y0, mut x1, mut y1) = (w, h, 0, 0);
+        let (mut pixels, mut total) = (0usize, 0.0f32);
+        while let Some(i) = stack.pop() {
+            let (x, y) = (i % w, i / w);
+            x0 = x0.min(x);
+            y0 = y0.min(y);
+            x1 = x1.max(x);
+            y1 = y1.max(y);
+            pixels += 1;
+            total += probabilities[i];
+
+            let neighbours = [
+                (x > 0).then(|| i - 1),
+                (x + 1 < w).then(|| i + 1),
+                (y > 0).then(|| i - w),
+                (y + 1 < h).then(|| i + w),
+            ];
+            for j in neighbours.into_iter().flatten() {
+                if !visited[j] && probabilities[j] > PIXEL_THRESHOLD {
+                    visited[j] = true;
+                    stack.push(j);
+                }
+            }
+        }
+
+        let (box_width, box_height) = ((x1 - x0 + 1) as f32, (y1 - y0 + 1) as f32);
+        if box_width.min(box_height) < 3.0 || total / (pixels as f32) < BOX_THRESHOLD {
+            continue;
+        }
+
+        // Grow by the offset the model shrank the text by
+        let offset = box_width * box_height * UNCLIP_RATIO / (2.0 * (box_width + box_height));
+        boxes.push(TextBox {
+            x0: (x0 as f32 - offset).max(0.0) as u32,
+            y0: (y0 as f32 - offset).max(0.0) as u32,
+            x1: ((x1 + 1) as f32 + offset).min(width as f32) as u32,
+            y1: ((y1 + 1) as f32 + offset).min(height as f32) as u32,
+        });
+    }
+
+    boxes
+}
+
+/// Decode the output of the recognition model greedily
+///
+/// The most likely class is taken at every time step; repeats are merged
+/// and the blank class 0 separates characters.
+///
+/// # Arguments
+/// * `probabilities` - Class probabilities for each time step
+/// * `classes` - Number of classes, the blank and the dictionary
+/// * `dictionary` - Character of each class after the blank
+fn ctc_decode(probabilities: &[f32], classes: usize, dictionary: &[String]) -> Option<(String, f32)> {
+    if classes == 0 {
+        return None;
+    }
+
+    let mut text = String::new();
+    let mut confidence = 0.0;
+    let mut characters = 0;
+    let mut previous = 0;
+    for step in probabilities.chunks_exact(classes) {
+        let (class, probability) = step
+            .iter()
+            .copied()
+            .enumerate()
+            .fold((0, f32::MIN), |best, (i, p)| if p > best.1 { (i, p) } else { best });
+
+        if class != 0 && class != previous {
+            if let Some(character) = dictionary.get(class - 1) {
+                text.push_str(character);
+                confidence += probability;
+                characters += 1;
+            }
+        }
+        previous = class;
+    }
+
+    (characters > 0).then(|| (text, confidence / characters as f32))
+}
+
+/// Arrange recognized words into lines, top to bottom and left to right
+///
+/// A word belongs to a line when its vertical center falls within the
+/// first word of the line.
+fn arrange_lines(mut words: Vec<(TextBox, String)>) -> String {
+    words.sort_by_key(|(b, _)| (b.y0, b.x0));
+
+    let mut lines: Vec<(TextBox, Vec<(TextBox, String)>)> = Vec::new();
+    for (text_box, word) in words {
+        let center = (text_box.y0 + text_box.y1) / 2;
+        match lines.iter_mut().find(|(first, _)| first.y0 <= center && center < first.y1) {
+            Some((_, line)) => line.push((text_box, word)),
+            None => lines.push((text_box, vec![(text_box, word)])),
+        }
+    }
+
+    lines
+        .into_iter()
+        .map(|(_, mut line)| {
+            line.sort_by_key(|(b, _)| b.x0);
+            line.into_iter().map(|(_, word)| word).collect::<Vec<_>>().join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Decode an image embedded in a PDF
+///
+/// JPEG images and uncompressed or deflated 8-bit gray and RGB images are
+/// supported, which covers what most scanners produce.
+fn decode_pdf_image(document: &lopdf::Document, image: &lopdf::xobject::PdfImage) -> Option<DynamicImage> {
+    let filters = image.filters.as_deref().unwrap_or_default();
+    if filters.iter().any(|f| f == "DCTDecode") {
+        if filters.len() > 1 {
+            return None;
+        }
+        return image::load_from_memory_with_format(image.content, image::ImageFormat::Jpeg).ok();
+    }
+    if filters.iter().any(|f| f != "FlateDecode") || image.bits_per_component != Some(8) {
+        return None;
+    }
+
+    let data = document.get_object(image.id).ok()?.as_stream().ok()?.decompressed_content().ok()?;
+    let (width, height) = (u32::try_from(image.width).ok()?, u32::try_from(image.height).ok()?);
+    match image.color_space.as_deref() {
+        Some("DeviceGray") | Some("CalGray") => GrayImage::from_raw(width, height, data).map(DynamicImage::ImageLuma8),
+        Some("DeviceRGB") | Some("CalRGB") => RgbImage::from_raw(width, height, data).map(DynamicImage::ImageRgb8),
+        _ => None,
+    }
+}
+
+/// Text of images and of PDF pages without a text layer, by OCR
+///
+/// Takes precedence over the built-in PDF extractor: pages with text are
+/// read as usual, and the images of the others are recognized. The numbers
+/// of the recognized pages are listed in the `ocr_pages` metadata.
+pub struct OcrExtractor {
+    // Sessions need exclusive access; extraction workers take turns
+    model: Mutex<OcrModel>,
+}
+
+impl OcrExtractor {
+    /// Create an extractor running the given models
+    pub fn new(model: OcrModel) -> Self {
+        Self { model: Mutex::new(model) }
+    }
+
+    /// Recognize the text of an image
+    fn recognize(&self, img: &DynamicImage) -> Result<String> {
+        self.model
+            .lock()
+            .map_err(|_| crate::Error::Extraction("OCR model is poisoned".to_string()))?
+            .recognize(img)
+    }
+
+    /// Extract the text of a PDF, recognizing pages without a text layer
+    fn extract_pdf(&self, path: &Path) -> Result<ExtractedContent> {
+        let bytes = fs::read(path)?;
+        let document = lopdf::Document::load_mem(&bytes)
+            .map_err(|e| crate::Error::Extraction(format!("PDF extraction failed: {}", e)))?;
+        let page_ids: Vec<_> = document.get_pages().into_values().collect();
+
+        // A text layer that can't be read is recognized like a missing one
+        let mut pages = extract_pdf_pages(&bytes).unwrap_or_default();
+        pages.resize(page_ids.len().max(pages.len()), String::new());
+
+        let mut recognized = Vec::new();
+        for (i, page_id) in page_ids.into_iter().enumerate() {
+            if pages[i].split_whitespace().next().is_some() {
+                continue;
+            }
+
+            let mut text = String::new();
+            for image in document.get_page_images(page_id).unwrap_or_default() {
+                // Images that can't be decoded or read leave the page empty
+                let Some(img) = decode_pdf_image(&document, &image) else {
+                    continue;
+                };
+                match self.recognize(&img) {
+                    Ok(lines) if !lines.is_empty() => {
+                        text.push_str(&lines);
+                        text.push('\n');
+                    }
+                    Ok(_) => {}
+                    Err(e) => tracing::debug!("OCR failed on page {} of {}: {}", i + 1, path.display(), e),
+                }
+            }
+            if !text.is_empty() {
+                pages[i] = text;
+                recognized.push((i + 1).to_string());
+            }
+        }
+
+        let mut content = ExtractedContent::from_pages(pages);
+        content.metadata.extend(pdf_metadata(&document));
+        if !recognized.is_empty() {
+            content.metadata.insert("ocr_pages".to_string(), recognized.join(", "));
+        }
+        Ok(content)
+    }
+}
+
+impl Extractor for OcrExtractor {
+    fn name(&self) -> &str {
+        "ocr"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Image, FileType::Pdf]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            text: true,
+            metadata: true,
+            pages: true,
+        }
+    }
+
+    fn priority(&self) -> i32 {
+        1
+    }
+
+    fn extract(&self, path: &Path, file_type: FileType) -> Result<ExtractedContent> {
+        if file_type == FileType::Pdf {
+            return self.extract_pdf(path);
+        }
+
+        let img = image::open(path).map_err(|e| crate::Error::Extraction(format!("Failed to read image: {}", e)))?;
+        Ok(ExtractedContent::new(self.recognize(&img)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn text_box(x0: u32, y0: u32, x1: u32, y1: u32) -> TextBox {
+        TextBox { x0, y0, x1, y1 }
+    }
+
+    #[test]
+    fn test_find_text_boxes() {
+        // Two blobs of text on a 32x16 map, and a faint one
+        let (width, height) = (32, 16);
+        let mut map = vec![0.0f32; width * height];
+        for y in 4..8 {
+            for x in 2..12 {
+                map[y * width + x] = 0.9;
+            }
+            for x in 20..30 {
+                map[y * width + x] = 0.4;
+            }
+        }
+        for x in 16..20 {
+            map[12 * width + x] = 0.9;
+        }
+
+        let boxes = find_text_boxes(&map, width as u32, height as u32);
+        assert_eq!(boxes, vec![text_box(0, 1, 14, 10)]);
+    }
+
+    #[test]
+    fn test_ctc_decode() {
+        let dictionary: Vec<String> = ["a", "b", " "].iter().map(|s| s.to_string()).collect();
+        let step = |class: usize| {
+            let mut p = vec![0.0f32; 4];
+            p[class] = 0.8;
+            p
+        };
+        // a a _ a b b _ " " b
+        let probabilities: Vec<f32> = [1, 1, 0, 1, 2, 2, 0, 3, 2].into_iter().flat_map(step).collect();
+
+        let (text, confidence) = ctc_decode(&probabilities, 4, &dictionary).unwrap();
+        assert_eq!(text, "aab b");
+        assert!((confidence - 0.8).abs() < 1e-6);
+        assert_eq!(ctc_decode(&step(0), 4, &dictionary), None);
+    }
+
+    #[test]
+    fn test_arrange_lines() {
+        let words = vec![
+            (text_box(60, 32, 90, 50), "world".to_string()),
+            (text_box(0, 0, 40, 20), "Error:".to_string()),
+            (text_box(0, 30, 50, 52), "hello".to_string()),
+            (text_box(50, 2, 120, 18), "disk full".to_string()),
+        ];
+        assert_eq!(arrange_lines(words), "Error: disk full\nhello world");
+    }
+
+    #[test]
+    #[ignore] // Only run when the models are available
+    fn test_recognize() {
+        let Some(model) = OcrModel::find() else {
+            return;
+        };
+        let extractor = OcrExtractor::new(model.unwrap());
+        let path = PathBuf::from("test_data/ocr_sample.png");
+        if !path.exists() {
+            return;
+        }
+
+        let content = extractor.extract(&path, FileType::Image).unwrap();
+        assert!(content.word_count > 0);
+    }
+}
@@ -0,0 +1,723 @@
+//! Slide deck and OpenDocument extractors
+//!
+//! PowerPoint (`.pptx`) and OpenDocument (`.odt`, `.odp`) files are zip
+//! packages of XML parts. Their text is read paragraph by paragraph; slide
+//! decks become one [`Page`] per slide, numbered in presentation order,
+//! with the speaker notes of a slide following its text. Slide titles and
+//! OpenDocument headings are kept in the `headings` metadata entry, one per
+//! line, and the document properties (title, author, subject, description
+//! and keywords) under their own names.
+//!
+//! The package helpers are shared with the other zip-based formats.
+
+use super::{Capabilities, ExtractedContent, Extractor, Page};
+use crate::types::FileType;
+use crate::Result;
+use quick_xml::events::{BytesStart, Event};
+use quick_xml::Reader;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+use zip::ZipArchive;
+
+/// Maximum size of a part read from a package
+///
+/// Packages are untrusted zip archives; larger parts are skipped.
+const MAX_PART_SIZE: u64 = 64 * 1024 * 1024;
+
+/// Placeholders of a slide whose text is not content
+const SKIPPED_PLACEHOLDERS: &[&str] = &["sldNum", "dt", "ftr", "hdr", "sldImg"];
+
+/// Document properties of OOXML packages (`docProps/core.xml`), by
+/// element name
+pub(crate) const CORE_PROPERTIES: &[(&str, &str)] = &[
+    ("title", "title"),
+    ("creator", "author"),
+    ("subject", "subject"),
+    ("description", "description"),
+    ("keywords", "keywords"),
+    ("lastModifiedBy", "modified_by"),
+    ("created", "created"),
+];
+
+/// Document properties of OpenDocument files (`meta.xml`), by element name
+const ODF_PROPERTIES: &[(&str, &str)] = &[
+    ("title", "title"),
+    ("initial-creator", "author"),
+    ("creator", "author"),
+    ("subject", "subject"),
+    ("description", "description"),
+    ("keyword", "keywords"),
+];
+
+/// PowerPoint slide decks
+pub struct PptxExtractor;
+
+impl Extractor for PptxExtractor {
+    fn name(&self) -> &str {
+        "pptx"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Pptx]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            text: true,
+            metadata: true,
+            pages: true,
+        }
+    }
+
+    fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+        let mut package = open_package(path)?;
+
+        let mut slides = Vec::new();
+        for slide in slide_parts(&mut package)? {
+            let Some(xml) = read_part(&mut package, &slide)? else {
+                continue;
+            };
+            let (mut text, title) = slide_text(&xml);
+
+            // Speaker notes, linked from the slide's relationships
+            let notes = relationships(&mut package, &slide)?
+                .into_iter()
+                .find(|rel| rel.kind.ends_with("/notesSlide"));
+            if let Some(notes) = notes {
+                if let Some(xml) = read_part(&mut package, &notes.target)? {
+                    let (notes, _) = slide_text(&xml);
+                    if !notes.is_empty() {
+                        text.push_str("\n\n");
+                        text.push_str(&notes);
+                    }
+                }
+            }
+            slides.push((text.trim().to_string(), title));
+        }
+
+        let mut content = paginate(slides);
+        content.metadata.extend(read_properties(&mut package, "docProps/core.xml", CORE_PROPERTIES)?);
+        Ok(content)
+    }
+}
+
+/// OpenDocument text documents and presentations
+pub struct OpenDocumentExtractor;
+
+impl Extractor for OpenDocumentExtractor {
+    fn name(&self) -> &str {
+        "opendocument"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Odt, FileType::Odp]
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["ott", "otp"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            text: true,
+            metadata: true,
+            pages: true,
+        }
+    }
+
+    fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+        let mut package = open_package(path)?;
+        let xml = read_part(&mut package, "content.xml")?
+            .ok_or_else(|| crate::Error::Extraction("OpenDocument extraction failed: no content.xml".to_string()))?;
+
+        let document = odf_text(&xml);
+        let mut content = if document.slides.is_empty() {
+            let mut content = ExtractedContent::new(document.text);
+            if !document.headings.is_empty() {
+                content.metadata.insert("headings".to_string(), document.headings.join("\n"));
+            }
+            content
+        } else {
+            paginate(document.slides)
+        };
+        content.metadata.extend(read_properties(&mut package, "meta.xml", ODF_PROPERTIES)?);
+        Ok(content)
+    }
+}
+
+/// Join slides into pages, their titles making up the headings
+fn paginate(slides: Vec<(String, Option<String>)>) -> ExtractedContent {
+    let mut text = String::new();
+    let mut pages = Vec::new();
+    let mut titles = Vec::new();
+    for (i, (slide, title)) in slides.into_iter().enumerate() {
+        if !text.is_empty() {
+            text.push_str("\n\n");
+        }
+        let start = text.len();
+        text.push_str(&slide);
+        pages.push(Page {
+            number: i + 1,
+            start,
+            end: text.len(),
+        });
+        titles.extend(title);
+    }
+
+    let mut content = ExtractedContent::new(text);
+    content.metadata.insert("slides".to_string(), pages.len().to_string());
+    if !titles.is_empty() {
+        content.metadata.insert("headings".to_string(), titles.join("\n"));
+    }
+    content.pages = pages;
+    content
+}
+
+/// Open a zip package
+pub(crate) fn open_package(path: &Path) -> Result<ZipArchive<File>> {
+    ZipArchive::new(File::open(path)?)
+        .map_err(|e| crate::Error::Extraction(format!("Failed to open {}: {}", path.display(), e)))
+}
+
+/// Read a part of a package
+///
+/// # Returns
+/// The content of the part, or `None` if it is missing or too large
+pub(crate) fn read_part(package: &mut ZipArchive<File>, name: &str) -> Result<Option<Vec<u8>>> {
+    let part = match package.by_name(name) {
+        Ok(part) => part,
+        Err(zip::result::ZipError::FileNotFound) => return Ok(None),
+        Err(e) => return Err(crate::Error::Extraction(format!("Failed to read {}: {}", name, e))),
+    };
+
+    let mut data = Vec::new();
+    part.take(MAX_PART_SIZE + 1).read_to_end(&mut data)?;
+    if data.len() as u64 > MAX_PART_SIZE {
+        tracing::debug!("Skipping part {} larger than {} bytes", name, MAX_PART_SIZE);
+        return Ok(None);
+    }
+    Ok(Some(data))
+}
+
+/// Read document properties from a part of a package
+///
+/// # Arguments
+/// * `package` - The package
+/// * `name` - Name of the part holding the properties
+/// * `properties` - Element names and the metadata names they map to; the
+///   first element found for a name wins
+pub(crate) fn read_properties(
+    package: &mut ZipArchive<File>,
+    name: &str,
+    properties: &[(&str, &'static str)],
+) -> Result<Vec<(String, String)>> {
+    let Some(xml) = read_part(package, name)? else {
+        return Ok(Vec::new());
+    };
+    Ok(xml_properties(&xml, properties))
+}
+
+/// Read the text of the first elements with the given names
+pub(crate) fn xml_properties(xml: &[u8], properties: &[(&str, &'static str)]) -> Vec<(String, String)> {
+    let mut reader = Reader::from_reader(xml);
+    let mut found: Vec<(String, String)> = Vec::new();
+    let mut current: Option<(&'static str, String)> = None;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(element)) => {
+                let name = local_name(&element);
+                current = properties
+                    .iter()
+                    .find(|(element, key)| *element == name && found.iter().all(|(seen, _)| seen != key))
+                    .map(|(_, key)| (*key, String::new()));
+            }
+            Ok(Event::Text(text)) => {
+                if let Some((_, value)) = &mut current {
+                    value.push_str(&text.unescape().unwrap_or_default());
+                }
+            }
+            Ok(Event::CData(text)) => {
+                if let Some((_, value)) = &mut current {
+                    value.push_str(&String::from_utf8_lossy(&text));
+                }
+            }
+            Ok(Event::End(_)) => {
+                if let Some((key, value)) = current.take() {
+                    let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+                    if !value.is_empty() {
+                        found.push((key.to_string(), value));
+                    }
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    found
+}
+
+/// A relationship of an OOXML part
+pub(crate) struct Relationship {
+    pub id: String,
+    /// Relationship type URI
+    pub kind: String,
+    /// Resolved name of the target part
+    pub target: String,
+}
+
+/// Read the relationships of a part
+pub(crate) fn relationships(package: &mut ZipArchive<File>, part: &str) -> Result<Vec<Relationship>> {
+    let (dir, name) = part.rsplit_once('/').unwrap_or(("", part));
+    let rels = if dir.is_empty() {
+        format!("_rels/{}.rels", name)
+    } else {
+        format!("{}/_rels/{}.rels", dir, name)
+    };
+    let Some(xml) = read_part(package, &rels)? else {
+        return Ok(Vec::new());
+    };
+
+    let mut reader = Reader::from_reader(xml.as_slice());
+    let mut found = Vec::new();
+    let mut buf = Vec::new();
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(element) | Event::Empty(element)) if local_name(&element) == "Relationship" => {
+                let attributes = attributes(&reader, &element);
+                // External targets such as hyperlinks are not parts
+                if attributes.get("TargetMode").is_some_and(|mode| mode == "External") {
+                    continue;
+                }
+                if let (Some(id), Some(target)) = (attributes.get("Id"), attributes.get("Target")) {
+                    found.push(Relationship {
+                        id: id.clone(),
+                        kind: attributes.get("Type").cloned().unwrap_or_default(),
+                        target: resolve(dir, target),
+                    });
+                }
+            }
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+    Ok(found)
+}
+
+/// Resolve a path relative to a directory of a package
+///
+/// Absolute paths start at the root of the package.
+pub(crate) fn resolve(dir: &str, target: &str) -> String {
+    let (mut parts, target) = match target.strip_prefix('/') {
+        Some(target) => (Vec::new(), target),
+        None => (dir.split('/').filter(|p| !p.is_empty()).collect::<Vec<_>>(), target),
+    };
+    for part in target.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+/// Local name of an element, without its namespace prefix
+pub(crate) fn local_name(element: &BytesStart) -> String {
+    String::from_utf8_lossy(element.local_name().as_ref()).into_owned()
+}
+
+/// Attributes of an element by local name
+pub(crate) fn attributes<R>(reader: &Reader<R>, element: &BytesStart) -> HashMap<String, String> {
+    element
+        .attributes()
+        .flatten()
+        .filter_map(|attribute| {
+            let name = String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned();
+            let value = attribute.decode_and_unescape_value(reader).ok()?.into_owned();
+            Some((name, value))
+        })
+        .collect()
+}
+
+/// Slide parts of a presentation, in presentation order
+fn slide_parts(package: &mut ZipArchive<File>) -> Result<Vec<String>> {
+    let targets: HashMap<String, String> = relationships(package, "ppt/presentation.xml")?
+        .into_iter()
+        .map(|rel| (rel.id, rel.target))
+        .collect();
+
+    let mut slides = Vec::new();
+    if let Some(xml) = read_part(package, "ppt/presentation.xml")? {
+        let mut reader = Reader::from_reader(xml.as_slice());
+        let mut buf = Vec::new();
+        loop {
+            match reader.read_event_into(&mut buf) {
+                Ok(Event::Start(element) | Event::Empty(element)) if local_name(&element) == "sldId" => {
+                    if let Some(target) = attributes(&reader, &element).get("id").and_then(|id| targets.get(id)) {
+                        slides.push(target.clone());
+                    }
+                }
+                Ok(Event::Eof) | Err(_) => break,
+                _ => {}
+            }
+            buf.clear();
+        }
+    }
+
+    // Without a readable slide list, go by the numbers in the part names
+    if slides.is_empty() {
+        let mut numbered: Vec<(u32, String)> = package
+            .file_names()
+            .filter_map(|name| {
+                let number = name.strip_prefix("ppt/slides/slide")?.strip_suffix(".xml")?.parse().ok()?;
+                Some((number, name.to_string()))
+            })
+            .collect();
+        numbered.sort();
+        slides = numbered.into_iter().map(|(_, name)| name).collect();
+    }
+    Ok(slides)
+}
+
+/// Read the text of a slide or notes part, and the text of its title
+fn slide_text(xml: &[u8]) -> (String, Option<String>) {
+    let mut reader = Reader::from_reader(xml);
+    let mut text = Paragraphs::default();
+    let mut title = None;
+    // Start of the current shape in the text, and its placeholder type
+    let mut shape: Option<(usize, String)> = None;
+    let mut in_text = false;
+    let mut buf = Vec::new();
+
+    loop {
+        match reader.read_event_into(&mut buf) {
+            Ok(Event::Start(element)) => match local_name(&element).as_str() {
+                "sp" => shape = Some((text.len(), String::new())),
+                "t" => in_text = true,
+                _ => {}
+            },
+            Ok(Event::Empty(element)) => match local_name(&element).as_str() {
+                "ph" => {
+                    if let Some((_, placeholder)) = &mut shape {
+                        // A placeholder without a type holds the body
+                        *placeholder = attributes(&reader, &element).remove("type").unwrap_or("body".to_string());
+                    }
+                }
+                "br" => text.break_line(),
+                _ => {}
+            },
+            Ok(Event::Text(content)) if in_text => text.push(&content.unescape().unwrap_or_default()),
+            Ok(Event::End(element)) => match String::from_utf8_lossy(element.local_name().as_ref()).as_ref() {
+                "t" => in_text = false,
+                "p" => text.break_line(),
+                "sp" => {
+                    if let Some((start, placeholder)) = shape.take() {
+                        if SKIPPED_PLACEHOLDERS.contains(&placeholder.as_str()) {
+                            text.truncate(start);
+                        } else if matches!(placeholder.as_str(), "title" | "ctrTitle") && title.is_none() {
+                            title = Some(text.since(start)).filter(|title| !title.is_empty());
+                        }
+                    }
+                }
+                _ => {}
+            },
+            Ok(Event::Eof) | Err(_) => break,
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    (text.finish(), title)
+}
+
+/// Text of an OpenDocument file
+#[derive(Default)]
+struct OdfText {
+    text: String,
+    headings: Vec<String>,
+    /// Text and title of each slide of a presentation
+    slides: Vec<(String, Option<String>)>,
+}
+
+/// Read the text of an OpenDocument `content.xml`
+fn odf_text(xml: &[u8]) -> OdfText {
+    let mut reader = Reader::from_reader(xml);
+    let mut text = Paragraphs::default();
+    let mut document = OdfText::default();
+    // Starts of the current heading, title frame and slide in the text
+    let mut heading = None;
+    let mut title: Option<(usize, usize)> = None;
+    let mut slide: Option<(usize, Option<String>)> = None;
+    // Depth of paragraphs, of frames, and of deleted text being skipped
+    let mut paragraphs = 0;
+    let mut frames = 0;
+    let mut skipped = 0;
+    let mut buf = Vec::new();
+
+    loop {
+        let event = match reader.read_event_into(&mut buf) {
+            Ok(Event::Eof) | Err(_) => break,
+            Ok(event) => event,
+        };
+        match event {
+            Event::Start(_) if skipped > 0 => skipped += 1,
+            Event::End(_) if skipped > 0 => skipped -= 1,
+            Event::Start(element) => match local_name(&element).as_str() {
+                "tracked-changes" => skipped = 1,
+                "p" => paragraphs += 1,
+                "h" => {
+                    paragraphs += 1;
+                    heading = Some(text.len());
+                }
+                "page" => slide = Some((text.len(), None)),
+                "frame" => {
+                    frames += 1;
+                    let class = attributes(&reader, &element).remove("class");
+                    if matches!(class.as_deref(), Some("title")) {
+                        title = Some((text.len(), frames));
+                    }
+                }
+                _ => {}
+            },
+            Event::Empty(element) if skipped == 0 => match local_name(&element).as_str() {
+                "s" => {
+                    let count = attributes(&reader, &element).get("c").and_then(|c| c.parse().ok()).unwrap_or(1);
+                    text.push(&" ".repeat(count));
+                }
+                "tab" => text.push("\t"),
+                "line-break" => text.break_line(),
+                _ => {}
+            },
+            // Whitespace collapses, spaces and tabs being elements of their own
+            Event::Text(content) if skipped == 0 && paragraphs > 0 => {
+                let content = content.unescape().unwrap_or_default();
+                for c in content.chars() {
+                    if !c.is_ascii_whitespace() {
+                        text.text.push(c);
+                    } else if !text.text.ends_with([' ', '\n']) {
+                        text.text.push(' ');
+                    }
+                }
+            }
+            Event::End(element) => match String::from_utf8_lossy(element.local_name().as_ref()).as_ref() {
+                "p" => {
+                    paragraphs -= 1;
+                    text.break_line();
+                }
+                "h" => {
+                    paragraphs -= 1;
+                    if let Some(start) = heading.take() {
+                        let heading = text.since(start);
+                        if !heading.is_empty() {
+                            document.headings.push(heading);
+                        }
+                    }
+                    text.break_line();
+                }
+                "frame" => {
+                    if let Some((start, _)) = title.filter(|(_, depth)| *depth == frames) {
+                        if let Some((_, slide_title @ None)) = &mut slide {
+                            *slide_title = Some(text.since(start));
+                        }
+                        title = None;
+                    }
+                    frames -= 1;
+                }
+                "page" => {
+                    if let Some((start, title)) = slide.take() {
+                        document.slides.push((text.since(start), title.filter(|t| !t.is_empty())));
+                    }
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+        buf.clear();
+    }
+
+    document.text = text.finish();
+    document
+}
+
+/// Text read paragraph by paragraph from XML
+#[derive(Default)]
+struct Paragraphs {
+    text: String,
+}
+
+impl Paragraphs {
+    fn push(&mut self, text: &str) {
+        self.text.push_str(text);
+    }
+
+    /// End the current line, unless it is empty
+    fn break_line(&mut self) {
+        if !self.text.is_empty() && !self.text.ends_with('\n') {
+            self.text.push('\n');
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.text.truncate(len);
+    }
+
+    /// Text written since a position, trimmed
+    fn since(&self, start: usize) -> String {
+        self.text[start..].trim().to_string()
+    }
+
+    fn finish(self) -> String {
+        self.text.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+    use zip::write::SimpleFileOptions;
+
+    /// Write a zip package with the given parts
+    pub(crate) fn write_package(path: &Path, parts: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(File::create(path).unwrap());
+        for (name, content) in parts {
+            zip.start_file(*name, SimpleFileOptions::default()).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    const NS: &str = r#"xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:p="http://schemas.openxmlformats.org/presentationml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships""#;
+
+    fn slide(title: &str, body: &str) -> String {
+        format!(
+            r#"<p:sld {NS}><p:cSld><p:spTree>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph type="title"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>{title}</a:t></a:r></a:p></p:txBody></p:sp>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph idx="1"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:r><a:t>{body}</a:t></a:r><a:br/><a:r><a:t>second line</a:t></a:r></a:p></p:txBody></p:sp>
+            <p:sp><p:nvSpPr><p:nvPr><p:ph type="sldNum"/></p:nvPr></p:nvSpPr><p:txBody><a:p><a:fld><a:t>7</a:t></a:fld></a:p></p:txBody></p:sp>
+            </p:spTree></p:cSld></p:sld>"#
+        )
+    }
+
+    #[test]
+    fn test_extract_pptx() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("deck.pptx");
+        let rels = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide" Target="slides/slide1.xml"/>
+            <Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/slide" Target="slides/slide2.xml"/>
+            </Relationships>"#;
+        let slide_rels = r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">
+            <Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/notesSlide" Target="../notesSlides/notesSlide1.xml"/>
+            <Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink" Target="https://example.com" TargetMode="External"/>
+            </Relationships>"#;
+        let notes = format!(r#"<p:notes {NS}><p:cSld><p:spTree><p:sp><p:txBody><a:p><a:r><a:t>Mention the &amp; pricing</a:t></a:r></a:p></p:txBody></p:sp></p:spTree></p:cSld></p:notes>"#);
+        let core = r#"<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/">
+            <dc:title>Quarterly review</dc:title><dc:creator>Alice Martin</dc:creator></cp:coreProperties>"#;
+        write_package(
+            &path,
+            &[
+                // The second slide part comes first in the presentation
+                ("ppt/presentation.xml", &format!(r#"<p:presentation {NS}><p:sldIdLst><p:sldId id="256" r:id="rId3"/><p:sldId id="257" r:id="rId2"/></p:sldIdLst></p:presentation>"#)),
+                ("ppt/_rels/presentation.xml.rels", rels),
+                ("ppt/slides/slide1.xml", &slide("Results", "Revenue grew")),
+                ("ppt/slides/slide2.xml", &slide("Agenda", "Welcome")),
+                ("ppt/slides/_rels/slide1.xml.rels", slide_rels),
+                ("ppt/notesSlides/notesSlide1.xml", &notes),
+                ("docProps/core.xml", core),
+            ],
+        );
+
+        let content = PptxExtractor.extract(&path, FileType::Pptx).unwrap();
+        assert_eq!(
+            content.text,
+            "Agenda\nWelcome\nsecond line\n\nResults\nRevenue grew\nsecond line\n\nMention the & pricing"
+        );
+        assert_eq!(content.pages.len(), 2);
+        assert_eq!(content.page_text(2), Some("Results\nRevenue grew\nsecond line\n\nMention the & pricing"));
+        assert_eq!(content.metadata["slides"], "2");
+        assert_eq!(content.metadata["headings"], "Agenda\nResults");
+        assert_eq!(content.metadata["title"], "Quarterly review");
+        assert_eq!(content.metadata["author"], "Alice Martin");
+    }
+
+    const ODF_NS: &str = r#"xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:text="urn:oasis:names:tc:opendocument:xmlns:text:1.0" xmlns:draw="urn:oasis:names:tc:opendocument:xmlns:drawing:1.0" xmlns:presentation="urn:oasis:names:tc:opendocument:xmlns:presentation:1.0""#;
+
+    #[test]
+    fn test_extract_odt() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("report.odt");
+        let content = format!(
+            r#"<office:document-content {ODF_NS}><office:body><office:text>
+            <text:tracked-changes><text:changed-region><text:deletion><text:p>Removed draft</text:p></text:deletion></text:changed-region></text:tracked-changes>
+            <text:h text:outline-level="1">Introduction</text:h>
+            <text:p>Two<text:s text:c="2"/>spaces<text:tab/>and a <text:span>styled</text:span> word<text:line-break/>next line</text:p>
+            <text:h text:outline-level="2">Scope</text:h>
+            <text:p>Everything.</text:p>
+            </office:text></office:body></office:document-content>"#
+        );
+        let meta = r#"<office:document-meta xmlns:office="urn:oasis:names:tc:opendocument:xmlns:office:1.0" xmlns:meta="urn:oasis:names:tc:opendocument:xmlns:meta:1.0" xmlns:dc="http://purl.org/dc/elements/1.1/">
+            <office:meta><meta:initial-creator>Bob</meta:initial-creator><dc:creator>Carol</dc:creator><dc:title>Annual report</dc:title></office:meta></office:document-meta>"#;
+        write_package(&path, &[("mimetype", "application/vnd.oasis.opendocument.text"), ("content.xml", &content), ("meta.xml", meta)]);
+
+        let content = OpenDocumentExtractor.extract(&path, FileType::Odt).unwrap();
+        assert_eq!(content.text, "Introduction\nTwo  spaces\tand a styled word\nnext line\nScope\nEverything.");
+        assert!(content.pages.is_empty());
+        assert_eq!(content.metadata["headings"], "Introduction\nScope");
+        assert_eq!(content.metadata["title"], "Annual report");
+        assert_eq!(content.metadata["author"], "Bob");
+    }
+
+    #[test]
+    fn test_extract_odp() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("talk.odp");
+        let content = format!(
+            r#"<office:document-content {ODF_NS}><office:body><office:presentation>
+            <draw:page draw:name="page1"><draw:frame presentation:class="title"><draw:text-box><text:p>Hello</text:p></draw:text-box></draw:frame>
+            <draw:frame presentation:class="outline"><draw:text-box><text:p>First point</text:p></draw:text-box></draw:frame></draw:page>
+            <draw:page draw:name="page2"><draw:frame><draw:text-box><text:p>No title here</text:p></draw:text-box></draw:frame>
+            <presentation:notes><draw:frame><draw:text-box><text:p>Speaker notes</text:p></draw:text-box></draw:frame></presentation:notes></draw:page>
+            </office:presentation></office:body></office:document-content>"#
+        );
+        write_package(&path, &[("content.xml", &content)]);
+
+        let content = OpenDocumentExtractor.extract(&path, FileType::Odp).unwrap();
+        assert_eq!(content.text, "Hello\nFirst point\n\nNo title here\nSpeaker notes");
+        assert_eq!(content.page_text(1), Some("Hello\nFirst point"));
+        assert_eq!(content.page_text(2), Some("No title here\nSpeaker notes"));
+        assert_eq!(content.metadata["slides"], "2");
+        assert_eq!(content.metadata["headings"], "Hello");
+        assert!(!content.metadata.contains_key("title"));
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert_eq!(resolve("ppt/slides", "../notesSlides/notesSlide1.xml"), "ppt/notesSlides/notesSlide1.xml");
+        assert_eq!(resolve("ppt", "slides/slide1.xml"), "ppt/slides/slide1.xml");
+        assert_eq!(resolve("word", "/word/media/image1.png"), "word/media/image1.png");
+        assert_eq!(resolve("", "./a.xml"), "a.xml");
+    }
+
+    #[test]
+    fn test_not_a_package() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("broken.pptx");
+        std::fs::write(&path, "not a zip").unwrap();
+        assert!(PptxExtractor.extract(&path, FileType::Pptx).is_err());
+    }
+}
@@ -0,0 +1,303 @@
+//! Pluggable content extractors
+//!
+//! An [`Extractor`] declares the file types, extensions and MIME types it
+//! handles and what it produces. The [`ExtractorRegistry`] dispatches each
+//! file to the matching extractor with the highest priority; among equal
+//! priorities the one registered last wins, so extractors registered by an
+//! application override the built-in ones.
+//!
+//! ```no_run
+//! use khoj::extractors::{Capabilities, ExtractedContent, Extractor, ExtractorRegistry};
+//! use khoj::types::FileType;
+//! use std::path::Path;
+//!
+//! struct LogbookExtractor;
+//!
+//! impl Extractor for LogbookExtractor {
+//!     fn name(&self) -> &str {
+//!         "logbook"
+//!     }
+//!
+//!     fn extensions(&self) -> &[&str] {
+//!         &["logbook"]
+//!     }
+//!
+//!     fn extract(&self, path: &Path, _file_type: FileType) -> khoj::Result<ExtractedContent> {
+//!         let bytes = std::fs::read(path)?;
+//!         Ok(ExtractedContent::new(String::from_utf8_lossy(&bytes[16..]).into_owned()))
+//!     }
+//! }
+//!
+//! let mut registry = ExtractorRegistry::default();
+//! registry.register(LogbookExtractor);
+//! ```
+
+use super::code::CodeExtractor;
+use super::email::EmailExtractor;
+use super::epub::EpubExtractor;
+use super::markdown::MarkdownExtractor;
+use super::markup::{HtmlExtractor, XmlExtractor};
+use super::notebook::NotebookExtractor;
+use super::office::{OpenDocumentExtractor, PptxExtractor};
+use super::rtf::RtfExtractor;
+use super::spreadsheet::SpreadsheetExtractor;
+use super::structured::StructuredExtractor;
+use super::docx::DocxExtractor;
+use super::text::{PdfExtractor, PlainTextExtractor};
+use super::ExtractedContent;
+use crate::config::IndexingConfig;
+use crate::types::FileType;
+use crate::Result;
+use std::path::Path;
+use std::sync::{Arc, OnceLock};
+
+/// What an extractor produces besides nothing at all
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Capabilities {
+    /// Searchable text
+    pub text: bool,
+    /// Document properties such as title or author
+    pub metadata: bool,
+    /// Page structure, see [`ExtractedContent::pages`]
+    pub pages: bool,
+}
+
+impl Capabilities {
+    /// Text only
+    pub const TEXT: Self = Self {
+        text: true,
+        metadata: false,
+        pages: false,
+    };
+}
+
+/// Extracts searchable content from files of some format
+pub trait Extractor: Send + Sync {
+    /// Short name of the extractor, used in logs and error messages
+    fn name(&self) -> &str;
+
+    /// File types handled by this extractor
+    fn file_types(&self) -> &[FileType] {
+        &[]
+    }
+
+    /// File extensions handled by this extractor, without the dot
+    fn extensions(&self) -> &[&str] {
+        &[]
+    }
+
+    /// MIME types handled by this extractor; `type/*` matches a whole type
+    fn mime_types(&self) -> &[&str] {
+        &[]
+    }
+
+    /// What [`extract`](Self::extract) produces
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::TEXT
+    }
+
+    /// Priority among extractors handling the same file, higher wins
+    fn priority(&self) -> i32 {
+        0
+    }
+
+    /// Extract the content of a file
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file
+    /// * `file_type` - Detected type of the file
+    fn extract(&self, path: &Path, file_type: FileType) -> Result<ExtractedContent>;
+}
+
+/// Set of extractors, dispatching files to the best match
+#[derive(Clone)]
+pub struct ExtractorRegistry {
+    extractors: Vec<Arc<dyn Extractor>>,
+}
+
+impl Default for ExtractorRegistry {
+    /// Registry with the built-in extractors
+    fn default() -> Self {
+        Self::for_config(&IndexingConfig::default())
+    }
+}
+
+impl ExtractorRegistry {
+    /// Create a registry with the built-in extractors, set up as configured
+    ///
+    /// # Arguments
+    /// * `config` - Indexing configuration, e.g. whether notebook outputs
+    ///   are indexed
+    pub fn for_config(config: &IndexingConfig) -> Self {
+        let mut registry = Self::new();
+        registry.register(PlainTextExtractor);
+        registry.register(CodeExtractor);
+        registry.register(MarkdownExtractor);
+        // After the text extractor, so that it takes JSON, YAML and TOML files
+        registry.register(StructuredExtractor);
+        registry.register(PdfExtractor);
+        registry.register(DocxExtractor);
+        registry.register(HtmlExtractor);
+        registry.register(XmlExtractor);
+        registry.register(EmailExtractor);
+        registry.register(PptxExtractor);
+        registry.register(OpenDocumentExtractor);
+        registry.register(RtfExtractor);
+        registry.register(EpubExtractor);
+        registry.register(NotebookExtractor {
+            outputs: config.notebook_outputs,
+        });
+        // After the text extractor, so that it takes CSV and TSV files
+        registry.register(SpreadsheetExtractor);
+        registry
+    }
+
+    /// Create a registry without any extractor
+    pub fn new() -> Self {
+        Self { extractors: Vec::new() }
+    }
+
+    /// Shared registry with the built-in extractors
+    pub fn builtin() -> &'static Self {
+        static BUILTIN: OnceLock<ExtractorRegistry> = OnceLock::new();
+        BUILTIN.get_or_init(Self::default)
+    }
+
+    /// Add an extractor
+    ///
+    /// It takes precedence over extractors registered earlier with the
+    /// same priority.
+    pub fn register<E: Extractor + 'static>(&mut self, extractor: E) {
+        self.extractors.push(Arc::new(extractor));
+    }
+
+    /// Add the extractors of another registry
+    ///
+    /// They take precedence over the extractors of this registry with the
+    /// same priority, as if registered after them.
+    pub fn extend(&mut self, other: &ExtractorRegistry) {
+        self.extractors.extend(other.extractors.iter().cloned());
+    }
+
+    /// Registered extractors, in registration order
+    pub fn extractors(&self) -> impl Iterator<Item = &dyn Extractor> {
+        self.extractors.iter().map(|e| e.as_ref())
+    }
+
+    /// Find the extractor for a file
+    ///
+    /// # Returns
+    /// The handling extractor with the highest priority, or `None` if no
+    /// extractor handles the file
+    pub fn find(&self, path: &Path, file_type: FileType) -> Option<&dyn Extractor> {
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+        let mime = mime_guess::from_path(path).first();
+
+        self.extractors()
+            .filter(|extractor| {
+                extractor.file_types().contains(&file_type)
+                    || extension.as_deref().is_some_and(|ext| {
+                        extractor.extensions().iter().any(|e| e.eq_ignore_ascii_case(ext))
+                    })
+                    || mime.as_ref().is_some_and(|mime| {
+                        extractor.mime_types().iter().any(|m| mime_matches(m, mime))
+                    })
+            })
+            // The last of equal maxima, i.e. the most recently registered
+            .max_by_key(|extractor| extractor.priority())
+    }
+
+    /// Extract the content of a file with the best matching extractor
+    ///
+    /// # Arguments
+    /// * `path` - Path to the file
+    /// * `file_type` - Detected type of the file
+    pub fn extract(&self, path: &Path, file_type: FileType) -> Result<ExtractedContent> {
+        match self.find(path, file_type) {
+            Some(extractor) => extractor.extract(path, file_type),
+            None => Err(crate::Error::UnsupportedFileType(format!(
+                "No extractor for {} files",
+                file_type.as_str()
+            ))),
+        }
+    }
+}
+
+/// Check a MIME type against a pattern such as `text/plain` or `text/*`
+fn mime_matches(pattern: &str, mime: &mime_guess::Mime) -> bool {
+    match pattern.strip_suffix("/*") {
+        Some(type_) => mime.type_().as_str().eq_ignore_ascii_case(type_),
+        None => mime.essence_str().eq_ignore_ascii_case(pattern),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    struct UpperExtractor {
+        priority: i32,
+    }
+
+    impl Extractor for UpperExtractor {
+        fn name(&self) -> &str {
+            "upper"
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["up"]
+        }
+
+        fn mime_types(&self) -> &[&str] {
+            &["text/csv"]
+        }
+
+        fn priority(&self) -> i32 {
+            self.priority
+        }
+
+        fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+            Ok(ExtractedContent::new(fs::read_to_string(path)?.to_uppercase()))
+        }
+    }
+
+    #[test]
+    fn test_builtin_dispatch() {
+        let registry = ExtractorRegistry::builtin();
+        assert_eq!(registry.find(Path::new("a.txt"), FileType::Text).unwrap().name(), "text");
+        assert_eq!(registry.find(Path::new("a.rs"), FileType::Code).unwrap().name(), "code");
+        assert_eq!(registry.find(Path::new("a.pdf"), FileType::Pdf).unwrap().name(), "pdf");
+        assert_eq!(registry.find(Path::new("a.ods"), FileType::Xlsx).unwrap().name(), "spreadsheet");
+        assert_eq!(registry.find(Path::new("a.csv"), FileType::Text).unwrap().name(), "spreadsheet");
+        assert_eq!(registry.find(Path::new("index"), FileType::Html).unwrap().name(), "html");
+        assert_eq!(registry.find(Path::new("a.pptx"), FileType::Pptx).unwrap().name(), "pptx");
+        assert_eq!(registry.find(Path::new("a.odp"), FileType::Odp).unwrap().name(), "opendocument");
+        assert_eq!(registry.find(Path::new("a.rtf"), FileType::Rtf).unwrap().name(), "rtf");
+        assert_eq!(registry.find(Path::new("a.epub"), FileType::Epub).unwrap().name(), "epub");
+        assert!(registry.find(Path::new("a.doc"), FileType::Binary).is_none());
+        assert!(registry.find(Path::new("a.bin"), FileType::Binary).is_none());
+        assert!(registry.extract(Path::new("a.bin"), FileType::Binary).is_err());
+    }
+
+    #[test]
+    fn test_custom_extractor() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("notes.up");
+        fs::write(&path, "shout").unwrap();
+
+        let mut registry = ExtractorRegistry::default();
+        registry.register(UpperExtractor { priority: 0 });
+
+        // Matched by extension, or by MIME type
+        assert_eq!(registry.extract(&path, FileType::Unknown).unwrap().text, "SHOUT");
+        assert_eq!(registry.find(Path::new("data.csv"), FileType::Text).unwrap().name(), "upper");
+
+        // A built-in with a higher priority wins over later registrations
+        let mut registry = ExtractorRegistry::new();
+        registry.register(UpperExtractor { priority: -1 });
+        registry.register(PlainTextExtractor);
+        assert_eq!(registry.find(Path::new("data.csv"), FileType::Text).unwrap().name(), "text");
+    }
+}
@@ -0,0 +1,422 @@
+//! RTF document extractor
+//!
+//! RTF is read group by group: the text of the body is kept, with
+//! paragraphs and table rows on lines of their own and cells separated by
+//! tabs, while font and style tables, pictures, headers, footers and other
+//! destinations that are not body text are skipped. Escaped bytes are
+//! decoded with the code page of the document (`\ansicpg`), and Unicode
+//! characters (`\u`) replace their fallback text. The title, author,
+//! subject and keywords of the `\info` group go into the metadata.
+
+use super::{Capabilities, ExtractedContent, Extractor};
+use crate::types::FileType;
+use crate::Result;
+use encoding_rs::{Encoding, WINDOWS_1252};
+use std::fs;
+use std::path::Path;
+
+/// Destinations whose text is not part of the document body
+const SKIPPED_DESTINATIONS: &[&str] = &[
+    "fonttbl", "colortbl", "stylesheet", "listtable", "listoverridetable", "revtbl", "rsidtbl",
+    "generator", "pict", "object", "objdata", "header", "headerl", "headerr", "headerf", "footer",
+    "footerl", "footerr", "footerf", "fldinst", "xmlnstbl", "themedata", "colorschememapping",
+    "datastore", "latentstyles", "filetbl", "private", "bkmkstart", "bkmkend",
+];
+
+/// Document properties of the `\info` group
+const INFO_FIELDS: &[(&str, &str)] = &[
+    ("title", "title"),
+    ("author", "author"),
+    ("subject", "subject"),
+    ("keywords", "keywords"),
+];
+
+/// RTF documents
+pub struct RtfExtractor;
+
+impl Extractor for RtfExtractor {
+    fn name(&self) -> &str {
+        "rtf"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Rtf]
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["application/rtf", "text/rtf"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            metadata: true,
+            ..Capabilities::TEXT
+        }
+    }
+
+    fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+        extract_rtf(&fs::read(path)?)
+    }
+}
+
+/// Where the text of a group goes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Destination {
+    Body,
+    /// A document property of the `\info` group
+    Info(Option<&'static str>),
+    Skipped,
+}
+
+/// State of a group, inherited by its subgroups
+#[derive(Debug, Clone, Copy)]
+struct Group {
+    destination: Destination,
+    /// Number of fallback characters following a `\u` character
+    unicode_skip: usize,
+}
+
+/// Extract the text and document properties of an RTF document
+pub fn extract_rtf(bytes: &[u8]) -> Result<ExtractedContent> {
+    if !bytes.trim_ascii_start().starts_with(b"{\\rtf") {
+        return Err(crate::Error::Extraction("RTF extraction failed: not an RTF document".to_string()));
+    }
+
+    let mut parser = Parser {
+        encoding: WINDOWS_1252,
+        body: String::new(),
+        property: None,
+        properties: Vec::new(),
+        pending: Vec::new(),
+        skip: 0,
+    };
+    let mut group = Group {
+        destination: Destination::Body,
+        unicode_skip: 1,
+    };
+    let mut stack = Vec::new();
+    // Whether the next control word starts an ignorable destination (`\*`)
+    let mut ignorable = false;
+    // Whether the current group has had no content yet, so that a control
+    // word may name its destination
+    let mut group_start = false;
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let byte = bytes[i];
+        i += 1;
+        match byte {
+            b'{' => {
+                stack.push(group);
+                group_start = true;
+                ignorable = false;
+            }
+            b'}' => {
+                parser.flush(group.destination);
+                let Some(parent) = stack.pop() else { break };
+                if matches!(group.destination, Destination::Info(Some(_))) && parent.destination != group.destination {
+                    parser.end_property();
+                }
+                group = parent;
+                group_start = false;
+            }
+            b'\\' => {
+                let Some(&next) = bytes.get(i) else { break };
+                if !next.is_ascii_alphabetic() {
+                    i += 1;
+                    match next {
+                        b'*' => {
+                            ignorable = true;
+                            continue;
+                        }
+                        b'\'' => {
+                            let hex = bytes.get(i..i + 2).and_then(|hex| std::str::from_utf8(hex).ok());
+                            if let Some(byte) = hex.and_then(|hex| u8::from_str_radix(hex, 16).ok()) {
+                                i += 2;
+                                parser.byte(byte, group.destination);
+                            }
+                        }
+                        b'\\' | b'{' | b'}' => parser.byte(next, group.destination),
+                        b'~' => parser.text("\u{a0}", group.destination),
+                        b'_' => parser.text("\u{2011}", group.destination),
+                        b'\n' | b'\r' => parser.text("\n", group.destination),
+                        _ => {}
+                    }
+                    group_start = false;
+                    continue;
+                }
+
+                // Control word: letters, an optional numeric parameter and an
+                // optional space delimiter
+                let start = i;
+                while bytes.get(i).is_some_and(|b| b.is_ascii_alphabetic()) {
+                    i += 1;
+                }
+                let word = std::str::from_utf8(&bytes[start..i]).unwrap_or_default();
+                let number_start = i;
+                if bytes.get(i) == Some(&b'-') {
+                    i += 1;
+                }
+                while bytes.get(i).is_some_and(|b| b.is_ascii_digit()) {
+                    i += 1;
+                }
+                let parameter: Option<i64> = std::str::from_utf8(&bytes[number_start..i]).ok().and_then(|n| n.parse().ok());
+                if bytes.get(i) == Some(&b' ') {
+                    i += 1;
+                }
+
+                if group_start || ignorable {
+                    if let Some(destination) = destination(word, group.destination, ignorable) {
+                        parser.flush(group.destination);
+                        group.destination = destination;
+                    }
+                }
+                group_start = false;
+                ignorable = false;
+
+                match word {
+                    "bin" => {
+                        parser.flush(group.destination);
+                        i = i.saturating_add(parameter.unwrap_or(0).max(0) as usize).min(bytes.len());
+                    }
+                    "ansicpg" => {
+                        if let Some(encoding) = parameter.and_then(code_page) {
+                            parser.encoding = encoding;
+                        }
+                    }
+                    "mac" => parser.encoding = encoding_rs::MACINTOSH,
+                    "uc" => group.unicode_skip = parameter.unwrap_or(1).max(0) as usize,
+                    "u" => {
+                        // Negative values stand for code points above 32767
+                        let code = parameter.unwrap_or(0);
+                        let code = if code < 0 { code + 65536 } else { code };
+                        let c = char::from_u32(code as u32).unwrap_or(char::REPLACEMENT_CHARACTER);
+                        parser.text(c.encode_utf8(&mut [0; 4]), group.destination);
+                        parser.skip = group.unicode_skip;
+                    }
+                    _ => {
+                        if let Some(text) = symbol(word) {
+                            parser.text(text, group.destination);
+                        }
+                    }
+                }
+            }
+            // Line breaks in the source are not part of the text
+            b'\n' | b'\r' => {}
+            byte => {
+                group_start = false;
+                parser.byte(byte, group.destination);
+            }
+        }
+    }
+    parser.flush(group.destination);
+
+    let text = parser
+        .body
+        .lines()
+        .map(str::trim_end)
+        .collect::<Vec<_>>()
+        .join("\n");
+    let mut content = ExtractedContent::new(collapse_blank_lines(text.trim()));
+    content.metadata.extend(parser.properties);
+    Ok(content)
+}
+
+/// Decoder of the text of a document
+struct Parser {
+    encoding: &'static Encoding,
+    body: String,
+    /// Name and text of the document property being read
+    property: Option<(&'static str, String)>,
+    properties: Vec<(String, String)>,
+    /// Bytes of text not yet decoded, as multibyte code pages escape a
+    /// character as several `\'hh` bytes
+    pending: Vec<u8>,
+    /// Fallback characters left to skip after a `\u` character
+    skip: usize,
+}
+
+impl Parser {
+    fn byte(&mut self, byte: u8, destination: Destination) {
+        if self.skip > 0 {
+            self.skip -= 1;
+        } else if destination != Destination::Skipped {
+            self.pending.push(byte);
+        }
+    }
+
+    fn text(&mut self, text: &str, destination: Destination) {
+        self.flush(destination);
+        // A control word counts as a single fallback character
+        if self.skip > 0 {
+            self.skip -= 1;
+            return;
+        }
+        self.output(text, destination);
+    }
+
+    /// Decode the pending bytes
+    fn flush(&mut self, destination: Destination) {
+        if self.pending.is_empty() {
+            return;
+        }
+        let bytes = std::mem::take(&mut self.pending);
+        let (text, _) = self.encoding.decode_without_bom_handling(&bytes);
+        self.output(&text, destination);
+    }
+
+    fn output(&mut self, text: &str, destination: Destination) {
+        match destination {
+            Destination::Body => self.body.push_str(text),
+            Destination::Info(Some(name)) => {
+                self.property.get_or_insert_with(|| (name, String::new())).1.push_str(text);
+            }
+            Destination::Info(None) | Destination::Skipped => {}
+        }
+    }
+
+    fn end_property(&mut self) {
+        if let Some((name, value)) = self.property.take() {
+            let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !value.is_empty() && self.properties.iter().all(|(seen, _)| seen != name) {
+                self.properties.push((name.to_string(), value));
+            }
+        }
+    }
+}
+
+/// Destination named by the first control word of a group
+///
+/// # Arguments
+/// * `word` - The control word
+/// * `current` - Destination of the enclosing group
+/// * `ignorable` - Whether the word follows `\*`, making unknown
+///   destinations skipped
+fn destination(word: &str, current: Destination, ignorable: bool) -> Option<Destination> {
+    if matches!(current, Destination::Skipped | Destination::Info(Some(_))) {
+        return None;
+    }
+    if word == "info" {
+        return Some(Destination::Info(None));
+    }
+    if let Destination::Info(_) = current {
+        let field = INFO_FIELDS.iter().find(|(name, _)| *name == word).map(|(_, key)| *key);
+        return Some(Destination::Info(field));
+    }
+    if ignorable || SKIPPED_DESTINATIONS.contains(&word) {
+        return Some(Destination::Skipped);
+    }
+    None
+}
+
+/// Text of a control word standing for a character
+fn symbol(word: &str) -> Option<&'static str> {
+    Some(match word {
+        "par" | "line" | "sect" | "page" | "row" => "\n",
+        "tab" | "cell" => "\t",
+        "emdash" => "\u{2014}",
+        "endash" => "\u{2013}",
+        "lquote" => "\u{2018}",
+        "rquote" => "\u{2019}",
+        "ldblquote" => "\u{201c}",
+        "rdblquote" => "\u{201d}",
+        "bullet" => "\u{2022}",
+        "emspace" | "enspace" | "qmspace" => " ",
+        _ => return None,
+    })
+}
+
+/// Encoding of a Windows code page
+fn code_page(code_page: i64) -> Option<&'static Encoding> {
+    let label = match code_page {
+        437 | 1252 => "windows-1252",
+        866 => "ibm866",
+        874 => "windows-874",
+        932 => "shift_jis",
+        936 => "gbk",
+        949 => "euc-kr",
+        950 => "big5",
+        1250..=1258 => return Encoding::for_label(format!("windows-{}", code_page).as_bytes()),
+        10000 => "macintosh",
+        65001 => "utf-8",
+        _ => return None,
+    };
+    Encoding::for_label(label.as_bytes())
+}
+
+/// Replace runs of blank lines by a single one
+fn collapse_blank_lines(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut blank = 0;
+    for line in text.split('\n') {
+        if line.trim().is_empty() {
+            blank += 1;
+            if blank > 1 {
+                continue;
+            }
+        } else {
+            blank = 0;
+        }
+        if !result.is_empty() {
+            result.push('\n');
+        }
+        result.push_str(line);
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_extract_rtf() {
+        let rtf = br#"{\rtf1\ansi\ansicpg1252\deff0{\fonttbl{\f0\fswiss Helvetica;}}{\colortbl;\red255\green0\blue0;}
+{\info{\title Budget {\b memo}}{\author Alice Martin}{\creatim\yr2024}}
+{\*\generator Riched20 10.0;}{\header Page header}
+\pard\f0\fs24 Caf\'e9 budget for Q3\par
+The team\rquote s numbers are {\b final}\emdash almost.\par
+\par\par
+\trowd A1\cell B1\cell\row
+Price: 5\'80\~net\par
+{\*\unknowndest ignored}{\field{\*\fldinst HYPERLINK "https://example.com"}{\fldrslt link text}}\par
+}"#;
+        let content = extract_rtf(rtf).unwrap();
+        assert_eq!(
+            content.text,
+            "Café budget for Q3\nThe team’s numbers are final—almost.\n\nA1\tB1\nPrice: 5€\u{a0}net\nlink text"
+        );
+        assert_eq!(content.metadata["title"], "Budget memo");
+        assert_eq!(content.metadata["author"], "Alice Martin");
+        assert!(!content.metadata.contains_key("subject"));
+    }
+
+    #[test]
+    fn test_code_pages() {
+        // Shift_JIS bytes escaped one by one
+        let content = extract_rtf(br"{\rtf1\ansi\ansicpg932 \'93\'fa\'96\'7b}").unwrap();
+        assert_eq!(content.text, "日本");
+
+        // Unicode characters with two fallback bytes, and binary data
+        let content = extract_rtf(b"{\\rtf1\\uc2 \\u26085\\'93\\'fa end\\bin3 {}x done}").unwrap();
+        assert_eq!(content.text, "日 end done");
+    }
+
+    #[test]
+    fn test_not_rtf() {
+        assert!(extract_rtf(b"Hello").is_err());
+    }
+
+    #[test]
+    fn test_extractor() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("letter.doc");
+        fs::write(&path, br"{\rtf1\ansi Dear reader,\par Regards}").unwrap();
+
+        let file_type = crate::indexer::file_type::detect(&path);
+        assert_eq!(file_type, FileType::Rtf);
+        let content = super::super::ExtractorRegistry::builtin().extract(&path, file_type).unwrap();
+        assert_eq!(content.text, "Dear reader,\nRegards");
+    }
+}
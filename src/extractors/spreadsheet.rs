@@ -0,0 +1,379 @@
+//! Spreadsheet extractor for workbooks and delimited text
+//!
+//! Workbooks (XLSX, XLSM, XLSB, XLS and ODS) are read with calamine, CSV
+//! and TSV files with the csv crate; both end up as a list of sheets and go
+//! through the same rendering. Each non-empty cell is written as
+//! `Sheet!C14 Header: value`, one line per row, so that a match names the
+//! sheet and cell it came from. The first non-empty row of a sheet is taken
+//! as its header row.
+
+use super::encoding::decode;
+use super::{Capabilities, ExtractedContent, Extractor};
+use crate::types::FileType;
+use crate::Result;
+use calamine::{open_workbook_auto, Data, Reader};
+use encoding_rs::Encoding;
+use std::fmt::Write;
+use std::path::Path;
+
+/// Maximum number of cells extracted from a file
+///
+/// Huge exports would otherwise produce more text than is useful to search.
+const MAX_CELLS: usize = 1_000_000;
+
+/// Workbooks, CSV and TSV files
+pub struct SpreadsheetExtractor;
+
+impl Extractor for SpreadsheetExtractor {
+    fn name(&self) -> &str {
+        "spreadsheet"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Xlsx]
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["xlsx", "xlsm", "xlsb", "xls", "ods", "csv", "tsv", "tab"]
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["text/csv", "text/tab-separated-values"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            metadata: true,
+            ..Capabilities::TEXT
+        }
+    }
+
+    fn extract(&self, path: &Path, file_type: FileType) -> Result<ExtractedContent> {
+        let extension = path
+            .extension()
+            .map(|e| e.to_string_lossy().to_lowercase())
+            .unwrap_or_default();
+
+        let (sheets, encoding) = match extension.as_str() {
+            "tsv" | "tab" => read_delimited(path, Some(b'\t'))?,
+            _ if file_type != FileType::Xlsx => read_delimited(path, None)?,
+            _ => (read_workbook(path)?, None),
+        };
+
+        let mut content = ExtractedContent::new(render(&sheets));
+        content.encoding = encoding.map(|encoding| encoding.name().to_string());
+        let names: Vec<&str> = sheets.iter().map(|sheet| sheet.name.as_str()).collect();
+        content.metadata.insert("sheets".to_string(), names.join(", "));
+        Ok(content)
+    }
+}
+
+/// Cells of one sheet
+struct Sheet {
+    name: String,
+    /// Zero-based row and column of the first cell
+    origin: (u32, u32),
+    rows: Vec<Vec<String>>,
+}
+
+/// Read all sheets of a workbook
+fn read_workbook(path: &Path) -> Result<Vec<Sheet>> {
+    let mut workbook = open_workbook_auto(path)
+        .map_err(|e| crate::Error::Extraction(format!("Spreadsheet extraction failed: {}", e)))?;
+
+    let mut sheets = Vec::new();
+    let mut cells = 0;
+    for name in workbook.sheet_names() {
+        let range = match workbook.worksheet_range(&name) {
+            Ok(range) => range,
+            Err(e) => {
+                tracing::debug!("Skipping sheet {} of {}: {}", name, path.display(), e);
+                continue;
+            }
+        };
+
+        let mut rows = Vec::new();
+        for row in range.rows() {
+            if cells >= MAX_CELLS {
+                break;
+            }
+            cells += row.len();
+            rows.push(row.iter().map(format_cell).collect());
+        }
+
+        sheets.push(Sheet {
+            name,
+            origin: range.start().unwrap_or((0, 0)),
+            rows,
+        });
+    }
+
+    Ok(sheets)
+}
+
+/// Format a cell value as text
+fn format_cell(cell: &Data) -> String {
+    match cell {
+        Data::DateTime(value) if value.is_datetime() => match value.as_datetime() {
+            Some(datetime) if datetime.time() == chrono::NaiveTime::MIN => datetime.date().to_string(),
+            Some(datetime) => datetime.to_string(),
+            None => value.to_string(),
+        },
+        _ => cell.to_string(),
+    }
+}
+
+/// Read a delimited text file as a single sheet named after the file
+///
+/// # Arguments
+/// * `path` - Path to the file
+/// * `delimiter` - Field delimiter, guessed from the first line if `None`
+///
+/// # Returns
+/// The sheet, and the encoding the file was decoded from
+fn read_delimited(path: &Path, delimiter: Option<u8>) -> Result<(Vec<Sheet>, Option<&'static Encoding>)> {
+    let decoded = decode(&std::fs::read(path)?);
+    let bytes = decoded.text.into_bytes();
+    let delimiter = delimiter.unwrap_or_else(|| guess_delimiter(&bytes));
+
+    let mut reader = csv::ReaderBuilder::new()
+        .delimiter(delimiter)
+        .has_headers(false)
+        .flexible(true)
+        .from_reader(bytes.as_slice());
+
+    let mut rows = Vec::new();
+    let mut cells = 0;
+    for record in reader.byte_records() {
+        let record = record
+            .map_err(|e| crate::Error::Extraction(format!("CSV extraction failed: {}", e)))?;
+        if cells >= MAX_CELLS {
+            break;
+        }
+        cells += record.len();
+        // Blank lines are skipped by the reader but still count as rows. The
+        // record position is where the reader started, before the blank lines.
+        if let Some(position) = record.position() {
+            let blank_lines = bytes[position.byte() as usize..]
+                .iter()
+                .take_while(|&&b| b == b'\n' || b == b'\r')
+                .filter(|&&b| b == b'\n')
+                .count();
+            rows.resize(position.line() as usize - 1 + blank_lines, Vec::new());
+        }
+        rows.push(
+            record
+                .iter()
+                .map(|field| String::from_utf8_lossy(field).into_owned())
+                .collect(),
+        );
+    }
+
+    let name = path
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let sheet = Sheet {
+        name,
+        origin: (0, 0),
+        rows,
+    };
+    Ok((vec![sheet], Some(decoded.encoding)))
+}
+
+/// Pick the most frequent of `,`, `;` and tab in the first line
+fn guess_delimiter(bytes: &[u8]) -> u8 {
+    let first_line = bytes.split(|&b| b == b'\n').next().unwrap_or_default();
+    // Reversed so that ties go to the comma
+    [b',', b';', b'\t']
+        .into_iter()
+        .rev()
+        .max_by_key(|&delimiter| first_line.iter().filter(|&&b| b == delimiter).count())
+        .unwrap_or(b',')
+}
+
+/// Render sheets as text, one line per row
+fn render(sheets: &[Sheet]) -> String {
+    let mut text = String::new();
+
+    for sheet in sheets {
+        let prefix = sheet_prefix(&sheet.name);
+        let _ = writeln!(text, "Sheet: {}", sheet.name);
+
+        let mut header: Option<&[String]> = None;
+        for (row_offset, row) in sheet.rows.iter().enumerate() {
+            if row.iter().all(|cell| cell.trim().is_empty()) {
+                continue;
+            }
+            let row_number = sheet.origin.0 as usize + row_offset + 1;
+
+            let mut line = String::new();
+            for (col_offset, cell) in row.iter().enumerate() {
+                let value = cell.trim();
+                if value.is_empty() {
+                    continue;
+                }
+                if !line.is_empty() {
+                    line.push_str(" | ");
+                }
+
+                let column = column_name(sheet.origin.1 as usize + col_offset);
+                let _ = write!(line, "{}!{}{} ", prefix, column, row_number);
+                let label = header
+                    .and_then(|header| header.get(col_offset))
+                    .map(|label| label.trim())
+                    .filter(|label| !label.is_empty());
+                if let Some(label) = label {
+                    let _ = write!(line, "{}: ", label);
+                }
+                line.push_str(value);
+            }
+
+            text.push_str(&line);
+            text.push('\n');
+            header.get_or_insert(row);
+        }
+        text.push('\n');
+    }
+
+    text
+}
+
+/// Sheet name as written in a cell reference, quoted if needed
+fn sheet_prefix(name: &str) -> String {
+    if !name.is_empty() && name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        name.to_string()
+    } else {
+        format!("'{}'", name.replace('\'', "''"))
+    }
+}
+
+/// Spreadsheet column name of a zero-based column index: A, B, ..., Z, AA, ...
+fn column_name(mut index: usize) -> String {
+    let mut name = Vec::new();
+    loop {
+        name.push(b'A' + (index % 26) as u8);
+        if index < 26 {
+            break;
+        }
+        index = index / 26 - 1;
+    }
+    name.reverse();
+    String::from_utf8(name).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::io::Write as _;
+    use tempfile::TempDir;
+
+    /// Write a minimal XLSX workbook with inline strings
+    fn write_xlsx(path: &Path, sheets: &[(&str, &str)]) {
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        let options = zip::write::SimpleFileOptions::default();
+        let mut add = |name: &str, content: &str| {
+            zip.start_file(name, options).unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        };
+
+        let mut workbook = String::from(
+            r#"<workbook xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main" xmlns:r="http://schemas.openxmlformats.org/officeDocument/2006/relationships"><sheets>"#,
+        );
+        let mut rels = String::from(r#"<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships">"#);
+        for (i, (name, _)) in sheets.iter().enumerate() {
+            workbook.push_str(&format!(r#"<sheet name="{}" sheetId="{}" r:id="rId{}"/>"#, name, i + 1, i + 1));
+            rels.push_str(&format!(
+                r#"<Relationship Id="rId{}" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/worksheet" Target="worksheets/sheet{}.xml"/>"#,
+                i + 1,
+                i + 1
+            ));
+        }
+        workbook.push_str("</sheets></workbook>");
+        rels.push_str("</Relationships>");
+
+        add("xl/workbook.xml", &workbook);
+        add("xl/_rels/workbook.xml.rels", &rels);
+        for (i, (_, data)) in sheets.iter().enumerate() {
+            add(
+                &format!("xl/worksheets/sheet{}.xml", i + 1),
+                &format!(
+                    r#"<worksheet xmlns="http://schemas.openxmlformats.org/spreadsheetml/2006/main"><sheetData>{}</sheetData></worksheet>"#,
+                    data
+                ),
+            );
+        }
+        zip.finish().unwrap();
+    }
+
+    #[test]
+    fn test_extract_xlsx() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("finance.xlsx");
+        write_xlsx(
+            &path,
+            &[
+                (
+                    "Budget",
+                    r#"<row r="1"><c r="A1" t="inlineStr"><is><t>Item</t></is></c><c r="C1" t="inlineStr"><is><t>Amount</t></is></c></row><row r="14"><c r="A14" t="inlineStr"><is><t>Rent</t></is></c><c r="C14"><v>1200</v></c></row>"#,
+                ),
+                ("Q1 Notes", r#"<row r="2"><c r="B2" t="inlineStr"><is><t>Review</t></is></c></row>"#),
+            ],
+        );
+
+        let content = SpreadsheetExtractor.extract(&path, FileType::Xlsx).unwrap();
+        assert!(content.text.contains("Budget!A1 Item | Budget!C1 Amount\n"));
+        assert!(content.text.contains("Budget!A14 Item: Rent | Budget!C14 Amount: 1200\n"));
+        assert!(content.text.contains("'Q1 Notes'!B2 Review\n"));
+        assert_eq!(content.metadata["sheets"], "Budget, Q1 Notes");
+    }
+
+    #[test]
+    fn test_extract_delimited() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let path = temp_dir.path().join("expenses.csv");
+        fs::write(&path, "Date,Vendor,Total\n2024-03-01,\"Acme, Inc.\",99.50\n\n2024-03-02,Globex\n").unwrap();
+        let content = SpreadsheetExtractor.extract(&path, FileType::Text).unwrap();
+        assert!(content.text.starts_with("Sheet: expenses\n"));
+        assert!(content.text.contains("expenses!B2 Vendor: Acme, Inc. | expenses!C2 Total: 99.50\n"));
+        assert!(content.text.contains("expenses!A4 Date: 2024-03-02 | expenses!B4 Vendor: Globex\n"));
+
+        let path = temp_dir.path().join("people.tsv");
+        fs::write(&path, "name\tcity\nAda\tLondon\n").unwrap();
+        let content = SpreadsheetExtractor.extract(&path, FileType::Text).unwrap();
+        assert!(content.text.contains("people!B2 city: London"));
+
+        // Semicolons, as exported with a comma decimal separator
+        let path = temp_dir.path().join("prices.csv");
+        fs::write(&path, "item;price\ntea;2,50\n").unwrap();
+        let content = SpreadsheetExtractor.extract(&path, FileType::Text).unwrap();
+        assert!(content.text.contains("prices!B2 price: 2,50"));
+        assert_eq!(content.encoding.as_deref(), Some("UTF-8"));
+
+        // Legacy encodings, as exported by Excel
+        let path = temp_dir.path().join("clients.csv");
+        fs::write(&path, b"Nom;Soci\xE9t\xE9\nRen\xE9e;Caf\xE9 du March\xE9\n").unwrap();
+        let content = SpreadsheetExtractor.extract(&path, FileType::Text).unwrap();
+        assert!(content.text.contains("clients!B2 Soci\u{e9}t\u{e9}: Caf\u{e9} du March\u{e9}"));
+        assert_eq!(content.encoding.as_deref(), Some("windows-1252"));
+
+        let path = temp_dir.path().join("unicode.tsv");
+        let utf16: Vec<u8> = "\u{feff}name\tcity\nZo\u{eb}\tK\u{f6}ln\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        fs::write(&path, utf16).unwrap();
+        let content = SpreadsheetExtractor.extract(&path, FileType::Text).unwrap();
+        assert!(content.text.contains("unicode!B2 city: K\u{f6}ln"));
+        assert_eq!(content.encoding.as_deref(), Some("UTF-16LE"));
+    }
+
+    #[test]
+    fn test_column_name() {
+        assert_eq!(column_name(0), "A");
+        assert_eq!(column_name(25), "Z");
+        assert_eq!(column_name(26), "AA");
+        assert_eq!(column_name(27), "AB");
+        assert_eq!(column_name(701), "ZZ");
+        assert_eq!(column_name(702), "AAA");
+    }
+}
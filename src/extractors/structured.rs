@@ -0,0 +1,374 @@
+//! JSON, YAML and TOML extractor
+//!
+//! Structured data files are indexed as text, and flattened into the key
+//! paths of their values: `{"database": {"host": "prod"}}` gives
+//! `database.host = prod`, and array elements are numbered from 0, as in
+//! `servers.0.name`. Queries such as `key:database.host=prod` search them,
+//! see [`KeyTerm`]. Files that don't parse are indexed as plain text.
+
+use super::text::PlainTextExtractor;
+use super::{ExtractedContent, Extractor};
+use crate::types::FileType;
+use crate::Result;
+use serde_json::Value;
+use std::path::Path;
+
+/// Maximum number of key paths kept per file, to bound the cost of large
+/// data dumps
+const MAX_ENTRIES: usize = 10_000;
+
+/// Maximum length of a value in characters; longer values are cut
+const MAX_VALUE_CHARS: usize = 200;
+
+/// A value of a structured document and its key path
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyValue {
+    /// Keys from the root to the value, separated by dots
+    pub path: String,
+    pub value: String,
+}
+
+/// A `key:` term of a query
+///
+/// `key:database.host` matches the key path and the paths below it, and
+/// `key:database.host=prod` a value, ignoring case. Values containing
+/// spaces or query syntax are quoted with the path: `key:"title=Q3 plan"`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyTerm {
+    /// Key path, in lowercase
+    pub path: String,
+    /// Value, in lowercase
+    pub value: Option<String>,
+}
+
+impl KeyTerm {
+    /// Find the `key:` terms of a query
+    pub fn parse_query(query: &str) -> Vec<KeyTerm> {
+        let mut terms = Vec::new();
+        let mut rest = query;
+        while let Some(start) = rest.find("key:") {
+            let at_word_start = rest[..start]
+                .chars()
+                .next_back()
+                .is_none_or(|c| c.is_whitespace() || "+-(".contains(c));
+            let after = &rest[start + 4..];
+            let (term, remaining) = match after.strip_prefix('"') {
+                Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+                None => {
+                    let end = after.find(|c: char| c.is_whitespace() || c == ')').unwrap_or(after.len());
+                    after.split_at(end)
+                }
+            };
+            if at_word_start && !term.is_empty() {
+                let term = term.to_lowercase();
+                terms.push(match term.split_once('=') {
+                    Some((path, value)) => KeyTerm {
+                        path: path.to_string(),
+                        value: Some(value.to_string()),
+                    },
+                    None => KeyTerm { path: term, value: None },
+                });
+            }
+            rest = remaining;
+        }
+        terms
+    }
+
+    /// Whether an entry matches the term
+    pub fn matches(&self, entry: &KeyValue) -> bool {
+        let path = entry.path.to_lowercase();
+        match &self.value {
+            Some(value) => path == self.path && entry.value.to_lowercase() == *value,
+            None => {
+                path == self.path
+                    || path.strip_prefix(&self.path).is_some_and(|below| below.starts_with('.'))
+            }
+        }
+    }
+}
+
+/// JSON, YAML and TOML files
+pub struct StructuredExtractor;
+
+impl Extractor for StructuredExtractor {
+    fn name(&self) -> &str {
+        "structured"
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["json", "yaml", "yml", "toml"]
+    }
+
+    fn extract(&self, path: &Path, file_type: FileType) -> Result<ExtractedContent> {
+        let mut content = PlainTextExtractor.extract(path, file_type)?;
+        let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+        let documents = match extension.as_deref() {
+            Some("json") => parse_json(&content.text),
+            Some("toml") => toml::from_str(&content.text).map(toml_to_json).into_iter().collect(),
+            _ => parse_yaml(&content.text),
+        };
+        for document in &documents {
+            flatten(document, &mut String::new(), &mut content.key_values);
+        }
+        Ok(content)
+    }
+}
+
+/// Parse JSON, allowing the comments and trailing commas of configuration
+/// files in the JSONC dialect
+fn parse_json(text: &str) -> Vec<Value> {
+    serde_json::from_str(text)
+        .or_else(|_| serde_json::from_str(&strip_json_comments(text)))
+        .into_iter()
+        .collect()
+}
+
+/// Remove `//` and `/* */` comments and trailing commas outside strings
+fn strip_json_comments(text: &str) -> String {
+    let mut stripped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    while let Some(c) = chars.next() {
+        if in_string {
+            stripped.push(c);
+            match c {
+                '\\' => stripped.extend(chars.next()),
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                stripped.push(c);
+            }
+            ('/', Some('/')) => {
+                while chars.next_if(|&c| c != '\n').is_some() {}
+            }
+            ('/', Some('*')) => {
+                chars.next();
+                let mut previous = ' ';
+                for c in chars.by_ref() {
+                    if previous == '*' && c == '/' {
+                        break;
+                    }
+                    previous = c;
+                }
+            }
+            (']' | '}', _) => {
+                let end = stripped.trim_end().len();
+                if stripped[..end].ends_with(',') {
+                    stripped.truncate(end - 1);
+                }
+                stripped.push(c);
+            }
+            _ => stripped.push(c),
+        }
+    }
+    stripped
+}
+
+/// Parse the documents of a YAML stream
+///
+/// A stream that fails to parse gives no documents at all, so that a
+/// template with YAML-like lines is indexed as text only.
+fn parse_yaml(text: &str) -> Vec<Value> {
+    let mut documents = Vec::new();
+    for document in serde_yaml::Deserializer::from_str(text) {
+        match serde::Deserialize::deserialize(document) {
+            Ok(value) => documents.push(yaml_to_json(value)),
+            Err(_) => return Vec::new(),
+        }
+    }
+    documents
+}
+
+/// Convert a YAML value to JSON, with keys that are not strings written
+/// out and tags dropped
+fn yaml_to_json(value: serde_yaml::Value) -> Value {
+    match value {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(value) => Value::Bool(value),
+        serde_yaml::Value::Number(number) => serde_json::from_str(&number.to_string()).unwrap_or(Value::Null),
+        serde_yaml::Value::String(value) => Value::String(value),
+        serde_yaml::Value::Sequence(values) => Value::Array(values.into_iter().map(yaml_to_json).collect()),
+        serde_yaml::Value::Mapping(mapping) => Value::Object(
+            mapping
+                .into_iter()
+                .map(|(key, value)| {
+                    let key = match yaml_to_json(key) {
+                        Value::String(key) => key,
+                        key => key.to_string(),
+                    };
+                    (key, yaml_to_json(value))
+                })
+                .collect(),
+        ),
+        serde_yaml::Value::Tagged(tagged) => yaml_to_json(tagged.value),
+    }
+}
+
+/// Convert a TOML value to JSON, dates and times becoming strings
+pub(crate) fn toml_to_json(value: toml::Value) -> Value {
+    match value {
+        toml::Value::String(value) => Value::String(value),
+        toml::Value::Integer(value) => Value::from(value),
+        toml::Value::Float(value) => Value::from(value),
+        toml::Value::Boolean(value) => Value::Bool(value),
+        toml::Value::Datetime(value) => Value::String(value.to_string()),
+        toml::Value::Array(values) => Value::Array(values.into_iter().map(toml_to_json).collect()),
+        toml::Value::Table(table) => Value::Object(table.into_iter().map(|(key, value)| (key, toml_to_json(value))).collect()),
+    }
+}
+
+/// Add the key paths of the scalar values below `value` to `entries`
+///
+/// # Arguments
+/// * `value` - Value found at `path`
+/// * `path` - Key path of the value, empty at the root
+/// * `entries` - Entries found so far, at most [`MAX_ENTRIES`]
+fn flatten(value: &Value, path: &mut String, entries: &mut Vec<KeyValue>) {
+    let descend = |key: &str, value: &Value, path: &mut String, entries: &mut Vec<KeyValue>| {
+        let length = path.len();
+        if !path.is_empty() {
+            path.push('.');
+        }
+        path.push_str(key);
+        flatten(value, path, entries);
+        path.truncate(length);
+    };
+
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                descend(key, value, path, entries);
+            }
+        }
+        Value::Array(values) => {
+            for (i, value) in values.iter().enumerate() {
+                descend(&i.to_string(), value, path, entries);
+            }
+        }
+        // Scalars at the root have no key
+        _ if path.is_empty() => {}
+        _ if entries.len() >= MAX_ENTRIES => {}
+        scalar => {
+            let value = match scalar {
+                Value::String(text) => text.chars().take(MAX_VALUE_CHARS).collect(),
+                scalar => scalar.to_string(),
+            };
+            entries.push(KeyValue {
+                path: path.clone(),
+                value,
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn entries(name: &str, text: &str) -> Vec<(String, String)> {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join(name);
+        std::fs::write(&path, text).unwrap();
+        let content = StructuredExtractor.extract(&path, FileType::Text).unwrap();
+        assert_eq!(content.text, text);
+        content.key_values.into_iter().map(|entry| (entry.path, entry.value)).collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(path, value)| (path.to_string(), value.to_string())).collect()
+    }
+
+    #[test]
+    fn test_json() {
+        let text = r#"{"database": {"host": "prod", "port": 5432, "replica": null}, "servers": [{"name": "web-1"}, {"name": "web-2", "tags": []}], "debug": false}"#;
+        assert_eq!(
+            entries("config.json", text),
+            pairs(&[
+                ("database.host", "prod"),
+                ("database.port", "5432"),
+                ("database.replica", "null"),
+                ("servers.0.name", "web-1"),
+                ("servers.1.name", "web-2"),
+                ("debug", "false"),
+            ])
+        );
+
+        // Comments and trailing commas, as in editor settings
+        let text = "{\n  // Editor\n  \"editor.fontSize\": 14, /* px */\n  \"url\": \"http://example.com/*\",\n}\n";
+        assert_eq!(
+            entries("settings.json", text),
+            pairs(&[("editor.fontSize", "14"), ("url", "http://example.com/*")])
+        );
+
+        // Not JSON at all
+        assert!(entries("broken.json", "{\"open\": ").is_empty());
+    }
+
+    #[test]
+    fn test_yaml() {
+        let text = "apiVersion: v1\nkind: Service\nmetadata:\n  name: billing\n  labels: {tier: backend}\n---\nkind: Deployment\nspec:\n  replicas: 3\n  ports:\n    - 80\n    - !custom 443\n";
+        assert_eq!(
+            entries("deploy.yaml", text),
+            pairs(&[
+                ("apiVersion", "v1"),
+                ("kind", "Service"),
+                ("metadata.name", "billing"),
+                ("metadata.labels.tier", "backend"),
+                ("kind", "Deployment"),
+                ("spec.replicas", "3"),
+                ("spec.ports.0", "80"),
+                ("spec.ports.1", "443"),
+            ])
+        );
+        assert_eq!(entries("ports.yml", "200: OK\ntrue: yes\n"), pairs(&[("200", "OK"), ("true", "yes")]));
+        assert!(entries("template.yaml", "name: web\n{{- if .Values.debug }}\ndebug: true\n").is_empty());
+    }
+
+    #[test]
+    fn test_toml() {
+        let text = "title = \"Site\"\n\n[database]\nhost = \"prod\"\ncreated = 2024-03-01\n\n[[plugins]]\nname = \"search\"\n";
+        assert_eq!(
+            entries("Config.toml", text),
+            pairs(&[
+                ("title", "Site"),
+                ("database.host", "prod"),
+                ("database.created", "2024-03-01"),
+                ("plugins.0.name", "search"),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_long_values() {
+        let text = format!("{{\"blob\": \"{}\"}}", "a".repeat(1000));
+        assert_eq!(entries("blob.json", &text)[0].1.len(), MAX_VALUE_CHARS);
+    }
+
+    #[test]
+    fn test_key_terms() {
+        let terms = KeyTerm::parse_query("key:Database.Host=prod deploy +key:\"title=Q3 plan\" (key:servers) monkey:x");
+        let key = |path: &str, value: Option<&str>| KeyTerm {
+            path: path.to_string(),
+            value: value.map(str::to_string),
+        };
+        assert_eq!(
+            terms,
+            vec![key("database.host", Some("prod")), key("title", Some("q3 plan")), key("servers", None)]
+        );
+
+        let entry = |path: &str, value: &str| KeyValue {
+            path: path.to_string(),
+            value: value.to_string(),
+        };
+        assert!(terms[0].matches(&entry("database.host", "Prod")));
+        assert!(!terms[0].matches(&entry("database.host", "staging")));
+        assert!(terms[2].matches(&entry("servers.0.name", "web-1")));
+        assert!(!terms[2].matches(&entry("serverless", "true")));
+    }
+}
@@ -1,19 +1,110 @@
-//! Text file content extractor
+//! Text and PDF content extractors
 
+use super::code::Symbol;
+use super::markdown::Link;
+use super::structured::KeyValue;
+use super::encoding::decode;
+use super::{Capabilities, Extractor, ExtractorRegistry};
 use crate::types::FileType;
 use crate::Result;
+use std::collections::BTreeMap;
 use std::fs;
 use std::path::Path;
 
 /// Extracted text content from a file
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct ExtractedContent {
     pub text: String,
     pub word_count: usize,
     pub language: Option<String>,
+    /// Character encoding the text was decoded from, for text files
+    pub encoding: Option<String>,
+    /// Document properties such as title or author, by name
+    pub metadata: BTreeMap<String, String>,
+    /// Pages of the text, for formats with a page structure
+    pub pages: Vec<Page>,
+    /// Definitions in source code
+    pub symbols: Vec<Symbol>,
+    /// Links to other notes and files
+    pub links: Vec<Link>,
+    /// Values of structured data such as JSON, by key path
+    pub key_values: Vec<KeyValue>,
 }
 
-/// Extract text content from a file
+impl ExtractedContent {
+    /// Content consisting of plain text, with its word count
+    pub fn new(text: String) -> Self {
+        Self {
+            // Count words (simple whitespace-based counting)
+            word_count: text.split_whitespace().count(),
+            text,
+            ..Default::default()
+        }
+    }
+
+    /// Content made of the texts of consecutive pages, numbered from 1
+    ///
+    /// Each page starts on a new line.
+    pub fn from_pages(pages: Vec<String>) -> Self {
+        let mut text = String::new();
+        let mut ranges = Vec::with_capacity(pages.len());
+        for (i, page) in pages.iter().enumerate() {
+            let start = text.len();
+            text.push_str(page);
+            ranges.push(Page {
+                number: i + 1,
+                start,
+                end: text.len(),
+            });
+            if !page.ends_with('\n') {
+                text.push('\n');
+            }
+        }
+
+        let mut content = Self::new(text);
+        content.pages = ranges;
+        content
+    }
+
+    /// Text of a page, by page number
+    pub fn page_text(&self, number: usize) -> Option<&str> {
+        let page = self.pages.iter().find(|page| page.number == number)?;
+        self.text.get(page.start..page.end)
+    }
+}
+
+/// A page of a document, as a byte range of its extracted text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Page {
+    /// Page number, starting at 1
+    pub number: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Page {
+    /// Number of the page a byte offset of the text falls on
+    ///
+    /// Offsets between two pages count towards the earlier one.
+    pub fn number_at(pages: &[Page], offset: usize) -> Option<usize> {
+        pages.iter().rev().find(|page| page.start <= offset).map(|page| page.number)
+    }
+
+    /// What the pages of a file are called: `Cell` for the cells of Jupyter
+    /// notebooks, `Page` otherwise
+    pub fn label(path: &str) -> &'static str {
+        let notebook = Path::new(path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("ipynb"));
+        if notebook {
+            "Cell"
+        } else {
+            "Page"
+        }
+    }
+}
+
+/// Extract text content from a file with the built-in extractors
 ///
 /// # Arguments
 /// * `path` - Path to the file
@@ -22,99 +113,149 @@ pub struct ExtractedContent {
 /// # Returns
 /// Extracted text content with metadata
 pub fn extract_text(path: &Path, file_type: FileType) -> Result<ExtractedContent> {
-    let content = match file_type {
-        FileType::Pdf => extract_pdf(path)?,
-        FileType::Docx => extract_docx(path)?,
-        FileType::Image => {
-            // For now, we just store the filename for images
-            // Later we can add OCR or image embedding
-            return Err(crate::Error::UnsupportedFileType(
-                "Image text extraction not yet implemented (OCR planned)".to_string()
-            ));
-        }
-        FileType::Text | FileType::Code | FileType::Markdown | FileType::Unknown => {
-            // Read file content as UTF-8
-            fs::read_to_string(path)?
-        }
-        FileType::Xlsx => {
-            return Err(crate::Error::UnsupportedFileType(
-                "Excel extraction not yet implemented".to_string()
-            ));
-        }
-        FileType::Archive => {
-            return Err(crate::Error::UnsupportedFileType(
-                "Archive extraction not supported".to_string()
-            ));
-        }
-    };
+    ExtractorRegistry::builtin().extract(path, file_type)
+}
+
+/// Plain text, code and Markdown files, and text in unknown formats
+pub struct PlainTextExtractor;
 
-    // Count words (simple whitespace-based counting)
-    let word_count = content.split_whitespace().count();
+impl Extractor for PlainTextExtractor {
+    fn name(&self) -> &str {
+        "text"
+    }
 
-    // Detect language based on file type
-    let language = detect_language(path, file_type);
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Text, FileType::Code, FileType::Markdown, FileType::Unknown]
+    }
 
-    Ok(ExtractedContent {
-        text: content,
-        word_count,
-        language,
-    })
+    fn extract(&self, path: &Path, file_type: FileType) -> Result<ExtractedContent> {
+        let decoded = decode(&fs::read(path)?);
+        let mut content = ExtractedContent::new(decoded.text);
+        content.encoding = Some(decoded.encoding.name().to_string());
+        content.language = detect_language(path, file_type).or_else(|| shebang_language(&content.text));
+        Ok(content)
+    }
 }
 
-/// Extract text from PDF files
-fn extract_pdf(path: &Path) -> Result<String> {
-    let bytes = fs::read(path)?;
-
-    // Try to extract text, but handle errors AND panics gracefully
-    // Some PDFs may be corrupted, encrypted, or have complex structures
-    // The pdf-extract library can panic on malformed PDFs
-    let result = std::panic::catch_unwind(|| {
-        pdf_extract::extract_text_from_mem(&bytes)
-    });
-
-    match result {
-        Ok(Ok(text)) => Ok(text),
-        Ok(Err(e)) => {
-            // PDF extraction error
-            Err(crate::Error::Extraction(format!(
-                "PDF extraction failed: {}",
-                e
-            )))
+/// PDF documents
+pub struct PdfExtractor;
+
+impl Extractor for PdfExtractor {
+    fn name(&self) -> &str {
+        "pdf"
+    }
+
+    fn file_types(&self) -> &[FileType] {
+        &[FileType::Pdf]
+    }
+
+    fn mime_types(&self) -> &[&str] {
+        &["application/pdf"]
+    }
+
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            text: true,
+            metadata: true,
+            pages: true,
         }
-        Err(_) => {
-            // PDF extraction panicked
-            Err(crate::Error::Extraction(
-                "PDF extraction failed (file may be corrupted or use unsupported features)".to_string()
-            ))
+    }
+
+    fn extract(&self, path: &Path, _file_type: FileType) -> Result<ExtractedContent> {
+        let bytes = fs::read(path)?;
+        let mut content = ExtractedContent::from_pages(extract_pdf_pages(&bytes)?);
+        if let Ok(document) = lopdf::Document::load_mem(&bytes) {
+            content.metadata.extend(pdf_metadata(&document));
         }
+        Ok(content)
     }
 }
 
-/// Extract text from DOCX files
-fn extract_docx(path: &Path) -> Result<String> {
-    let bytes = fs::read(path)?;
-    let docx = docx_rs::read_docx(&bytes)
-        .map_err(|e| crate::Error::Other(anyhow::anyhow!("DOCX extraction failed: {}", e)))?;
-
-    // Extract all text from paragraphs
-    let mut text = String::new();
-    for child in docx.document.children {
-        if let docx_rs::DocumentChild::Paragraph(para) = child {
-            for child in para.children {
-                if let docx_rs::ParagraphChild::Run(run) = child {
-                    for child in run.children {
-                        if let docx_rs::RunChild::Text(t) = child {
-                            text.push_str(&t.text);
-                            text.push(' ');
-                        }
-                    }
-                }
-            }
-            text.push('\n');
+/// Extract the text of each page of a PDF
+pub(crate) fn extract_pdf_pages(bytes: &[u8]) -> Result<Vec<String>> {
+    // Some PDFs are corrupted, encrypted or have complex structures that
+    // pdf-extract panics on
+    match std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(bytes)) {
+        Ok(Ok(pages)) => Ok(pages),
+        Ok(Err(e)) => Err(crate::Error::Extraction(format!("PDF extraction failed: {}", e))),
+        Err(_) => Err(crate::Error::Extraction(
+            "PDF extraction failed (file may be corrupted or use unsupported features)".to_string(),
+        )),
+    }
+}
+
+/// Properties of a PDF from its document information dictionary
+///
+/// The title, author, subject and keywords are returned under these names,
+/// the creation date as `created`, in RFC 3339 format.
+pub(crate) fn pdf_metadata(document: &lopdf::Document) -> BTreeMap<String, String> {
+    let mut metadata = BTreeMap::new();
+    let Some(info) = document
+        .trailer
+        .get(b"Info")
+        .and_then(|info| document.dereference(info))
+        .and_then(|(_, info)| info.as_dict())
+        .ok()
+    else {
+        return metadata;
+    };
+
+    let text = |key: &[u8]| {
+        let object = info.get_deref(key, document).ok()?;
+        let text = lopdf::decode_text_string(object).ok()?;
+        let text = text.trim();
+        (!text.is_empty()).then(|| text.to_string())
+    };
+    for (key, name) in [
+        (&b"Title"[..], "title"),
+        (b"Author", "author"),
+        (b"Subject", "subject"),
+        (b"Keywords", "keywords"),
+    ] {
+        if let Some(value) = text(key) {
+            metadata.insert(name.to_string(), value);
         }
     }
+    if let Some(created) = text(b"CreationDate").and_then(|date| pdf_date(&date)) {
+        metadata.insert("created".to_string(), created.to_rfc3339());
+    }
+    metadata
+}
 
-    Ok(text)
+/// Parse a PDF date such as `D:20240314093000+01'00'`
+///
+/// Everything after the year is optional; dates without a time zone are
+/// taken as UTC.
+fn pdf_date(text: &str) -> Option<chrono::DateTime<chrono::FixedOffset>> {
+    let text = text.strip_prefix("D:").unwrap_or(text);
+    let digits = text.bytes().take_while(u8::is_ascii_digit).count();
+    if digits < 4 {
+        return None;
+    }
+    // Two-digit field starting at a byte offset, if present
+    let field = |start: usize, default: u32| -> Option<u32> {
+        if start + 2 > digits {
+            return Some(default);
+        }
+        text[start..start + 2].parse().ok()
+    };
+    let year = text[..4].parse().ok()?;
+    let date = chrono::NaiveDate::from_ymd_opt(year, field(4, 1)?, field(6, 1)?)?;
+    let time = date.and_hms_opt(field(8, 0)?, field(10, 0)?, field(12, 0)?)?;
+
+    // Z, or +HH'mm' / -HH'mm'
+    let zone = &text[digits.min(14)..];
+    let offset = match zone.chars().next() {
+        Some(sign @ ('+' | '-')) => {
+            let zone: String = zone[1..].chars().filter(char::is_ascii_digit).collect();
+            let hours: i32 = zone.get(..2)?.parse().ok()?;
+            let minutes: i32 = zone.get(2..4).map_or(Some(0), |m| m.parse().ok())?;
+            let seconds = (hours * 60 + minutes) * 60;
+            chrono::FixedOffset::east_opt(if sign == '-' { -seconds } else { seconds })?
+        }
+        _ => chrono::FixedOffset::east_opt(0)?,
+    };
+    time.and_local_timezone(offset).single()
 }
 
 /// Detect programming language from file extension
@@ -127,25 +268,72 @@ fn detect_language(path: &Path, file_type: FileType) -> Option<String> {
         .and_then(|ext| ext.to_str())
         .map(|ext| match ext.to_lowercase().as_str() {
             "rs" => "rust",
-            "py" => "python",
-            "js" => "javascript",
-            "ts" => "typescript",
+            "py" | "pyw" | "pyi" => "python",
+            "js" | "mjs" | "cjs" | "jsx" => "javascript",
+            "ts" | "mts" | "cts" | "tsx" => "typescript",
             "java" => "java",
-            "c" => "c",
-            "cpp" | "cc" | "cxx" => "cpp",
+            "c" | "h" => "c",
+            "cpp" | "cc" | "cxx" | "hpp" | "hh" | "hxx" => "cpp",
             "go" => "go",
             "rb" => "ruby",
             "php" => "php",
             "cs" => "csharp",
             "swift" => "swift",
-            "kt" => "kotlin",
+            "kt" | "kts" => "kotlin",
             "scala" => "scala",
-            "sh" | "bash" => "shell",
+            "lua" => "lua",
+            "pl" | "pm" => "perl",
+            "r" => "r",
+            "dart" => "dart",
+            "hs" => "haskell",
+            "ex" | "exs" => "elixir",
+            "sql" => "sql",
+            "sh" | "bash" | "zsh" | "fish" => "shell",
+            "ps1" => "powershell",
+            "css" | "scss" | "sass" | "less" => "css",
             _ => "unknown",
         })
         .map(String::from)
 }
 
+/// Detect the language of a script from its `#!` line
+fn shebang_language(text: &str) -> Option<String> {
+    let line = text.lines().next()?.strip_prefix("#!")?;
+    let mut words = line.split_whitespace();
+    let mut interpreter = words.next()?.rsplit('/').next()?;
+    if interpreter == "env" {
+        interpreter = words.find(|word| !word.starts_with('-'))?;
+    }
+
+    let language = match interpreter.trim_end_matches(|c: char| c.is_ascii_digit() || c == '.') {
+        "python" => "python",
+        "node" | "deno" | "bun" => "javascript",
+        "ruby" => "ruby",
+        "perl" => "perl",
+        "php" => "php",
+        "lua" => "lua",
+        "sh" | "bash" | "zsh" | "dash" | "ksh" | "fish" => "shell",
+        _ => return None,
+    };
+    Some(language.to_string())
+}
+
+/// Byte offset of the first match of a query in a text
+///
+/// Looks for the whole query first, then for the earliest of its words.
+pub fn match_offset(text: &str, query: &str) -> Option<usize> {
+    let text_lower = text.to_lowercase();
+    let query_lower = query.trim().to_lowercase();
+    if query_lower.is_empty() {
+        return None;
+    }
+    text_lower
+        .find(&query_lower)
+        .or_else(|| query_lower.split_whitespace().filter_map(|word| text_lower.find(word)).min())
+        // Lowercasing can change the length of a few characters
+        .map(|offset| offset.min(text.len()))
+}
+
 /// Extract a snippet from text around a search term
 ///
 /// # Arguments
@@ -160,8 +348,22 @@ pub fn extract_snippet(text: &str, query: &str, context_chars: usize) -> Option<
     let text_lower = text.to_lowercase();
 
     if let Some(pos) = text_lower.find(&query_lower) {
-        let start = pos.saturating_sub(context_chars);
-        let end = (pos + query.len() + context_chars).min(text.len());
+        // Lowercasing can change the length of a few characters, so the
+        // offset may not fall on a character of the original text
+        let mut pos = pos.min(text.len());
+        while !text.is_char_boundary(pos) {
+            pos -= 1;
+        }
+        let start = text[..pos]
+            .char_indices()
+            .rev()
+            .take(context_chars)
+            .last()
+            .map_or(pos, |(i, _)| i);
+        let end = text[pos..]
+            .char_indices()
+            .nth(query_lower.chars().count() + context_chars)
+            .map_or(text.len(), |(i, _)| pos + i);
 
         let snippet = &text[start..end];
         let prefix = if start > 0 { "..." } else { "" };
@@ -170,18 +372,17 @@ pub fn extract_snippet(text: &str, query: &str, context_chars: usize) -> Option<
         Some(format!("{}{}{}", prefix, snippet, suffix))
     } else {
         // If no exact match, return the first N characters
-        if text.len() > context_chars * 2 {
-            Some(format!("{}...", &text[..context_chars * 2]))
-        } else {
-            Some(text.to_string())
+        match text.char_indices().nth(context_chars * 2) {
+            Some((end, _)) => Some(format!("{}...", &text[..end])),
+            None => Some(text.to_string()),
         }
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
-    use std::io::Write;
+    use lopdf::dictionary;
     use tempfile::TempDir;
 
     #[test]
@@ -197,6 +398,132 @@ mod tests {
         assert_eq!(extracted.text, content);
         assert_eq!(extracted.word_count, 7);
         assert_eq!(extracted.language, None);
+        assert_eq!(extracted.encoding.as_deref(), Some("UTF-8"));
+    }
+
+    #[test]
+    fn test_extract_legacy_encodings() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let file_path = temp_dir.path().join("movie.srt");
+        fs::write(&file_path, b"1\n00:00:01,000 --> 00:00:03,000\nO\xF9 est la biblioth\xE8que, s'il vous pla\xEEt ?\n").unwrap();
+        let extracted = extract_text(&file_path, FileType::Text).unwrap();
+        assert!(extracted.text.contains("Où est la bibliothèque, s'il vous plaît ?"));
+        assert_eq!(extracted.encoding.as_deref(), Some("windows-1252"));
+
+        let file_path = temp_dir.path().join("setup.log");
+        let utf16: Vec<u8> = "\u{feff}Installation réussie\r\n".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        fs::write(&file_path, utf16).unwrap();
+        let extracted = extract_text(&file_path, FileType::Text).unwrap();
+        assert_eq!(extracted.text, "Installation réussie\r\n");
+        assert_eq!(extracted.encoding.as_deref(), Some("UTF-16LE"));
+    }
+
+    /// Write a PDF with a page per text and a document information dictionary
+    pub(crate) fn write_pdf(path: &Path, pages: &[&str], info: lopdf::Dictionary) {
+        use lopdf::content::{Content, Operation};
+        use lopdf::{Object, Stream};
+
+        let mut document = lopdf::Document::with_version("1.5");
+        let pages_id = document.new_object_id();
+        let font_id = document.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Helvetica",
+        });
+        let resources_id = document.add_object(dictionary! {
+            "Font" => dictionary! { "F1" => font_id },
+        });
+        let mut kids = Vec::new();
+        for text in pages {
+            let content = Content {
+                operations: vec![
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), 12.into()]),
+                    Operation::new("Td", vec![72.into(), 720.into()]),
+                    Operation::new("Tj", vec![Object::string_literal(*text)]),
+                    Operation::new("ET", vec![]),
+                ],
+            };
+            let content_id = document.add_object(Stream::new(dictionary! {}, content.encode().unwrap()));
+            let page_id = document.add_object(dictionary! {
+                "Type" => "Page",
+                "Parent" => pages_id,
+                "Contents" => content_id,
+                "Resources" => resources_id,
+                "MediaBox" => vec![0.into(), 0.into(), 612.into(), 792.into()],
+            });
+            kids.push(page_id.into());
+        }
+        document.objects.insert(
+            pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Count" => kids.len() as i64,
+                "Kids" => kids,
+            }),
+        );
+        let catalog_id = document.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => pages_id,
+        });
+        document.trailer.set("Root", catalog_id);
+        let info_id = document.add_object(info);
+        document.trailer.set("Info", info_id);
+        document.save(path).unwrap();
+    }
+
+    #[test]
+    fn test_extract_pdf() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("manual.pdf");
+        let info = dictionary! {
+            "Title" => lopdf::Object::string_literal("Pump manual"),
+            "Author" => lopdf::text_string("Zoë Martin"),
+            "CreationDate" => lopdf::Object::string_literal("D:20190314093000+01'00'"),
+        };
+        write_pdf(&file_path, &["Safety instructions", "Replacing the impeller"], info);
+
+        let extracted = extract_text(&file_path, FileType::Pdf).unwrap();
+        assert_eq!(extracted.pages.len(), 2);
+        assert!(extracted.page_text(1).unwrap().contains("Safety instructions"));
+        assert!(extracted.page_text(2).unwrap().contains("Replacing the impeller"));
+        let offset = match_offset(&extracted.text, "impeller").unwrap();
+        assert_eq!(Page::number_at(&extracted.pages, offset), Some(2));
+
+        assert_eq!(extracted.metadata.get("title").map(String::as_str), Some("Pump manual"));
+        assert_eq!(extracted.metadata.get("author").map(String::as_str), Some("Zoë Martin"));
+        assert_eq!(extracted.metadata.get("created").map(String::as_str), Some("2019-03-14T09:30:00+01:00"));
+    }
+
+    #[test]
+    fn test_pdf_date() {
+        let date = |text| pdf_date(text).map(|date| date.to_rfc3339());
+        assert_eq!(date("D:20240314093000+01'00'").as_deref(), Some("2024-03-14T09:30:00+01:00"));
+        assert_eq!(date("D:20240314093000-05'30").as_deref(), Some("2024-03-14T09:30:00-05:30"));
+        assert_eq!(date("D:20240314093000Z").as_deref(), Some("2024-03-14T09:30:00+00:00"));
+        assert_eq!(date("D:2024").as_deref(), Some("2024-01-01T00:00:00+00:00"));
+        assert_eq!(date("20240314").as_deref(), Some("2024-03-14T00:00:00+00:00"));
+        assert_eq!(date("D:20241340"), None);
+        assert_eq!(date("yesterday"), None);
+    }
+
+    #[test]
+    fn test_match_offset() {
+        let text = "Chapter one\nThe pump runs dry";
+        assert_eq!(match_offset(text, "Pump runs"), Some(16));
+        // Words on their own if the query doesn't match as a whole
+        assert_eq!(match_offset(text, "dry pump"), Some(16));
+        assert_eq!(match_offset(text, "valve"), None);
+
+        let pages = [
+            Page { number: 1, start: 0, end: 11 },
+            Page { number: 2, start: 12, end: 29 },
+        ];
+        assert_eq!(Page::number_at(&pages, 0), Some(1));
+        assert_eq!(Page::number_at(&pages, 11), Some(1));
+        assert_eq!(Page::number_at(&pages, 16), Some(2));
+        assert_eq!(Page::number_at(&[], 16), None);
     }
 
     #[test]
@@ -229,6 +556,14 @@ mod tests {
         assert_eq!(extracted.language, Some("rust".to_string()));
     }
 
+    #[test]
+    fn test_shebang_language() {
+        assert_eq!(shebang_language("#!/usr/bin/env python3\nprint()").as_deref(), Some("python"));
+        assert_eq!(shebang_language("#!/bin/bash -e\n").as_deref(), Some("shell"));
+        assert_eq!(shebang_language("#!/usr/bin/env -S node --harmony").as_deref(), Some("javascript"));
+        assert_eq!(shebang_language("no shebang"), None);
+    }
+
     #[test]
     fn test_detect_language() {
         let test_cases = vec![
@@ -272,6 +607,20 @@ mod tests {
         assert!(snippet.starts_with("This is"));
     }
 
+    #[test]
+    fn test_extract_snippet_multibyte() {
+        let text = "Évaluation trimestrielle: les prévisions révisées à la baisse après l'été.";
+        let snippet = extract_snippet(text, "révisées", 5).unwrap();
+        assert_eq!(snippet, "...ions révisées à la...");
+
+        // Lowercasing "İ" adds a byte, shifting the match off a character
+        let snippet = extract_snippet("İİİİ été", "été", 2).unwrap();
+        assert!(snippet.contains("été"));
+
+        let snippet = extract_snippet(text, "nonexistent", 3).unwrap();
+        assert_eq!(snippet, "Évalua...");
+    }
+
     #[test]
     fn test_extract_snippet_short_text() {
         let text = "Short text";
@@ -0,0 +1,753 @@
+//! Reading the members of archives
+//!
+//! Zip, tar, gzip-compressed tar (and single gzip-compressed files) and 7z
+//! archives are read member by member. A member is identified by a virtual
+//! path made of the archive's path, `!/` and its path inside the archive,
+//! e.g. `backup.zip!/notes/todo.md`; members of nested archives repeat the
+//! separator: `backup.zip!/old.tar!/a.txt`.
+//!
+//! Mail is read the same way. The members of an mbox mailbox are its
+//! messages, numbered from 1 in the order they appear (`inbox.mbox!/3.eml`),
+//! and the members of an email message are its attachments, named after
+//! their file names (`inbox.mbox!/3.eml!/report.pdf`).
+//!
+//! Archives are untrusted input. Sizes declared in headers are only used to
+//! skip members early, never trusted: every read is metered, members larger
+//! than [`ArchiveConfig::max_member_size`] are skipped, and reading fails as
+//! soon as an archive expands beyond the total size or compression ratio
+//! limits.
+
+use crate::config::ArchiveConfig;
+use crate::extractors::email;
+use crate::Result;
+use mail_parser::mailbox::mbox::MessageIterator;
+use mail_parser::MimeHeaders;
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, BufReader, Cursor, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Separator between the path of an archive and the path of a member
+pub const MEMBER_SEPARATOR: &str = "!/";
+
+/// Number of leading bytes used to recognize the format of an archive
+const HEAD_LEN: u64 = 8192;
+
+/// Expansion allowed regardless of the compression ratio, so that small
+/// but highly compressible archives are still read
+const RATIO_ALLOWANCE: u64 = 1024 * 1024;
+
+/// Virtual path of a member of an archive
+pub fn member_path(archive: &str, member: &str) -> String {
+    format!("{}{}{}", archive, MEMBER_SEPARATOR, member)
+}
+
+/// Check whether a path refers to a member of an archive
+pub fn is_member_path(path: &str) -> bool {
+    path.contains(MEMBER_SEPARATOR)
+}
+
+/// Path of the file on disk a possibly virtual path belongs to
+pub fn disk_path(path: &str) -> &str {
+    path.split_once(MEMBER_SEPARATOR).map_or(path, |(disk, _)| disk)
+}
+
+/// Location of an indexed file inside an archive
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ArchiveMember {
+    /// Path of the archive on disk
+    pub archive_path: String,
+    /// Path inside the archive; members of nested archives include the
+    /// nested archive's path and a separator
+    pub member_path: String,
+    /// Uncompressed size in bytes
+    pub size: u64,
+    /// Compressed size in bytes, if the format records it per member
+    pub compressed_size: Option<u64>,
+    /// Nesting depth, 1 for members of the archive on disk
+    pub depth: usize,
+}
+
+/// Supported archive formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    Tar,
+    /// Gzip-compressed tar archive or single file
+    Gzip,
+    SevenZ,
+    /// Mailbox in mbox format, whose members are its messages
+    Mbox,
+    /// Email message, whose members are its attachments
+    Email,
+}
+
+impl ArchiveFormat {
+    /// Recognize an archive from its first bytes
+    ///
+    /// Tar archives are only recognized with at least 262 bytes, mail
+    /// from its header fields.
+    pub fn detect(head: &[u8]) -> Option<Self> {
+        if is_tar(head) {
+            return Some(ArchiveFormat::Tar);
+        }
+        match infer::get(head).map(|kind| kind.mime_type()) {
+            Some("application/zip") => Some(ArchiveFormat::Zip),
+            Some("application/gzip") => Some(ArchiveFormat::Gzip),
+            Some("application/x-7z-compressed") => Some(ArchiveFormat::SevenZ),
+            _ if email::is_mbox(head) => Some(ArchiveFormat::Mbox),
+            _ if email::is_message(head) => Some(ArchiveFormat::Email),
+            _ => None,
+        }
+    }
+}
+
+/// A file read from an archive
+#[derive(Debug)]
+pub struct Member {
+    /// Path inside the archive, with `/` separators
+    pub path: String,
+    pub data: Vec<u8>,
+    /// Compressed size in bytes, if the format records it per member
+    pub compressed_size: Option<u64>,
+    /// Modification time in seconds since the Unix epoch
+    pub modified_at: Option<i64>,
+}
+
+/// Callback receiving each member; it may read nested archives through
+/// the reader it is given
+pub type Visitor<'v, 'a> = dyn FnMut(&mut ArchiveReader<'a>, Member) -> Result<()> + 'v;
+
+/// Reads archives within the configured limits
+///
+/// The limits on the total size and on the number of members apply to
+/// everything read through the same reader, nested archives included.
+pub struct ArchiveReader<'a> {
+    limits: &'a ArchiveConfig,
+    /// Uncompressed bytes read so far
+    expanded: u64,
+    /// Members read so far
+    members: usize,
+}
+
+impl<'a> ArchiveReader<'a> {
+    /// Create a reader enforcing the given limits
+    pub fn new(limits: &'a ArchiveConfig) -> Self {
+        Self {
+            limits,
+            expanded: 0,
+            members: 0,
+        }
+    }
+
+    /// Read the members of an archive on disk
+    ///
+    /// # Arguments
+    /// * `path` - Path to the archive
+    /// * `visit` - Called with every regular file in the archive
+    pub fn read_file(&mut self, path: &Path, visit: &mut Visitor<'_, 'a>) -> Result<()> {
+        let mut file = File::open(path)?;
+        let len = file.metadata()?.len();
+
+        let mut head = Vec::new();
+        (&mut file).take(HEAD_LEN).read_to_end(&mut head)?;
+        file.seek(SeekFrom::Start(0))?;
+
+        let name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+        self.read(&name, &head, file, len, visit)
+    }
+
+    /// Read the members of an archive held in memory, e.g. a member of
+    /// another archive
+    ///
+    /// # Arguments
+    /// * `name` - File name of the archive, used to name a gzip-compressed file
+    /// * `data` - Content of the archive
+    /// * `visit` - Called with every regular file in the archive
+    pub fn read_bytes(&mut self, name: &str, data: &[u8], visit: &mut Visitor<'_, 'a>) -> Result<()> {
+        self.read(name, data, Cursor::new(data), data.len() as u64, visit)
+    }
+
+    fn read<R: Read + Seek>(
+        &mut self,
+        name: &str,
+        head: &[u8],
+        reader: R,
+        len: u64,
+        visit: &mut Visitor<'_, 'a>,
+    ) -> Result<()> {
+        let format = ArchiveFormat::detect(head)
+            .ok_or_else(|| crate::Error::UnsupportedFileType(format!("Unsupported archive format: {}", name)))?;
+
+        // Uncompressed bytes this archive may expand to
+        let limit = self
+            .limits
+            .max_total_size
+            .saturating_sub(self.expanded)
+            .min(len.saturating_mul(self.limits.max_ratio).max(RATIO_ALLOWANCE));
+
+        let expanded = match format {
+            ArchiveFormat::Zip => self.read_zip(reader, limit, visit),
+            ArchiveFormat::Tar => {
+                let mut reader = Bounded::new(reader, limit);
+                self.read_tar(&mut reader, visit).map(|_| reader.read)
+            }
+            ArchiveFormat::Gzip => self.read_gzip(name, reader, limit, visit),
+            ArchiveFormat::SevenZ => self.read_7z(reader, len, limit, visit),
+            ArchiveFormat::Mbox => self.read_mbox(reader, limit, visit),
+            ArchiveFormat::Email => self.read_message(reader, limit, visit),
+        }
+        .map_err(|e| match e {
+            crate::Error::Io(e) => crate::Error::Extraction(format!("Failed to read archive {}: {}", name, e)),
+            e => e,
+        })?;
+
+        self.expanded += expanded;
+        Ok(())
+    }
+
+    /// Read a zip archive, returning the number of bytes expanded
+    fn read_zip<R: Read + Seek>(&mut self, reader: R, limit: u64, visit: &mut Visitor<'_, 'a>) -> Result<u64> {
+        let mut zip = zip::ZipArchive::new(reader).map_err(|e| crate::Error::Extraction(e.to_string()))?;
+
+        let mut expanded = 0;
+        for i in 0..zip.len() {
+            if !self.admit() {
+                break;
+            }
+            let entry = match zip.by_index(i) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    // Encrypted or using an unsupported compression method
+                    tracing::debug!("Skipping zip member {}: {}", i, e);
+                    continue;
+                }
+            };
+            if entry.is_dir() {
+                continue;
+            }
+            let Some(path) = entry.enclosed_name().and_then(|p| normalize(&p.to_string_lossy())) else {
+                continue;
+            };
+            let compressed_size = entry.compressed_size();
+            let modified_at = entry.last_modified().and_then(zip_time);
+            let declared_size = entry.size();
+
+            // Members are read independently, so skipped ones cost nothing
+            let mut reader = Bounded::new(entry, limit - expanded);
+            let data = self.read_data(&mut reader, declared_size, false)?;
+            expanded += reader.read;
+
+            if let Some(data) = data {
+                visit(
+                    self,
+                    Member {
+                        path,
+                        data,
+                        compressed_size: Some(compressed_size),
+                        modified_at,
+                    },
+                )?;
+            }
+        }
+
+        Ok(expanded)
+    }
+
+    /// Read a tar archive from a metered stream
+    fn read_tar<R: Read>(&mut self, reader: R, visit: &mut Visitor<'_, 'a>) -> Result<()> {
+        let mut archive = tar::Archive::new(reader);
+
+        for entry in archive.entries()? {
+            if !self.admit() {
+                break;
+            }
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let Some(path) = entry.path().ok().and_then(|p| normalize(&p.to_string_lossy())) else {
+                continue;
+            };
+            let modified_at = entry.header().mtime().ok().map(|t| t as i64);
+            let declared_size = entry.size();
+
+            if let Some(data) = self.read_data(&mut entry, declared_size, false)? {
+                visit(
+                    self,
+                    Member {
+                        path,
+                        data,
+                        compressed_size: None,
+                        modified_at,
+                    },
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Read a gzip-compressed tar archive, or a single compressed file
+    fn read_gzip<R: Read>(&mut self, name: &str, reader: R, limit: u64, visit: &mut Visitor<'_, 'a>) -> Result<u64> {
+        let mut decoder = Bounded::new(flate2::read::GzDecoder::new(reader), limit);
+
+        let mut head = Vec::with_capacity(512);
+        (&mut decoder).take(512).read_to_end(&mut head)?;
+
+        if is_tar(&head) {
+            let mut stream = Cursor::new(head).chain(&mut decoder);
+            self.read_tar(&mut stream, visit)?;
+            return Ok(decoder.read);
+        }
+
+        if self.admit() {
+            let mut stream = Cursor::new(head).chain(&mut decoder);
+            if let Some(data) = self.read_data(&mut stream, 0, true)? {
+                let path = name
+                    .strip_suffix(".gz")
+                    .or_else(|| name.strip_suffix(".GZ"))
+                    .filter(|stem| !stem.is_empty())
+                    .unwrap_or("data")
+                    .to_string();
+                visit(
+                    self,
+                    Member {
+                        path,
+                        data,
+                        compressed_size: None,
+                        modified_at: None,
+                    },
+                )?;
+            }
+        }
+        Ok(decoder.read)
+    }
+
+    /// Read a 7z archive, returning the number of bytes expanded
+    fn read_7z<R: Read + Seek>(&mut self, reader: R, len: u64, limit: u64, visit: &mut Visitor<'_, 'a>) -> Result<u64> {
+        let mut archive = sevenz_rust::SevenZReader::new(reader, len, sevenz_rust::Password::empty())
+            .map_err(|e| crate::Error::Extraction(e.to_string()))?;
+
+        let mut expanded = 0;
+        let mut failure = None;
+        let result = archive.for_each_entries(|entry, reader| {
+            if entry.is_directory() {
+                return Ok(true);
+            }
+            if !self.admit() {
+                return Ok(false);
+            }
+
+            // Entries of a solid archive share one stream, so skipped
+            // members must still be read through
+            let mut reader = Bounded::new(reader, limit - expanded);
+            let data = self.read_data(&mut reader, entry.size(), true);
+            expanded += reader.read;
+
+            let member = data.and_then(|data| {
+                let Some(data) = data else {
+                    return Ok(());
+                };
+                let Some(path) = normalize(entry.name()) else {
+                    return Ok(());
+                };
+                let member = Member {
+                    path,
+                    data,
+                    compressed_size: None,
+                    modified_at: entry.has_last_modified_date.then(|| entry.last_modified_date().to_unix_time()),
+                };
+                visit(self, member)
+            });
+            match member {
+                Ok(()) => Ok(true),
+                Err(e) => {
+                    failure = Some(e);
+                    Ok(false)
+                }
+            }
+        });
+
+        if let Some(e) = failure {
+            return Err(e);
+        }
+        result.map_err(|e| crate::Error::Extraction(e.to_string()))?;
+        Ok(expanded)
+    }
+
+    /// Read the messages of an mbox mailbox, returning the number of bytes read
+    fn read_mbox<R: Read>(&mut self, reader: R, limit: u64, visit: &mut Visitor<'_, 'a>) -> Result<u64> {
+        let mut reader = Bounded::new(reader, limit);
+
+        for (i, message) in MessageIterator::new(BufReader::new(&mut reader)).enumerate() {
+            let message = message?;
+            if !self.admit() {
+                break;
+            }
+            if message.contents().len() as u64 > self.limits.max_member_size {
+                tracing::debug!("Skipping message larger than {} bytes", self.limits.max_member_size);
+                continue;
+            }
+
+            let modified_at = (message.internal_date() > 0).then(|| message.internal_date() as i64);
+            visit(
+                self,
+                Member {
+                    path: format!("{}.eml", i + 1),
+                    data: message.unwrap_contents(),
+                    compressed_size: None,
+                    modified_at,
+                },
+            )?;
+        }
+
+        Ok(reader.read)
+    }
+
+    /// Read the attachments of an email message, returning the number of
+    /// bytes read
+    ///
+    /// Attachments without a file name are named after their position, and
+    /// repeated names are prefixed with it.
+    fn read_message<R: Read>(&mut self, reader: R, limit: u64, visit: &mut Visitor<'_, 'a>) -> Result<u64> {
+        let mut data = Vec::new();
+        Bounded::new(reader, limit).read_to_end(&mut data)?;
+        let message = email::parse_message(&data)?;
+
+        let mut names = HashSet::new();
+        for (i, part) in message.attachments().enumerate() {
+            if !self.admit() {
+                break;
+            }
+            let contents = part.contents();
+            if contents.len() as u64 > self.limits.max_member_size {
+                tracing::debug!("Skipping attachment larger than {} bytes", self.limits.max_member_size);
+                continue;
+            }
+
+            let mut path = part
+                .attachment_name()
+                .and_then(|name| normalize(name.rsplit(['/', '\\']).next().unwrap_or_default()))
+                .unwrap_or_else(|| match part.is_message() {
+                    true => format!("message-{}.eml", i + 1),
+                    false => format!("attachment-{}", i + 1),
+                });
+            if !names.insert(path.clone()) {
+                path = format!("{}-{}", i + 1, path);
+            }
+
+            visit(
+                self,
+                Member {
+                    path,
+                    data: contents.to_vec(),
+                    compressed_size: None,
+                    modified_at: None,
+                },
+            )?;
+        }
+
+        Ok(data.len() as u64)
+    }
+
+    /// Count a member against the limit on the number of members
+    fn admit(&mut self) -> bool {
+        if self.members >= self.limits.max_members {
+            tracing::debug!("Archive has more than {} members, ignoring the rest", self.limits.max_members);
+            return false;
+        }
+        self.members += 1;
+        true
+    }
+
+    /// Read the content of a member within the size limit
+    ///
+    /// # Arguments
+    /// * `reader` - Content of the member
+    /// * `declared_size` - Size recorded in the archive, possibly wrong
+    /// * `drain` - Read the rest of a member that is too large, to get to
+    ///   the next one in a stream
+    ///
+    /// # Returns
+    /// The content, or `None` if the member is too large
+    fn read_data<R: Read>(&self, reader: &mut R, declared_size: u64, drain: bool) -> Result<Option<Vec<u8>>> {
+        let max = self.limits.max_member_size;
+
+        let mut data = Vec::new();
+        if declared_size <= max {
+            reader.take(max + 1).read_to_end(&mut data)?;
+            if data.len() as u64 <= max {
+                return Ok(Some(data));
+            }
+        }
+
+        tracing::debug!("Skipping archive member larger than {} bytes", max);
+        if drain {
+            io::copy(reader, &mut io::sink())?;
+        }
+        Ok(None)
+    }
+}
+
+/// Read the content of a member from its virtual path
+///
+/// # Arguments
+/// * `path` - Virtual path, e.g. `backup.zip!/notes/todo.md`
+/// * `limits` - Limits applied while reading the archive
+pub fn read_member(path: &str, limits: &ArchiveConfig) -> Result<Vec<u8>> {
+    let mut parts = path.split(MEMBER_SEPARATOR);
+    let archive = parts.next().unwrap_or_default();
+    let wanted: Vec<&str> = parts.collect();
+    if wanted.is_empty() {
+        return Err(crate::Error::InvalidInput(format!("Not an archive member: {}", path)));
+    }
+
+    let mut found = None;
+    ArchiveReader::new(limits).read_file(Path::new(archive), &mut |reader, member| {
+        find_member(reader, member, &wanted, &mut found)
+    })?;
+    found.ok_or_else(|| crate::Error::FileNotFound(path.to_string()))
+}
+
+/// Visitor looking for the member at `wanted`, descending into nested archives
+fn find_member(
+    reader: &mut ArchiveReader<'_>,
+    member: Member,
+    wanted: &[&str],
+    found: &mut Option<Vec<u8>>,
+) -> Result<()> {
+    if found.is_some() || member.path != wanted[0] {
+        return Ok(());
+    }
+    if wanted.len() == 1 {
+        *found = Some(member.data);
+        return Ok(());
+    }
+
+    let name = member.path.rsplit('/').next().unwrap_or_default().to_string();
+    reader.read_bytes(&name, &member.data, &mut |reader, inner| find_member(reader, inner, &wanted[1..], found))
+}
+
+/// Check for the `ustar` magic of a tar header
+fn is_tar(head: &[u8]) -> bool {
+    head.get(257..262) == Some(b"ustar".as_slice())
+}
+
+/// Normalize a member path to a relative path with `/` separators
+///
+/// Returns `None` for paths escaping the archive.
+fn normalize(path: &str) -> Option<String> {
+    let parts: Vec<&str> = path
+        .split(['/', '\\'])
+        .filter(|part| !part.is_empty() && *part != ".")
+        .collect();
+    if parts.is_empty() || parts.contains(&"..") {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+/// Convert the modification time of a zip member to Unix seconds
+fn zip_time(time: zip::DateTime) -> Option<i64> {
+    let date = chrono::NaiveDate::from_ymd_opt(time.year() as i32, time.month() as u32, time.day() as u32)?;
+    let datetime = date.and_hms_opt(time.hour() as u32, time.minute() as u32, time.second() as u32)?;
+    Some(datetime.and_utc().timestamp())
+}
+
+/// Reader failing once more than a given number of bytes were read
+struct Bounded<R> {
+    inner: R,
+    read: u64,
+    limit: u64,
+}
+
+impl<R> Bounded<R> {
+    fn new(inner: R, limit: u64) -> Self {
+        Self { inner, read: 0, limit }
+    }
+}
+
+impl<R: Read> Read for Bounded<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.read += n as u64;
+        if self.read > self.limit {
+            return Err(io::Error::other(
+                "archive expands beyond the size or compression ratio limits",
+            ));
+        }
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    fn zip_bytes(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options =
+            zip::write::SimpleFileOptions::default().compression_method(zip::CompressionMethod::Deflated);
+        for (name, data) in members {
+            zip.start_file(*name, options).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap().into_inner()
+    }
+
+    fn tar_gz_bytes(members: &[(&str, &[u8])]) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut tar = tar::Builder::new(encoder);
+        for (name, data) in members {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            tar.append_data(&mut header, name, *data).unwrap();
+        }
+        tar.into_inner().unwrap().finish().unwrap()
+    }
+
+    fn read_all(path: &Path, limits: &ArchiveConfig) -> Result<Vec<(String, Vec<u8>)>> {
+        let mut members = Vec::new();
+        ArchiveReader::new(limits).read_file(path, &mut |_, member| {
+            members.push((member.path, member.data));
+            Ok(())
+        })?;
+        Ok(members)
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(member_path("/a/backup.zip", "notes/todo.md"), "/a/backup.zip!/notes/todo.md");
+        assert_eq!(disk_path("/a/backup.zip!/old.tar!/a.txt"), "/a/backup.zip");
+        assert_eq!(disk_path("/a/notes.txt"), "/a/notes.txt");
+        assert!(is_member_path("/a/backup.zip!/a.txt"));
+        assert_eq!(normalize("./notes//todo.md").as_deref(), Some("notes/todo.md"));
+        assert_eq!(normalize("../etc/passwd"), None);
+    }
+
+    #[test]
+    fn test_read_formats() {
+        let temp_dir = TempDir::new().unwrap();
+        let members: &[(&str, &[u8])] = &[("notes/todo.md", b"buy milk"), ("readme.txt", b"hello")];
+
+        let zip_path = temp_dir.path().join("backup.zip");
+        fs_write(&zip_path, &zip_bytes(members));
+        let tgz_path = temp_dir.path().join("backup.tar.gz");
+        fs_write(&tgz_path, &tar_gz_bytes(members));
+        let gz_path = temp_dir.path().join("notes.txt.gz");
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(b"compressed notes").unwrap();
+        fs_write(&gz_path, &encoder.finish().unwrap());
+
+        let limits = ArchiveConfig::default();
+        for path in [&zip_path, &tgz_path] {
+            let read = read_all(path, &limits).unwrap();
+            assert_eq!(read.len(), 2, "{}", path.display());
+            assert_eq!(read[0], ("notes/todo.md".to_string(), b"buy milk".to_vec()));
+        }
+        assert_eq!(read_all(&gz_path, &limits).unwrap(), vec![("notes.txt".to_string(), b"compressed notes".to_vec())]);
+
+        let not_archive = temp_dir.path().join("plain.zip");
+        fs_write(&not_archive, b"not really a zip");
+        assert!(read_all(&not_archive, &limits).is_err());
+    }
+
+    #[test]
+    fn test_read_7z() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source");
+        std::fs::create_dir_all(source.join("docs")).unwrap();
+        fs_write(&source.join("docs/plan.txt"), b"launch on friday");
+        let path = temp_dir.path().join("backup.7z");
+        sevenz_rust::compress_to_path(&source, &path).unwrap();
+
+        let read = read_all(&path, &ArchiveConfig::default()).unwrap();
+        assert_eq!(read, vec![("docs/plan.txt".to_string(), b"launch on friday".to_vec())]);
+    }
+
+    #[test]
+    fn test_nested_member() {
+        let temp_dir = TempDir::new().unwrap();
+        let inner = tar_gz_bytes(&[("deep/secret.txt", b"found me")]);
+        let path = temp_dir.path().join("outer.zip");
+        fs_write(&path, &zip_bytes(&[("inner.tar.gz", &inner), ("top.txt", b"top")]));
+
+        let limits = ArchiveConfig::default();
+        let virtual_path = member_path(&path.to_string_lossy(), "inner.tar.gz!/deep/secret.txt");
+        assert_eq!(read_member(&virtual_path, &limits).unwrap(), b"found me");
+        assert!(read_member(&member_path(&path.to_string_lossy(), "missing.txt"), &limits).is_err());
+    }
+
+    #[test]
+    fn test_read_mail() {
+        let temp_dir = TempDir::new().unwrap();
+        let mbox = "From alice@example.com Tue May 14 09:30:00 2024\n\
+            From: alice@example.com\nSubject: Lunch\n\nNoon?\n\
+            >From the cafeteria menu\n\
+            \n\
+            From bob@example.com Wed May 15 10:00:00 2024\n\
+            From: bob@example.com\nSubject: Files\nContent-Type: multipart/mixed; boundary=b\n\n\
+            --b\nContent-Type: text/plain\n\nSee attached.\n\
+            --b\nContent-Type: text/plain\nContent-Disposition: attachment; filename=\"../notes.txt\"\n\nfirst\n\
+            --b\nContent-Type: text/plain\nContent-Disposition: attachment; filename=\"notes.txt\"\n\nsecond\n\
+            --b\nContent-Type: application/octet-stream\nContent-Transfer-Encoding: base64\n\nAAEC\n\
+            --b--\n";
+        let path = temp_dir.path().join("inbox.mbox");
+        fs_write(&path, mbox.as_bytes());
+
+        let limits = ArchiveConfig::default();
+        let messages = read_all(&path, &limits).unwrap();
+        assert_eq!(messages.iter().map(|(path, _)| path.as_str()).collect::<Vec<_>>(), ["1.eml", "2.eml"]);
+        // The separator line is dropped and quoted `From ` lines unquoted
+        assert!(messages[0].1.starts_with(b"From: alice@example.com\n"));
+        assert!(messages[0].1.ends_with(b"From the cafeteria menu\n\n"));
+
+        let mut attachments = Vec::new();
+        ArchiveReader::new(&limits)
+            .read_bytes("2.eml", &messages[1].1, &mut |_, member| {
+                attachments.push(member.path);
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(attachments, ["notes.txt", "2-notes.txt", "attachment-3"]);
+
+        let attachment = member_path(&path.to_string_lossy(), "2.eml!/2-notes.txt");
+        assert_eq!(read_member(&attachment, &limits).unwrap(), b"second");
+    }
+
+    #[test]
+    fn test_limits() {
+        let temp_dir = TempDir::new().unwrap();
+        let zeros = vec![0u8; 8 * 1024 * 1024];
+        let path = temp_dir.path().join("bomb.zip");
+        fs_write(&path, &zip_bytes(&[("small.txt", b"ok"), ("zeros.bin", &zeros)]));
+
+        // Expands far beyond 100 times its compressed size
+        let err = read_all(&path, &ArchiveConfig::default()).unwrap_err();
+        assert!(err.to_string().contains("limits"), "{}", err);
+
+        // Too large members are skipped, the rest is read
+        let limits = ArchiveConfig {
+            max_member_size: 1024,
+            ..Default::default()
+        };
+        assert_eq!(read_all(&path, &limits).unwrap().len(), 1);
+
+        // Reading stops after the maximum number of members
+        let limits = ArchiveConfig {
+            max_members: 1,
+            max_ratio: u64::MAX,
+            ..Default::default()
+        };
+        assert_eq!(read_all(&path, &limits).unwrap().len(), 1);
+    }
+
+    fn fs_write(path: &Path, data: &[u8]) {
+        std::fs::write(path, data).unwrap();
+    }
+}
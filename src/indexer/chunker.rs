@@ -0,0 +1,203 @@
+//! Split document text into overlapping chunks for embedding
+//!
+//! Embedding models only look at the first few hundred tokens of their
+//! input, so long documents are embedded chunk by chunk. Chunks are built
+//! from whole words, end on a sentence boundary whenever one is close
+//! enough, and overlap so that a passage cut in two is still found.
+
+/// A passage of a document, as byte offsets into its text
+///
+/// Offsets always fall on UTF-8 character boundaries.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chunk {
+    /// Position of the chunk within the document, starting at 0
+    pub index: usize,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Chunk {
+    /// Text of the chunk within the document it was made from
+    pub fn text<'a>(&self, text: &'a str) -> &'a str {
+        &text[self.start..self.end]
+    }
+}
+
+/// Word-based text chunker
+#[derive(Debug, Clone)]
+pub struct Chunker {
+    chunk_words: usize,
+    overlap_words: usize,
+    max_chunks: usize,
+}
+
+impl Default for Chunker {
+    fn default() -> Self {
+        // ~200 words stay below the 512 token limit of all-MiniLM-L6-v2
+        Self::new(200, 40, 1000)
+    }
+}
+
+impl Chunker {
+    /// Create a chunker
+    ///
+    /// # Arguments
+    /// * `chunk_words` - Maximum number of words per chunk
+    /// * `overlap_words` - Number of words shared by consecutive chunks
+    /// * `max_chunks` - Maximum number of chunks per document; the rest of
+    ///   a very long document is left to keyword search
+    pub fn new(chunk_words: usize, overlap_words: usize, max_chunks: usize) -> Self {
+        let chunk_words = chunk_words.max(1);
+        Self {
+            chunk_words,
+            overlap_words: overlap_words.min(chunk_words - 1),
+            max_chunks: max_chunks.max(1),
+        }
+    }
+
+    /// Split text into chunks
+    ///
+    /// # Returns
+    /// Chunks in document order; empty if the text has no words
+    pub fn chunk(&self, text: &str) -> Vec<Chunk> {
+        let words = words(text);
+        let mut chunks = Vec::new();
+        let mut first = 0;
+
+        while first < words.len() && chunks.len() < self.max_chunks {
+            let mut last = (first + self.chunk_words).min(words.len());
+
+            // Prefer ending on a sentence boundary in the second half of the chunk
+            if last < words.len() {
+                let min_last = first + self.chunk_words / 2;
+                if let Some(sentence_end) = (min_last..last).rev().find(|&i| words[i].ends_sentence) {
+                    last = sentence_end + 1;
+                }
+            }
+
+            chunks.push(Chunk {
+                index: chunks.len(),
+                start: words[first].start,
+                end: words[last - 1].end,
+            });
+
+            if last == words.len() {
+                break;
+            }
+
+            // Start the overlap at a sentence boundary when it contains one
+            let overlap_start = last.saturating_sub(self.overlap_words).max(first + 1);
+            first = (overlap_start..last)
+                .find(|&i| i > 0 && words[i - 1].ends_sentence)
+                .unwrap_or(overlap_start);
+        }
+
+        chunks
+    }
+}
+
+/// A whitespace-separated word of the text
+struct Word {
+    start: usize,
+    end: usize,
+    /// The word ends a sentence or a paragraph
+    ends_sentence: bool,
+}
+
+/// Find the words of a text and mark sentence boundaries
+fn words(text: &str) -> Vec<Word> {
+    let mut words: Vec<Word> = Vec::new();
+    let mut start = None;
+    let mut newlines = 0;
+
+    for (i, c) in text.char_indices() {
+        if c.is_whitespace() {
+            if let Some(s) = start.take() {
+                words.push(word(text, s, i));
+                newlines = 0;
+            }
+            if c == '\n' {
+                newlines += 1;
+                // A blank line ends a paragraph, even without punctuation
+                if newlines == 2 {
+                    if let Some(last) = words.last_mut() {
+                        last.ends_sentence = true;
+                    }
+                }
+            }
+        } else if start.is_none() {
+            start = Some(i);
+        }
+    }
+    if let Some(s) = start {
+        words.push(word(text, s, text.len()));
+    }
+
+    words
+}
+
+fn word(text: &str, start: usize, end: usize) -> Word {
+    let trimmed = text[start..end].trim_end_matches(['"', '\'', ')', ']', '”', '’']);
+    Word {
+        start,
+        end,
+        ends_sentence: trimmed.ends_with(['.', '!', '?', '。', '！', '？']),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_short_text_is_one_chunk() {
+        let text = "  Just a few words.  ";
+        let chunks = Chunker::default().chunk(text);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].text(text), "Just a few words.");
+
+        assert!(Chunker::default().chunk(" \n\t ").is_empty());
+    }
+
+    #[test]
+    fn test_chunks_overlap_and_cover_text() {
+        let text = (0..100).map(|i| format!("w{}", i)).collect::<Vec<_>>().join(" ");
+        let chunks = Chunker::new(30, 10, 100).chunk(&text);
+
+        assert_eq!(chunks[0].text(&text).split_whitespace().count(), 30);
+        assert!(chunks[1].text(&text).starts_with("w20 "));
+        assert!(chunks.last().unwrap().text(&text).ends_with("w99"));
+        assert!(chunks.iter().enumerate().all(|(i, chunk)| chunk.index == i));
+    }
+
+    #[test]
+    fn test_chunks_end_on_sentences() {
+        let text = "One two three four five six. Seven eight nine ten eleven twelve.";
+        let chunks = Chunker::new(8, 2, 100).chunk(text);
+
+        assert_eq!(chunks[0].text(text), "One two three four five six.");
+        assert_eq!(chunks[1].text(text), "five six. Seven eight nine ten eleven twelve.");
+
+        // An overlap containing a sentence boundary starts there
+        let text = "One two three. Four five six seven eight nine ten eleven twelve";
+        let chunks = Chunker::new(8, 6, 100).chunk(text);
+        assert!(chunks[0].text(text).ends_with("eight"));
+        assert!(chunks[1].text(text).starts_with("Four"));
+    }
+
+    #[test]
+    fn test_multibyte_text() {
+        // Byte 5000 of this text falls inside a multi-byte character
+        let text = "é".repeat(2501) + " ünïcödé wörds";
+        let chunks = Chunker::new(1, 0, 100).chunk(&text);
+
+        assert_eq!(chunks.len(), 3);
+        assert_eq!(chunks[2].text(&text), "wörds");
+    }
+
+    #[test]
+    fn test_max_chunks() {
+        let text = "word ".repeat(1000);
+        assert_eq!(Chunker::new(10, 0, 5).chunk(&text).len(), 5);
+    }
+}
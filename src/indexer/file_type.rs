@@ -0,0 +1,243 @@
+//! File type detection
+//!
+//! The extension (or a well-known file name such as `Makefile`) gives a
+//! first guess that costs nothing. [`detect`] then looks at the first bytes
+//! of the file: magic numbers recognized by `infer` win over the extension,
+//! a `#!` line marks a script, a mail header marks an email message (as
+//! found in Maildir folders) or an mbox mailbox, and a text/binary
+//! heuristic catches blobs
+//! that would otherwise be read as text, as well as text files with a
+//! misleading extension.
+
+use crate::extractors::email;
+use crate::extractors::encoding::utf16_without_bom;
+use crate::types::FileType;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Number of leading bytes inspected by [`detect`]
+const SNIFF_LEN: usize = 8192;
+
+/// Guess the type of a file from its name alone
+pub fn from_path(path: &Path) -> FileType {
+    if let Some(ext) = path.extension() {
+        return FileType::from_extension(&ext.to_string_lossy());
+    }
+
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    match name.as_str() {
+        "makefile" | "gnumakefile" | "dockerfile" | "containerfile" | "rakefile" | "gemfile"
+        | "vagrantfile" | "jenkinsfile" | "justfile" | "procfile" | "brewfile" => FileType::Code,
+        "readme" | "license" | "licence" | "copying" | "authors" | "contributors" | "changelog"
+        | "changes" | "news" | "notice" | "todo" => FileType::Text,
+        _ => FileType::Unknown,
+    }
+}
+
+/// Detect the type of a file from its name and its leading bytes
+///
+/// Falls back to [`from_path`] if the file can't be read.
+pub fn detect(path: &Path) -> FileType {
+    let mut head = Vec::with_capacity(SNIFF_LEN);
+    let read = File::open(path).and_then(|file| file.take(SNIFF_LEN as u64).read_to_end(&mut head));
+
+    match read {
+        Ok(_) => detect_from_head(path, &head),
+        Err(_) => from_path(path),
+    }
+}
+
+/// Detect the type of a file from its name and its first bytes
+fn detect_from_head(path: &Path, head: &[u8]) -> FileType {
+    let by_name = from_path(path);
+    if head.is_empty() {
+        return by_name;
+    }
+
+    if let Some(file_type) = infer::get(head).and_then(|kind| from_magic(kind, by_name)) {
+        return file_type;
+    }
+
+    if head.starts_with(b"#!") {
+        return FileType::Code;
+    }
+
+    if matches!(by_name, FileType::Unknown | FileType::Archive) && email::is_mbox(head) {
+        return FileType::Archive;
+    }
+    if by_name == FileType::Unknown && email::is_message(head) {
+        return FileType::Email;
+    }
+
+    let binary = looks_binary(head);
+    match by_name {
+        // No magic number: a misnamed text file, or a format we don't know
+        FileType::Pdf
+        | FileType::Docx
+        | FileType::Xlsx
+        | FileType::Pptx
+        | FileType::Odt
+        | FileType::Odp
+        | FileType::Epub
+        | FileType::Image
+        | FileType::Archive
+            if !binary =>
+        {
+            FileType::Text
+        }
+        FileType::Text
+        | FileType::Code
+        | FileType::Markdown
+        | FileType::Html
+        | FileType::Xml
+        | FileType::Email
+        | FileType::Unknown
+            if binary =>
+        {
+            FileType::Binary
+        }
+        FileType::Unknown => FileType::Text,
+        _ => by_name,
+    }
+}
+
+/// Map a type recognized by its magic number to a file type
+///
+/// Returns `None` for text formats, which are left to the extension and
+/// the text heuristic.
+fn from_magic(kind: infer::Type, by_name: FileType) -> Option<FileType> {
+    let file_type = match kind.mime_type() {
+        "application/pdf" => FileType::Pdf,
+        "application/vnd.openxmlformats-officedocument.wordprocessingml.document" => FileType::Docx,
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+        | "application/vnd.ms-excel"
+        | "application/vnd.oasis.opendocument.spreadsheet" => FileType::Xlsx,
+        "application/vnd.openxmlformats-officedocument.presentationml.presentation" => FileType::Pptx,
+        "application/vnd.oasis.opendocument.text" => FileType::Odt,
+        "application/vnd.oasis.opendocument.presentation" => FileType::Odp,
+        "application/epub+zip" => FileType::Epub,
+        "application/rtf" => FileType::Rtf,
+        // Generic containers of office documents; legacy Word and
+        // PowerPoint binaries have no extractor and stay binary
+        "application/zip"
+            if matches!(
+                by_name,
+                FileType::Docx | FileType::Xlsx | FileType::Pptx | FileType::Odt | FileType::Odp | FileType::Epub
+            ) =>
+        {
+            by_name
+        }
+        "application/x-ole-storage" if by_name == FileType::Xlsx => by_name,
+        "application/x-ole-storage" => FileType::Binary,
+        "image/jpeg" | "image/png" | "image/gif" | "image/bmp" | "image/webp" => FileType::Image,
+        // Markup without a telling extension, e.g. a saved page named `index`
+        "text/html" if by_name == FileType::Unknown => FileType::Html,
+        "text/xml" if by_name == FileType::Unknown => FileType::Xml,
+        // Text-based formats
+        "application/postscript" => return None,
+        "text/x-shellscript" => FileType::Code,
+        _ => match kind.matcher_type() {
+            infer::MatcherType::Text => return (by_name == FileType::Unknown).then_some(FileType::Text),
+            infer::MatcherType::Archive => FileType::Archive,
+            _ => FileType::Binary,
+        },
+    };
+    Some(file_type)
+}
+
+/// Heuristic for binary content
+///
+/// Text is valid UTF-8 (possibly cut in the middle of a character at the
+/// end of the sample), UTF-16, or a legacy 8-bit encoding with few control
+/// characters. Other than in UTF-16, NUL bytes only occur in binaries.
+fn looks_binary(head: &[u8]) -> bool {
+    if head.starts_with(&[0xFF, 0xFE]) || head.starts_with(&[0xFE, 0xFF]) || utf16_without_bom(head).is_some() {
+        return false;
+    }
+    if head.contains(&0) {
+        return true;
+    }
+
+    match std::str::from_utf8(head) {
+        Ok(_) => false,
+        Err(e) if e.error_len().is_none() => false,
+        Err(_) => {
+            let control = head
+                .iter()
+                .filter(|&&b| b < 0x20 && !matches!(b, b'\t' | b'\n' | b'\r' | 0x0C | 0x1B))
+                .count();
+            control * 10 > head.len()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(from_path(Path::new("config.toml")), FileType::Text);
+        assert_eq!(from_path(Path::new("App.kt")), FileType::Code);
+        assert_eq!(from_path(Path::new("src/Makefile")), FileType::Code);
+        assert_eq!(from_path(Path::new("LICENSE")), FileType::Text);
+        assert_eq!(from_path(Path::new("program.exe")), FileType::Binary);
+        assert_eq!(from_path(Path::new("data")), FileType::Unknown);
+    }
+
+    #[test]
+    fn test_detect_by_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let detect_content = |name: &str, content: &[u8]| {
+            let path = temp_dir.path().join(name);
+            fs::write(&path, content).unwrap();
+            detect(&path)
+        };
+
+        // Extensionless script
+        assert_eq!(detect_content("deploy", b"#!/usr/bin/env python3\nprint('hi')\n"), FileType::Code);
+        // Legacy Word binary, and an RTF file saved as `.doc`
+        let ole = [&[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1][..], &[0u8; 504]].concat();
+        assert_eq!(detect_content("letter.doc", &ole), FileType::Binary);
+        assert_eq!(detect_content("memo.doc", b"{\\rtf1\\ansi Hello}"), FileType::Rtf);
+        // Misnamed PDF
+        assert_eq!(detect_content("report.txt", b"%PDF-1.7\n%\xE2\xE3\xCF\xD3\n"), FileType::Pdf);
+        // Binary blob without an extension
+        assert_eq!(detect_content("blob", &[0x13, 0x00, 0x42, 0x99, 0x00, 0x01]), FileType::Binary);
+        // Executable with a text extension
+        assert_eq!(detect_content("notes.md", b"\x7FELF\x02\x01\x01\x00\x00\x00\x00\x00\x00\x00\x00\x00"), FileType::Binary);
+        // Text with an unknown or misleading extension
+        assert_eq!(detect_content("notes.weird", "plain text, ünïcödé".as_bytes()), FileType::Text);
+        assert_eq!(detect_content("fake.png", b"not an image"), FileType::Text);
+        // Markup without an extension
+        assert_eq!(detect_content("saved", b"<!DOCTYPE html>\n<html><body>hi</body></html>"), FileType::Html);
+        assert_eq!(detect_content("feed", b"<?xml version=\"1.0\"?>\n<rss/>"), FileType::Xml);
+        // Maildir message and mbox mailbox
+        let message = b"From: a@example.com\nSubject: Hi\n\nHello\n";
+        assert_eq!(detect_content("1715671800.M1P2.host:2,S", message), FileType::Email);
+        assert_eq!(detect_content("Inbox", &[b"From a@example.com Tue May 14 09:30:00 2024\n", &message[..]].concat()), FileType::Archive);
+        assert_eq!(detect_content("notes.txt", message), FileType::Text);
+        // Empty files keep their extension's type
+        assert_eq!(detect_content("empty.rs", b""), FileType::Code);
+    }
+
+    #[test]
+    fn test_looks_binary() {
+        assert!(!looks_binary("héllo wörld".as_bytes()));
+        // Cut in the middle of a multi-byte character
+        assert!(!looks_binary(&"héllo".as_bytes()[..2]));
+        // Latin-1 text
+        assert!(!looks_binary(b"caf\xE9 cr\xE8me"));
+        assert!(!looks_binary(&[0xFF, 0xFE, b'h', 0, b'i', 0]));
+        // UTF-16 without a byte order mark
+        assert!(!looks_binary(&[b'l', 0, b'o', 0, b'g', 0]));
+        assert!(looks_binary(b"abc\x00def"));
+        assert!(looks_binary(&[0x01, 0x02, 0x03, 0xF0, 0x04, 0x05]));
+    }
+}
@@ -3,10 +3,14 @@
 use crate::types::{FileMetadata, FileType};
 use crate::Result;
 use sha2::{Digest, Sha256};
-use std::fs;
+use std::fs::{self, File};
+use std::io::{self, BufReader};
 use std::path::Path;
 use std::time::SystemTime;
 
+/// Read buffer size used while hashing
+const HASH_BUFFER_SIZE: usize = 64 * 1024;
+
 /// Extract metadata from a file
 ///
 /// # Arguments
@@ -25,25 +29,9 @@ pub fn extract_metadata(path: &Path, file_type: FileType) -> Result<FileMetadata
     let hash = compute_file_hash(path)?;
 
     // Get timestamps
-    let created_at = metadata
-        .created()
-        .or_else(|_| metadata.modified())
-        .unwrap_or(SystemTime::now())
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
-
-    let modified_at = metadata
-        .modified()
-        .unwrap_or(SystemTime::now())
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
-
-    let indexed_at = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64;
+    let created_at = unix_seconds(metadata.created().or_else(|_| metadata.modified()));
+    let modified_at = unix_seconds(metadata.modified());
+    let indexed_at = unix_seconds(Ok(SystemTime::now()));
 
     // Extract filename
     let filename = path
@@ -71,15 +59,70 @@ pub fn extract_metadata(path: &Path, file_type: FileType) -> Result<FileMetadata
     })
 }
 
+/// Build the metadata of a member of an archive
+///
+/// # Arguments
+/// * `path` - Virtual path of the member, see [`archive`](super::archive)
+/// * `data` - Content of the member
+/// * `file_type` - Detected file type
+/// * `modified_at` - Modification time recorded in the archive
+pub fn member_metadata(path: &str, data: &[u8], file_type: FileType, modified_at: i64) -> FileMetadata {
+    let filename = path.rsplit('/').next().unwrap_or("unknown").to_string();
+    let mime_type = mime_guess::from_path(&filename).first().map(|m| m.to_string());
+
+    FileMetadata {
+        id: 0, // Will be set by database
+        path: path.to_string(),
+        filename,
+        file_type,
+        mime_type,
+        size: data.len() as u64,
+        hash: format!("{:x}", Sha256::digest(data)),
+        created_at: modified_at,
+        modified_at,
+        indexed_at: unix_seconds(Ok(SystemTime::now())),
+    }
+}
+
+/// Size and modification time of a file
+///
+/// Cheap to obtain compared to a content hash, and used to skip hashing
+/// files that were not touched since they were indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FileStat {
+    pub size: u64,
+    pub modified_at: i64,
+}
+
+/// Read the size and modification time of a file without reading it
+pub fn stat_file(path: &Path) -> Result<FileStat> {
+    let metadata = fs::metadata(path)?;
+    Ok(FileStat {
+        size: metadata.len(),
+        modified_at: unix_seconds(metadata.modified()),
+    })
+}
+
 /// Compute SHA256 hash of a file
-fn compute_file_hash(path: &Path) -> Result<String> {
-    let contents = fs::read(path)?;
+///
+/// The file is streamed through the hasher, so memory use does not
+/// depend on the file size.
+pub fn compute_file_hash(path: &Path) -> Result<String> {
+    let mut file = BufReader::with_capacity(HASH_BUFFER_SIZE, File::open(path)?);
     let mut hasher = Sha256::new();
-    hasher.update(&contents);
+    io::copy(&mut file, &mut hasher)?;
     let result = hasher.finalize();
     Ok(format!("{:x}", result))
 }
 
+/// Seconds since the Unix epoch, falling back to now if unavailable
+fn unix_seconds(time: io::Result<SystemTime>) -> i64 {
+    time.unwrap_or_else(|_| SystemTime::now())
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
 /// Check if a file has been modified since last index
 ///
 /// # Arguments
@@ -96,7 +139,6 @@ pub fn is_modified(path: &Path, stored_hash: &str) -> Result<bool> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::fs::File;
     use std::io::Write;
     use tempfile::TempDir;
 
@@ -141,6 +183,31 @@ mod tests {
         assert_ne!(hash, hash3);
     }
 
+    #[test]
+    fn test_compute_file_hash_streams_large_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("large.bin");
+
+        // Spans several read buffers
+        let contents: Vec<u8> = (0..HASH_BUFFER_SIZE * 3 + 17).map(|i| (i % 251) as u8).collect();
+        fs::write(&file_path, &contents).unwrap();
+
+        let expected = format!("{:x}", Sha256::digest(&contents));
+        assert_eq!(compute_file_hash(&file_path).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_stat_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.txt");
+        fs::write(&file_path, "12345").unwrap();
+
+        let stat = stat_file(&file_path).unwrap();
+        let metadata = extract_metadata(&file_path, FileType::Text).unwrap();
+        assert_eq!(stat.size, 5);
+        assert_eq!(stat.modified_at, metadata.modified_at);
+    }
+
     #[test]
     fn test_is_modified() {
         let temp_dir = TempDir::new().unwrap();
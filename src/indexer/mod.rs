@@ -1,21 +1,1516 @@
 //! File indexing module
+//!
+//! [`Indexer`] owns the full indexing pipeline: it opens the SQLite,
+//! Tantivy and vector stores of an index directory, walks one or more
+//! roots, extracts metadata and content, generates embeddings for the
+//! [`chunker`]'s passages of every document and reports the outcome for
+//! every file through a callback.
+//!
+//! Members of zip, tar and 7z archives are indexed like files of their own,
+//! under virtual paths such as `backup.zip!/notes/todo.md`; see [`archive`].
+//!
+//! Re-indexing a root is incremental: files whose size and modification
+//! time match the index are not even read, files whose hash is unchanged
+//! are left alone, and files that disappeared from disk are purged from all
+//! stores. Files that failed with a transient error, e.g. because they
+//! were locked, or whose embedding failed are tried again; files that can't
+//! be extracted at all are indexed by name until they change.
+//!
+//! Hashing, extraction and embedding run in the parallel, bounded stages of
+//! [`pipeline`]; the indexer itself is the single writer for all stores.
+//!
+//! Long runs are checkpointed: every [`IndexingConfig::checkpoint_files`]
+//! files or [`IndexingConfig::checkpoint_secs`] seconds, Tantivy and the
+//! vector stores are committed before the SQLite rows written since the
+//! previous checkpoint are marked as complete. Rows still pending after a
+//! crash are re-indexed by the next run, and deletions only reach SQLite
+//! once the other stores forgot the file, so the three stores never
+//! disagree about a file the index considers up to date. A run stopped
+//! through a [`StopHandle`] checkpoints before returning.
 
 pub mod walker;
 pub mod metadata;
+pub mod archive;
+pub mod chunker;
+pub mod file_type;
+mod pipeline;
 
+use crate::config::{IndexingConfig, PrivacyConfig};
+use crate::embedding::image::ImageEmbedding;
+use crate::embedding::{EmbeddingModel, IMAGE_EMBEDDING_DIM, TEXT_EMBEDDING_DIM};
+use crate::extractors::{Extractor, ExtractorRegistry};
+use crate::storage::{Database, IndexPaths, IndexedRoot, StoredFile, TantivyIndex, VectorStore};
+use crate::types::{FileId, FileType};
 use crate::Result;
+use archive::ArchiveMember;
+use pipeline::{ChangeDetection, Models, Pipeline, PreparedFile, PreparedKind};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Notify;
+use walker::{DiscoveredFile, FileWalker};
+
+/// Progress event reported while indexing
+#[derive(Debug, Clone)]
+pub enum IndexEvent {
+    /// A root was walked and its files are about to be indexed
+    Discovered { root: PathBuf, files: usize },
+    /// A new file was written to the index
+    Added { path: PathBuf, file_type: FileType },
+    /// A changed file was re-indexed
+    Updated { path: PathBuf, file_type: FileType },
+    /// A file's hash matched the index, so it was left alone
+    Unchanged { path: PathBuf },
+    /// A file no longer exists on disk and was purged from the index
+    Removed { path: PathBuf },
+    /// A file could not be indexed
+    Skipped { path: PathBuf, reason: String },
+}
+
+/// Totals for an indexing run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IndexSummary {
+    pub added: usize,
+    pub updated: usize,
+    pub unchanged: usize,
+    pub removed: usize,
+    pub skipped: usize,
+    pub image_embeddings: usize,
+    /// The run was stopped through a [`StopHandle`] before it finished
+    pub interrupted: bool,
+}
+
+impl IndexSummary {
+    /// Number of files written to the index during the run
+    pub fn indexed(&self) -> usize {
+        self.added + self.updated
+    }
+}
+
+/// Handle to stop a running [`Indexer`] after the file being written
+#[derive(Debug, Clone, Default)]
+pub struct StopHandle(Arc<StopState>);
+
+#[derive(Debug, Default)]
+struct StopState {
+    stopped: AtomicBool,
+    notify: Notify,
+}
+
+impl StopHandle {
+    /// Ask the indexer to checkpoint and return as soon as possible
+    pub fn stop(&self) {
+        self.0.stopped.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    /// Check whether [`stop`](Self::stop) was called
+    pub fn is_stopped(&self) -> bool {
+        self.0.stopped.load(Ordering::SeqCst)
+    }
+
+    /// Wait until [`stop`](Self::stop) is called
+    pub async fn stopped(&self) {
+        let notified = self.0.notify.notified();
+        tokio::pin!(notified);
+        notified.as_mut().enable();
+
+        if !self.is_stopped() {
+            notified.await;
+        }
+    }
+}
 
 /// File indexer
 pub struct Indexer {
-    // TODO: implement
+    paths: IndexPaths,
+    db: Database,
+    tantivy_index: TantivyIndex,
+    vector_store: VectorStore,
+    image_vector_store: VectorStore,
+    walker: FileWalker,
+    indexing_config: IndexingConfig,
+    models: Models,
+    /// Built-in extractors set up for `indexing_config`, then `custom_extractors`
+    extractors: Arc<ExtractorRegistry>,
+    /// Extractors added by the application
+    custom_extractors: ExtractorRegistry,
+    stop: StopHandle,
+    /// Files removed from Tantivy and the vectors, deleted from SQLite at
+    /// the next checkpoint
+    pending_deletes: HashMap<String, FileId>,
+    /// Members written ahead of their new archive, linked once it is written
+    unlinked_members: Vec<(FileId, ArchiveMember)>,
+    writes_since_checkpoint: usize,
+    last_checkpoint: Instant,
+    text_vectors_dirty: bool,
+    image_vectors_dirty: bool,
 }
 
 impl Indexer {
-    pub fn new(_index_path: &str) -> Result<Self> {
-        todo!("Implement Indexer::new")
+    /// Open (or create) the stores of an index directory
+    ///
+    /// # Arguments
+    /// * `index_dir` - Directory holding the SQLite, Tantivy and vector stores
+    /// * `privacy_config` - Exclusion rules applied while walking roots
+    pub async fn new<P: AsRef<Path>>(index_dir: P, privacy_config: PrivacyConfig) -> Result<Self> {
+        let paths = IndexPaths::new(index_dir);
+        std::fs::create_dir_all(&paths.root)?;
+
+        let db = Database::new(&paths.db).await?;
+        let tantivy_index = TantivyIndex::new(&paths.tantivy)?;
+        if tantivy_index.num_docs() == 0 && db.count_files().await? > 0 {
+            // The keyword index was rebuilt for a newer schema: fill it again
+            db.mark_all_pending().await?;
+        }
+        let vector_store = load_vector_store(&paths.vectors, TEXT_EMBEDDING_DIM)?;
+        let image_vector_store = load_vector_store(&paths.image_vectors, IMAGE_EMBEDDING_DIM)?;
+
+        Ok(Self {
+            paths,
+            db,
+            tantivy_index,
+            vector_store,
+            image_vector_store,
+            walker: FileWalker::new(privacy_config),
+            indexing_config: IndexingConfig::default(),
+            models: Models::default(),
+            extractors: Arc::new(ExtractorRegistry::default()),
+            custom_extractors: ExtractorRegistry::new(),
+            stop: StopHandle::default(),
+            pending_deletes: HashMap::new(),
+            unlinked_members: Vec::new(),
+            writes_since_checkpoint: 0,
+            last_checkpoint: Instant::now(),
+            text_vectors_dirty: false,
+            image_vectors_dirty: false,
+        })
+    }
+
+    /// Generate text embeddings for semantic search
+    pub fn with_embedding_model(mut self, model: EmbeddingModel) -> Self {
+        self.models.text = Some(model);
+        // Create the vector file even if no document gets embedded
+        self.text_vectors_dirty = true;
+        self
+    }
+
+    /// Generate CLIP embeddings for image search
+    pub fn with_image_model(mut self, model: ImageEmbedding) -> Self {
+        self.models.image = Some(model);
+        self
+    }
+
+    /// Extract content with an additional extractor
+    ///
+    /// It takes precedence over the built-in extractors for the files it
+    /// handles, unless they have a higher priority.
+    pub fn with_extractor<E: Extractor + 'static>(mut self, extractor: E) -> Self {
+        self.custom_extractors.register(extractor);
+        self.build_extractors();
+        self
+    }
+
+    /// Set worker count, queue capacity, embedding batch size and what is
+    /// indexed of notebooks
+    pub fn with_indexing_config(mut self, config: IndexingConfig) -> Self {
+        self.indexing_config = config;
+        self.build_extractors();
+        self
+    }
+
+    /// Set up the built-in extractors for the indexing configuration,
+    /// followed by the extractors added by the application
+    fn build_extractors(&mut self) {
+        let mut extractors = ExtractorRegistry::for_config(&self.indexing_config);
+        extractors.extend(&self.custom_extractors);
+        self.extractors = Arc::new(extractors);
     }
 
-    pub fn index_directory(&self, _path: &str) -> Result<()> {
-        todo!("Implement index_directory")
+    /// Handle to stop runs of this indexer, e.g. from a signal handler
+    pub fn stop_handle(&self) -> StopHandle {
+        self.stop.clone()
+    }
+
+    /// Locations of the stores managed by this indexer
+    pub fn paths(&self) -> &IndexPaths {
+        &self.paths
+    }
+
+    /// Metadata database of the index
+    pub fn database(&self) -> &Database {
+        &self.db
+    }
+
+    /// Index a single directory
+    pub async fn index_directory<P, F>(&mut self, root: P, on_event: F) -> Result<IndexSummary>
+    where
+        P: AsRef<Path>,
+        F: FnMut(IndexEvent),
+    {
+        self.index_roots(&[root.as_ref().to_path_buf()], on_event).await
+    }
+
+    /// Index one or more directories and commit all stores
+    ///
+    /// Each directory is registered as a root, so that it can later be
+    /// refreshed, listed or removed. Unchanged files are skipped, and
+    /// indexed files under a root that no longer exist on disk are removed
+    /// from every store.
+    ///
+    /// # Arguments
+    /// * `roots` - Directories to walk
+    /// * `on_event` - Called with the outcome of every file
+    ///
+    /// # Returns
+    /// Totals for the whole run
+    pub async fn index_roots<F>(&mut self, roots: &[PathBuf], on_event: F) -> Result<IndexSummary>
+    where
+        F: FnMut(IndexEvent),
+    {
+        self.index_roots_with(roots, self.change_detection(), on_event).await
+    }
+
+    /// Index one or more directories from scratch and commit all stores
+    ///
+    /// Unlike [`index_roots`](Self::index_roots), every file is extracted
+    /// and embedded again, even if it did not change.
+    pub async fn reindex_roots<F>(&mut self, roots: &[PathBuf], on_event: F) -> Result<IndexSummary>
+    where
+        F: FnMut(IndexEvent),
+    {
+        self.index_roots_with(roots, ChangeDetection::Always, on_event).await
+    }
+
+    async fn index_roots_with<F>(
+        &mut self,
+        roots: &[PathBuf],
+        detection: ChangeDetection,
+        mut on_event: F,
+    ) -> Result<IndexSummary>
+    where
+        F: FnMut(IndexEvent),
+    {
+        let mut summary = IndexSummary::default();
+
+        for root in roots {
+            // Store absolute paths so the same folder always maps to the same rows
+            let root = root
+                .canonicalize()
+                .map_err(|_| crate::Error::FileNotFound(root.display().to_string()))?;
+
+            let root_str = root.to_string_lossy();
+            self.db.add_root(&root_str).await?;
+            self.index_root(&root, &root, detection, &mut summary, &mut on_event).await?;
+            if self.stop.is_stopped() {
+                summary.interrupted = true;
+                break;
+            }
+            self.db.mark_root_indexed(&root_str).await?;
+        }
+
+        self.commit().await?;
+        Ok(summary)
+    }
+
+    /// Roots registered by previous indexing runs
+    pub async fn roots(&self) -> Result<Vec<IndexedRoot>> {
+        self.db.list_roots().await
+    }
+
+    /// Unregister a root and purge its files from every store
+    ///
+    /// Files that are also covered by another registered root, e.g. one
+    /// nested inside this one, stay in the index.
+    ///
+    /// # Arguments
+    /// * `root` - Registered root; needn't exist on disk anymore
+    /// * `on_event` - Called with every removed file
+    pub async fn remove_root<F>(&mut self, root: &Path, mut on_event: F) -> Result<IndexSummary>
+    where
+        F: FnMut(IndexEvent),
+    {
+        let root = self.registered_root(root).await?;
+
+        let other_roots: Vec<PathBuf> = self
+            .db
+            .list_roots()
+            .await?
+            .into_iter()
+            .filter(|r| r.path != root)
+            .map(|r| PathBuf::from(r.path))
+            .collect();
+
+        let mut summary = IndexSummary::default();
+        for file in self.db.list_files_under(&root).await? {
+            if other_roots.iter().any(|other| Path::new(&file.path).starts_with(other)) {
+                continue;
+            }
+            self.remove_file(file.id, &file.path).await?;
+            summary.removed += 1;
+            on_event(IndexEvent::Removed { path: PathBuf::from(file.path) });
+        }
+
+        // Unregister last, so a crash can't leave unreachable files behind
+        self.commit().await?;
+        self.db.remove_root(&root).await?;
+        Ok(summary)
+    }
+
+    /// Change detection mode for regular runs
+    fn change_detection(&self) -> ChangeDetection {
+        if self.indexing_config.paranoid {
+            ChangeDetection::Hash
+        } else {
+            ChangeDetection::Stat
+        }
+    }
+
+    /// Resolve a path to the form it was registered under
+    async fn registered_root(&self, root: &Path) -> Result<String> {
+        let candidates = [
+            root.canonicalize().ok(),
+            std::path::absolute(root).ok(),
+        ];
+
+        let registered = self.db.list_roots().await?;
+        candidates
+            .into_iter()
+            .flatten()
+            .map(|path| path.to_string_lossy().to_string())
+            .find(|path| registered.iter().any(|r| &r.path == path))
+            .ok_or_else(|| crate::Error::InvalidInput(format!("Not a registered root: {}", root.display())))
+    }
+
+    /// Re-index individual paths below already indexed roots and commit
+    ///
+    /// This is the incremental counterpart of [`index_roots`](Self::index_roots)
+    /// used by the file watcher. Each path is handled according to what is
+    /// on disk now, so a burst of events for the same path only needs one
+    /// call:
+    ///
+    /// * a file is re-indexed if its hash changed
+    /// * a directory is rescanned like a root
+    /// * a missing or excluded path is purged, together with everything
+    ///   indexed below it
+    ///
+    /// Paths outside of `roots` and inside the index directory are ignored.
+    ///
+    /// # Arguments
+    /// * `roots` - Canonical roots the paths belong to
+    /// * `paths` - Created, modified, renamed or removed paths
+    /// * `on_event` - Called with the outcome of every file
+    pub async fn index_paths<F>(&mut self, roots: &[PathBuf], paths: &[PathBuf], mut on_event: F) -> Result<IndexSummary>
+    where
+        F: FnMut(IndexEvent),
+    {
+        let mut summary = IndexSummary::default();
+        let mut files = Vec::new();
+
+        for path in paths {
+            if self.stop.is_stopped() {
+                summary.interrupted = true;
+                break;
+            }
+            if path.starts_with(&self.paths.root) {
+                continue;
+            }
+            let Some(root) = roots
+                .iter()
+                .filter(|root| path.starts_with(root))
+                .max_by_key(|root| root.components().count())
+            else {
+                continue;
+            };
+
+            if !path.exists() || self.walker.is_excluded(root, path) {
+                self.purge(&path.to_string_lossy(), &mut summary, &mut on_event).await?;
+            } else if path.is_dir() {
+                self.index_root(root, path, self.change_detection(), &mut summary, &mut on_event).await?;
+            } else {
+                match self.walker.discover(root, path)? {
+                    Some(file) => files.push(file),
+                    None => self.purge(&path.to_string_lossy(), &mut summary, &mut on_event).await?,
+                }
+            }
+        }
+
+        // The files and, for archives, their members
+        let mut stored_files = HashMap::new();
+        for file in &files {
+            for stored in self.db.list_files_under(&file.path.to_string_lossy()).await? {
+                stored_files.insert(stored.path.clone(), stored);
+            }
+        }
+        self.process_files(files, stored_files, self.change_detection(), &mut summary, &mut on_event)
+            .await?;
+        summary.interrupted |= self.stop.is_stopped();
+
+        self.commit().await?;
+        Ok(summary)
+    }
+
+    /// Walk a canonical directory below `root`, index its files and purge
+    /// deleted ones
+    ///
+    /// `dir` is `root` itself, or a directory below it when rescanning part
+    /// of a root; exclusion patterns are relative to `root` either way.
+    async fn index_root<F: FnMut(IndexEvent)>(
+        &mut self,
+        root: &Path,
+        dir: &Path,
+        detection: ChangeDetection,
+        summary: &mut IndexSummary,
+        on_event: &mut F,
+    ) -> Result<()> {
+        let discovered = self.walker.walk_dir(root, dir)?;
+        on_event(IndexEvent::Discovered {
+            root: dir.to_path_buf(),
+            files: discovered.len(),
+        });
+
+        let stored = self.db.list_files_under(&dir.to_string_lossy()).await?;
+        let stored_files: HashMap<String, StoredFile> = stored
+            .iter()
+            .map(|file| (file.path.clone(), file.clone()))
+            .collect();
+        let present: HashSet<String> = discovered
+            .iter()
+            .map(|f| f.path.to_string_lossy().to_string())
+            .collect();
+
+        self.process_files(discovered, stored_files, detection, summary, on_event).await?;
+        if self.stop.is_stopped() {
+            return Ok(());
+        }
+
+        // Purge files that disappeared from disk since the last run, and
+        // the members of archives that did
+        for file in stored {
+            let disk_path = archive::disk_path(&file.path);
+            if !present.contains(disk_path) && !Path::new(disk_path).exists() {
+                self.remove_file(file.id, &file.path).await?;
+                summary.removed += 1;
+                on_event(IndexEvent::Removed { path: PathBuf::from(file.path) });
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Run files through the pipeline and write the results
+    async fn process_files<F: FnMut(IndexEvent)>(
+        &mut self,
+        files: Vec<DiscoveredFile>,
+        stored_files: HashMap<String, StoredFile>,
+        detection: ChangeDetection,
+        summary: &mut IndexSummary,
+        on_event: &mut F,
+    ) -> Result<()> {
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        let models = std::mem::take(&mut self.models);
+        let mut pipeline = Pipeline::start(
+            files,
+            stored_files,
+            detection,
+            models,
+            self.extractors.clone(),
+            &self.indexing_config,
+        )?;
+
+        let mut write_result = Ok(());
+        while let Some(prepared) = pipeline.output.recv().await {
+            if let Some(member_paths) = &prepared.member_paths {
+                let archive_path = prepared.file.path.to_string_lossy();
+                if let Err(e) = self.remove_stale_members(&archive_path, member_paths, summary, on_event).await {
+                    write_result = Err(e);
+                    break;
+                }
+            }
+            match self.write_file(prepared, summary).await {
+                Ok(event) => on_event(event),
+                Err(e) => {
+                    write_result = Err(e);
+                    break;
+                }
+            }
+            if self.stop.is_stopped() {
+                break;
+            }
+            if let Err(e) = self.checkpoint_if_due().await {
+                write_result = Err(e);
+                break;
+            }
+        }
+
+        // Always get the models back, even when the writer failed
+        self.models = pipeline.finish()?;
+        write_result
+    }
+
+    /// Remove everything indexed at or below a path
+    async fn purge<F: FnMut(IndexEvent)>(
+        &mut self,
+        path: &str,
+        summary: &mut IndexSummary,
+        on_event: &mut F,
+    ) -> Result<()> {
+        for file in self.db.list_files_under(path).await? {
+            self.remove_file(file.id, &file.path).await?;
+            summary.removed += 1;
+            on_event(IndexEvent::Removed { path: PathBuf::from(file.path) });
+        }
+        Ok(())
+    }
+
+    /// Remove indexed members of an archive that are no longer in it
+    async fn remove_stale_members<F: FnMut(IndexEvent)>(
+        &mut self,
+        archive_path: &str,
+        member_paths: &HashSet<String>,
+        summary: &mut IndexSummary,
+        on_event: &mut F,
+    ) -> Result<()> {
+        for file in self.db.list_files_under(archive_path).await? {
+            if file.path != archive_path && !member_paths.contains(&file.path) {
+                self.remove_file(file.id, &file.path).await?;
+                summary.removed += 1;
+                on_event(IndexEvent::Removed { path: PathBuf::from(file.path) });
+            }
+        }
+        Ok(())
+    }
+
+    /// Remove a file from SQLite, Tantivy and both vector stores
+    ///
+    /// The removal takes effect on the next [`commit`](Self::commit); the
+    /// SQLite row is only deleted once the other stores are committed.
+    pub async fn remove_file(&mut self, file_id: FileId, path: &str) -> Result<()> {
+        self.tantivy_index.queue_delete(file_id)?;
+        self.vector_store.delete(file_id)?;
+        self.image_vector_store.delete(file_id)?;
+        self.text_vectors_dirty = true;
+        self.image_vectors_dirty = true;
+        self.pending_deletes.insert(path.to_string(), file_id);
+        Ok(())
+    }
+
+    /// Checkpoint: commit Tantivy, persist the vector stores, then complete
+    /// the pending SQLite rows and deletions
+    pub async fn commit(&mut self) -> Result<()> {
+        self.tantivy_index.commit()?;
+
+        if self.text_vectors_dirty
+            && (self.models.text.is_some() || !self.vector_store.is_empty() || self.paths.vectors.exists())
+        {
+            self.vector_store.save_changes(&self.paths.vectors)?;
+        }
+        if self.image_vectors_dirty && (!self.image_vector_store.is_empty() || self.paths.image_vectors.exists()) {
+            self.image_vector_store.save_changes(&self.paths.image_vectors)?;
+        }
+        self.text_vectors_dirty = false;
+        self.image_vectors_dirty = false;
+
+        let deleted: Vec<FileId> = self.pending_deletes.drain().map(|(_, id)| id).collect();
+        self.db.complete_checkpoint(&deleted).await?;
+
+        self.writes_since_checkpoint = 0;
+        self.last_checkpoint = Instant::now();
+        Ok(())
+    }
+
+    /// Commit if enough files or time passed since the last checkpoint
+    async fn checkpoint_if_due(&mut self) -> Result<()> {
+        self.writes_since_checkpoint += 1;
+
+        let due = self.writes_since_checkpoint >= self.indexing_config.checkpoint_files.max(1)
+            || self.last_checkpoint.elapsed() >= Duration::from_secs(self.indexing_config.checkpoint_secs);
+        if due {
+            self.commit().await?;
+        }
+        Ok(())
+    }
+
+    /// Write one prepared file to SQLite, Tantivy and the vector stores
+    ///
+    /// Storage failures abort the run; problems with the file itself are
+    /// reported as [`IndexEvent::Skipped`].
+    async fn write_file(&mut self, prepared: PreparedFile, summary: &mut IndexSummary) -> Result<IndexEvent> {
+        let PreparedFile { file, kind, member, .. } = prepared;
+        let path_str = file.path.to_string_lossy().to_string();
+
+        // A file removed earlier in this run is back
+        self.pending_deletes.remove(&path_str);
+
+        let (metadata, is_new, content, image, text_chunks, image_embedding) = match kind {
+            PreparedKind::Unchanged(refreshed) => {
+                if let Some(metadata) = refreshed {
+                    self.db.upsert_file(&metadata).await?;
+                }
+                summary.unchanged += 1;
+                return Ok(IndexEvent::Unchanged { path: file.path });
+            }
+            PreparedKind::Skipped(reason) => {
+                summary.skipped += 1;
+                return Ok(IndexEvent::Skipped { path: file.path, reason });
+            }
+            PreparedKind::Image {
+                metadata,
+                is_new,
+                properties,
+                text,
+                chunks,
+                embedding,
+            } => (metadata, is_new, None, Some((properties, text)), chunks, embedding),
+            PreparedKind::Document { metadata, is_new, content, chunks } => {
+                (metadata, is_new, Some(content), None, chunks, None)
+            }
+        };
+
+        let file_id = self.db.upsert_pending_file(&metadata).await?;
+        if let Some(member) = member {
+            match self.db.get_file_by_path(&member.archive_path).await? {
+                Some(archive) => self.db.upsert_archive_member(file_id, archive.id, &member).await?,
+                // Members are streamed ahead of their archive
+                None => self.unlinked_members.push((file_id, member)),
+            }
+        }
+        if !self.unlinked_members.is_empty() {
+            let (members, others): (Vec<_>, Vec<_>) = std::mem::take(&mut self.unlinked_members)
+                .into_iter()
+                .partition(|(_, member)| member.archive_path == path_str);
+            self.unlinked_members = others;
+            for (member_id, member) in members {
+                self.db.upsert_archive_member(member_id, file_id, &member).await?;
+            }
+        }
+        if !is_new {
+            // Drop stale entries so a failed re-extraction can't leave old content behind
+            self.tantivy_index.queue_delete(file_id)?;
+            self.db.delete_content(file_id).await?;
+            self.vector_store.delete(file_id)?;
+            self.image_vector_store.delete(file_id)?;
+            self.db.replace_text_chunks(file_id, &[]).await?;
+            self.db.replace_symbols(file_id, &[]).await?;
+            self.db.replace_pages(file_id, &[]).await?;
+            self.db.replace_links(file_id, &[]).await?;
+            self.db.replace_key_values(file_id, &[]).await?;
+            self.text_vectors_dirty = true;
+            self.image_vectors_dirty = true;
+        }
+
+        // Failures that would happen again are final: the file is indexed
+        // by name, and only retried once it changes
+        let mut skipped = None;
+        match content {
+            // Handle images separately
+            None => {
+                match image_embedding {
+                    Some(Ok(embedding)) => {
+                        self.image_vector_store.upsert(file_id, &embedding)?;
+                        self.image_vectors_dirty = true;
+                        summary.image_embeddings += 1;
+                    }
+                    Some(Err(failure)) if failure.transient => {
+                        self.db.mark_file_failed(file_id).await?;
+                        summary.skipped += 1;
+                        return Ok(IndexEvent::Skipped {
+                            path: file.path,
+                            reason: format!("image error: {}", failure.reason),
+                        });
+                    }
+                    Some(Err(failure)) => skipped = Some(format!("image error: {}", failure.reason)),
+                    None => {}
+                }
+
+                let (properties, text) = image.unwrap_or_default();
+                self.db.upsert_image_metadata(file_id, &properties).await?;
+
+                // Text recognized in the image is searchable like a document
+                let description = match text {
+                    Some(text) => {
+                        self.db.upsert_content(file_id, &text).await?;
+                        text.text
+                    }
+                    None => format!("image file: {}", metadata.filename),
+                };
+                self.tantivy_index.upsert_image_document(
+                    file_id,
+                    &path_str,
+                    &metadata.filename,
+                    &description,
+                    &properties,
+                )?;
+            }
+            Some(Err(failure)) if failure.transient => {
+                self.db.mark_file_failed(file_id).await?;
+                summary.skipped += 1;
+                return Ok(IndexEvent::Skipped {
+                    path: file.path,
+                    reason: failure.reason,
+                });
+            }
+            Some(Err(failure)) => {
+                self.tantivy_index.upsert_document(file_id, &path_str, &metadata.filename, "")?;
+                skipped = Some(failure.reason);
+            }
+            Some(Ok(content)) => {
+                self.db.upsert_content(file_id, &content).await?;
+                if !content.symbols.is_empty() {
+                    self.db.replace_symbols(file_id, &content.symbols).await?;
+                }
+                if !content.pages.is_empty() {
+                    self.db.replace_pages(file_id, &content.pages).await?;
+                }
+                if !content.links.is_empty() {
+                    let links: Vec<_> = content.links.iter().map(|link| link.resolve(&path_str)).collect();
+                    self.db.replace_links(file_id, &links).await?;
+                }
+                if !content.key_values.is_empty() {
+                    self.db.replace_key_values(file_id, &content.key_values).await?;
+                }
+                self.tantivy_index
+                    .upsert_extracted_document(file_id, &path_str, &metadata.filename, &content)?;
+            }
+        }
+
+        match text_chunks {
+            Ok(text_chunks) if !text_chunks.is_empty() => {
+                let (chunks, embeddings): (Vec<_>, Vec<_>) = text_chunks.into_iter().unzip();
+                self.vector_store.upsert_chunks(file_id, &embeddings)?;
+                self.db.replace_text_chunks(file_id, &chunks).await?;
+                self.text_vectors_dirty = true;
+            }
+            Ok(_) => {}
+            // The model failed rather than the file, so always try again
+            Err(failure) => {
+                self.db.mark_file_failed(file_id).await?;
+                summary.skipped += 1;
+                return Ok(IndexEvent::Skipped {
+                    path: file.path,
+                    reason: format!("embedding error: {}", failure.reason),
+                });
+            }
+        }
+
+        if let Some(reason) = skipped {
+            summary.skipped += 1;
+            return Ok(IndexEvent::Skipped { path: file.path, reason });
+        }
+        Ok(if is_new {
+            summary.added += 1;
+            IndexEvent::Added {
+                path: file.path,
+                file_type: file.file_type,
+            }
+        } else {
+            summary.updated += 1;
+            IndexEvent::Updated {
+                path: file.path,
+                file_type: file.file_type,
+            }
+        })
+    }
+}
+
+/// Load a persisted vector store, or start an empty one
+fn load_vector_store(path: &Path, dimension: usize) -> Result<VectorStore> {
+    if path.exists() {
+        VectorStore::load(path)
+    } else {
+        VectorStore::new(dimension)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::notebook::tests::write_notebook;
+    use crate::extractors::text::tests::write_pdf;
+    use crate::extractors::{match_offset, Page, SymbolKind};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_index_directory() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+
+        fs::write(docs.path().join("notes.txt"), "quarterly budget review").unwrap();
+        fs::write(docs.path().join("main.rs"), "fn main() {}").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+
+        let mut events = Vec::new();
+        let summary = indexer
+            .index_directory(docs.path(), |event| events.push(event))
+            .await
+            .unwrap();
+
+        assert_eq!(summary.added, 2);
+        assert_eq!(summary.skipped, 0);
+        assert!(matches!(events[0], IndexEvent::Discovered { files: 2, .. }));
+        assert_eq!(
+            events.iter().filter(|e| matches!(e, IndexEvent::Added { .. })).count(),
+            2
+        );
+        assert_eq!(indexer.database().count_files().await.unwrap(), 2);
+
+        let results = indexer.tantivy_index.search("budget", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].filename, "notes.txt");
+    }
+
+    struct LogbookExtractor;
+
+    impl Extractor for LogbookExtractor {
+        fn name(&self) -> &str {
+            "logbook"
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["logbook"]
+        }
+
+        fn extract(&self, path: &Path, _file_type: FileType) -> Result<crate::extractors::ExtractedContent> {
+            let entries = fs::read_to_string(path)?.replace(';', "\n");
+            Ok(crate::extractors::ExtractedContent::new(entries))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_custom_extractor() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        fs::write(docs.path().join("ship.logbook"), "departed;storm;arrived").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default())
+            .await
+            .unwrap()
+            .with_extractor(LogbookExtractor);
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+
+        let results = indexer.tantivy_index.search("storm", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        let content = indexer.database().get_content(results[0].file_id).await.unwrap().unwrap();
+        assert_eq!(content.text, "departed\nstorm\narrived");
     }
-}
\ No newline at end of file
+
+    /// Stands in for OCR, finding text in screenshots only
+    struct FakeOcrExtractor;
+
+    impl Extractor for FakeOcrExtractor {
+        fn name(&self) -> &str {
+            "fake-ocr"
+        }
+
+        fn file_types(&self) -> &[FileType] {
+            &[FileType::Image]
+        }
+
+        fn extract(&self, path: &Path, _file_type: FileType) -> Result<crate::extractors::ExtractedContent> {
+            let text = if path.ends_with("screenshot.png") { "permission denied" } else { "" };
+            Ok(crate::extractors::ExtractedContent::new(text.to_string()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_image_text() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let pixels = image::RgbImage::from_pixel(4, 4, image::Rgb([255, 255, 255]));
+        pixels.save(docs.path().join("screenshot.png")).unwrap();
+        pixels.save(docs.path().join("photo.png")).unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default())
+            .await
+            .unwrap()
+            .with_extractor(FakeOcrExtractor);
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+
+        let results = indexer.tantivy_index.search("denied", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].filename, "screenshot.png");
+        let content = indexer.database().get_content(results[0].file_id).await.unwrap().unwrap();
+        assert_eq!(content.text, "permission denied");
+
+        // Images without text are still indexed by name
+        let results = indexer.tantivy_index.search("photo", 10).unwrap();
+        assert_eq!(results[0].filename, "photo.png");
+    }
+
+    #[tokio::test]
+    async fn test_index_multiple_roots() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+
+        fs::write(first.path().join("a.txt"), "alpha").unwrap();
+        fs::write(second.path().join("b.txt"), "beta").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        let roots = vec![first.path().to_path_buf(), second.path().to_path_buf()];
+        let summary = indexer.index_roots(&roots, |_| {}).await.unwrap();
+
+        assert_eq!(summary.indexed(), 2);
+        assert_eq!(indexer.database().count_files().await.unwrap(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_reindex_is_incremental() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+
+        fs::write(docs.path().join("keep.txt"), "unchanged text").unwrap();
+        fs::write(docs.path().join("edit.txt"), "apple orange").unwrap();
+        fs::write(docs.path().join("gone.txt"), "obsolete words").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        let first = indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        assert_eq!(first.added, 3);
+
+        fs::write(docs.path().join("edit.txt"), "banana grape").unwrap();
+        fs::remove_file(docs.path().join("gone.txt")).unwrap();
+        fs::write(docs.path().join("new.txt"), "fresh content").unwrap();
+
+        let second = indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        assert_eq!(second.added, 1);
+        assert_eq!(second.updated, 1);
+        assert_eq!(second.unchanged, 1);
+        assert_eq!(second.removed, 1);
+
+        assert_eq!(indexer.database().count_files().await.unwrap(), 3);
+        assert!(indexer.tantivy_index.search("obsolete", 10).unwrap().is_empty());
+        assert!(indexer.tantivy_index.search("apple", 10).unwrap().is_empty());
+        assert_eq!(indexer.tantivy_index.search("banana", 10).unwrap().len(), 1);
+    }
+
+    /// Fails like a file that is locked by another program
+    struct LockedExtractor;
+
+    impl Extractor for LockedExtractor {
+        fn name(&self) -> &str {
+            "locked"
+        }
+
+        fn extensions(&self) -> &[&str] {
+            &["locked"]
+        }
+
+        fn extract(&self, _path: &Path, _file_type: FileType) -> Result<crate::extractors::ExtractedContent> {
+            Err(std::io::Error::from(std::io::ErrorKind::WouldBlock).into())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_files() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let path = docs.path().join("forecast.ipynb");
+        write_notebook(&path, serde_json::json!([{"cell_type": "markdown", "metadata": {}, "source": "# Quarterly outlook"}]));
+        fs::write(docs.path().join("ledger.locked"), "x").unwrap();
+        fs::write(docs.path().join("clip.mp4"), [0x00, 0x00, 0x00, 0x18, 0x66, 0x74, 0x79, 0x70, 0x00]).unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default())
+            .await
+            .unwrap()
+            .with_extractor(LockedExtractor);
+        let first = indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        assert_eq!(first.skipped, 2);
+        let file_id = indexer.tantivy_index.search("quarterly", 10).unwrap()[0].file_id;
+
+        // A failed re-extraction leaves no stale content behind, but the
+        // file is still found by name
+        fs::write(&path, "not json").unwrap();
+        let failed = indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        assert_eq!((failed.skipped, failed.unchanged), (2, 1));
+        assert!(indexer.database().get_content(file_id).await.unwrap().is_none());
+        assert!(indexer.tantivy_index.search("quarterly", 10).unwrap().is_empty());
+        assert_eq!(indexer.tantivy_index.search("forecast", 10).unwrap()[0].file_id, file_id);
+        assert_eq!(indexer.tantivy_index.search("clip", 10).unwrap().len(), 1);
+
+        // Only the transient failure is tried again while files don't change
+        let next = indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        assert_eq!(next.skipped, 1);
+        assert_eq!(next.unchanged, 2);
+    }
+
+    #[tokio::test]
+    async fn test_index_symbols() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let path = docs.path().join("query.py");
+        fs::write(&path, "# Query parsing\n\ndef parse_query(text):\n    return text.split()\n").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+
+        let results = indexer.tantivy_index.search("sym:parseQuery", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        let symbols = indexer.database().get_symbols(results[0].file_id).await.unwrap();
+        assert_eq!(symbols.len(), 1);
+        assert_eq!((symbols[0].name.as_str(), symbols[0].kind, symbols[0].line), ("parse_query", SymbolKind::Function, 3));
+        assert_eq!(indexer.tantivy_index.search("comment:parsing", 10).unwrap().len(), 1);
+
+        // Symbols of an earlier version don't outlive it
+        fs::write(&path, "print('no definitions')\n").unwrap();
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        assert!(indexer.database().get_symbols(results[0].file_id).await.unwrap().is_empty());
+        assert!(indexer.tantivy_index.search("sym:parse_query", 10).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_index_pdf_pages() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let path = docs.path().join("manual.pdf");
+        let info = lopdf::dictionary! { "Author" => lopdf::Object::string_literal("Grace Hopper") };
+        write_pdf(&path, &["Safety instructions", "Replacing the impeller"], info);
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+
+        let results = indexer.tantivy_index.search("impeller AND author:hopper", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        let content = indexer.database().get_content(results[0].file_id).await.unwrap().unwrap();
+        let pages = indexer.database().get_pages(results[0].file_id).await.unwrap();
+        assert_eq!(pages.len(), 2);
+        let offset = match_offset(&content.text, "impeller").unwrap();
+        assert_eq!(Page::number_at(&pages, offset), Some(2));
+    }
+
+    #[tokio::test]
+    async fn test_index_vault() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let root = docs.path().canonicalize().unwrap();
+        fs::create_dir(root.join("projects")).unwrap();
+        fs::write(
+            root.join("projects/alpha.md"),
+            "---\ntags: [work]\ndate: 2024-03-01\n---\n# Project Alpha\n\n## Milestones\n\nSee [budget](../budget.md).\n",
+        )
+        .unwrap();
+        fs::write(root.join("budget.md"), "# Budget\n").unwrap();
+        fs::write(root.join("weekly.md"), "Progress on [[alpha]].\n").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        indexer.index_directory(&root, |_| {}).await.unwrap();
+
+        let results = indexer.tantivy_index.search("tags:work AND headings:milestones", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].filename, "alpha.md");
+
+        let alpha = root.join("projects/alpha.md").to_string_lossy().into_owned();
+        let backlinks = indexer.database().get_backlinks(&alpha).await.unwrap();
+        assert_eq!(backlinks.len(), 1);
+        assert_eq!((backlinks[0].path.as_str(), backlinks[0].line), (root.join("weekly.md").to_str().unwrap(), 1));
+        let budget = root.join("budget.md").to_string_lossy().into_owned();
+        assert_eq!(indexer.database().count_backlinks(&budget).await.unwrap(), 1);
+
+        // Links of an earlier version don't outlive it
+        fs::write(root.join("weekly.md"), "Nothing linked\n").unwrap();
+        indexer.index_directory(&root, |_| {}).await.unwrap();
+        assert!(indexer.database().get_backlinks(&alpha).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_index_key_values() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        fs::write(docs.path().join("prod.yaml"), "database:\n  host: prod-db.internal\n  port: 5432\n").unwrap();
+        fs::write(docs.path().join("staging.json"), r#"{"database": {"host": "staging", "url": "postgres://db:5432/Staging DB"}}"#).unwrap();
+        fs::write(docs.path().join("notes.txt"), "database host prod-db.internal").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+
+        let results = indexer.tantivy_index.search("key:database.host=prod-db.internal", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].filename, "prod.yaml");
+        let key_values = indexer.database().get_key_values(results[0].file_id).await.unwrap();
+        assert_eq!(key_values.len(), 2);
+        assert_eq!(indexer.tantivy_index.search("key:database.host", 10).unwrap().len(), 2);
+        assert_eq!(indexer.tantivy_index.search("key:database", 10).unwrap().len(), 2);
+        assert_eq!(indexer.tantivy_index.search("database.port", 10).unwrap().len(), 1);
+        let results = indexer.tantivy_index.search(r#"key:"database.url=postgres://db:5432/staging db""#, 10).unwrap();
+        assert_eq!(results[0].filename, "staging.json");
+    }
+
+    #[tokio::test]
+    async fn test_index_notebook() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let cells = serde_json::json!([
+            {"cell_type": "markdown", "metadata": {}, "source": "# Forecast"},
+            {"cell_type": "code", "metadata": {}, "source": "def seasonal_trend(series):\n    return series.diff(12)",
+             "outputs": [{"output_type": "display_data", "metadata": {}, "data": {"image/png": "iVBORw0KGgoAAAANSUhEUg"}}]},
+            {"cell_type": "code", "metadata": {}, "source": "print(mape)", "outputs": [{"output_type": "stream", "name": "stdout", "text": "holdout error 4.2%"}]}
+        ]);
+        write_notebook(&docs.path().join("forecast.ipynb"), cells);
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+
+        let results = indexer.tantivy_index.search("sym:seasonal_trend AND title:forecast", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert!(indexer.tantivy_index.search("iVBORw0KGgoAAAANSUhEUg", 10).unwrap().is_empty());
+
+        // Matches point at their cell
+        let content = indexer.database().get_content(results[0].file_id).await.unwrap().unwrap();
+        let pages = indexer.database().get_pages(results[0].file_id).await.unwrap();
+        let offset = match_offset(&content.text, "holdout").unwrap();
+        assert_eq!(Page::number_at(&pages, offset), Some(3));
+        assert_eq!(Page::label(&results[0].path), "Cell");
+
+        // Outputs can be left out
+        let index = TempDir::new().unwrap();
+        let config = IndexingConfig {
+            notebook_outputs: false,
+            ..Default::default()
+        };
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default())
+            .await
+            .unwrap()
+            .with_indexing_config(config);
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        assert!(indexer.tantivy_index.search("holdout", 10).unwrap().is_empty());
+        assert_eq!(indexer.tantivy_index.search("mape", 10).unwrap().len(), 1);
+
+        // The last configuration counts
+        let mut indexer = indexer.with_indexing_config(IndexingConfig::default());
+        indexer.reindex_roots(&[docs.path().to_path_buf()], |_| {}).await.unwrap();
+        assert_eq!(indexer.tantivy_index.search("holdout", 10).unwrap().len(), 1);
+
+        // Extractors added by the application stay registered
+        let indexer = indexer.with_extractor(LogbookExtractor).with_indexing_config(IndexingConfig::default());
+        let extractor = indexer.extractors.find(Path::new("ship.logbook"), FileType::Unknown);
+        assert_eq!(extractor.unwrap().name(), "logbook");
+    }
+
+    #[tokio::test]
+    async fn test_index_paths() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let root = docs.path().canonicalize().unwrap();
+
+        fs::write(root.join("edit.txt"), "apple orange").unwrap();
+        fs::create_dir(root.join("old")).unwrap();
+        fs::write(root.join("old/a.txt"), "obsolete words").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        indexer.index_directory(&root, |_| {}).await.unwrap();
+
+        fs::write(root.join("edit.txt"), "banana grape").unwrap();
+        fs::remove_dir_all(root.join("old")).unwrap();
+        fs::create_dir(root.join("new")).unwrap();
+        fs::write(root.join("new/b.txt"), "fresh content").unwrap();
+        fs::write(root.join("secret.key"), "hunter2").unwrap();
+
+        let roots = vec![root.clone()];
+        let paths = vec![
+            root.join("edit.txt"),
+            root.join("old"),
+            root.join("new"),
+            root.join("secret.key"),
+            PathBuf::from("/outside/root.txt"),
+        ];
+        let summary = indexer.index_paths(&roots, &paths, |_| {}).await.unwrap();
+
+        assert_eq!(summary.added, 1);
+        assert_eq!(summary.updated, 1);
+        assert_eq!(summary.removed, 1);
+        assert_eq!(indexer.database().count_files().await.unwrap(), 2);
+        assert!(indexer.tantivy_index.search("obsolete", 10).unwrap().is_empty());
+        assert_eq!(indexer.tantivy_index.search("banana", 10).unwrap().len(), 1);
+        assert_eq!(indexer.tantivy_index.search("fresh", 10).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_index_paths_rescans_directory_below_root() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let root = docs.path().canonicalize().unwrap();
+        let privacy = PrivacyConfig {
+            exclude_patterns: vec!["/build".to_string(), "docs/**/*.tmp".to_string()],
+            ..PrivacyConfig::default()
+        };
+
+        let mut indexer = Indexer::new(index.path(), privacy).await.unwrap();
+        indexer.index_directory(&root, |_| {}).await.unwrap();
+
+        fs::create_dir_all(root.join("docs/drafts")).unwrap();
+        fs::write(root.join("docs/drafts/plan.tmp"), "scratch notes").unwrap();
+        fs::write(root.join("docs/drafts/plan.txt"), "final plan").unwrap();
+        fs::create_dir_all(root.join("src/build")).unwrap();
+        fs::write(root.join("src/build/steps.txt"), "compile steps").unwrap();
+
+        let roots = vec![root.clone()];
+        let paths = vec![root.join("docs"), root.join("src")];
+        let summary = indexer.index_paths(&roots, &paths, |_| {}).await.unwrap();
+
+        assert_eq!(summary.added, 2);
+        assert!(indexer.tantivy_index.search("scratch", 10).unwrap().is_empty());
+        assert_eq!(indexer.tantivy_index.search("plan", 10).unwrap().len(), 1);
+        assert_eq!(indexer.tantivy_index.search("compile", 10).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_roots_are_registered() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        fs::write(docs.path().join("a.txt"), "alpha").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+
+        let roots = indexer.roots().await.unwrap();
+        assert_eq!(roots.len(), 1);
+        assert_eq!(roots[0].path, docs.path().canonicalize().unwrap().to_string_lossy());
+        assert_eq!(roots[0].file_count, 1);
+        assert!(roots[0].last_indexed_at.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_remove_root() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let nested = docs.path().join("nested");
+        fs::create_dir(&nested).unwrap();
+        fs::write(docs.path().join("a.txt"), "alpha").unwrap();
+        fs::write(nested.join("b.txt"), "beta").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        let roots = vec![docs.path().to_path_buf(), nested.clone()];
+        indexer.index_roots(&roots, |_| {}).await.unwrap();
+
+        // The nested root keeps its files
+        let summary = indexer.remove_root(docs.path(), |_| {}).await.unwrap();
+        assert_eq!(summary.removed, 1);
+        assert_eq!(indexer.database().count_files().await.unwrap(), 1);
+        assert!(indexer.tantivy_index.search("alpha", 10).unwrap().is_empty());
+        assert_eq!(indexer.tantivy_index.search("beta", 10).unwrap().len(), 1);
+
+        let summary = indexer.remove_root(&nested, |_| {}).await.unwrap();
+        assert_eq!(summary.removed, 1);
+        assert!(indexer.roots().await.unwrap().is_empty());
+
+        assert!(indexer.remove_root(&nested, |_| {}).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_reindex_roots() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        fs::write(docs.path().join("a.txt"), "alpha").unwrap();
+        fs::write(docs.path().join("b.txt"), "beta").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        fs::remove_file(docs.path().join("b.txt")).unwrap();
+
+        let summary = indexer.reindex_roots(&[docs.path().to_path_buf()], |_| {}).await.unwrap();
+        assert_eq!(summary.updated, 1);
+        assert_eq!(summary.unchanged, 0);
+        assert_eq!(summary.removed, 1);
+        assert_eq!(indexer.tantivy_index.search("alpha", 10).unwrap().len(), 1);
+        assert!(indexer.tantivy_index.search("beta", 10).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_stop_checkpoints_and_resumes() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        for i in 0..40 {
+            fs::write(docs.path().join(format!("file{}.txt", i)), format!("document number{}", i)).unwrap();
+        }
+
+        let config = IndexingConfig {
+            checkpoint_files: 10,
+            ..Default::default()
+        };
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default())
+            .await
+            .unwrap()
+            .with_indexing_config(config.clone());
+        let stop = indexer.stop_handle();
+
+        let mut added = 0;
+        let summary = indexer
+            .index_directory(docs.path(), |event| {
+                if let IndexEvent::Added { .. } = event {
+                    added += 1;
+                    if added == 15 {
+                        stop.stop();
+                    }
+                }
+            })
+            .await
+            .unwrap();
+
+        assert!(summary.interrupted);
+        assert_eq!(summary.added, 15);
+        assert_eq!(indexer.database().count_pending_files().await.unwrap(), 0);
+        assert_eq!(indexer.tantivy_index.num_docs(), 15);
+        drop(indexer);
+
+        // The next run only indexes what is left
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default())
+            .await
+            .unwrap()
+            .with_indexing_config(config);
+        let summary = indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        assert!(!summary.interrupted);
+        assert_eq!(summary.added, 25);
+        assert_eq!(summary.unchanged, 15);
+        assert_eq!(indexer.tantivy_index.num_docs(), 40);
+    }
+
+    #[tokio::test]
+    async fn test_removal_waits_for_commit() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        fs::write(docs.path().join("a.txt"), "alpha").unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+
+        let path = docs.path().canonicalize().unwrap().join("a.txt");
+        let file = indexer.database().get_file_by_path(&path.to_string_lossy()).await.unwrap().unwrap();
+        indexer.remove_file(file.id, &file.path).await.unwrap();
+
+        // Until the other stores are committed, SQLite keeps the row
+        assert_eq!(indexer.database().count_files().await.unwrap(), 1);
+        indexer.commit().await.unwrap();
+        assert_eq!(indexer.database().count_files().await.unwrap(), 0);
+        assert!(indexer.tantivy_index.search("alpha", 10).unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_index_missing_root() {
+        let index = TempDir::new().unwrap();
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+
+        let result = indexer.index_directory("/nonexistent/path", |_| {}).await;
+        assert!(result.is_err());
+    }
+
+    fn write_zip(path: &Path, members: &[(&str, &[u8])]) {
+        use std::io::Write;
+
+        let mut zip = zip::ZipWriter::new(fs::File::create(path).unwrap());
+        for (name, data) in members {
+            zip.start_file(*name, zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_index_archives() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let zip_path = docs.path().canonicalize().unwrap().join("backup.zip");
+
+        let mut inner = Vec::new();
+        {
+            use std::io::Write;
+            let mut zip = zip::ZipWriter::new(std::io::Cursor::new(&mut inner));
+            zip.start_file("deep.txt", zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(b"nested treasure").unwrap();
+            zip.finish().unwrap();
+        }
+        write_zip(
+            &zip_path,
+            &[("notes/todo.md", b"buy oat milk"), ("keep.txt", b"kept words"), ("old.zip", &inner)],
+        );
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        let first = indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        // The archive, its three members and the member of the nested archive
+        assert_eq!(first.added, 5);
+
+        let results = indexer.tantivy_index.search("milk", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        let todo = format!("{}!/notes/todo.md", zip_path.display());
+        assert_eq!(results[0].path, todo);
+        let member = indexer.database().get_archive_member(results[0].file_id).await.unwrap().unwrap();
+        assert_eq!(member.archive_path, zip_path.to_string_lossy());
+        assert_eq!(member.member_path, "notes/todo.md");
+        assert_eq!(member.depth, 1);
+
+        let results = indexer.tantivy_index.search("treasure", 10).unwrap();
+        assert_eq!(results[0].path, format!("{}!/old.zip!/deep.txt", zip_path.display()));
+
+        // Members dropped from the archive are removed, unchanged ones kept
+        write_zip(&zip_path, &[("keep.txt", b"kept words")]);
+        let second = indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        assert_eq!((second.updated, second.unchanged, second.removed), (1, 1, 3));
+        assert!(indexer.database().get_file_by_path(&todo).await.unwrap().is_none());
+
+        // Deleting the archive removes its members
+        fs::remove_file(&zip_path).unwrap();
+        let third = indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        assert_eq!(third.removed, 2);
+        assert_eq!(indexer.database().count_files().await.unwrap(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_index_mail() {
+        let docs = TempDir::new().unwrap();
+        let index = TempDir::new().unwrap();
+        let root = docs.path().canonicalize().unwrap();
+
+        let message = |from: &str, subject: &str, body: &str| {
+            format!(
+                "From: {from}\nTo: team@example.com\nSubject: {subject}\nDate: Tue, 14 May 2024 09:30:00 +0000\n\
+                 Content-Type: multipart/mixed; boundary=b\n\n\
+                 --b\nContent-Type: text/plain\nContent-Transfer-Encoding: quoted-printable\n\n{body}\n\
+                 --b\nContent-Type: text/plain\nContent-Disposition: attachment; filename=\"minutes.txt\"\n\
+                 Content-Transfer-Encoding: base64\n\nUXVhcnRlcmx5IGhlYWRjb3VudCBwbGFu\n\
+                 --b--\n"
+            )
+        };
+        let mbox = format!(
+            "From alice@example.com Tue May 14 09:30:00 2024\n{}\nFrom bob@example.com Tue May 14 10:00:00 2024\n{}",
+            message("alice@example.com", "Offsite", "Venue is booked"),
+            message("bob@example.com", "Budget", "Numbers are in"),
+        );
+        fs::write(root.join("archive.mbox"), mbox).unwrap();
+        fs::create_dir_all(root.join("Maildir/cur")).unwrap();
+        let maildir_message = root.join("Maildir/cur/1715679000.M1P2.host:2,S");
+        fs::write(&maildir_message, message("carol@example.com", "Hiring", "Two offers=20sent")).unwrap();
+
+        let mut indexer = Indexer::new(index.path(), PrivacyConfig::default()).await.unwrap();
+        let first = indexer.index_directory(docs.path(), |_| {}).await.unwrap();
+        // The mailbox, its two messages, the Maildir message, and three attachments
+        assert_eq!(first.added, 7);
+
+        // Each message is a result of its own, searchable by header fields
+        let results = indexer.tantivy_index.search("from:bob", 10).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, format!("{}!/2.eml", root.join("archive.mbox").display()));
+        let results = indexer.tantivy_index.search("subject:hiring AND to:team", 10).unwrap();
+        assert_eq!(results[0].path, maildir_message.to_string_lossy());
+        assert_eq!(indexer.tantivy_index.search("\"offers sent\"", 10).unwrap().len(), 1);
+
+        // Attachments go through the extractors of their own type
+        let results = indexer.tantivy_index.search("headcount", 10).unwrap();
+        let mut paths: Vec<_> = results.into_iter().map(|r| r.path).collect();
+        paths.sort();
+        assert_eq!(
+            paths,
+            vec![
+                format!("{}!/minutes.txt", maildir_message.display()),
+                format!("{}!/1.eml!/minutes.txt", root.join("archive.mbox").display()),
+                format!("{}!/2.eml!/minutes.txt", root.join("archive.mbox").display()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,859 @@
+//! Multi-stage indexing pipeline with bounded queues
+//!
+//! ```text
+//! discovered files ─▶ [hash + extract] × N ─▶ [embed, batched] ─▶ writer
+//!                    rayon pool          std thread           async caller
+//! ```
+//!
+//! Every queue between stages is bounded by `queue_capacity`, so at most
+//! `workers + 2 * queue_capacity + embedding_batch_size` extracted documents
+//! are held in memory at any time, regardless of the size of the tree. The
+//! writer (SQLite, Tantivy, vector stores) stays on the caller's task so
+//! that all storage writes happen from a single place.
+//!
+//! Documents are split into overlapping chunks by the [`Chunker`] and every
+//! chunk is embedded, so that the whole text is searchable semantically.
+//!
+//! Archives are expanded by the extraction stage: each member is sent
+//! downstream as a file of its own as soon as it is read, under a virtual
+//! path (see [`archive`](super::archive)), and the archive itself follows
+//! its members with the list of them as content. Members are unpacked into
+//! a scratch directory one at a time to run the extractors on them, so an
+//! archive never holds more than one member in memory. Email messages
+//! are expanded the same way into their attachments, but keep their own
+//! extracted text as content; mbox mailboxes are archives of messages.
+
+use super::archive::{self, ArchiveMember, ArchiveReader, Member};
+use super::chunker::{Chunk, Chunker};
+use super::file_type;
+use super::metadata::{self, FileStat};
+use super::walker::DiscoveredFile;
+use crate::config::{ArchiveConfig, IndexingConfig};
+use crate::embedding::image::ImageEmbedding;
+use crate::embedding::EmbeddingModel;
+use crate::extractors::{read_image_metadata, ExtractedContent, ExtractorRegistry, ImageMetadata};
+use crate::storage::StoredFile;
+use crate::types::{Embedding, FileMetadata, FileType};
+use crate::Result;
+use rayon::prelude::*;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use tokio::sync::mpsc;
+
+/// How the pipeline decides whether an indexed file needs re-indexing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChangeDetection {
+    /// Trust unchanged size and modification time, otherwise compare hashes
+    Stat,
+    /// Always compare hashes
+    Hash,
+    /// Re-index every file
+    Always,
+}
+
+/// Embedding models moved into the embedding stage for the duration of a run
+#[derive(Default)]
+pub(crate) struct Models {
+    pub text: Option<EmbeddingModel>,
+    pub image: Option<ImageEmbedding>,
+}
+
+/// A file after hashing and extraction, ready to be written
+pub(crate) struct PreparedFile {
+    pub file: DiscoveredFile,
+    pub kind: PreparedKind,
+    /// Location of a file inside an archive
+    pub member: Option<ArchiveMember>,
+    /// Virtual paths of all members of an expanded archive on disk; other
+    /// files indexed below the archive are stale
+    pub member_paths: Option<HashSet<String>>,
+}
+
+impl PreparedFile {
+    fn new(file: DiscoveredFile, kind: PreparedKind) -> Self {
+        Self {
+            file,
+            kind,
+            member: None,
+            member_paths: None,
+        }
+    }
+}
+
+pub(crate) enum PreparedKind {
+    /// Content matches the index; carries refreshed metadata when only the
+    /// modification time changed, so the next run can skip hashing
+    Unchanged(Option<FileMetadata>),
+    /// Metadata could not be read
+    Skipped(String),
+    /// Image, with its EXIF and XMP properties, its CLIP embedding when a
+    /// model is loaded, and the text recognized in it with its embedded
+    /// chunks when an extractor handles images (an error if embedding them
+    /// failed)
+    Image {
+        metadata: FileMetadata,
+        is_new: bool,
+        properties: ImageMetadata,
+        text: Option<ExtractedContent>,
+        chunks: std::result::Result<Vec<(Chunk, Embedding)>, Failure>,
+        embedding: Option<std::result::Result<Embedding, Failure>>,
+    },
+    /// Text document, with its extracted content and the embedded chunks
+    /// of its text (none without a text model, an error if embedding them
+    /// failed)
+    Document {
+        metadata: FileMetadata,
+        is_new: bool,
+        content: std::result::Result<ExtractedContent, Failure>,
+        chunks: std::result::Result<Vec<(Chunk, Embedding)>, Failure>,
+    },
+}
+
+/// Why the content or embedding of a file is missing
+#[derive(Debug, Clone)]
+pub(crate) struct Failure {
+    pub reason: String,
+    /// The next run should try again, see [`crate::Error::is_transient`]
+    pub transient: bool,
+}
+
+impl From<crate::Error> for Failure {
+    fn from(e: crate::Error) -> Self {
+        Self {
+            transient: e.is_transient(),
+            reason: e.to_string(),
+        }
+    }
+}
+
+/// Running pipeline stages feeding the writer
+pub(crate) struct Pipeline {
+    pub output: mpsc::Receiver<PreparedFile>,
+    cancelled: Arc<AtomicBool>,
+    extract_stage: JoinHandle<()>,
+    embed_stage: JoinHandle<Models>,
+}
+
+impl Pipeline {
+    /// Start the hashing/extraction and embedding stages
+    ///
+    /// # Arguments
+    /// * `files` - Files to process
+    /// * `stored_files` - Files already in the index, by path
+    /// * `detection` - How to tell whether a stored file changed
+    /// * `models` - Embedding models, handed back by [`finish`](Self::finish)
+    /// * `extractors` - Extractors for the content of documents
+    /// * `config` - Worker count, queue capacity, batch size and chunking
+    pub fn start(
+        files: Vec<DiscoveredFile>,
+        stored_files: HashMap<String, StoredFile>,
+        detection: ChangeDetection,
+        models: Models,
+        extractors: Arc<ExtractorRegistry>,
+        config: &IndexingConfig,
+    ) -> Result<Self> {
+        let queue_capacity = config.queue_capacity.max(1);
+        let batch_size = config.embedding_batch_size.max(1);
+        let chunker = Chunker::new(config.chunk_words, config.chunk_overlap_words, config.max_chunks_per_file);
+        let archives = config.archives.clone();
+        let cancelled = Arc::new(AtomicBool::new(false));
+
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(config.workers.max(1))
+            .thread_name(|i| format!("khoj-extract-{}", i))
+            .build()
+            .map_err(|e| crate::Error::Other(e.into()))?;
+
+        let (extracted_tx, extracted_rx) = sync_channel(queue_capacity);
+        let (output_tx, output) = mpsc::channel(queue_capacity);
+
+        let extract_cancelled = cancelled.clone();
+        let extract_stage = std::thread::Builder::new()
+            .name("khoj-extract".to_string())
+            .spawn(move || {
+                pool.install(|| {
+                    files.into_par_iter().for_each_with(extracted_tx, |tx, file| {
+                        if extract_cancelled.load(Ordering::Relaxed) {
+                            return;
+                        }
+                        let send = |prepared| tx.send(prepared).is_ok();
+                        let prepared = prepare(file, &stored_files, detection, &extractors, &archives, &send);
+                        if !send(prepared) {
+                            extract_cancelled.store(true, Ordering::Relaxed);
+                        }
+                    });
+                });
+            })?;
+
+        let embed_stage = std::thread::Builder::new()
+            .name("khoj-embed".to_string())
+            .spawn(move || embed_stage(extracted_rx, output_tx, models, &chunker, batch_size))?;
+
+        Ok(Self {
+            output,
+            cancelled,
+            extract_stage,
+            embed_stage,
+        })
+    }
+
+    /// Stop the stages and hand back the embedding models
+    ///
+    /// Called once the writer has drained `output`, or early when the
+    /// writer failed; in that case in-flight files are discarded.
+    pub fn finish(self) -> Result<Models> {
+        self.cancelled.store(true, Ordering::Relaxed);
+        drop(self.output);
+
+        self.extract_stage
+            .join()
+            .map_err(|_| crate::Error::Other(anyhow::anyhow!("extraction stage panicked")))?;
+        self.embed_stage
+            .join()
+            .map_err(|_| crate::Error::Other(anyhow::anyhow!("embedding stage panicked")))
+    }
+}
+
+/// Hash a file, sniff its type and extract its content (stage 1)
+///
+/// With [`ChangeDetection::Stat`], files whose size and modification time
+/// match the index are not hashed at all. Changed archives are expanded
+/// into their members, which are handed to `send_member` one at a time
+/// before the archive itself is returned.
+fn prepare(
+    mut file: DiscoveredFile,
+    stored_files: &HashMap<String, StoredFile>,
+    detection: ChangeDetection,
+    extractors: &ExtractorRegistry,
+    archives: &ArchiveConfig,
+    send_member: &dyn Fn(PreparedFile) -> bool,
+) -> PreparedFile {
+    let stored = stored_files.get(file.path.to_string_lossy().as_ref());
+
+    // Written by a run that never reached its checkpoint, so other stores may
+    // lack it, or failed last time
+    let detection = match stored {
+        Some(stored) if stored.pending || stored.failed => ChangeDetection::Always,
+        _ => detection,
+    };
+
+    if let (Some(stored), ChangeDetection::Stat) = (stored, detection) {
+        if let Ok(stat) = metadata::stat_file(&file.path) {
+            if stat_matches(stored, &stat) {
+                return PreparedFile::new(file, PreparedKind::Unchanged(None));
+            }
+        }
+    }
+
+    // The walker only looked at the name
+    file.file_type = file_type::detect(&file.path);
+
+    let metadata = match metadata::extract_metadata(&file.path, file.file_type) {
+        Ok(m) => m,
+        Err(e) => {
+            return PreparedFile::new(file, PreparedKind::Skipped(e.to_string()))
+        }
+    };
+
+    if let Some(stored) = stored {
+        if stored.hash == metadata.hash && detection != ChangeDetection::Always {
+            let refreshed = (stored.size != metadata.size || stored.modified_at != metadata.modified_at)
+                .then_some(metadata);
+            return PreparedFile::new(file, PreparedKind::Unchanged(refreshed));
+        }
+    }
+    let is_new = stored.is_none();
+
+    if matches!(file.file_type, FileType::Archive | FileType::Email) && archives.max_depth > 0 {
+        return prepare_archive(file, metadata, is_new, stored_files, detection, extractors, archives, send_member);
+    }
+
+    let kind = if file.file_type == FileType::Image {
+        PreparedKind::Image {
+            metadata,
+            is_new,
+            properties: read_image_metadata(&file.path).unwrap_or_default(),
+            text: image_text(extractors, &file.path),
+            chunks: Ok(Vec::new()),
+            embedding: None,
+        }
+    } else {
+        let content = extractors.extract(&file.path, file.file_type).map_err(Failure::from);
+        PreparedKind::Document {
+            metadata,
+            is_new,
+            content,
+            chunks: Ok(Vec::new()),
+        }
+    };
+
+    PreparedFile::new(file, kind)
+}
+
+/// Expand a changed archive or email message on disk
+///
+/// Members are handed to `send_member` as they are read. The archive's
+/// content is the list of its members, the message's content its extracted
+/// text. If the file can't be read or exceeds the limits, the archive lists
+/// no members, so that the writer removes those already sent.
+#[allow(clippy::too_many_arguments)]
+fn prepare_archive(
+    file: DiscoveredFile,
+    metadata: FileMetadata,
+    is_new: bool,
+    stored_files: &HashMap<String, StoredFile>,
+    detection: ChangeDetection,
+    extractors: &ExtractorRegistry,
+    archives: &ArchiveConfig,
+    send_member: &dyn Fn(PreparedFile) -> bool,
+) -> PreparedFile {
+    let archive_path = file.path.to_string_lossy().to_string();
+    let mut expansion = Expansion {
+        archive_path: &archive_path,
+        modified_at: metadata.modified_at,
+        stored_files,
+        // Members of an archive that isn't indexed were left behind by an
+        // interrupted run, before they could be linked to it
+        detection: if is_new { ChangeDetection::Always } else { detection },
+        extractors,
+        max_depth: archives.max_depth,
+        scratch: PathBuf::new(),
+        unpacked: 0,
+        send_member,
+        member_paths: HashSet::new(),
+    };
+
+    let mut listing = String::new();
+    let expanded = tempfile::Builder::new()
+        .prefix("khoj-archive-")
+        .tempdir()
+        .map_err(crate::Error::from)
+        .and_then(|scratch| {
+            expansion.scratch = scratch.path().to_path_buf();
+            ArchiveReader::new(archives).read_file(&file.path, &mut |reader, member| {
+                expansion.visit(reader, member, &archive_path, 1, &mut listing)
+            })
+        });
+
+    let member_paths = match &expanded {
+        Ok(()) => expansion.member_paths,
+        Err(_) => HashSet::new(),
+    };
+    let content = if file.file_type == FileType::Email {
+        extractors.extract(&file.path, file.file_type).map_err(Failure::from)
+    } else {
+        expanded.map(|_| ExtractedContent::new(listing)).map_err(Failure::from)
+    };
+
+    PreparedFile {
+        member_paths: Some(member_paths),
+        ..PreparedFile::new(
+            file,
+            PreparedKind::Document {
+                metadata,
+                is_new,
+                content,
+                chunks: Ok(Vec::new()),
+            },
+        )
+    }
+}
+
+/// State of the expansion of an archive on disk into prepared members
+struct Expansion<'a> {
+    archive_path: &'a str,
+    /// Modification time of the archive, for members without their own
+    modified_at: i64,
+    stored_files: &'a HashMap<String, StoredFile>,
+    detection: ChangeDetection,
+    extractors: &'a ExtractorRegistry,
+    max_depth: usize,
+    /// Directory members are unpacked to for extraction
+    scratch: PathBuf,
+    /// Number of members unpacked so far
+    unpacked: usize,
+    /// Sends a prepared member downstream; false once the pipeline stopped
+    send_member: &'a dyn Fn(PreparedFile) -> bool,
+    /// Virtual paths of the members sent so far
+    member_paths: HashSet<String>,
+}
+
+impl Expansion<'_> {
+    /// Prepare a member of the archive at `parent`, expanding nested archives
+    ///
+    /// # Arguments
+    /// * `reader` - Reader of the archive, used for nested archives
+    /// * `member` - Member read from the archive
+    /// * `parent` - Virtual path of the archive containing the member
+    /// * `depth` - Nesting depth of the member, 1 in the archive on disk
+    /// * `listing` - Content of the archive on disk, one member per line
+    fn visit(
+        &mut self,
+        reader: &mut ArchiveReader<'_>,
+        member: Member,
+        parent: &str,
+        depth: usize,
+        listing: &mut String,
+    ) -> crate::Result<()> {
+        let path = archive::member_path(parent, &member.path);
+        let member_path = path[self.archive_path.len() + archive::MEMBER_SEPARATOR.len()..].to_string();
+        listing.push_str(&member_path);
+        listing.push('\n');
+
+        // Unpack into a directory of its own, keeping the file name for the extractors
+        let name = member.path.rsplit('/').next().unwrap_or_default();
+        self.unpacked += 1;
+        let dir = self.scratch.join(self.unpacked.to_string());
+        std::fs::create_dir_all(&dir)?;
+        let unpacked = dir.join(name);
+        std::fs::write(&unpacked, &member.data)?;
+        let file_type = file_type::detect(&unpacked);
+
+        let metadata = metadata::member_metadata(
+            &path,
+            &member.data,
+            file_type,
+            member.modified_at.unwrap_or(self.modified_at),
+        );
+        let stored = self.stored_files.get(&path);
+        let file = DiscoveredFile {
+            path: PathBuf::from(&path),
+            file_type,
+            size: metadata.size,
+        };
+        let info = ArchiveMember {
+            archive_path: self.archive_path.to_string(),
+            member_path,
+            size: metadata.size,
+            compressed_size: member.compressed_size,
+            depth,
+        };
+
+        let unchanged = stored.is_some_and(|stored| {
+            stored.hash == metadata.hash && !stored.pending && !stored.failed && self.detection != ChangeDetection::Always
+        });
+
+        let nested = matches!(file_type, FileType::Archive | FileType::Email) && depth < self.max_depth;
+        let kind = if nested {
+            // Nested archives and messages are always expanded, so that the member list is complete
+            let mut nested = String::new();
+            let expanded = reader.read_bytes(name, &member.data, &mut |reader, inner| {
+                self.visit(reader, inner, &path, depth + 1, &mut nested)
+            });
+            listing.push_str(&nested);
+            if file_type == FileType::Archive {
+                PreparedKind::Document {
+                    metadata,
+                    is_new: stored.is_none(),
+                    content: expanded.map(|_| ExtractedContent::new(nested)).map_err(Failure::from),
+                    chunks: Ok(Vec::new()),
+                }
+            } else if unchanged {
+                PreparedKind::Unchanged(None)
+            } else {
+                if let Err(e) = expanded {
+                    tracing::debug!("Attachments of {} not indexed: {}", path, e);
+                }
+                PreparedKind::Document {
+                    metadata,
+                    is_new: stored.is_none(),
+                    content: self.extractors.extract(&unpacked, file_type).map_err(Failure::from),
+                    chunks: Ok(Vec::new()),
+                }
+            }
+        } else if unchanged {
+            PreparedKind::Unchanged(None)
+        } else if file_type == FileType::Image {
+            PreparedKind::Image {
+                metadata,
+                is_new: stored.is_none(),
+                properties: read_image_metadata(&unpacked).unwrap_or_default(),
+                text: image_text(self.extractors, &unpacked),
+                chunks: Ok(Vec::new()),
+                embedding: None,
+            }
+        } else {
+            PreparedKind::Document {
+                metadata,
+                is_new: stored.is_none(),
+                content: self.extractors.extract(&unpacked, file_type).map_err(Failure::from),
+                chunks: Ok(Vec::new()),
+            }
+        };
+
+        let _ = std::fs::remove_dir_all(&dir);
+        self.member_paths.insert(path);
+        let sent = (self.send_member)(PreparedFile {
+            member: Some(info),
+            ..PreparedFile::new(file, kind)
+        });
+        if !sent {
+            return Err(crate::Error::Other(anyhow::anyhow!("indexing stopped")));
+        }
+        Ok(())
+    }
+}
+
+/// Text of an image, from the extractor handling images if any
+///
+/// Images are indexed without text when it finds none or fails.
+fn image_text(extractors: &ExtractorRegistry, path: &Path) -> Option<ExtractedContent> {
+    let extractor = extractors.find(path, FileType::Image)?;
+    match extractor.extract(path, FileType::Image) {
+        Ok(content) => (content.word_count > 0).then_some(content),
+        Err(e) => {
+            tracing::debug!("No text read from image {}: {}", path.display(), e);
+            None
+        }
+    }
+}
+
+/// Check whether a file can be assumed unchanged without hashing it
+///
+/// A file modified in the same second it was indexed is always hashed,
+/// since a second write within that second would leave its modification
+/// time unchanged.
+fn stat_matches(stored: &StoredFile, stat: &FileStat) -> bool {
+    stored.size == stat.size
+        && stored.modified_at == stat.modified_at
+        && stored.modified_at < stored.indexed_at
+}
+
+/// Generate embeddings in batches and forward files to the writer (stage 2)
+fn embed_stage(
+    input: Receiver<PreparedFile>,
+    output: mpsc::Sender<PreparedFile>,
+    mut models: Models,
+    chunker: &Chunker,
+    batch_size: usize,
+) -> Models {
+    let mut batch = Vec::with_capacity(batch_size);
+
+    for prepared in input {
+        batch.push(prepared);
+        if batch.len() >= batch_size && flush_batch(&mut models, chunker, batch_size, &mut batch, &output).is_err() {
+            return models;
+        }
+    }
+
+    let _ = flush_batch(&mut models, chunker, batch_size, &mut batch, &output);
+    models
+}
+
+/// Embed every document and image of a batch, then send it downstream
+///
+/// The chunks of all documents in the batch are embedded `batch_size` at a
+/// time. Fails only when the writer has gone away.
+fn flush_batch(
+    models: &mut Models,
+    chunker: &Chunker,
+    batch_size: usize,
+    batch: &mut Vec<PreparedFile>,
+    output: &mpsc::Sender<PreparedFile>,
+) -> std::result::Result<(), ()> {
+    if let Some(ref mut model) = models.text {
+        let chunks: Vec<(usize, Chunk)> = batch
+            .iter()
+            .enumerate()
+            .filter_map(|(i, prepared)| Some((i, document_text(prepared)?)))
+            .flat_map(|(i, text)| chunker.chunk(text).into_iter().map(move |chunk| (i, chunk)))
+            .collect();
+
+        let mut embedded = Vec::with_capacity(chunks.len());
+        for group in chunks.chunks(batch_size) {
+            let texts: Vec<&str> = group
+                .iter()
+                .map(|(i, chunk)| chunk.text(document_text(&batch[*i]).unwrap_or_default()))
+                .collect();
+
+            match model.embed_batch(&texts) {
+                Ok(embeddings) => embedded
+                    .extend(group.iter().zip(embeddings).map(|(&(i, chunk), embedding)| (i, chunk, embedding))),
+                // The files stay keyword-searchable, and the writer marks
+                // them to be embedded again by the next run
+                Err(e) => {
+                    let failure = Failure::from(e);
+                    for &(i, _) in group {
+                        if let PreparedKind::Document { chunks, .. } | PreparedKind::Image { chunks, .. } =
+                            &mut batch[i].kind
+                        {
+                            *chunks = Err(failure.clone());
+                        }
+                    }
+                }
+            }
+        }
+
+        for (i, mut chunk, embedding) in embedded {
+            if let PreparedKind::Document { chunks: Ok(chunks), .. } | PreparedKind::Image { chunks: Ok(chunks), .. } =
+                &mut batch[i].kind
+            {
+                chunk.index = chunks.len();
+                chunks.push((chunk, embedding));
+            }
+        }
+    }
+
+    if let Some(ref mut model) = models.image {
+        for prepared in batch.iter_mut() {
+            // Members of archives have no file to read the image from
+            if prepared.member.is_some() {
+                continue;
+            }
+            if let PreparedKind::Image { embedding, .. } = &mut prepared.kind {
+                *embedding = Some(model.embed_image(&prepared.file.path).map_err(Failure::from));
+            }
+        }
+    }
+
+    for prepared in batch.drain(..) {
+        output.blocking_send(prepared).map_err(|_| ())?;
+    }
+    Ok(())
+}
+
+/// Extracted text of a document, or recognized text of an image
+fn document_text(prepared: &PreparedFile) -> Option<&str> {
+    match &prepared.kind {
+        PreparedKind::Document { content: Ok(content), .. } => Some(&content.text),
+        PreparedKind::Image { text: Some(text), .. } => Some(&text.text),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn discovered(path: std::path::PathBuf, file_type: FileType) -> DiscoveredFile {
+        let size = fs::metadata(&path).unwrap().len();
+        DiscoveredFile { path, file_type, size }
+    }
+
+    fn prepare_with_builtins(
+        file: DiscoveredFile,
+        stored_files: &HashMap<String, StoredFile>,
+        detection: ChangeDetection,
+    ) -> PreparedFile {
+        prepare(file, stored_files, detection, ExtractorRegistry::builtin(), &ArchiveConfig::default(), &|_| true)
+    }
+
+    fn stored(path: &std::path::Path, hash: &str, size: u64, modified_at: i64, indexed_at: i64) -> HashMap<String, StoredFile> {
+        let path = path.to_string_lossy().to_string();
+        HashMap::from([(
+            path.clone(),
+            StoredFile {
+                id: 1,
+                path,
+                hash: hash.to_string(),
+                size,
+                modified_at,
+                indexed_at,
+                pending: false,
+                failed: false,
+            },
+        )])
+    }
+
+    #[test]
+    fn test_prepare_detects_unchanged() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        fs::write(&path, "hello").unwrap();
+
+        let first = prepare_with_builtins(discovered(path.clone(), FileType::Text), &HashMap::new(), ChangeDetection::Stat);
+        let metadata = match first.kind {
+            PreparedKind::Document { metadata, is_new, content, .. } => {
+                assert!(is_new);
+                assert_eq!(content.unwrap().text, "hello");
+                metadata
+            }
+            _ => panic!("expected a document"),
+        };
+
+        let stored_files = stored(&path, &metadata.hash, metadata.size, metadata.modified_at, metadata.indexed_at);
+        let second = prepare_with_builtins(discovered(path, FileType::Text), &stored_files, ChangeDetection::Stat);
+        assert!(matches!(second.kind, PreparedKind::Unchanged(None)));
+    }
+
+    #[test]
+    fn test_prepare_skips_hashing_when_stat_matches() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        fs::write(&path, "hello").unwrap();
+        let stat = metadata::stat_file(&path).unwrap();
+
+        // A bogus stored hash is only noticed when the file is hashed
+        let stored_files = stored(&path, "stale", stat.size, stat.modified_at, stat.modified_at + 1);
+        let fast = prepare_with_builtins(discovered(path.clone(), FileType::Text), &stored_files, ChangeDetection::Stat);
+        assert!(matches!(fast.kind, PreparedKind::Unchanged(None)));
+
+        let paranoid = prepare_with_builtins(discovered(path.clone(), FileType::Text), &stored_files, ChangeDetection::Hash);
+        assert!(matches!(paranoid.kind, PreparedKind::Document { is_new: false, .. }));
+
+        let forced = prepare_with_builtins(discovered(path.clone(), FileType::Text), &stored_files, ChangeDetection::Always);
+        assert!(matches!(forced.kind, PreparedKind::Document { is_new: false, .. }));
+
+        // Rows left pending by an interrupted run are always re-indexed
+        let mut pending = stored_files.clone();
+        pending.values_mut().for_each(|stored| stored.pending = true);
+        let resumed = prepare_with_builtins(discovered(path.clone(), FileType::Text), &pending, ChangeDetection::Stat);
+        assert!(matches!(resumed.kind, PreparedKind::Document { is_new: false, .. }));
+
+        // So are files whose extraction failed last time
+        let mut failed = stored_files.clone();
+        failed.values_mut().for_each(|stored| stored.failed = true);
+        let retried = prepare_with_builtins(discovered(path.clone(), FileType::Text), &failed, ChangeDetection::Stat);
+        assert!(matches!(retried.kind, PreparedKind::Document { is_new: false, .. }));
+
+        // Indexed in the same second as the last write: the stat can't be trusted
+        let racy = stored(&path, "stale", stat.size, stat.modified_at, stat.modified_at);
+        let racy = prepare_with_builtins(discovered(path, FileType::Text), &racy, ChangeDetection::Stat);
+        assert!(matches!(racy.kind, PreparedKind::Document { is_new: false, .. }));
+    }
+
+    #[test]
+    fn test_prepare_refreshes_touched_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("a.txt");
+        fs::write(&path, "hello").unwrap();
+        let hash = metadata::compute_file_hash(&path).unwrap();
+        let stat = metadata::stat_file(&path).unwrap();
+
+        let stored_files = stored(&path, &hash, stat.size, stat.modified_at - 60, stat.modified_at);
+        let prepared = prepare_with_builtins(discovered(path, FileType::Text), &stored_files, ChangeDetection::Stat);
+        match prepared.kind {
+            PreparedKind::Unchanged(Some(metadata)) => assert_eq!(metadata.modified_at, stat.modified_at),
+            _ => panic!("expected refreshed metadata"),
+        }
+    }
+
+    #[test]
+    fn test_prepare_sniffs_content() {
+        let temp_dir = TempDir::new().unwrap();
+        let script = temp_dir.path().join("deploy");
+        let blob = temp_dir.path().join("blob.txt");
+        fs::write(&script, "#!/bin/sh\necho deploying\n").unwrap();
+        fs::write(&blob, [0x7F, 0x00, 0x13, 0x37, 0x00]).unwrap();
+
+        let prepared = prepare_with_builtins(discovered(script, FileType::Unknown), &HashMap::new(), ChangeDetection::Stat);
+        assert_eq!(prepared.file.file_type, FileType::Code);
+        match prepared.kind {
+            PreparedKind::Document { metadata, content, .. } => {
+                assert_eq!(metadata.file_type, FileType::Code);
+                assert_eq!(content.unwrap().language.as_deref(), Some("shell"));
+            }
+            _ => panic!("expected a document"),
+        }
+
+        // Binary content is never read as text
+        let prepared = prepare_with_builtins(discovered(blob, FileType::Text), &HashMap::new(), ChangeDetection::Stat);
+        assert_eq!(prepared.file.file_type, FileType::Binary);
+        assert!(matches!(prepared.kind, PreparedKind::Document { content: Err(_), .. }));
+    }
+
+    #[test]
+    fn test_prepare_streams_archive_members() {
+        use std::cell::RefCell;
+        use std::io::Write;
+
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("backup.zip");
+        let mut zip = zip::ZipWriter::new(fs::File::create(&path).unwrap());
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            zip.start_file(name, zip::write::SimpleFileOptions::default()).unwrap();
+            zip.write_all(name.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+
+        // Members are sent one by one, before the archive is returned
+        let sent = RefCell::new(Vec::new());
+        let send = |member: PreparedFile| {
+            sent.borrow_mut().push(member.file.path.to_string_lossy().to_string());
+            true
+        };
+        let prepared = prepare(
+            discovered(path.clone(), FileType::Unknown),
+            &HashMap::new(),
+            ChangeDetection::Stat,
+            ExtractorRegistry::builtin(),
+            &ArchiveConfig::default(),
+            &send,
+        );
+        let sent = sent.into_inner();
+        assert_eq!(sent.len(), 3);
+        assert!(sent[0].ends_with("backup.zip!/a.txt"));
+        assert_eq!(prepared.member_paths.unwrap(), sent.into_iter().collect::<HashSet<_>>());
+
+        // Once the pipeline stops, the archive stops being read and lists no members
+        let stopped = prepare(
+            discovered(path, FileType::Unknown),
+            &HashMap::new(),
+            ChangeDetection::Stat,
+            ExtractorRegistry::builtin(),
+            &ArchiveConfig::default(),
+            &|_| false,
+        );
+        assert!(stopped.member_paths.unwrap().is_empty());
+        assert!(matches!(stopped.kind, PreparedKind::Document { content: Err(_), .. }));
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_pipeline_processes_every_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let files: Vec<DiscoveredFile> = (0..50)
+            .map(|i| {
+                let path = temp_dir.path().join(format!("file{}.txt", i));
+                fs::write(&path, format!("content {}", i)).unwrap();
+                discovered(path, FileType::Text)
+            })
+            .collect();
+
+        let config = IndexingConfig {
+            workers: 4,
+            queue_capacity: 2,
+            embedding_batch_size: 3,
+            ..Default::default()
+        };
+        let mut pipeline = Pipeline::start(files, HashMap::new(), ChangeDetection::Stat, Models::default(), Arc::default(), &config).unwrap();
+
+        let mut received = 0;
+        while let Some(prepared) = pipeline.output.recv().await {
+            assert!(matches!(prepared.kind, PreparedKind::Document { content: Ok(_), .. }));
+            received += 1;
+        }
+        assert_eq!(received, 50);
+
+        let models = pipeline.finish().unwrap();
+        assert!(models.text.is_none());
+    }
+
+    #[test]
+    fn test_finish_before_draining() {
+        let temp_dir = TempDir::new().unwrap();
+        let files: Vec<DiscoveredFile> = (0..20)
+            .map(|i| {
+                let path = temp_dir.path().join(format!("file{}.txt", i));
+                fs::write(&path, "x").unwrap();
+                discovered(path, FileType::Text)
+            })
+            .collect();
+
+        let config = IndexingConfig {
+            workers: 2,
+            queue_capacity: 1,
+            embedding_batch_size: 1,
+            ..Default::default()
+        };
+        let pipeline = Pipeline::start(files, HashMap::new(), ChangeDetection::Stat, Models::default(), Arc::default(), &config).unwrap();
+
+        // Stages must shut down even though nobody reads the output
+        assert!(pipeline.finish().is_ok());
+    }
+}
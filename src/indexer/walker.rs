@@ -1,9 +1,11 @@
 //! File system walker for traversing directories
 
+use super::file_type;
 use crate::config::PrivacyConfig;
 use crate::types::FileType;
 use crate::Result;
-use ignore::WalkBuilder;
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::{Match, WalkBuilder};
 use std::path::{Path, PathBuf};
 
 /// Represents a discovered file during traversal
@@ -14,7 +16,12 @@ pub struct DiscoveredFile {
     pub size: u64,
 }
 
-/// File system walker that respects .gitignore and privacy settings
+/// File system walker that respects ignore files and privacy settings
+///
+/// Exclusion patterns use gitignore syntax relative to the indexed root:
+/// `**/target` excludes every `target` directory, `/build` only the one at
+/// the top, `docs/**/*.tmp` temporary files anywhere below `docs`, and a
+/// later `!keep.log` re-includes a file excluded by `*.log`.
 pub struct FileWalker {
     privacy_config: PrivacyConfig,
 }
@@ -34,20 +41,47 @@ impl FileWalker {
     /// Iterator over discovered files
     pub fn walk<P: AsRef<Path>>(&self, root_path: P) -> Result<Vec<DiscoveredFile>> {
         let root_path = root_path.as_ref();
+        self.walk_dir(root_path, root_path)
+    }
 
-        if !root_path.exists() {
+    /// Walk a directory below `root` and return all discovered files
+    ///
+    /// Exclusion patterns stay anchored at `root`, so rescanning part of a
+    /// root finds the same files as walking the whole root.
+    ///
+    /// # Arguments
+    /// * `root` - Root the exclusion patterns are relative to
+    /// * `dir` - Directory to start walking from, `root` or one below it
+    ///
+    /// # Returns
+    /// Iterator over discovered files
+    pub fn walk_dir(&self, root: &Path, dir: &Path) -> Result<Vec<DiscoveredFile>> {
+        if !dir.exists() {
             return Err(crate::Error::FileNotFound(
-                root_path.display().to_string(),
+                dir.display().to_string(),
             ));
         }
 
-        let mut builder = WalkBuilder::new(root_path);
-
-        // Respect .gitignore and .searchignore files
-        if self.privacy_config.respect_ignore_files.contains(&".gitignore".to_string()) {
-            builder.git_ignore(true);
+        let mut builder = WalkBuilder::new(dir);
+
+        // Respect .gitignore, .searchignore and other configured ignore files
+        let git_ignore = self.respects_gitignore();
+        builder
+            .git_ignore(git_ignore)
+            .git_exclude(git_ignore)
+            .git_global(git_ignore)
+            .ignore(false);
+        for name in self.custom_ignore_filenames() {
+            builder.add_custom_ignore_filename(name);
         }
 
+        // Prune excluded directories instead of descending into them
+        let excludes = self.exclude_matcher(root);
+        builder.filter_entry(move |entry| {
+            let is_dir = entry.file_type().is_some_and(|t| t.is_dir());
+            entry.depth() == 0 || !excludes.matched(entry.path(), is_dir).is_ignore()
+        });
+
         let mut files = Vec::new();
 
         for result in builder.build() {
@@ -72,85 +106,187 @@ impl FileWalker {
                 continue;
             }
 
-            // Check exclusion patterns
-            let should_exclude = self.privacy_config.exclude_patterns.iter().any(|pattern| {
-                self.matches_pattern(path, pattern)
-            });
-
-            if should_exclude {
-                tracing::debug!("Skipping excluded file: {}", path.display());
-                continue;
+            if let Some(file) = self.classify(path)? {
+                files.push(file);
             }
+        }
 
-            // Get file metadata
-            let metadata = match std::fs::metadata(path) {
-                Ok(m) => m,
-                Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
-                    tracing::debug!("Skipping file (permission denied): {}", path.display());
-                    continue;
-                }
-                Err(e) => return Err(e.into()),
-            };
-            let size = metadata.len();
+        Ok(files)
+    }
 
-            // Skip files that are too large
-            if size > self.privacy_config.max_file_size {
-                tracing::debug!("Skipping large file: {} ({} bytes)", path.display(), size);
-                continue;
+    /// Classify a single file below `root` the way [`walk`](Self::walk) does
+    ///
+    /// Applies the exclusion patterns and the size limit, but not ignore
+    /// files or hidden-file rules; see
+    /// [`is_excluded`](Self::is_excluded) for those.
+    ///
+    /// # Returns
+    /// The discovered file, or `None` if it should not be indexed
+    pub fn discover(&self, root: &Path, path: &Path) -> Result<Option<DiscoveredFile>> {
+        if self.matches_excludes(root, path) {
+            tracing::debug!("Skipping excluded file: {}", path.display());
+            return Ok(None);
+        }
+
+        self.classify(path)
+    }
+
+    /// Check whether a path below `root` is skipped while walking `root`
+    ///
+    /// A path is excluded when it or one of its parents below `root` is
+    /// hidden, matches an exclusion pattern, or is ignored by an ignore
+    /// file. Configured ignore files such as `.searchignore` apply anywhere
+    /// and take precedence over `.gitignore`, which only counts inside a git
+    /// repository. Used to filter single paths reported by the file watcher
+    /// without walking the whole root.
+    pub fn is_excluded(&self, root: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let hidden = relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+        if hidden {
+            return true;
+        }
+
+        if self.matches_excludes(root, path) {
+            return true;
+        }
+
+        match self.is_custom_ignored(path) {
+            Some(ignored) => ignored,
+            None => self.respects_gitignore() && self.is_git_ignored(path),
+        }
+    }
+
+    /// Apply the size limit to a file
+    fn classify(&self, path: &Path) -> Result<Option<DiscoveredFile>> {
+        // Get file metadata
+        let metadata = match std::fs::metadata(path) {
+            Ok(m) => m,
+            Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                tracing::debug!("Skipping file (permission denied): {}", path.display());
+                return Ok(None);
             }
+            Err(e) => return Err(e.into()),
+        };
+        let size = metadata.len();
+
+        // Skip files that are too large
+        if size > self.privacy_config.max_file_size {
+            tracing::debug!("Skipping large file: {} ({} bytes)", path.display(), size);
+            return Ok(None);
+        }
 
-            // Determine file type
-            let file_type = self.detect_file_type(path);
+        // Include all types, even Unknown: we'll at least store metadata
+        // even if we can't extract text, and archives are expanded later
+        let file_type = self.detect_file_type(path);
 
-            // Skip archives, but include all other types (even Unknown)
-            // We'll at least store metadata even if we can't extract text
-            if matches!(file_type, FileType::Archive) {
-                continue;
+        Ok(Some(DiscoveredFile {
+            path: path.to_path_buf(),
+            file_type,
+            size,
+        }))
+    }
+
+    /// Compile the exclusion patterns, anchored at `root`
+    fn exclude_matcher(&self, root: &Path) -> Gitignore {
+        let mut builder = GitignoreBuilder::new(root);
+        for pattern in &self.privacy_config.exclude_patterns {
+            if let Err(e) = builder.add_line(None, pattern) {
+                tracing::warn!("Ignoring invalid exclude pattern {:?}: {}", pattern, e);
             }
+        }
+
+        builder.build().unwrap_or_else(|e| {
+            tracing::warn!("Ignoring exclude patterns: {}", e);
+            Gitignore::empty()
+        })
+    }
 
-            files.push(DiscoveredFile {
-                path: path.to_path_buf(),
-                file_type,
-                size,
-            });
+    /// Check a path and its parents below `root` against the exclusion patterns
+    fn matches_excludes(&self, root: &Path, path: &Path) -> bool {
+        if path == root || !path.starts_with(root) {
+            return false;
         }
 
-        Ok(files)
+        self.exclude_matcher(root)
+            .matched_path_or_any_parents(path, path.is_dir())
+            .is_ignore()
     }
 
-    /// Check if a path matches an exclusion pattern
-    fn matches_pattern(&self, path: &Path, pattern: &str) -> bool {
-        // Simple glob-like pattern matching
-        let path_str = path.to_string_lossy();
-
-        if pattern.starts_with("**/") {
-            let suffix = &pattern[3..];
-            if suffix.starts_with("*") {
-                // Pattern like "**/*.key" - match file extension
-                let ext_pattern = &suffix[2..]; // Skip "*."
-                path_str.ends_with(ext_pattern)
-            } else {
-                // Pattern like "**/.git" - match anywhere in path
-                path_str.contains(suffix)
+    fn respects_gitignore(&self) -> bool {
+        self.privacy_config.respect_ignore_files.iter().any(|name| name == ".gitignore")
+    }
+
+    /// Configured ignore files other than `.gitignore`, by increasing precedence
+    fn custom_ignore_filenames(&self) -> impl DoubleEndedIterator<Item = &String> {
+        self.privacy_config
+            .respect_ignore_files
+            .iter()
+            .filter(|name| name.as_str() != ".gitignore")
+    }
+
+    /// Check the configured ignore files of all parent directories
+    ///
+    /// # Returns
+    /// Whether the closest matching rule ignores the path, or `None` if no
+    /// rule matches
+    fn is_custom_ignored(&self, path: &Path) -> Option<bool> {
+        let is_dir = path.is_dir();
+
+        for dir in path.ancestors().skip(1) {
+            for name in self.custom_ignore_filenames().rev() {
+                let ignore_path = dir.join(name);
+                if !ignore_path.is_file() {
+                    continue;
+                }
+                let (ignore, _) = Gitignore::new(&ignore_path);
+                match ignore.matched_path_or_any_parents(path, is_dir) {
+                    Match::Ignore(_) => return Some(true),
+                    Match::Whitelist(_) => return Some(false),
+                    Match::None => {}
+                }
             }
-        } else if pattern.starts_with("**") {
-            // Match at end
-            let suffix = &pattern[2..];
-            path_str.ends_with(suffix)
-        } else {
-            // Exact match
-            path_str.contains(pattern)
         }
+
+        None
+    }
+
+    /// Check `.gitignore` files from the enclosing git repository
+    ///
+    /// Like git, the closest `.gitignore` with a matching rule wins and
+    /// ignore files outside a repository are not consulted.
+    fn is_git_ignored(&self, path: &Path) -> bool {
+        let is_dir = path.is_dir();
+        let mut ignored = None;
+
+        for dir in path.ancestors().skip(1) {
+            if ignored.is_none() {
+                let gitignore_path = dir.join(".gitignore");
+                if gitignore_path.is_file() {
+                    let (gitignore, _) = Gitignore::new(&gitignore_path);
+                    match gitignore.matched_path_or_any_parents(path, is_dir) {
+                        Match::Ignore(_) => ignored = Some(true),
+                        Match::Whitelist(_) => ignored = Some(false),
+                        Match::None => {}
+                    }
+                }
+            }
+
+            if dir.join(".git").exists() {
+                return ignored == Some(true);
+            }
+        }
+
+        false
     }
 
     /// Detect file type from path
+    ///
+    /// Only looks at the name; the content is sniffed later by the
+    /// indexing pipeline, see [`file_type::detect`].
     fn detect_file_type(&self, path: &Path) -> FileType {
-        if let Some(ext) = path.extension() {
-            let ext_str = ext.to_string_lossy();
-            FileType::from_extension(&ext_str)
-        } else {
-            FileType::Unknown
-        }
+        file_type::from_path(path)
     }
 }
 
@@ -258,15 +394,120 @@ mod tests {
     }
 
     #[test]
-    fn test_matches_pattern() {
+    fn test_discover_single_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut config = create_test_config();
+        config.max_file_size = 10;
+        let walker = FileWalker::new(config);
+
+        let small = temp_dir.path().join("small.txt");
+        let large = temp_dir.path().join("large.txt");
+        let archive = temp_dir.path().join("backup.zip");
+        fs::write(&small, "hello").unwrap();
+        fs::write(&large, "more than ten bytes").unwrap();
+        fs::write(&archive, "PK").unwrap();
+
+        let root = temp_dir.path();
+        let file = walker.discover(root, &small).unwrap().unwrap();
+        assert_eq!(file.file_type, FileType::Text);
+        assert_eq!(file.size, 5);
+        assert!(walker.discover(root, &large).unwrap().is_none());
+        assert_eq!(walker.discover(root, &archive).unwrap().unwrap().file_type, FileType::Archive);
+        assert!(walker.discover(root, &root.join("secret.key")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_is_excluded() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
         let walker = FileWalker::new(create_test_config());
 
-        // Test wildcard patterns
-        assert!(walker.matches_pattern(Path::new("/path/to/.git/file"), "**/.git"));
-        assert!(walker.matches_pattern(Path::new("/path/node_modules/pkg"), "**/node_modules"));
-        assert!(walker.matches_pattern(Path::new("/path/secret.key"), "**/*.key"));
+        fs::create_dir(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "build/\n*.log\n!keep.log\n").unwrap();
+
+        assert!(!walker.is_excluded(root, &root.join("notes.txt")));
+        assert!(walker.is_excluded(root, &root.join(".hidden/notes.txt")));
+        assert!(walker.is_excluded(root, &root.join("node_modules/pkg/index.js")));
+        assert!(walker.is_excluded(root, &root.join("debug.log")));
+        assert!(!walker.is_excluded(root, &root.join("keep.log")));
+        assert!(walker.is_excluded(root, &root.join("build/out.txt")));
+    }
+
+    #[test]
+    fn test_exclude_patterns() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let walker = FileWalker::new(PrivacyConfig {
+            exclude_patterns: vec![
+                "**/target".to_string(),
+                "/build".to_string(),
+                "docs/**/*.tmp".to_string(),
+                "*.log".to_string(),
+                "!keep.log".to_string(),
+            ],
+            ..create_test_config()
+        });
+
+        for file in [
+            "targets.txt",
+            "target/debug/app",
+            "crate/target/out.txt",
+            "build/out.txt",
+            "src/build/mod.rs",
+            "docs/a/b/draft.tmp",
+            "draft.tmp",
+            "debug.log",
+            "keep.log",
+        ] {
+            let path = root.join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, "x").unwrap();
+        }
+
+        let mut found: Vec<String> = walker
+            .walk(root)
+            .unwrap()
+            .iter()
+            .map(|f| f.path.strip_prefix(root).unwrap().to_string_lossy().replace('\\', "/"))
+            .collect();
+        found.sort();
+        assert_eq!(found, ["draft.tmp", "keep.log", "src/build/mod.rs", "targets.txt"]);
+
+        // Rescanning part of the root applies the patterns the same way
+        assert!(walker.walk_dir(root, &root.join("docs")).unwrap().is_empty());
+        let src = walker.walk_dir(root, &root.join("src")).unwrap();
+        assert_eq!(src.len(), 1);
+        assert!(src[0].path.ends_with("src/build/mod.rs"));
+
+        assert!(walker.is_excluded(root, &root.join("crate/target/out.txt")));
+        assert!(!walker.is_excluded(root, &root.join("src/build/mod.rs")));
+        assert!(walker.discover(root, &root.join("docs/a/b/draft.tmp")).unwrap().is_none());
+        assert!(walker.discover(root, &root.join("keep.log")).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_custom_ignore_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let walker = FileWalker::new(PrivacyConfig {
+            respect_ignore_files: vec![".gitignore".to_string(), ".searchignore".to_string()],
+            ..create_test_config()
+        });
+
+        // Honored without a git repository, unlike .gitignore
+        fs::write(root.join(".searchignore"), "private/\n*.bak\n").unwrap();
+        fs::write(root.join(".gitignore"), "*.txt\n").unwrap();
+        fs::create_dir(root.join("private")).unwrap();
+        fs::write(root.join("private/diary.md"), "x").unwrap();
+        fs::write(root.join("notes.bak"), "x").unwrap();
+        fs::write(root.join("notes.txt"), "x").unwrap();
+
+        let files = walker.walk(root).unwrap();
+        assert_eq!(files.len(), 1);
+        assert!(files[0].path.ends_with("notes.txt"));
 
-        // Test non-matches
-        assert!(!walker.matches_pattern(Path::new("/path/to/file.txt"), "**/.git"));
+        assert!(walker.is_excluded(root, &root.join("private/diary.md")));
+        assert!(walker.is_excluded(root, &root.join("notes.bak")));
+        assert!(!walker.is_excluded(root, &root.join("notes.txt")));
     }
 }
\ No newline at end of file
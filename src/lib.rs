@@ -6,7 +6,7 @@
 //! # Example
 //!
 //! ```no_run
-//! use file_search::{
+//! use khoj::{
 //!     storage::{TantivyIndex, VectorStore},
 //!     search::HybridSearch,
 //! };
@@ -50,11 +50,25 @@ pub mod types {
         Text,
         Code,
         Markdown,
+        Html,
+        Xml,
         Pdf,
         Docx,
         Xlsx,
+        /// PowerPoint slide deck
+        Pptx,
+        /// OpenDocument text
+        Odt,
+        /// OpenDocument presentation
+        Odp,
+        Rtf,
+        Epub,
+        /// Email message; mbox mailboxes are archives of messages
+        Email,
         Image,
         Archive,
+        /// Binary content without extractable text
+        Binary,
         Unknown,
     }
 
@@ -62,14 +76,62 @@ pub mod types {
         /// Get file type from extension
         pub fn from_extension(ext: &str) -> Self {
             match ext.to_lowercase().as_str() {
-                "txt" | "text" => FileType::Text,
-                "md" | "markdown" => FileType::Markdown,
-                "rs" | "py" | "js" | "ts" | "java" | "c" | "cpp" | "go" | "rb" => FileType::Code,
+                "txt" | "text" | "log" | "rst" | "adoc" | "asciidoc" | "org" | "tex" | "bib" | "csv"
+                | "tsv" | "json" | "jsonl" | "ndjson" | "yaml" | "yml" | "toml" | "ini" | "cfg"
+                | "conf" | "properties" | "env" | "srt" | "vtt" | "diff" | "patch" => FileType::Text,
+                "md" | "markdown" | "mdown" | "mkd" | "mdx" => FileType::Markdown,
+                "html" | "htm" | "xhtml" | "shtml" => FileType::Html,
+                "xml" | "svg" | "xsd" | "xsl" | "xslt" | "rss" | "atom" | "opml" => FileType::Xml,
+                "rs" | "py" | "pyw" | "pyi" | "js" | "mjs" | "cjs" | "jsx" | "ts" | "mts" | "cts"
+                | "tsx" | "java" | "kt" | "kts" | "scala" | "groovy" | "gradle" | "clj" | "cljs"
+                | "c" | "h" | "cc" | "cpp" | "cxx" | "hpp" | "hh" | "hxx" | "m" | "mm" | "cs"
+                | "fs" | "fsx" | "vb" | "go" | "rb" | "php" | "pl" | "pm" | "lua" | "r" | "jl"
+                | "swift" | "dart" | "zig" | "nim" | "hs" | "ml" | "mli" | "ex" | "exs" | "erl"
+                | "elm" | "sh" | "bash" | "zsh" | "fish" | "ps1" | "bat" | "cmd" | "sql" | "css"
+                | "scss" | "sass" | "less" | "vue" | "svelte" | "proto" | "graphql" | "tf" | "nix"
+                | "cmake" | "mk" | "asm" | "s" => FileType::Code,
                 "pdf" => FileType::Pdf,
-                "docx" | "doc" => FileType::Docx,
-                "xlsx" | "xls" => FileType::Xlsx,
+                "docx" | "docm" => FileType::Docx,
+                "xlsx" | "xlsm" | "xlsb" | "xls" | "ods" => FileType::Xlsx,
+                "pptx" | "pptm" => FileType::Pptx,
+                "odt" => FileType::Odt,
+                "odp" => FileType::Odp,
+                "rtf" => FileType::Rtf,
+                "epub" => FileType::Epub,
+                "eml" => FileType::Email,
                 "jpg" | "jpeg" | "png" | "gif" | "bmp" | "webp" => FileType::Image,
-                "zip" | "tar" | "gz" | "7z" => FileType::Archive,
+                "zip" | "tar" | "gz" | "tgz" | "bz2" | "xz" | "zst" | "7z" | "rar" | "mbox" | "mbx" => {
+                    FileType::Archive
+                }
+                "exe" | "dll" | "so" | "dylib" | "o" | "a" | "lib" | "obj" | "class" | "jar" | "pyc"
+                | "wasm" | "bin" | "dat" | "iso" | "dmg" | "img" | "mp3" | "wav" | "flac" | "ogg"
+                | "m4a" | "aac" | "mp4" | "m4v" | "mov" | "avi" | "mkv" | "webm" | "wmv" | "ttf"
+                | "otf" | "woff" | "woff2" | "eot" | "sqlite" | "db" | "psd" | "ico" | "heic"
+                | "tif" | "tiff" | "doc" | "ppt" => FileType::Binary,
+                _ => FileType::Unknown,
+            }
+        }
+
+        /// Parse the representation returned by [`as_str`](Self::as_str)
+        pub fn from_name(name: &str) -> Self {
+            match name {
+                "text" => FileType::Text,
+                "code" => FileType::Code,
+                "markdown" => FileType::Markdown,
+                "html" => FileType::Html,
+                "xml" => FileType::Xml,
+                "pdf" => FileType::Pdf,
+                "docx" => FileType::Docx,
+                "xlsx" => FileType::Xlsx,
+                "pptx" => FileType::Pptx,
+                "odt" => FileType::Odt,
+                "odp" => FileType::Odp,
+                "rtf" => FileType::Rtf,
+                "epub" => FileType::Epub,
+                "email" => FileType::Email,
+                "image" => FileType::Image,
+                "archive" => FileType::Archive,
+                "binary" => FileType::Binary,
                 _ => FileType::Unknown,
             }
         }
@@ -80,11 +142,20 @@ pub mod types {
                 FileType::Text => "text",
                 FileType::Code => "code",
                 FileType::Markdown => "markdown",
+                FileType::Html => "html",
+                FileType::Xml => "xml",
                 FileType::Pdf => "pdf",
                 FileType::Docx => "docx",
                 FileType::Xlsx => "xlsx",
+                FileType::Pptx => "pptx",
+                FileType::Odt => "odt",
+                FileType::Odp => "odp",
+                FileType::Rtf => "rtf",
+                FileType::Epub => "epub",
+                FileType::Email => "email",
                 FileType::Image => "image",
                 FileType::Archive => "archive",
+                FileType::Binary => "binary",
                 FileType::Unknown => "unknown",
             }
         }
@@ -113,5 +184,11 @@ pub mod types {
         pub filename: String,
         pub score: f32,
         pub snippet: Option<String>,
+        /// Chunk of the file's text that matched best, for semantic matches
+        #[serde(default)]
+        pub chunk_index: Option<usize>,
+        /// Date a photo was taken, as a Unix timestamp
+        #[serde(default)]
+        pub taken_at: Option<i64>,
     }
 }
\ No newline at end of file
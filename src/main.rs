@@ -1,14 +1,16 @@
 use clap::{Parser, Subcommand};
 use khoj::{
-    config::PrivacyConfig,
-    embedding::{EmbeddingModel, image::{ImageEmbedding, ClipTextEmbedding}},
-    extractors::text,
-    indexer::{metadata, walker},
-    search::HybridSearch,
-    storage::{Database, TantivyIndex, VectorStore},
+    config::{IndexingConfig, PrivacyConfig},
+    embedding::{find_model_path, EmbeddingModel, image::{ImageEmbedding, ClipTextEmbedding}},
+    extractors::{code::split_identifier, match_offset, KeyTerm, OcrExtractor, OcrModel, Page},
+    indexer::{IndexEvent, Indexer, StopHandle},
+    search::{boost_linked, HybridSearch},
+    storage::{tantivy_index::SortOrder, Database, IndexPaths, TantivyIndex, VectorStore},
     types::FileType,
+    watcher::{Watcher, DEFAULT_DEBOUNCE},
 };
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
 #[derive(Parser)]
 #[command(name = "khoj")]
@@ -29,6 +31,10 @@ struct Cli {
     #[arg(long, default_value = "0.7")]
     keyword_weight: f32,
 
+    /// Order of results: relevance, taken (newest photos first) or taken-asc
+    #[arg(long, default_value = "relevance")]
+    sort: SortOrder,
+
     /// Index directory (default: ~/.khoj)
     #[arg(long, global = true)]
     index_dir: Option<PathBuf>,
@@ -39,18 +45,33 @@ struct Cli {
 
 #[derive(Subcommand)]
 enum Commands {
-    /// Index a folder for searching
+    /// Index folders for searching (all registered folders if none given)
     Index {
-        /// Folder to index
-        path: PathBuf,
+        /// Folders to index
+        paths: Vec<PathBuf>,
 
-        /// Enable semantic search (requires ONNX model)
-        #[arg(long, short)]
-        semantic: bool,
+        #[command(flatten)]
+        options: IndexOptions,
+    },
 
-        /// Show progress for each file
-        #[arg(long, short)]
-        verbose: bool,
+    /// Manage the folders registered in the index
+    Roots {
+        #[command(subcommand)]
+        command: RootsCommand,
+    },
+
+    /// Watch folders and keep the index up to date as files change
+    Watch {
+        /// Folders to watch
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Quiet period in milliseconds before a burst of changes is indexed
+        #[arg(long, default_value_t = DEFAULT_DEBOUNCE.as_millis() as u64)]
+        debounce_ms: u64,
+
+        #[command(flatten)]
+        options: IndexOptions,
     },
 
     /// Start web interface
@@ -60,6 +81,12 @@ enum Commands {
         port: u16,
     },
 
+    /// List the notes linking to a file
+    Backlinks {
+        /// File the notes link to
+        path: PathBuf,
+    },
+
     /// Show statistics about the index
     Stats,
 
@@ -78,6 +105,81 @@ enum Commands {
     },
 }
 
+#[derive(Subcommand)]
+enum RootsCommand {
+    /// Register folders and index them
+    Add {
+        /// Folders to add
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        #[command(flatten)]
+        options: IndexOptions,
+    },
+
+    /// List registered folders
+    List,
+
+    /// Unregister folders and remove their files from the index
+    Remove {
+        /// Folders to remove
+        #[arg(required = true)]
+        paths: Vec<PathBuf>,
+
+        /// Show every removed file
+        #[arg(long, short)]
+        verbose: bool,
+    },
+
+    /// Re-extract every file of registered folders (all if none given)
+    Reindex {
+        /// Folders to re-index
+        paths: Vec<PathBuf>,
+
+        #[command(flatten)]
+        options: IndexOptions,
+    },
+}
+
+/// Options shared by the commands that index folders
+#[derive(clap::Args)]
+struct IndexOptions {
+    /// Enable semantic search (requires ONNX model)
+    #[arg(long, short)]
+    semantic: bool,
+
+    /// Show progress for each file
+    #[arg(long, short)]
+    verbose: bool,
+
+    /// Number of extraction workers (default: number of CPUs)
+    #[arg(long, short)]
+    jobs: Option<usize>,
+
+    /// Hash every file instead of trusting unchanged size and modification time
+    #[arg(long)]
+    paranoid: bool,
+
+    /// Leave the output of Jupyter notebook cells out of the index
+    #[arg(long)]
+    no_notebook_outputs: bool,
+}
+
+impl IndexOptions {
+    /// Indexing configuration with these options applied
+    fn indexing_config(&self) -> IndexingConfig {
+        let mut config = IndexingConfig {
+            paranoid: self.paranoid,
+            notebook_outputs: !self.no_notebook_outputs,
+            ..Default::default()
+        };
+        if let Some(jobs) = self.jobs {
+            config.workers = jobs;
+        }
+        config
+    }
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
@@ -92,11 +194,31 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     std::fs::create_dir_all(&index_dir)?;
 
     match cli.command {
-        Some(Commands::Index { path, semantic, verbose }) => {
-            index_folder(&path, &index_dir, semantic, verbose).await?;
+        Some(Commands::Index { paths, options }) => {
+            index_folders(&paths, &index_dir, &options, false).await?;
+        }
+        Some(Commands::Roots { command }) => match command {
+            RootsCommand::Add { paths, options } => {
+                index_folders(&paths, &index_dir, &options, false).await?;
+            }
+            RootsCommand::List => {
+                list_roots(&index_dir).await?;
+            }
+            RootsCommand::Remove { paths, verbose } => {
+                remove_roots(&paths, &index_dir, verbose).await?;
+            }
+            RootsCommand::Reindex { paths, options } => {
+                index_folders(&paths, &index_dir, &options, true).await?;
+            }
+        },
+        Some(Commands::Watch { paths, debounce_ms, options }) => {
+            watch_folders(&paths, &index_dir, &options, debounce_ms).await?;
         }
         Some(Commands::Serve { port }) => {
-            khoj::web::serve(index_dir, port).await?;
+            khoj::web::serve(index_dir, port, IndexingConfig::default().archives).await?;
+        }
+        Some(Commands::Backlinks { path }) => {
+            show_backlinks(&path, &index_dir).await?;
         }
         Some(Commands::Stats) => {
             show_stats(&index_dir).await?;
@@ -110,10 +232,10 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         None => {
             // Default action: search
             if let Some(query) = cli.query {
-                search_index(&query, &index_dir, cli.limit, cli.semantic, cli.keyword_weight).await?;
+                search_index(&query, &index_dir, cli.limit, cli.semantic, cli.keyword_weight, cli.sort).await?;
             } else {
                 eprintln!("Error: Please provide a search query or use a subcommand");
-                eprintln!("");
+                eprintln!();
                 eprintln!("Examples:");
                 eprintln!("  khoj \"tax forms\"              # Search for tax forms");
                 eprintln!("  khoj index ~/Documents        # Index your documents");
@@ -126,125 +248,83 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
-/// Find a model file in common locations
-fn find_model_path(filename: &str) -> Option<PathBuf> {
-    let exe_dir = std::env::current_exe()
-        .ok()
-        .and_then(|p| p.parent().map(|p| p.to_path_buf()));
-
-    let mut possible_paths = vec![
-        // Current directory
-        Some(PathBuf::from(format!("models/{}", filename))),
-        // Project directory (if running from subdirectory)
-        Some(PathBuf::from(format!("../models/{}", filename))),
-        // Home directory
-        dirs::home_dir().map(|h| h.join(".khoj/models").join(filename)),
-    ];
-
-    // Add executable directory paths
-    if let Some(ref d) = exe_dir {
-        possible_paths.push(Some(d.join("models").join(filename)));
-        possible_paths.push(d.parent().map(|p| p.join("models").join(filename)));
-        possible_paths.push(d.parent().and_then(|p| p.parent()).map(|p| p.join("models").join(filename)));
-        possible_paths.push(d.parent().and_then(|p| p.parent()).and_then(|p| p.parent()).map(|p| p.join("models").join(filename)));
-    }
-
-    for path in possible_paths.into_iter().flatten() {
-        if path.exists() {
-            return Some(path);
-        }
-    }
-
-    None
-}
-
-async fn index_folder(
-    path: &PathBuf,
-    index_dir: &PathBuf,
-    enable_semantic: bool,
-    verbose: bool,
+/// Index folders, or every registered root if `paths` is empty
+///
+/// With `rebuild`, every file is extracted again even if it is unchanged.
+async fn index_folders(
+    paths: &[PathBuf],
+    index_dir: &Path,
+    options: &IndexOptions,
+    rebuild: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
     use colored::Colorize;
     use indicatif::{ProgressBar, ProgressStyle};
 
-    if !path.exists() {
-        eprintln!("{} Path does not exist: {}", "Error:".red().bold(), path.display());
-        std::process::exit(1);
-    }
-
-    println!("{} {}", "Indexing:".cyan().bold(), path.display());
-    println!("{} {}", "Index location:".cyan(), index_dir.display());
-    println!();
-
-    // Initialize storage
-    let db_path = index_dir.join("db.sqlite");
-    let tantivy_path = index_dir.join("tantivy");
-    let vector_path = index_dir.join("vectors.json");
-    let image_vector_path = index_dir.join("image_vectors.json");
-
-    let db = Database::new(&db_path).await?;
-    let mut tantivy_index = TantivyIndex::new(&tantivy_path)?;
-    let vector_store = VectorStore::new(384)?;
-    let image_vector_store = VectorStore::new(512)?; // CLIP image embeddings are 512-dim
+    let verbose = options.verbose;
 
-    // Initialize embedding model if semantic search is enabled
-    let mut embedding_model = if enable_semantic {
-        println!("{}", "Loading AI model for semantic search...".cyan());
-
-        let model_path = find_model_path("model.onnx").unwrap_or_else(|| {
-            eprintln!("{}", "Error: ONNX model not found!".red().bold());
-            eprintln!("Searched in:");
-            eprintln!("  - ./models/model.onnx");
-            eprintln!("  - ~/.khoj/models/model.onnx");
-            eprintln!("");
-            eprintln!("Download with:");
-            eprintln!("  mkdir -p models");
-            eprintln!("  curl -L -o models/model.onnx https://huggingface.co/sentence-transformers/all-MiniLM-L6-v2/resolve/main/onnx/model.onnx");
-            std::process::exit(1);
-        });
-
-        let tokenizer_path = find_model_path("tokenizer.json").unwrap_or_else(|| {
-            eprintln!("{}", "Error: Tokenizer not found!".red().bold());
+    for path in paths {
+        if !path.exists() {
+            eprintln!("{} Path does not exist: {}", "Error:".red().bold(), path.display());
             std::process::exit(1);
-        });
+        }
+    }
 
-        Some(EmbeddingModel::new(&model_path, &tokenizer_path)?)
-    } else {
-        None
-    };
+    let mut indexer = Indexer::new(index_dir, PrivacyConfig::default())
+        .await?
+        .with_indexing_config(options.indexing_config());
 
-    // Initialize image embedding model for visual search
-    let mut image_embedding_model = if enable_semantic {
-        if let Some(clip_model_path) = find_model_path("clip_vision.onnx") {
-            println!("{}", "Loading CLIP model for image search...".cyan());
-            match ImageEmbedding::new(&clip_model_path) {
-                Ok(model) => Some(model),
-                Err(e) => {
-                    eprintln!("{} Failed to load CLIP model: {}", "Warning:".yellow().bold(), e);
-                    eprintln!("Image search will be disabled. Continuing with text search only.");
-                    None
-                }
+    let paths = if paths.is_empty() {
+        let mut registered = Vec::new();
+        for root in indexer.roots().await? {
+            let path = PathBuf::from(&root.path);
+            if path.exists() {
+                registered.push(path);
+            } else {
+                eprintln!(
+                    "{} Skipping missing folder {} (remove it with: khoj roots remove {})",
+                    "Warning:".yellow().bold(),
+                    root.path,
+                    root.path
+                );
             }
-        } else {
-            println!("{}", "Note: CLIP model not found. Image search will be disabled.".yellow());
-            println!("Download with:");
-            println!("  curl -L -o models/clip_vision.onnx https://huggingface.co/Qdrant/clip-ViT-B-32-vision/resolve/main/model.onnx");
-            None
         }
+        if registered.is_empty() {
+            println!("{}", "No folders registered yet.".yellow());
+            println!("Run: khoj index <folder>");
+            return Ok(());
+        }
+        registered
     } else {
-        None
+        paths.to_vec()
     };
 
-    // Discover files
-    let privacy_config = PrivacyConfig::default();
-    let walker = walker::FileWalker::new(privacy_config);
-    let discovered = walker.walk(path)?;
-
-    println!("{} {} files", "Discovered:".green(), discovered.len());
+    for path in &paths {
+        println!("{} {}", "Indexing:".cyan().bold(), path.display());
+    }
+    println!("{} {}", "Index location:".cyan(), index_dir.display());
     println!();
 
+    let pending = indexer.database().count_pending_files().await?;
+    if pending > 0 {
+        println!(
+            "{} {} files from an interrupted run will be indexed again",
+            "Resuming:".cyan().bold(),
+            pending
+        );
+        println!();
+    }
+
+    indexer = load_ocr_model(indexer);
+
+    // Initialize embedding model if semantic search is enabled
+    if options.semantic {
+        indexer = load_semantic_models(indexer)?;
+    }
+
+    stop_on_ctrl_c(indexer.stop_handle());
+
     // Setup progress bar
-    let pb = ProgressBar::new(discovered.len() as u64);
+    let pb = ProgressBar::new(0);
     pb.set_style(
         ProgressStyle::default_bar()
             .template("{spinner:.green} [{bar:40.cyan/blue}] {pos}/{len} {msg}")
@@ -252,173 +332,285 @@ async fn index_folder(
             .progress_chars("#>-"),
     );
 
-    let mut indexed_count = 0;
-    let mut skipped_count = 0;
+    let on_event = |event| match event {
+        IndexEvent::Discovered { root, files } => {
+            pb.println(format!("{} {} files in {}", "Discovered:".green(), files, root.display()));
+            pb.inc_length(files as u64);
+        }
+        IndexEvent::Added { path, file_type } | IndexEvent::Updated { path, file_type } => {
+            let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            if verbose {
+                if file_type == FileType::Image {
+                    pb.println(format!("  {} {} [image]", "✓".green(), filename));
+                } else {
+                    pb.println(format!("  {} {}", "✓".green(), filename));
+                }
+            }
+            pb.set_message(filename);
+            pb.inc(1);
+        }
+        IndexEvent::Unchanged { .. } => {
+            pb.inc(1);
+        }
+        IndexEvent::Removed { path } => {
+            if verbose {
+                pb.println(format!("  {} {} [removed]", "-".dimmed(), path.display()));
+            }
+        }
+        IndexEvent::Skipped { path, reason } => {
+            let filename = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+            if verbose {
+                pb.println(format!("  {} {} [{}]", "✗".red(), filename, reason));
+            }
+            pb.set_message(filename);
+            pb.inc(1);
+        }
+    };
+    let summary = if rebuild {
+        indexer.reindex_roots(&paths, on_event).await?
+    } else {
+        indexer.index_roots(&paths, on_event).await?
+    };
 
-    for disc_file in discovered {
-        let filename = disc_file.path.file_name()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
+    if summary.interrupted {
+        pb.abandon_with_message("Interrupted");
+        println!();
+        println!("{}", "Indexing interrupted, progress saved.".yellow().bold());
+        println!("Run the same command again to resume.");
+    } else {
+        pb.finish_with_message("Done!");
+        println!();
+        println!("{}", "Indexing complete!".green().bold());
+    }
+    println!("  {} {} files added", "✓".green(), summary.added);
+    println!("  {} {} files updated", "✓".green(), summary.updated);
+    println!("  {} {} files removed", "✓".green(), summary.removed);
+    println!("  {} {} files unchanged", "✓".green(), summary.unchanged);
+    if summary.skipped > 0 {
+        println!("  {} {} files skipped", "⚠".yellow(), summary.skipped);
+    }
+    if summary.image_embeddings > 0 {
+        println!("  {} {} images with embeddings", "🖼️ ".cyan(), summary.image_embeddings);
+    }
+    println!();
 
-        pb.set_message(filename.clone());
+    Ok(())
+}
 
-        let metadata = match metadata::extract_metadata(&disc_file.path, disc_file.file_type) {
-            Ok(m) => m,
-            Err(_) => {
-                skipped_count += 1;
-                pb.inc(1);
-                continue;
-            }
-        };
+/// Stop the indexer cleanly on Ctrl+C, and exit right away on a second one
+fn stop_on_ctrl_c(stop: StopHandle) {
+    use colored::Colorize;
 
-        let file_id = db.upsert_file(&metadata).await?;
-
-        // Handle images separately
-        if disc_file.file_type == FileType::Image {
-            // Try to generate image embedding
-            if let Some(ref mut img_model) = image_embedding_model {
-                match img_model.embed_image(&disc_file.path) {
-                    Ok(embedding) => {
-                        image_vector_store.upsert(file_id, &embedding)?;
-
-                        // Add basic metadata to tantivy for filtering
-                        tantivy_index.upsert_document(
-                            file_id,
-                            &disc_file.path.to_string_lossy(),
-                            &metadata.filename,
-                            &format!("image file: {}", metadata.filename),
-                        )?;
-
-                        if verbose {
-                            pb.println(format!("  {} {} [image]", "✓".green(), filename));
-                        }
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_err() {
+            return;
+        }
+        eprintln!();
+        eprintln!(
+            "{} saving progress... (press Ctrl+C again to quit immediately)",
+            "Stopping:".yellow().bold()
+        );
+        stop.stop();
+
+        if tokio::signal::ctrl_c().await.is_ok() {
+            std::process::exit(130);
+        }
+    });
+}
 
-                        indexed_count += 1;
-                    }
-                    Err(e) => {
-                        if verbose {
-                            pb.println(format!("  {} {} [image error: {}]", "✗".red(), filename, e));
-                        }
-                        skipped_count += 1;
-                    }
-                }
-            } else {
-                // Just index metadata without embedding
-                tantivy_index.upsert_document(
-                    file_id,
-                    &disc_file.path.to_string_lossy(),
-                    &metadata.filename,
-                    &format!("image file: {}", metadata.filename),
-                )?;
-                indexed_count += 1;
-            }
-        } else {
-            // Handle text/document files
-            match text::extract_text(&disc_file.path, disc_file.file_type) {
-                Ok(content) => {
-                    db.upsert_content(file_id, &content).await?;
-
-                    tantivy_index.upsert_document(
-                        file_id,
-                        &disc_file.path.to_string_lossy(),
-                        &metadata.filename,
-                        &content.text,
-                    )?;
-
-                    // Generate embedding if semantic search is enabled
-                    if let Some(ref mut model) = embedding_model {
-                        let text_chunk = if content.text.len() > 5000 {
-                            &content.text[..5000]
-                        } else {
-                            &content.text
-                        };
-
-                        if let Ok(embedding) = model.embed(text_chunk) {
-                            vector_store.upsert(file_id, &embedding)?;
-                        }
-                    }
+/// Load the text and (if available) CLIP models into an indexer
+fn load_semantic_models(mut indexer: Indexer) -> Result<Indexer, Box<dyn std::error::Error>> {
+    use colored::Colorize;
 
-                    if verbose {
-                        pb.println(format!("  {} {}", "✓".green(), filename));
-                    }
+    println!("{}", "Loading AI model for semantic search...".cyan());
+
+    let model_path = find_model_path("model.onnx").unwrap_or_else(|| {
+        eprintln!("{}", "Error: ONNX model not found!".red().bold());
+        eprintln!("Searched in:");
+        eprintln!("  - ./models/model.onnx");
+        eprintln!("  - ~/.khoj/models/model.onnx");
+        eprintln!();
+        eprintln!("Download with:");
+        eprintln!("  mkdir -p models");
+        eprintln!("  curl -L -o models/model.onnx https://huggingface.co/sentence-transformers/all-MiniLM-L6-v2/resolve/main/onnx/model.onnx");
+        std::process::exit(1);
+    });
 
-                    indexed_count += 1;
-                }
-                Err(_) => {
-                    skipped_count += 1;
-                }
+    let tokenizer_path = find_model_path("tokenizer.json").unwrap_or_else(|| {
+        eprintln!("{}", "Error: Tokenizer not found!".red().bold());
+        std::process::exit(1);
+    });
+
+    indexer = indexer.with_embedding_model(EmbeddingModel::new(&model_path, &tokenizer_path)?);
+
+    // Initialize image embedding model for visual search
+    if let Some(clip_model_path) = find_model_path("clip_vision.onnx") {
+        println!("{}", "Loading CLIP model for image search...".cyan());
+        match ImageEmbedding::new(&clip_model_path) {
+            Ok(model) => indexer = indexer.with_image_model(model),
+            Err(e) => {
+                eprintln!("{} Failed to load CLIP model: {}", "Warning:".yellow().bold(), e);
+                eprintln!("Image search will be disabled. Continuing with text search only.");
             }
         }
-
-        pb.inc(1);
+    } else {
+        println!("{}", "Note: CLIP model not found. Image search will be disabled.".yellow());
+        println!("Download with:");
+        println!("  curl -L -o models/clip_vision.onnx https://huggingface.co/Qdrant/clip-ViT-B-32-vision/resolve/main/model.onnx");
     }
 
-    pb.finish_with_message("Done!");
-    println!();
+    Ok(indexer)
+}
 
-    tantivy_index.commit()?;
+/// Recognize text in images and scanned PDFs if the OCR models are installed
+fn load_ocr_model(indexer: Indexer) -> Indexer {
+    use colored::Colorize;
 
-    // Save vector stores if semantic search was enabled
-    if embedding_model.is_some() {
-        vector_store.save(&vector_path)?;
+    match OcrModel::find() {
+        Some(Ok(model)) => {
+            println!("{}", "Loaded OCR models for images and scanned PDFs".cyan());
+            indexer.with_extractor(OcrExtractor::new(model))
+        }
+        Some(Err(e)) => {
+            eprintln!("{} Failed to load OCR models: {}", "Warning:".yellow().bold(), e);
+            eprintln!("Text in images and scanned PDFs will not be searchable.");
+            indexer
+        }
+        None => indexer,
     }
+}
 
-    if image_embedding_model.is_some() && !image_vector_store.is_empty() {
-        image_vector_store.save(&image_vector_path)?;
-    }
+async fn watch_folders(
+    paths: &[PathBuf],
+    index_dir: &Path,
+    options: &IndexOptions,
+    debounce_ms: u64,
+) -> Result<(), Box<dyn std::error::Error>> {
+    use colored::Colorize;
+    use std::time::Duration;
 
-    println!("{}", "Indexing complete!".green().bold());
-    println!("  {} {} files indexed", "✓".green(), indexed_count);
-    if skipped_count > 0 {
-        println!("  {} {} files skipped", "⚠".yellow(), skipped_count);
+    for path in paths {
+        if !path.is_dir() {
+            eprintln!("{} Not a directory: {}", "Error:".red().bold(), path.display());
+            std::process::exit(1);
+        }
     }
-    if !image_vector_store.is_empty() {
-        println!("  {} {} images with embeddings", "🖼️ ".cyan(), image_vector_store.len());
+
+    let verbose = options.verbose;
+    let indexer = Indexer::new(index_dir, PrivacyConfig::default())
+        .await?
+        .with_indexing_config(options.indexing_config());
+    let mut indexer = load_ocr_model(indexer);
+    if options.semantic {
+        indexer = load_semantic_models(indexer)?;
     }
+    let stop = indexer.stop_handle();
+    stop_on_ctrl_c(stop.clone());
+
+    // Subscribe before catching up so no change slips through in between
+    let mut watcher = Watcher::new(paths, Duration::from_millis(debounce_ms))?;
+
+    println!("{}", "Catching up with changes since the last run...".cyan());
+    let summary = indexer.index_roots(watcher.roots(), |_| {}).await?;
+    println!(
+        "  {} {} added, {} updated, {} removed, {} unchanged",
+        "✓".green(),
+        summary.added,
+        summary.updated,
+        summary.removed,
+        summary.unchanged
+    );
     println!();
+    if summary.interrupted {
+        println!("{}", "Stopped before watching, progress saved.".yellow());
+        return Ok(());
+    }
+
+    for root in watcher.roots() {
+        println!("{} {}", "Watching:".cyan().bold(), root.display());
+    }
+    println!("Press Ctrl+C to stop.");
+
+    loop {
+        let batch = tokio::select! {
+            batch = watcher.next_batch() => match batch {
+                Some(batch) => batch,
+                None => break,
+            },
+            _ = stop.stopped() => break,
+        };
+
+        for dir in &batch.rescans {
+            println!("{} {} (events were lost)", "Rescanning:".yellow(), dir.display());
+        }
 
+        let changed: Vec<PathBuf> = batch.paths.into_iter().collect();
+        let summary = indexer
+            .index_paths(watcher.roots(), &changed, |event| match event {
+                IndexEvent::Added { path, .. } | IndexEvent::Updated { path, .. } if verbose => {
+                    println!("  {} {}", "✓".green(), path.display());
+                }
+                IndexEvent::Removed { path } if verbose => {
+                    println!("  {} {} [removed]", "-".dimmed(), path.display());
+                }
+                IndexEvent::Skipped { path, reason } => {
+                    println!("  {} {} [{}]", "✗".red(), path.display(), reason);
+                }
+                _ => {}
+            })
+            .await?;
+
+        if summary.indexed() + summary.removed > 0 {
+            println!(
+                "{} {} indexed, {} removed",
+                "Updated:".green(),
+                summary.indexed(),
+                summary.removed
+            );
+        }
+    }
+
+    println!();
+    println!("{}", "Stopped watching.".cyan());
     Ok(())
 }
 
 async fn search_index(
     query: &str,
-    index_dir: &PathBuf,
+    index_dir: &Path,
     limit: usize,
     use_semantic: bool,
     keyword_weight: f32,
+    sort: SortOrder,
 ) -> Result<(), Box<dyn std::error::Error>> {
     use colored::Colorize;
 
-    let db_path = index_dir.join("db.sqlite");
-    let tantivy_path = index_dir.join("tantivy");
-    let vector_path = index_dir.join("vectors.json");
-    let image_vector_path = index_dir.join("image_vectors.json");
+    let paths = IndexPaths::new(index_dir);
 
-    if !tantivy_path.exists() {
+    if !paths.tantivy.exists() {
         eprintln!("{}", "Error: No index found!".red().bold());
         eprintln!("Run: khoj index <folder>");
         std::process::exit(1);
     }
 
-    let db = Database::new(&db_path).await?;
-    let tantivy_index = TantivyIndex::new(&tantivy_path)?;
+    let db = Database::new(&paths.db).await?;
+    let tantivy_index = TantivyIndex::open_read_only(&paths.tantivy)?;
 
-    let vector_store = if use_semantic && vector_path.exists() {
-        VectorStore::load(&vector_path)?
+    let vector_store = if use_semantic && paths.vectors.exists() {
+        VectorStore::load(&paths.vectors)?
     } else {
         VectorStore::new(384)?
     };
 
-    let image_vector_store = if use_semantic && image_vector_path.exists() {
-        VectorStore::load(&image_vector_path)?
+    let image_vector_store = if use_semantic && paths.image_vectors.exists() {
+        VectorStore::load(&paths.image_vectors)?
     } else {
         VectorStore::new(512)?
     };
 
     let search_engine = HybridSearch::new(tantivy_index, vector_store);
 
-    let results = if use_semantic {
+    let mut results = if use_semantic {
         // Load embedding model
         let model_path = find_model_path("model.onnx").ok_or_else(|| {
             std::io::Error::new(std::io::ErrorKind::NotFound, "model.onnx not found")
@@ -430,10 +622,20 @@ async fn search_index(
         let mut embedding_model = EmbeddingModel::new(&model_path, &tokenizer_path)?;
 
         let query_embedding = embedding_model.embed(query)?;
-        search_engine.hybrid_search(query, Some(&query_embedding), limit, keyword_weight)?
+        let mut results = search_engine.hybrid_search(query, Some(&query_embedding), limit, keyword_weight)?;
+        sort.sort(&mut results);
+        results
     } else {
-        search_engine.keyword_search(query, limit)?
+        search_engine.keyword_search_sorted(query, limit, sort)?
     };
+    // Notes that others link to rank higher
+    if sort == SortOrder::Relevance {
+        let mut backlinks = HashMap::new();
+        for result in &results {
+            backlinks.insert(result.file_id, db.count_backlinks(&result.path).await?);
+        }
+        boost_linked(&mut results, &backlinks);
+    }
 
     // Also search images if image vectors are available
     let mut image_results = Vec::new();
@@ -450,6 +652,9 @@ async fn search_index(
     println!("{} \"{}\"", "Results for:".cyan().bold(), query);
     println!();
 
+    let symbol_terms = symbol_terms(query);
+    let key_terms = KeyTerm::parse_query(query);
+
     // Display text/document results
     if !results.is_empty() {
         println!("{}", "Documents:".green().bold());
@@ -457,16 +662,70 @@ async fn search_index(
             println!("{}", format!("{}. {}", i + 1, result.filename).green());
             println!("   {}: {}", "Path".dimmed(), result.path);
             println!("   {}: {:.2}", "Score".dimmed(), result.score);
+            if let Some(taken) = result.taken_at.and_then(|t| chrono::DateTime::from_timestamp(t, 0)) {
+                println!("   {}: {}", "Taken".dimmed(), taken.format("%Y-%m-%d %H:%M"));
+            }
+
+            // Values a `key:` query found stand in for the preview
+            let mut key_values = Vec::new();
+            if !key_terms.is_empty() {
+                if let Ok(entries) = db.get_key_values(result.file_id).await {
+                    key_values = entries
+                        .into_iter()
+                        .filter(|entry| key_terms.iter().any(|term| term.matches(entry)))
+                        .collect();
+                }
+            }
+            for entry in key_values.iter().take(5) {
+                println!("   {}: {} = {}", "Key".dimmed(), entry.path, preview(&entry.value, 100));
+            }
+            if key_values.len() > 5 {
+                println!("   {}", format!("and {} more keys", key_values.len() - 5).dimmed());
+            }
 
             // Get snippet from database
             if let Ok(Some(content)) = db.get_content(result.file_id).await {
-                if let Some(snippet) = khoj::extractors::text::extract_snippet(&content.text, query, 100) {
-                    let truncated = if snippet.len() > 150 {
-                        format!("{}...", &snippet[..150])
-                    } else {
-                        snippet
-                    };
-                    println!("   {}: {}", "Preview".dimmed(), truncated);
+                let pages = db.get_pages(result.file_id).await.unwrap_or_default();
+                if let Some(page) = match_offset(&content.text, query).and_then(|offset| Page::number_at(&pages, offset)) {
+                    println!("   {}: {}", Page::label(&result.path).dimmed(), page);
+                }
+                if let Some(snippet) = khoj::extractors::text::extract_snippet(&content.text, query, 100)
+                    .filter(|_| key_values.is_empty())
+                {
+                    println!("   {}: {}", "Preview".dimmed(), preview(&snippet, 150));
+                }
+
+                // Show the passage that matched semantically
+                if let Some(chunk_index) = result.chunk_index {
+                    if let Ok(Some(chunk)) = db.get_text_chunk(result.file_id, chunk_index).await {
+                        if let Some(passage) = content.text.get(chunk.start..chunk.end) {
+                            let label = match Page::number_at(&pages, chunk.start) {
+                                Some(page) => format!(
+                                    "Passage {} ({} {})",
+                                    chunk_index + 1,
+                                    Page::label(&result.path).to_lowercase(),
+                                    page
+                                ),
+                                None => format!("Passage {}", chunk_index + 1),
+                            };
+                            println!("   {}: {}", label.dimmed(), preview(passage, 150));
+                        }
+                    }
+                }
+            }
+
+            // Point at the definitions a `sym:` query found
+            if !symbol_terms.is_empty() {
+                if let Ok(symbols) = db.get_symbols(result.file_id).await {
+                    for symbol in symbols.iter().filter(|s| matches_symbol(&s.name, &symbol_terms)) {
+                        println!(
+                            "   {}: {} {} (line {})",
+                            "Defines".dimmed(),
+                            symbol.kind.as_str(),
+                            symbol.name,
+                            symbol.line
+                        );
+                    }
                 }
             }
 
@@ -495,19 +754,155 @@ async fn search_index(
     Ok(())
 }
 
-async fn show_stats(index_dir: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
+/// Identifier parts of the `sym:` terms of a query
+fn symbol_terms(query: &str) -> Vec<Vec<String>> {
+    query
+        .split_whitespace()
+        .filter_map(|term| term.trim_start_matches(['+', '-', '(']).strip_prefix("sym:"))
+        .map(split_identifier)
+        .filter(|parts| !parts.is_empty())
+        .collect()
+}
+
+/// Whether the name of a symbol contains the parts of one of the terms in order
+fn matches_symbol(name: &str, terms: &[Vec<String>]) -> bool {
+    let parts = split_identifier(name);
+    terms
+        .iter()
+        .any(|term| parts.windows(term.len()).any(|window| window == term.as_slice()))
+}
+
+/// Single-line preview of a text, cut after `max_chars` characters
+fn preview(text: &str, max_chars: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}...", &text[..end]),
+        None => text,
+    }
+}
+
+async fn list_roots(index_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use colored::Colorize;
+
+    let paths = IndexPaths::new(index_dir);
+
+    if !paths.db.exists() {
+        println!("{}", "No index found.".yellow());
+        return Ok(());
+    }
+
+    let db = Database::new(&paths.db).await?;
+    let roots = db.list_roots().await?;
+
+    if roots.is_empty() {
+        println!("{}", "No folders registered yet.".yellow());
+        println!("Run: khoj roots add <folder>");
+        return Ok(());
+    }
+
+    println!();
+    println!("{}", "Registered folders".cyan().bold());
+    println!("──────────────────");
+    for root in roots {
+        let last_indexed = root
+            .last_indexed_at
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|dt| dt.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+            .unwrap_or_else(|| "never".to_string());
+        let missing = if Path::new(&root.path).exists() {
+            String::new()
+        } else {
+            format!(" {}", "[missing]".red())
+        };
+
+        println!("  {}{}", root.path.bold(), missing);
+        println!(
+            "    {} files, last indexed {}",
+            root.file_count,
+            last_indexed.dimmed()
+        );
+    }
+    println!();
+
+    Ok(())
+}
+
+async fn remove_roots(paths: &[PathBuf], index_dir: &Path, verbose: bool) -> Result<(), Box<dyn std::error::Error>> {
+    use colored::Colorize;
+
+    let mut indexer = Indexer::new(index_dir, PrivacyConfig::default()).await?;
+
+    for path in paths {
+        let summary = match indexer
+            .remove_root(path, |event| {
+                if let IndexEvent::Removed { path } = event {
+                    if verbose {
+                        println!("  {} {} [removed]", "-".dimmed(), path.display());
+                    }
+                }
+            })
+            .await
+        {
+            Ok(summary) => summary,
+            Err(e) => {
+                eprintln!("{} {}", "Error:".red().bold(), e);
+                std::process::exit(1);
+            }
+        };
+
+        println!(
+            "{} {} ({} files removed from the index)",
+            "Removed:".green().bold(),
+            path.display(),
+            summary.removed
+        );
+    }
+
+    Ok(())
+}
+
+async fn show_backlinks(path: &Path, index_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    use colored::Colorize;
+
+    let paths = IndexPaths::new(index_dir);
+
+    if !paths.db.exists() {
+        println!("{}", "No index found.".yellow());
+        return Ok(());
+    }
+
+    let db = Database::new(&paths.db).await?;
+    // Indexed paths are absolute
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    let backlinks = db.get_backlinks(&path.to_string_lossy()).await?;
+
+    if backlinks.is_empty() {
+        println!("{}", "No notes link to this file.".yellow());
+        return Ok(());
+    }
+
+    println!();
+    println!("{} {}", "Links to".cyan().bold(), path.display());
+    println!();
+    for backlink in backlinks {
+        println!("  {} {}", backlink.path, format!("(line {})", backlink.line).dimmed());
+    }
+    println!();
+
+    Ok(())
+}
+
+async fn show_stats(index_dir: &Path) -> Result<(), Box<dyn std::error::Error>> {
     use colored::Colorize;
 
-    let db_path = index_dir.join("db.sqlite");
-    let tantivy_path = index_dir.join("tantivy");
-    let vector_path = index_dir.join("vectors.json");
+    let paths = IndexPaths::new(index_dir);
 
-    if !db_path.exists() {
+    if !paths.db.exists() {
         println!("{}", "No index found.".yellow());
         return Ok(());
     }
 
-    let db = Database::new(&db_path).await?;
+    let db = Database::new(&paths.db).await?;
     let stats = db.get_stats().await?;
 
     println!();
@@ -517,11 +912,11 @@ async fn show_stats(index_dir: &PathBuf) -> Result<(), Box<dyn std::error::Error
     println!("  {}: {}", "Location".dimmed(), index_dir.display());
     println!();
 
-    if tantivy_path.exists() {
+    if paths.tantivy.exists() {
         println!("  {} Keyword search index (Tantivy)", "✓".green());
     }
 
-    if vector_path.exists() {
+    if paths.vectors.exists() {
         println!("  {} Semantic search index (Vectors)", "✓".green());
     }
     println!();
@@ -529,17 +924,17 @@ async fn show_stats(index_dir: &PathBuf) -> Result<(), Box<dyn std::error::Error
     Ok(())
 }
 
-async fn list_files(index_dir: &PathBuf, limit: usize) -> Result<(), Box<dyn std::error::Error>> {
+async fn list_files(index_dir: &Path, limit: usize) -> Result<(), Box<dyn std::error::Error>> {
     use colored::Colorize;
 
-    let db_path = index_dir.join("db.sqlite");
+    let paths = IndexPaths::new(index_dir);
 
-    if !db_path.exists() {
+    if !paths.db.exists() {
         println!("{}", "No index found.".yellow());
         return Ok(());
     }
 
-    let db = Database::new(&db_path).await?;
+    let db = Database::new(&paths.db).await?;
 
     // This is a simplified version - you'd need to add a list method to Database
     println!();
@@ -556,7 +951,7 @@ async fn list_files(index_dir: &PathBuf, limit: usize) -> Result<(), Box<dyn std
     Ok(())
 }
 
-fn clear_index(index_dir: &PathBuf, skip_confirm: bool) -> Result<(), Box<dyn std::error::Error>> {
+fn clear_index(index_dir: &Path, skip_confirm: bool) -> Result<(), Box<dyn std::error::Error>> {
     use colored::Colorize;
 
     if !index_dir.exists() {
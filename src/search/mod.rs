@@ -1,5 +1,6 @@
 //! Hybrid search combining keyword (BM25) and semantic (vector) search
 
+use crate::storage::tantivy_index::SortOrder;
 use crate::storage::{TantivyIndex, VectorStore};
 use crate::types::{Embedding, FileId, SearchResult};
 use crate::Result;
@@ -25,6 +26,11 @@ impl HybridSearch {
         self.tantivy_index.search(query, limit)
     }
 
+    /// Search using keyword search only, in the given order
+    pub fn keyword_search_sorted(&self, query: &str, limit: usize, order: SortOrder) -> Result<Vec<SearchResult>> {
+        self.tantivy_index.search_sorted(query, limit, order)
+    }
+
     /// Search using semantic search only (vector similarity)
     pub fn semantic_search(&self, query_embedding: &Embedding, limit: usize) -> Result<Vec<(FileId, f32)>> {
         self.vector_store.search(query_embedding, limit)
@@ -51,12 +57,20 @@ impl HybridSearch {
         let keyword_results = self.tantivy_index.search(query, limit * 2)?;
 
         // Get semantic search results if embedding provided
-        let semantic_results = if let Some(embedding) = query_embedding {
+        let semantic_chunks = if let Some(embedding) = query_embedding {
             let embedding_vec = embedding.to_vec();
-            self.vector_store.search(&embedding_vec, limit * 2)?
+            self.vector_store.search_chunks(&embedding_vec, limit * 2)?
         } else {
             Vec::new()
         };
+        let semantic_results: Vec<(FileId, f32)> = semantic_chunks
+            .iter()
+            .map(|&(file_id, _, similarity)| (file_id, similarity))
+            .collect();
+        let matched_chunks: HashMap<FileId, usize> = semantic_chunks
+            .iter()
+            .map(|&(file_id, chunk_index, _)| (file_id, chunk_index))
+            .collect();
 
         // If no semantic results, return keyword results only
         if semantic_results.is_empty() {
@@ -84,6 +98,8 @@ impl HybridSearch {
                     filename: result.filename.clone(),
                     score,
                     snippet: result.snippet.clone(),
+                    chunk_index: matched_chunks.get(&file_id).copied(),
+                    taken_at: result.taken_at,
                 });
             } else {
                 // If not in keyword results, create result without snippet
@@ -93,6 +109,8 @@ impl HybridSearch {
                     filename: format!("file_{}", file_id),
                     score,
                     snippet: None,
+                    chunk_index: matched_chunks.get(&file_id).copied(),
+                    taken_at: None,
                 });
             }
         }
@@ -101,6 +119,26 @@ impl HybridSearch {
     }
 }
 
+/// Rank files that other notes link to higher
+///
+/// Scores grow with the logarithm of the number of linking files, so that
+/// a few links count without hub notes drowning everything else. Results
+/// are re-sorted by score, keeping the order of equal scores.
+///
+/// # Arguments
+/// * `results` - Results sorted by relevance
+/// * `backlinks` - Number of files linking to each result, if any
+pub fn boost_linked(results: &mut [SearchResult], backlinks: &HashMap<FileId, usize>) {
+    const LINK_BOOST: f32 = 0.2;
+
+    for result in results.iter_mut() {
+        if let Some(&count) = backlinks.get(&result.file_id) {
+            result.score *= 1.0 + LINK_BOOST * (count as f32).ln_1p();
+        }
+    }
+    results.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+}
+
 /// Reciprocal Rank Fusion (RRF) algorithm
 ///
 /// Combines rankings from multiple sources using the formula:
@@ -152,6 +190,8 @@ mod tests {
                 filename: "file1.txt".to_string(),
                 score: 10.0,
                 snippet: None,
+                chunk_index: None,
+                taken_at: None,
             },
             SearchResult {
                 file_id: 2,
@@ -159,6 +199,8 @@ mod tests {
                 filename: "file2.txt".to_string(),
                 score: 8.0,
                 snippet: None,
+                chunk_index: None,
+                taken_at: None,
             },
             SearchResult {
                 file_id: 3,
@@ -166,6 +208,8 @@ mod tests {
                 filename: "file3.txt".to_string(),
                 score: 6.0,
                 snippet: None,
+                chunk_index: None,
+                taken_at: None,
             },
         ];
 
@@ -195,6 +239,8 @@ mod tests {
                 filename: "file1.txt".to_string(),
                 score: 10.0,
                 snippet: None,
+                chunk_index: None,
+                taken_at: None,
             },
         ];
 
@@ -216,4 +262,25 @@ mod tests {
         assert_eq!(combined.len(), 2);
         assert_eq!(combined[0].0, 1); // Higher similarity ranks first
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_boost_linked() {
+        let result = |file_id: FileId, score| SearchResult {
+            file_id,
+            path: format!("note{}.md", file_id),
+            filename: format!("note{}.md", file_id),
+            score,
+            snippet: None,
+            chunk_index: None,
+            taken_at: None,
+        };
+        let mut results = vec![result(1, 10.0), result(2, 9.0), result(3, 8.0), result(4, 8.0)];
+        boost_linked(&mut results, &HashMap::from([(2, 5), (4, 0)]));
+
+        // Five backlinks outweigh a slightly better match
+        let order: Vec<_> = results.iter().map(|r| r.file_id).collect();
+        assert_eq!(order, vec![2, 1, 3, 4]);
+        assert!((results[1].score - 10.0).abs() < 1e-6);
+        assert!((results[3].score - 8.0).abs() < 1e-6);
+    }
+}
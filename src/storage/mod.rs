@@ -3,15 +3,46 @@
 pub mod tantivy_index;
 pub mod vector_store;
 
-use crate::extractors::ExtractedContent;
+use crate::extractors::{ExtractedContent, ImageMetadata, KeyValue, Link, LinkKind, Page, Symbol, SymbolKind};
+use crate::indexer::archive::{ArchiveMember, MEMBER_SEPARATOR};
+use crate::indexer::chunker::Chunk;
 use crate::types::{FileId, FileMetadata, FileType};
 use crate::Result;
 use sqlx::{sqlite::SqlitePool, Row};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 pub use tantivy_index::TantivyIndex;
 pub use vector_store::VectorStore;
 
+/// On-disk layout of an index directory
+#[derive(Debug, Clone)]
+pub struct IndexPaths {
+    /// Root of the index directory
+    pub root: PathBuf,
+    /// SQLite metadata database
+    pub db: PathBuf,
+    /// Tantivy keyword index directory
+    pub tantivy: PathBuf,
+    /// Text embedding vectors
+    pub vectors: PathBuf,
+    /// CLIP image embedding vectors
+    pub image_vectors: PathBuf,
+}
+
+impl IndexPaths {
+    /// Resolve the store locations inside an index directory
+    pub fn new<P: AsRef<Path>>(index_dir: P) -> Self {
+        let root = index_dir.as_ref().to_path_buf();
+        Self {
+            db: root.join("db.sqlite"),
+            tantivy: root.join("tantivy"),
+            vectors: root.join("vectors.json"),
+            image_vectors: root.join("image_vectors.json"),
+            root,
+        }
+    }
+}
+
 /// Database connection pool
 pub struct Database {
     pool: SqlitePool,
@@ -39,6 +70,42 @@ impl Database {
 
         let schema = include_str!("schema.sql");
         sqlx::query(schema).execute(&self.pool).await?;
+
+        self.migrate().await
+    }
+
+    /// Add columns introduced after an index was created
+    async fn migrate(&self) -> Result<()> {
+        let columns: Vec<String> = sqlx::query("SELECT name FROM pragma_table_info('files')")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| row.get("name"))
+            .collect();
+
+        for column in ["pending", "failed"] {
+            if !columns.iter().any(|c| c == column) {
+                sqlx::query(&format!("ALTER TABLE files ADD COLUMN {} INTEGER NOT NULL DEFAULT 0", column))
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
+        let vector_columns: Vec<String> = sqlx::query("SELECT name FROM pragma_table_info('vectors')")
+            .fetch_all(&self.pool)
+            .await?
+            .into_iter()
+            .map(|row| row.get("name"))
+            .collect();
+
+        for column in ["start_offset", "end_offset"] {
+            if !vector_columns.iter().any(|c| c == column) {
+                sqlx::query(&format!("ALTER TABLE vectors ADD COLUMN {} INTEGER", column))
+                    .execute(&self.pool)
+                    .await?;
+            }
+        }
+
         Ok(())
     }
 
@@ -50,12 +117,25 @@ impl Database {
     /// # Returns
     /// File ID (new or existing)
     pub async fn upsert_file(&self, metadata: &FileMetadata) -> Result<FileId> {
+        self.upsert_file_row(metadata, false).await
+    }
+
+    /// Insert or update a file's metadata, marked as pending
+    ///
+    /// Pending rows are re-indexed by the next run unless
+    /// [`complete_checkpoint`](Self::complete_checkpoint) is called after
+    /// the matching Tantivy documents and vectors were committed.
+    pub async fn upsert_pending_file(&self, metadata: &FileMetadata) -> Result<FileId> {
+        self.upsert_file_row(metadata, true).await
+    }
+
+    async fn upsert_file_row(&self, metadata: &FileMetadata, pending: bool) -> Result<FileId> {
         let file_type_str = metadata.file_type.as_str();
 
         let result = sqlx::query(
             r#"
-            INSERT INTO files (path, filename, file_type, mime_type, size, hash, created_at, modified_at, indexed_at)
-            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+            INSERT INTO files (path, filename, file_type, mime_type, size, hash, created_at, modified_at, indexed_at, pending)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
             ON CONFLICT(path) DO UPDATE SET
                 filename = excluded.filename,
                 file_type = excluded.file_type,
@@ -63,7 +143,9 @@ impl Database {
                 size = excluded.size,
                 hash = excluded.hash,
                 modified_at = excluded.modified_at,
-                indexed_at = excluded.indexed_at
+                indexed_at = excluded.indexed_at,
+                pending = max(pending, excluded.pending),
+                failed = 0
             RETURNING id
             "#,
         )
@@ -76,6 +158,7 @@ impl Database {
         .bind(metadata.created_at)
         .bind(metadata.modified_at)
         .bind(metadata.indexed_at)
+        .bind(pending)
         .fetch_one(&self.pool)
         .await?;
 
@@ -147,6 +230,7 @@ impl Database {
             text: row.get("text"),
             word_count: row.get::<i64, _>("word_count") as usize,
             language: row.get("language"),
+            ..Default::default()
         }))
     }
 
@@ -201,6 +285,573 @@ impl Database {
         })
     }
 
+    /// List indexed files located under a root directory
+    ///
+    /// # Arguments
+    /// * `root` - Root directory path as stored in the index, or the path
+    ///   of an archive
+    ///
+    /// # Returns
+    /// Change-detection records for the root itself and everything below
+    /// it, including the members of an archive
+    pub async fn list_files_under(&self, root: &str) -> Result<Vec<StoredFile>> {
+        let root = root.trim_end_matches(std::path::MAIN_SEPARATOR);
+        let prefix = format!("{}{}", root, std::path::MAIN_SEPARATOR);
+        let member_prefix = format!("{}{}", root, MEMBER_SEPARATOR);
+
+        // Range queries on the path index, rather than scanning every row
+        let rows = sqlx::query(
+            "SELECT id, path, hash, size, modified_at, indexed_at, pending, failed FROM files
+             WHERE path = ? OR (path >= ? AND path < ?) OR (path >= ? AND path < ?)"
+        )
+        .bind(root)
+        .bind(&prefix)
+        .bind(prefix_end(&prefix))
+        .bind(&member_prefix)
+        .bind(prefix_end(&member_prefix))
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| StoredFile {
+                id: row.get("id"),
+                path: row.get("path"),
+                hash: row.get("hash"),
+                size: row.get::<i64, _>("size") as u64,
+                modified_at: row.get("modified_at"),
+                indexed_at: row.get("indexed_at"),
+                pending: row.get("pending"),
+                failed: row.get("failed"),
+            })
+            .collect())
+    }
+
+    /// Register a root folder, keeping its history if already registered
+    ///
+    /// # Arguments
+    /// * `path` - Canonical absolute path of the root
+    pub async fn add_root(&self, path: &str) -> Result<()> {
+        sqlx::query("INSERT INTO roots (path, added_at) VALUES (?, ?) ON CONFLICT(path) DO NOTHING")
+            .bind(path)
+            .bind(unix_now())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Record a successful indexing run of a root
+    pub async fn mark_root_indexed(&self, path: &str) -> Result<()> {
+        sqlx::query("UPDATE roots SET last_indexed_at = ? WHERE path = ?")
+            .bind(unix_now())
+            .bind(path)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Unregister a root folder
+    ///
+    /// Files below the root are left alone; see
+    /// [`Indexer::remove_root`](crate::indexer::Indexer::remove_root).
+    ///
+    /// # Returns
+    /// True if the root was registered
+    pub async fn remove_root(&self, path: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM roots WHERE path = ?")
+            .bind(path)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /// List registered roots with the number of files indexed below each
+    pub async fn list_roots(&self) -> Result<Vec<IndexedRoot>> {
+        let rows = sqlx::query(
+            r#"
+            SELECT r.id, r.path, r.added_at, r.last_indexed_at,
+                (SELECT COUNT(*) FROM files f
+                 WHERE f.path = r.path OR substr(f.path, 1, length(r.path) + 1) = r.path || ?) AS file_count
+            FROM roots r
+            ORDER BY r.path
+            "#,
+        )
+        .bind(std::path::MAIN_SEPARATOR.to_string())
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| IndexedRoot {
+                id: row.get("id"),
+                path: row.get("path"),
+                added_at: row.get("added_at"),
+                last_indexed_at: row.get("last_indexed_at"),
+                file_count: row.get("file_count"),
+            })
+            .collect())
+    }
+
+    /// Finish a checkpoint once Tantivy and the vector stores are committed
+    ///
+    /// Deletes the rows of removed files and clears the pending flag of all
+    /// other rows, in a single transaction.
+    ///
+    /// # Arguments
+    /// * `deleted` - Files removed from the other stores since the last checkpoint
+    pub async fn complete_checkpoint(&self, deleted: &[FileId]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        for file_id in deleted {
+            sqlx::query("DELETE FROM files WHERE id = ?")
+                .bind(file_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("UPDATE files SET pending = 0 WHERE pending = 1")
+            .execute(&mut *tx)
+            .await?;
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Mark a file whose extraction or embedding failed transiently
+    ///
+    /// Failed files are indexed again by the next run, even if they didn't
+    /// change. The flag is cleared when the file's metadata is written again.
+    ///
+    /// # Arguments
+    /// * `file_id` - File ID
+    pub async fn mark_file_failed(&self, file_id: FileId) -> Result<()> {
+        sqlx::query("UPDATE files SET failed = 1 WHERE id = ?")
+            .bind(file_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Delete the extracted content of a file
+    ///
+    /// # Arguments
+    /// * `file_id` - File ID
+    pub async fn delete_content(&self, file_id: FileId) -> Result<()> {
+        sqlx::query("DELETE FROM content WHERE file_id = ?")
+            .bind(file_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Count files written by a run that stopped before its checkpoint
+    pub async fn count_pending_files(&self) -> Result<i64> {
+        let row = sqlx::query("SELECT COUNT(*) as count FROM files WHERE pending = 1")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(row.get("count"))
+    }
+
+    /// Mark every file as pending, so that the next run indexes it again
+    ///
+    /// Used when another store had to be rebuilt from scratch.
+    pub async fn mark_all_pending(&self) -> Result<()> {
+        sqlx::query("UPDATE files SET pending = 1").execute(&self.pool).await?;
+        Ok(())
+    }
+
+    /// Record the embedded chunks of a file's text
+    ///
+    /// Replaces the chunks recorded for an earlier version of the file.
+    ///
+    /// # Arguments
+    /// * `file_id` - File the chunks belong to
+    /// * `chunks` - Chunks whose embeddings were added to the vector store
+    pub async fn replace_text_chunks(&self, file_id: FileId, chunks: &[Chunk]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM vectors WHERE file_id = ? AND vector_type = 'text'")
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+        for chunk in chunks {
+            sqlx::query(
+                r#"
+                INSERT INTO vectors (file_id, vector_type, vector_id, chunk_index, start_offset, end_offset)
+                VALUES (?, 'text', ?, ?, ?, ?)
+                "#,
+            )
+            .bind(file_id)
+            .bind(file_id)
+            .bind(chunk.index as i64)
+            .bind(chunk.start as i64)
+            .bind(chunk.end as i64)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get a chunk of a file's text by its index
+    ///
+    /// # Returns
+    /// The chunk, or `None` if the file has no such embedded chunk
+    pub async fn get_text_chunk(&self, file_id: FileId, chunk_index: usize) -> Result<Option<Chunk>> {
+        let row = sqlx::query(
+            r#"
+            SELECT start_offset, end_offset FROM vectors
+            WHERE file_id = ? AND vector_type = 'text' AND chunk_index = ?
+            "#,
+        )
+        .bind(file_id)
+        .bind(chunk_index as i64)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.and_then(|row| {
+            let start: Option<i64> = row.get("start_offset");
+            let end: Option<i64> = row.get("end_offset");
+            Some(Chunk {
+                index: chunk_index,
+                start: start? as usize,
+                end: end? as usize,
+            })
+        }))
+    }
+
+    /// Record where a file indexed from an archive is located
+    ///
+    /// # Arguments
+    /// * `file_id` - The member's file
+    /// * `archive_id` - The archive on disk holding it
+    /// * `member` - Location and sizes of the member
+    pub async fn upsert_archive_member(&self, file_id: FileId, archive_id: FileId, member: &ArchiveMember) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO archive_members (file_id, archive_id, member_path, size, compressed_size, depth)
+            VALUES (?, ?, ?, ?, ?, ?)
+            ON CONFLICT(file_id) DO UPDATE SET
+                archive_id = excluded.archive_id,
+                member_path = excluded.member_path,
+                size = excluded.size,
+                compressed_size = excluded.compressed_size,
+                depth = excluded.depth
+            "#,
+        )
+        .bind(file_id)
+        .bind(archive_id)
+        .bind(&member.member_path)
+        .bind(member.size as i64)
+        .bind(member.compressed_size.map(|size| size as i64))
+        .bind(member.depth as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Get the location of a file indexed from an archive
+    ///
+    /// # Returns
+    /// The member, or `None` if the file is not from an archive
+    pub async fn get_archive_member(&self, file_id: FileId) -> Result<Option<ArchiveMember>> {
+        let row = sqlx::query(
+            r#"
+            SELECT a.path AS archive_path, m.member_path, m.size, m.compressed_size, m.depth
+            FROM archive_members m
+            JOIN files a ON a.id = m.archive_id
+            WHERE m.file_id = ?
+            "#,
+        )
+        .bind(file_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|row| ArchiveMember {
+            archive_path: row.get("archive_path"),
+            member_path: row.get("member_path"),
+            size: row.get::<i64, _>("size") as u64,
+            compressed_size: row.get::<Option<i64>, _>("compressed_size").map(|size| size as u64),
+            depth: row.get::<i64, _>("depth") as usize,
+        }))
+    }
+
+    /// Store the EXIF and XMP properties of an image
+    ///
+    /// # Arguments
+    /// * `file_id` - The image's file
+    /// * `metadata` - Properties read from the image
+    pub async fn upsert_image_metadata(&self, file_id: FileId, metadata: &ImageMetadata) -> Result<()> {
+        sqlx::query(
+            r#"
+            INSERT INTO image_metadata (file_id, camera_make, camera_model, lens, taken_at, width, height, orientation, latitude, longitude)
+            VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+            ON CONFLICT(file_id) DO UPDATE SET
+                camera_make = excluded.camera_make,
+                camera_model = excluded.camera_model,
+                lens = excluded.lens,
+                taken_at = excluded.taken_at,
+                width = excluded.width,
+                height = excluded.height,
+                orientation = excluded.orientation,
+                latitude = excluded.latitude,
+                longitude = excluded.longitude
+            "#,
+        )
+        .bind(file_id)
+        .bind(&metadata.camera_make)
+        .bind(&metadata.camera_model)
+        .bind(&metadata.lens)
+        .bind(metadata.taken_at)
+        .bind(metadata.width.map(i64::from))
+        .bind(metadata.height.map(i64::from))
+        .bind(metadata.orientation.map(i64::from))
+        .bind(metadata.latitude)
+        .bind(metadata.longitude)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Get the EXIF and XMP properties of an image
+    ///
+    /// # Returns
+    /// The properties, or `None` if the file is not an indexed image
+    pub async fn get_image_metadata(&self, file_id: FileId) -> Result<Option<ImageMetadata>> {
+        let row = sqlx::query("SELECT * FROM image_metadata WHERE file_id = ?")
+            .bind(file_id)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        Ok(row.map(|row| ImageMetadata {
+            camera_make: row.get("camera_make"),
+            camera_model: row.get("camera_model"),
+            lens: row.get("lens"),
+            taken_at: row.get("taken_at"),
+            width: row.get::<Option<i64>, _>("width").map(|width| width as u32),
+            height: row.get::<Option<i64>, _>("height").map(|height| height as u32),
+            orientation: row.get::<Option<i64>, _>("orientation").map(|orientation| orientation as u16),
+            latitude: row.get("latitude"),
+            longitude: row.get("longitude"),
+        }))
+    }
+
+    /// Replace the symbols defined in a file
+    ///
+    /// # Arguments
+    /// * `file_id` - The file defining the symbols
+    /// * `symbols` - Definitions found in the file
+    pub async fn replace_symbols(&self, file_id: FileId, symbols: &[Symbol]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM symbols WHERE file_id = ?")
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+        for symbol in symbols {
+            sqlx::query("INSERT INTO symbols (file_id, name, kind, line) VALUES (?, ?, ?, ?)")
+                .bind(file_id)
+                .bind(&symbol.name)
+                .bind(symbol.kind.as_str())
+                .bind(symbol.line as i64)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get the symbols defined in a file, in order of their lines
+    pub async fn get_symbols(&self, file_id: FileId) -> Result<Vec<Symbol>> {
+        let rows = sqlx::query("SELECT name, kind, line FROM symbols WHERE file_id = ? ORDER BY line, id")
+            .bind(file_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(Symbol {
+                    name: row.get("name"),
+                    kind: SymbolKind::from_name(row.get("kind"))?,
+                    line: row.get::<i64, _>("line") as usize,
+                })
+            })
+            .collect())
+    }
+
+    /// Replace the pages of a file's content
+    ///
+    /// # Arguments
+    /// * `file_id` - The file the pages belong to
+    /// * `pages` - Pages as byte ranges of the stored content text
+    pub async fn replace_pages(&self, file_id: FileId, pages: &[Page]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM pages WHERE file_id = ?")
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+        for page in pages {
+            sqlx::query("INSERT INTO pages (file_id, number, start_offset, end_offset) VALUES (?, ?, ?, ?)")
+                .bind(file_id)
+                .bind(page.number as i64)
+                .bind(page.start as i64)
+                .bind(page.end as i64)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get the pages of a file's content, in page order
+    pub async fn get_pages(&self, file_id: FileId) -> Result<Vec<Page>> {
+        let rows = sqlx::query("SELECT number, start_offset, end_offset FROM pages WHERE file_id = ? ORDER BY number")
+            .bind(file_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Page {
+                number: row.get::<i64, _>("number") as usize,
+                start: row.get::<i64, _>("start_offset") as usize,
+                end: row.get::<i64, _>("end_offset") as usize,
+            })
+            .collect())
+    }
+
+    /// Replace the links from a file
+    ///
+    /// # Arguments
+    /// * `file_id` - The linking file
+    /// * `links` - Links found in the file, with path links resolved
+    pub async fn replace_links(&self, file_id: FileId, links: &[Link]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM links WHERE file_id = ?")
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+        for link in links {
+            sqlx::query("INSERT INTO links (file_id, target, kind, line) VALUES (?, ?, ?, ?)")
+                .bind(file_id)
+                .bind(&link.target)
+                .bind(link.kind.as_str())
+                .bind(link.line as i64)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get the links from a file, in order of their lines
+    pub async fn get_links(&self, file_id: FileId) -> Result<Vec<Link>> {
+        let rows = sqlx::query("SELECT target, kind, line FROM links WHERE file_id = ? ORDER BY line, id")
+            .bind(file_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .filter_map(|row| {
+                Some(Link {
+                    target: row.get("target"),
+                    kind: LinkKind::from_name(row.get("kind"))?,
+                    line: row.get::<i64, _>("line") as usize,
+                })
+            })
+            .collect())
+    }
+
+    /// Get the links to a file from other files
+    ///
+    /// Path links match the path of the file. Wiki-links match its file
+    /// name without `.md`, or with a leading folder such as
+    /// `[[projects/alpha]]` its path suffix, ignoring ASCII case.
+    ///
+    /// # Returns
+    /// The linking files and lines, ordered by path
+    pub async fn get_backlinks(&self, path: &str) -> Result<Vec<Backlink>> {
+        let rows = sqlx::query(&format!(
+            "SELECT files.id, files.path, links.line FROM links JOIN files ON files.id = links.file_id
+             WHERE {} AND files.path != ?1 ORDER BY files.path, links.line",
+            LINKS_TO_PATH
+        ))
+        .bind(path)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| Backlink {
+                file_id: row.get("id"),
+                path: row.get("path"),
+                line: row.get::<i64, _>("line") as usize,
+            })
+            .collect())
+    }
+
+    /// Count the files linking to a file, see [`Database::get_backlinks`]
+    pub async fn count_backlinks(&self, path: &str) -> Result<usize> {
+        let count: i64 = sqlx::query(&format!(
+            "SELECT COUNT(DISTINCT links.file_id) AS count FROM links JOIN files ON files.id = links.file_id
+             WHERE {} AND files.path != ?1",
+            LINKS_TO_PATH
+        ))
+        .bind(path)
+        .fetch_one(&self.pool)
+        .await?
+        .get("count");
+        Ok(count as usize)
+    }
+
+    /// Replace the key values of a structured data file
+    ///
+    /// # Arguments
+    /// * `file_id` - The file holding the values
+    /// * `key_values` - Values by key path, in document order
+    pub async fn replace_key_values(&self, file_id: FileId, key_values: &[KeyValue]) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+
+        sqlx::query("DELETE FROM key_values WHERE file_id = ?")
+            .bind(file_id)
+            .execute(&mut *tx)
+            .await?;
+        for entry in key_values {
+            sqlx::query("INSERT INTO key_values (file_id, key_path, value) VALUES (?, ?, ?)")
+                .bind(file_id)
+                .bind(&entry.path)
+                .bind(&entry.value)
+                .execute(&mut *tx)
+                .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    /// Get the key values of a file, in document order
+    pub async fn get_key_values(&self, file_id: FileId) -> Result<Vec<KeyValue>> {
+        let rows = sqlx::query("SELECT key_path, value FROM key_values WHERE file_id = ? ORDER BY id")
+            .bind(file_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|row| KeyValue {
+                path: row.get("key_path"),
+                value: row.get("value"),
+            })
+            .collect())
+    }
+
     /// Delete a file from the index
     pub async fn delete_file(&self, path: &str) -> Result<()> {
         sqlx::query("DELETE FROM files WHERE path = ?")
@@ -211,6 +862,43 @@ impl Database {
     }
 }
 
+/// A link to a file, from another file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backlink {
+    pub file_id: FileId,
+    /// Path of the linking file
+    pub path: String,
+    /// Line of the link in the linking file
+    pub line: usize,
+}
+
+/// A folder registered for indexing
+#[derive(Debug, Clone)]
+pub struct IndexedRoot {
+    pub id: i64,
+    pub path: String,
+    pub added_at: i64,
+    pub last_indexed_at: Option<i64>,
+    /// Number of indexed files below the root
+    pub file_count: i64,
+}
+
+/// Stored state of an indexed file, used for change detection
+#[derive(Debug, Clone)]
+pub struct StoredFile {
+    pub id: FileId,
+    pub path: String,
+    pub hash: String,
+    pub size: u64,
+    pub modified_at: i64,
+    pub indexed_at: i64,
+    /// Written after the last checkpoint, so other stores may lack it
+    pub pending: bool,
+    /// Last extraction or embedding failed transiently, so it must be
+    /// indexed again
+    pub failed: bool,
+}
+
 /// Statistics about the index
 #[derive(Debug)]
 pub struct IndexStats {
@@ -219,6 +907,19 @@ pub struct IndexStats {
     pub by_type: Vec<(String, i64)>,
 }
 
+/// Condition on `links` matching links to the path bound as `?1`
+const LINKS_TO_PATH: &str = "((links.kind = 'path' AND links.target = ?1)
+    OR (links.kind = 'wiki' AND (lower(substr(?1, -length(links.target) - 4)) = lower('/' || links.target || '.md')
+        OR lower(substr(?1, -length(links.target) - 1)) = lower('/' || links.target))))";
+
+/// Current time in seconds since the Unix epoch
+fn unix_now() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
 /// Helper struct for deserializing file metadata from database
 #[derive(sqlx::FromRow)]
 struct FileMetadataRow {
@@ -240,7 +941,7 @@ impl From<FileMetadataRow> for FileMetadata {
             id: row.id,
             path: row.path,
             filename: row.filename,
-            file_type: FileType::from_extension(&row.file_type),
+            file_type: FileType::from_name(&row.file_type),
             mime_type: row.mime_type,
             size: row.size as u64,
             hash: row.hash,
@@ -251,6 +952,18 @@ impl From<FileMetadataRow> for FileMetadata {
     }
 }
 
+/// Smallest string greater than every string starting with `prefix`
+///
+/// `prefix` must end with an ASCII separator, which is bumped to the next
+/// character, e.g. `/docs/` to `/docs0`.
+fn prefix_end(prefix: &str) -> String {
+    let mut end = prefix.to_string();
+    let last = end.pop().expect("prefix ends with a separator");
+    debug_assert!(last.is_ascii());
+    end.push((last as u8 + 1) as char);
+    end
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -305,6 +1018,7 @@ mod tests {
             text: "Hello, world!".to_string(),
             word_count: 2,
             language: None,
+            ..Default::default()
         };
 
         db.upsert_content(file_id, &content).await.unwrap();
@@ -385,4 +1099,270 @@ mod tests {
         let content = db.get_content(file_id).await.unwrap();
         assert!(content.is_none());
     }
-}
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_list_files_under() {
+        let (db, _temp_dir) = create_test_db().await;
+
+        for path in [
+            "/docs/a.txt",
+            "/docs/sub/b.txt",
+            "/docs/été.txt",
+            "/docs-old/c.txt",
+            "/docs0/e.txt",
+            "/other/d.txt",
+        ] {
+            let metadata = FileMetadata {
+                path: path.to_string(),
+                ..create_test_metadata()
+            };
+            db.upsert_file(&metadata).await.unwrap();
+        }
+
+        let mut paths: Vec<String> = db
+            .list_files_under("/docs")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        paths.sort();
+
+        // Sibling directories sharing the prefix are not included
+        assert_eq!(paths, vec!["/docs/a.txt", "/docs/sub/b.txt", "/docs/été.txt"]);
+    }
+
+    #[tokio::test]
+    async fn test_roots() {
+        let (db, _temp_dir) = create_test_db().await;
+
+        db.add_root("/docs").await.unwrap();
+        db.add_root("/photos").await.unwrap();
+        db.add_root("/docs").await.unwrap();
+
+        for path in ["/docs/a.txt", "/docs/sub/b.txt", "/docs-old/c.txt"] {
+            let metadata = FileMetadata {
+                path: path.to_string(),
+                ..create_test_metadata()
+            };
+            db.upsert_file(&metadata).await.unwrap();
+        }
+        db.mark_root_indexed("/docs").await.unwrap();
+
+        let roots = db.list_roots().await.unwrap();
+        assert_eq!(roots.len(), 2);
+        assert_eq!(roots[0].path, "/docs");
+        assert_eq!(roots[0].file_count, 2);
+        assert!(roots[0].last_indexed_at.is_some());
+        assert_eq!(roots[1].file_count, 0);
+        assert!(roots[1].last_indexed_at.is_none());
+
+        assert!(db.remove_root("/photos").await.unwrap());
+        assert!(!db.remove_root("/photos").await.unwrap());
+        assert_eq!(db.list_roots().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_checkpoint() {
+        let (db, _temp_dir) = create_test_db().await;
+
+        let kept = db.upsert_file(&create_test_metadata()).await.unwrap();
+        let pending = db
+            .upsert_pending_file(&FileMetadata {
+                path: "/test/pending.txt".to_string(),
+                ..create_test_metadata()
+            })
+            .await
+            .unwrap();
+        assert_eq!(db.count_pending_files().await.unwrap(), 1);
+
+        // Refreshing metadata doesn't clear the pending flag
+        db.upsert_file(&FileMetadata {
+            path: "/test/pending.txt".to_string(),
+            ..create_test_metadata()
+        })
+        .await
+        .unwrap();
+        let stored = db.list_files_under("/test/pending.txt").await.unwrap();
+        assert!(stored[0].pending);
+
+        db.complete_checkpoint(&[kept]).await.unwrap();
+        assert_eq!(db.count_pending_files().await.unwrap(), 0);
+        assert!(db.get_file(kept).await.unwrap().is_none());
+        assert!(db.get_file(pending).await.unwrap().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_text_chunks() {
+        let (db, _temp_dir) = create_test_db().await;
+        let file_id = db.upsert_file(&create_test_metadata()).await.unwrap();
+
+        let chunks = [
+            Chunk { index: 0, start: 0, end: 10 },
+            Chunk { index: 1, start: 6, end: 20 },
+        ];
+        db.replace_text_chunks(file_id, &chunks).await.unwrap();
+        assert_eq!(db.get_text_chunk(file_id, 1).await.unwrap(), Some(chunks[1]));
+
+        // Re-indexing replaces the chunks of the previous version
+        db.replace_text_chunks(file_id, &chunks[..1]).await.unwrap();
+        assert_eq!(db.get_text_chunk(file_id, 1).await.unwrap(), None);
+
+        db.delete_file("/test/file.txt").await.unwrap();
+        assert_eq!(db.get_text_chunk(file_id, 0).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_archive_members() {
+        let (db, _temp_dir) = create_test_db().await;
+
+        let mut ids = Vec::new();
+        for path in ["/docs/backup.zip", "/docs/backup.zip!/notes/todo.md", "/docs/backup.zip.txt"] {
+            let metadata = FileMetadata {
+                path: path.to_string(),
+                ..create_test_metadata()
+            };
+            ids.push(db.upsert_file(&metadata).await.unwrap());
+        }
+
+        let member = ArchiveMember {
+            archive_path: "/docs/backup.zip".to_string(),
+            member_path: "notes/todo.md".to_string(),
+            size: 100,
+            compressed_size: Some(60),
+            depth: 1,
+        };
+        db.upsert_archive_member(ids[1], ids[0], &member).await.unwrap();
+        assert_eq!(db.get_archive_member(ids[1]).await.unwrap(), Some(member));
+        assert_eq!(db.get_archive_member(ids[0]).await.unwrap(), None);
+
+        // Members are listed with their archive
+        let paths: Vec<String> = db
+            .list_files_under("/docs/backup.zip")
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|file| file.path)
+            .collect();
+        assert_eq!(paths, vec!["/docs/backup.zip", "/docs/backup.zip!/notes/todo.md"]);
+
+        db.delete_file("/docs/backup.zip").await.unwrap();
+        assert_eq!(db.get_archive_member(ids[1]).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_image_metadata() {
+        let (db, _temp_dir) = create_test_db().await;
+        let file_id = db.upsert_file(&create_test_metadata()).await.unwrap();
+
+        let metadata = ImageMetadata {
+            camera_make: Some("FUJIFILM".to_string()),
+            camera_model: Some("X100V".to_string()),
+            taken_at: Some(1684052521),
+            width: Some(6240),
+            height: Some(4160),
+            orientation: Some(1),
+            latitude: Some(48.5),
+            longitude: Some(-2.25),
+            ..Default::default()
+        };
+        db.upsert_image_metadata(file_id, &metadata).await.unwrap();
+        assert_eq!(db.get_image_metadata(file_id).await.unwrap(), Some(metadata));
+
+        db.upsert_image_metadata(file_id, &ImageMetadata::default()).await.unwrap();
+        assert_eq!(db.get_image_metadata(file_id).await.unwrap(), Some(ImageMetadata::default()));
+        assert_eq!(db.get_image_metadata(file_id + 1).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_symbols() {
+        let (db, _temp_dir) = create_test_db().await;
+        let file_id = db.upsert_file(&create_test_metadata()).await.unwrap();
+
+        let symbol = |name: &str, kind, line| Symbol {
+            name: name.to_string(),
+            kind,
+            line,
+        };
+        let symbols = vec![symbol("Query", SymbolKind::Type, 4), symbol("parse_query", SymbolKind::Method, 7)];
+        db.replace_symbols(file_id, &symbols).await.unwrap();
+        assert_eq!(db.get_symbols(file_id).await.unwrap(), symbols);
+
+        // Re-indexing replaces the symbols of the previous version
+        db.replace_symbols(file_id, &symbols[1..]).await.unwrap();
+        assert_eq!(db.get_symbols(file_id).await.unwrap(), symbols[1..]);
+
+        db.delete_file("/test/file.txt").await.unwrap();
+        assert!(db.get_symbols(file_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pages() {
+        let (db, _temp_dir) = create_test_db().await;
+        let file_id = db.upsert_file(&create_test_metadata()).await.unwrap();
+
+        let content = ExtractedContent::from_pages(vec!["Contents".to_string(), "Chapter one".to_string()]);
+        db.replace_pages(file_id, &content.pages).await.unwrap();
+        let pages = db.get_pages(file_id).await.unwrap();
+        assert_eq!(pages, content.pages);
+        assert_eq!(Page::number_at(&pages, content.text.find("one").unwrap()), Some(2));
+
+        db.replace_pages(file_id, &[]).await.unwrap();
+        assert!(db.get_pages(file_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_key_values() {
+        let (db, _temp_dir) = create_test_db().await;
+        let file_id = db.upsert_file(&create_test_metadata()).await.unwrap();
+
+        let entry = |path: &str, value: &str| KeyValue {
+            path: path.to_string(),
+            value: value.to_string(),
+        };
+        let key_values = vec![entry("database.port", "5432"), entry("database.host", "prod")];
+        db.replace_key_values(file_id, &key_values).await.unwrap();
+        assert_eq!(db.get_key_values(file_id).await.unwrap(), key_values);
+
+        db.replace_key_values(file_id, &[]).await.unwrap();
+        assert!(db.get_key_values(file_id).await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_links() {
+        let (db, _temp_dir) = create_test_db().await;
+        let note = |path: &str| FileMetadata {
+            path: path.to_string(),
+            filename: Path::new(path).file_name().unwrap().to_string_lossy().into_owned(),
+            file_type: FileType::Markdown,
+            ..create_test_metadata()
+        };
+        let link = |target: &str, kind, line| Link {
+            target: target.to_string(),
+            kind,
+            line,
+        };
+        let weekly = db.upsert_file(&note("/vault/weekly.md")).await.unwrap();
+        let daily = db.upsert_file(&note("/vault/daily/2024-03-01.md")).await.unwrap();
+        let alpha = db.upsert_file(&note("/vault/projects/Alpha.md")).await.unwrap();
+
+        let links = vec![link("alpha", LinkKind::Wiki, 3), link("/vault/budget.md", LinkKind::Path, 5)];
+        db.replace_links(weekly, &links).await.unwrap();
+        assert_eq!(db.get_links(weekly).await.unwrap(), links);
+        db.replace_links(daily, &[link("projects/Alpha", LinkKind::Wiki, 1), link("Alpha", LinkKind::Wiki, 2)])
+            .await
+            .unwrap();
+        // Links from a note to itself are not backlinks
+        db.replace_links(alpha, &[link("Alpha", LinkKind::Wiki, 1)]).await.unwrap();
+
+        let backlinks = db.get_backlinks("/vault/projects/Alpha.md").await.unwrap();
+        let lines: Vec<_> = backlinks.iter().map(|b| (b.path.as_str(), b.line)).collect();
+        assert_eq!(lines, vec![("/vault/daily/2024-03-01.md", 1), ("/vault/daily/2024-03-01.md", 2), ("/vault/weekly.md", 3)]);
+        assert_eq!(db.count_backlinks("/vault/projects/Alpha.md").await.unwrap(), 2);
+        assert_eq!(db.count_backlinks("/vault/budget.md").await.unwrap(), 1);
+        assert_eq!(db.count_backlinks("/vault/projects/Beta-Alpha.md").await.unwrap(), 0);
+
+        db.delete_file("/vault/weekly.md").await.unwrap();
+        assert_eq!(db.count_backlinks("/vault/projects/Alpha.md").await.unwrap(), 1);
+    }
+}
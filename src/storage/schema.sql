@@ -11,7 +11,9 @@ CREATE TABLE IF NOT EXISTS files (
     hash TEXT NOT NULL,       -- SHA256 hash for change detection
     created_at INTEGER NOT NULL,
     modified_at INTEGER NOT NULL,
-    indexed_at INTEGER NOT NULL
+    indexed_at INTEGER NOT NULL,
+    pending INTEGER NOT NULL DEFAULT 0, -- 1 until a checkpoint commits Tantivy and the vectors
+    failed INTEGER NOT NULL DEFAULT 0   -- 1 after a transient failure, so the next run retries
 );
 
 -- Content table: Extracted text content
@@ -28,11 +30,87 @@ CREATE TABLE IF NOT EXISTS vectors (
     id INTEGER PRIMARY KEY AUTOINCREMENT,
     file_id INTEGER NOT NULL,
     vector_type TEXT NOT NULL, -- 'text' or 'image'
-    vector_id INTEGER NOT NULL, -- ID in HNSW index
+    vector_id INTEGER NOT NULL, -- ID in HNSW index (the file ID in the flat vector store)
     chunk_index INTEGER DEFAULT 0, -- For chunked documents
+    start_offset INTEGER,      -- Byte range of the chunk in the content text
+    end_offset INTEGER,
     FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
 );
 
+-- Archive members table: Files indexed from inside archives
+CREATE TABLE IF NOT EXISTS archive_members (
+    file_id INTEGER PRIMARY KEY,  -- The member, with a virtual path like 'backup.zip!/notes/todo.md'
+    archive_id INTEGER NOT NULL,  -- The archive on disk holding it
+    member_path TEXT NOT NULL,    -- Path inside the archive; nested archives are separated by '!/'
+    size INTEGER NOT NULL,        -- Uncompressed size
+    compressed_size INTEGER,      -- NULL for formats compressed as a whole
+    depth INTEGER NOT NULL,       -- 1 for members of the archive on disk
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE,
+    FOREIGN KEY (archive_id) REFERENCES files(id) ON DELETE CASCADE
+);
+
+-- Image metadata table: EXIF and XMP properties of photos
+CREATE TABLE IF NOT EXISTS image_metadata (
+    file_id INTEGER PRIMARY KEY,
+    camera_make TEXT,
+    camera_model TEXT,
+    lens TEXT,
+    taken_at INTEGER,             -- Unix timestamp; times without a zone are stored as UTC
+    width INTEGER,
+    height INTEGER,
+    orientation INTEGER,          -- EXIF orientation, 1 (upright) to 8
+    latitude REAL,
+    longitude REAL,
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+);
+
+-- Symbols table: Definitions found in source code
+CREATE TABLE IF NOT EXISTS symbols (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    file_id INTEGER NOT NULL,
+    name TEXT NOT NULL,
+    kind TEXT NOT NULL,           -- 'function', 'method', 'type' or 'constant'
+    line INTEGER NOT NULL,        -- Line of the name, starting at 1
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+);
+
+-- Pages table: Page structure of documents such as PDFs
+CREATE TABLE IF NOT EXISTS pages (
+    file_id INTEGER NOT NULL,
+    number INTEGER NOT NULL,      -- Page number, starting at 1
+    start_offset INTEGER NOT NULL, -- Byte range of the page in the content text
+    end_offset INTEGER NOT NULL,
+    PRIMARY KEY (file_id, number),
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+);
+
+-- Links table: Links from notes to other notes and files
+CREATE TABLE IF NOT EXISTS links (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    file_id INTEGER NOT NULL,     -- The linking file
+    target TEXT NOT NULL,         -- Note name of wiki-links, resolved path of path links
+    kind TEXT NOT NULL,           -- 'wiki' or 'path'
+    line INTEGER NOT NULL,        -- Line of the link, starting at 1
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+);
+
+-- Key values table: Values of structured data files by key path
+CREATE TABLE IF NOT EXISTS key_values (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    file_id INTEGER NOT NULL,
+    key_path TEXT NOT NULL,       -- Keys separated by dots, e.g. 'database.host'
+    value TEXT NOT NULL,
+    FOREIGN KEY (file_id) REFERENCES files(id) ON DELETE CASCADE
+);
+
+-- Roots table: Folders registered for indexing
+CREATE TABLE IF NOT EXISTS roots (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    path TEXT UNIQUE NOT NULL,  -- Canonical absolute path
+    added_at INTEGER NOT NULL,
+    last_indexed_at INTEGER     -- NULL until the first successful run
+);
+
 -- Indexes for performance
 CREATE INDEX IF NOT EXISTS idx_files_path ON files(path);
 CREATE INDEX IF NOT EXISTS idx_files_type ON files(file_type);
@@ -40,6 +118,13 @@ CREATE INDEX IF NOT EXISTS idx_files_modified ON files(modified_at);
 CREATE INDEX IF NOT EXISTS idx_files_hash ON files(hash);
 CREATE INDEX IF NOT EXISTS idx_vectors_file_id ON vectors(file_id);
 CREATE INDEX IF NOT EXISTS idx_vectors_type ON vectors(vector_type);
+CREATE INDEX IF NOT EXISTS idx_archive_members_archive ON archive_members(archive_id);
+CREATE INDEX IF NOT EXISTS idx_image_metadata_taken ON image_metadata(taken_at);
+CREATE INDEX IF NOT EXISTS idx_symbols_file_id ON symbols(file_id);
+CREATE INDEX IF NOT EXISTS idx_symbols_name ON symbols(name);
+CREATE INDEX IF NOT EXISTS idx_links_file_id ON links(file_id);
+CREATE INDEX IF NOT EXISTS idx_links_target ON links(target);
+CREATE INDEX IF NOT EXISTS idx_key_values_file_id ON key_values(file_id);
 
 -- Full-text search index on content
 CREATE VIRTUAL TABLE IF NOT EXISTS content_fts USING fts5(
@@ -1,31 +1,214 @@
 //! Tantivy full-text search index
+//!
+//! Queries search the file name and content, as well as the title,
+//! description and headings of documents, the subject of emails and the
+//! definitions and identifiers of source code, with matches in titles,
+//! subjects, headings and definitions ranking higher. Emails also
+//! have fields for their sender, recipients and date, documents for their
+//! author and creation date, and photos for their
+//! EXIF and XMP properties, usable in queries such as
+//! `camera:x100v year:2023` or `taken:[2023-06-01T00:00:00Z TO 2023-09-01T00:00:00Z]`:
+//!
+//! | Field | Content |
+//! |-------|---------|
+//! | `title` | Document title |
+//! | `description` | Document summary, such as an HTML meta description |
+//! | `headings` | Section headings |
+//! | `tags` | Tags of a note, from its front matter and `#tags` |
+//! | `author` | Document author and last editor |
+//! | `from`, `to` | Sender and recipients of an email |
+//! | `subject` | Subject of an email |
+//! | `date` | Date an email was sent, or a document or note created |
+//! | `camera` | Camera make and model |
+//! | `lens` | Lens model |
+//! | `taken` | Date taken |
+//! | `year` | Year taken |
+//! | `width`, `height` | Dimensions in pixels |
+//! | `orientation` | EXIF orientation, 1 to 8 |
+//! | `lat`, `lon` | GPS coordinates in degrees |
+//! | `sym` | Functions, methods, types and constants defined in source code |
+//! | `ident` | Identifiers used in source code |
+//! | `comment` | Comments in source code |
+//! | `key` | Key paths and values of JSON, YAML and TOML files |
+//!
+//! Source code fields match identifiers by their parts, so that
+//! `sym:parse_query` finds `parse_query` as well as `parseQuery`. The `key`
+//! field holds whole key paths rather than words: `key:database.host` finds
+//! the files with that key or keys below it, and `key:database.host=prod`
+//! the files where it has that value, ignoring case.
 
+use crate::extractors::code::identifier_parts;
+use crate::extractors::{ExtractedContent, ImageMetadata};
 use crate::types::{FileId, SearchResult};
 use crate::Result;
+use chrono::Datelike;
+use std::collections::HashSet;
 use std::path::Path;
+use std::str::FromStr;
 use tantivy::collector::TopDocs;
 use tantivy::query::QueryParser;
 use tantivy::schema::*;
-use tantivy::{doc, Index, IndexReader, IndexWriter, ReloadPolicy};
+use tantivy::tokenizer::{LowerCaser, RawTokenizer, TextAnalyzer, Token, TokenStream, Tokenizer};
+use tantivy::{doc, DateTime, DocId, Index, IndexReader, IndexWriter, ReloadPolicy, Score, SegmentReader};
+
+/// Order of search results
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum SortOrder {
+    /// Best match first
+    #[default]
+    Relevance,
+    /// Most recently taken photos first, then files without a date by relevance
+    TakenNewest,
+    /// Earliest taken photos first, then files without a date by relevance
+    TakenOldest,
+}
+
+impl FromStr for SortOrder {
+    type Err = crate::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "relevance" => Ok(Self::Relevance),
+            "taken" | "taken-desc" => Ok(Self::TakenNewest),
+            "taken-asc" => Ok(Self::TakenOldest),
+            _ => Err(crate::Error::InvalidInput(format!(
+                "Unknown sort order '{}', expected relevance, taken or taken-asc",
+                s
+            ))),
+        }
+    }
+}
+
+impl SortOrder {
+    /// Sort results by this order, keeping the order of equal results
+    ///
+    /// Useful for results merged from several searches; see
+    /// [`TantivyIndex::search_sorted`] to sort all matches of a query.
+    pub fn sort(self, results: &mut [SearchResult]) {
+        if self != Self::Relevance {
+            results.sort_by_key(|result| std::cmp::Reverse(self.key(result.taken_at)));
+        }
+    }
+
+    /// Sort key of a date taken, higher first; files without a date come last
+    pub fn key(self, taken_at: Option<i64>) -> i64 {
+        match (self, taken_at) {
+            (Self::TakenOldest, Some(taken_at)) => -taken_at,
+            (_, Some(taken_at)) => taken_at,
+            (_, None) => i64::MIN,
+        }
+    }
+}
+
+/// Name of the tokenizer of source code fields
+const CODE_TOKENIZER: &str = "code";
+
+/// Name of the tokenizer of the `key` field, keeping key paths whole
+const KEY_TOKENIZER: &str = "key";
+
+/// Tokenizer splitting identifiers into their parts, see [`identifier_parts`]
+#[derive(Clone)]
+struct CodeTokenizer;
+
+impl Tokenizer for CodeTokenizer {
+    type TokenStream<'a> = CodeTokenStream;
+
+    fn token_stream<'a>(&'a mut self, text: &'a str) -> CodeTokenStream {
+        let tokens = identifier_parts(text)
+            .into_iter()
+            .enumerate()
+            .map(|(position, (start, end))| Token {
+                offset_from: start,
+                offset_to: end,
+                position,
+                text: text[start..end].to_string(),
+                position_length: 1,
+            })
+            .collect();
+        CodeTokenStream { tokens, next: 0 }
+    }
+}
+
+struct CodeTokenStream {
+    tokens: Vec<Token>,
+    /// Index of the token after the current one
+    next: usize,
+}
+
+impl TokenStream for CodeTokenStream {
+    fn advance(&mut self) -> bool {
+        self.next += 1;
+        self.next <= self.tokens.len()
+    }
+
+    fn token(&self) -> &Token {
+        &self.tokens[self.next - 1]
+    }
+
+    fn token_mut(&mut self) -> &mut Token {
+        &mut self.tokens[self.next - 1]
+    }
+}
 
 /// Tantivy search index for BM25 keyword search
 pub struct TantivyIndex {
     index: Index,
     reader: IndexReader,
-    writer: IndexWriter,
+    /// `None` when opened read-only
+    writer: Option<IndexWriter>,
     file_id_field: Field,
     path_field: Field,
     filename_field: Field,
     content_field: Field,
+    title_field: Field,
+    description_field: Field,
+    headings_field: Field,
+    tags_field: Field,
+    author_field: Field,
+    from_field: Field,
+    to_field: Field,
+    subject_field: Field,
+    date_field: Field,
+    camera_field: Field,
+    lens_field: Field,
+    taken_field: Field,
+    year_field: Field,
+    width_field: Field,
+    height_field: Field,
+    orientation_field: Field,
+    lat_field: Field,
+    lon_field: Field,
+    sym_field: Field,
+    ident_field: Field,
+    comment_field: Field,
+    key_field: Field,
 }
 
 impl TantivyIndex {
     /// Create a new Tantivy index
     ///
+    /// Takes the index's writer lock until dropped.
+    ///
     /// # Arguments
     /// * `index_path` - Directory to store the index
     pub fn new<P: AsRef<Path>>(index_path: P) -> Result<Self> {
-        let index_path = index_path.as_ref();
+        Self::open(index_path.as_ref(), true)
+    }
+
+    /// Open an existing index for searching only
+    ///
+    /// Doesn't take the writer lock, so searches work while another
+    /// process, e.g. `khoj watch`, is indexing. Commits of the writer are
+    /// picked up automatically. Unlike [`new`](Self::new), an index with an
+    /// older schema is an error rather than rebuilt.
+    ///
+    /// # Arguments
+    /// * `index_path` - Directory of the index
+    pub fn open_read_only<P: AsRef<Path>>(index_path: P) -> Result<Self> {
+        Self::open(index_path.as_ref(), false)
+    }
+
+    fn open(index_path: &Path, writable: bool) -> Result<Self> {
 
         // Create schema
         let mut schema_builder = Schema::builder();
@@ -33,18 +216,76 @@ impl TantivyIndex {
         let path_field = schema_builder.add_text_field("path", STRING | STORED);
         let filename_field = schema_builder.add_text_field("filename", TEXT | STORED);
         let content_field = schema_builder.add_text_field("content", TEXT);
+        let title_field = schema_builder.add_text_field("title", TEXT);
+        let description_field = schema_builder.add_text_field("description", TEXT);
+        let headings_field = schema_builder.add_text_field("headings", TEXT);
+        let tags_field = schema_builder.add_text_field("tags", TEXT);
+        let author_field = schema_builder.add_text_field("author", TEXT);
+        let from_field = schema_builder.add_text_field("from", TEXT);
+        let to_field = schema_builder.add_text_field("to", TEXT);
+        let subject_field = schema_builder.add_text_field("subject", TEXT);
+        let date_field = schema_builder.add_date_field(
+            "date",
+            DateOptions::from(INDEXED).set_precision(DateTimePrecision::Seconds),
+        );
+        let camera_field = schema_builder.add_text_field("camera", TEXT);
+        let lens_field = schema_builder.add_text_field("lens", TEXT);
+        let taken_field = schema_builder.add_date_field(
+            "taken",
+            DateOptions::from(INDEXED | STORED | FAST).set_precision(DateTimePrecision::Seconds),
+        );
+        let year_field = schema_builder.add_u64_field("year", INDEXED);
+        let width_field = schema_builder.add_u64_field("width", INDEXED);
+        let height_field = schema_builder.add_u64_field("height", INDEXED);
+        let orientation_field = schema_builder.add_u64_field("orientation", INDEXED);
+        let lat_field = schema_builder.add_f64_field("lat", INDEXED);
+        let lon_field = schema_builder.add_f64_field("lon", INDEXED);
+        let code_options = TextOptions::default().set_indexing_options(
+            TextFieldIndexing::default()
+                .set_tokenizer(CODE_TOKENIZER)
+                .set_index_option(IndexRecordOption::WithFreqsAndPositions),
+        );
+        let sym_field = schema_builder.add_text_field("sym", code_options.clone());
+        let ident_field = schema_builder.add_text_field("ident", code_options);
+        let comment_field = schema_builder.add_text_field("comment", TEXT);
+        let key_field = schema_builder.add_text_field(
+            "key",
+            TextOptions::default().set_indexing_options(
+                TextFieldIndexing::default()
+                    .set_tokenizer(KEY_TOKENIZER)
+                    .set_index_option(IndexRecordOption::WithFreqs),
+            ),
+        );
         let schema = schema_builder.build();
 
-        // Create or open index
-        let index = if index_path.exists() {
-            Index::open_in_dir(index_path)?
-        } else {
-            std::fs::create_dir_all(index_path)?;
-            Index::create_in_dir(index_path, schema.clone())?
+        // Create or open index; an index with an older schema is rebuilt
+        // from scratch, see Indexer::new
+        let index = match Index::open_in_dir(index_path) {
+            Ok(index) if index.schema() == schema => index,
+            Ok(_) if !writable => {
+                return Err(crate::Error::SearchIndex(
+                    "Index was built by another version, re-index to update it".to_string(),
+                ))
+            }
+            Err(e) if !writable => return Err(e.into()),
+            _ => {
+                if index_path.exists() {
+                    std::fs::remove_dir_all(index_path)?;
+                }
+                std::fs::create_dir_all(index_path)?;
+                Index::create_in_dir(index_path, schema.clone())?
+            }
         };
+        // Tokenizers are not stored with the index
+        index
+            .tokenizers()
+            .register(CODE_TOKENIZER, TextAnalyzer::builder(CodeTokenizer).filter(LowerCaser).build());
+        index
+            .tokenizers()
+            .register(KEY_TOKENIZER, TextAnalyzer::builder(RawTokenizer::default()).filter(LowerCaser).build());
 
         // Create writer with 50MB buffer
-        let writer = index.writer(50_000_000)?;
+        let writer = if writable { Some(index.writer(50_000_000)?) } else { None };
 
         // Create reader with auto-reload
         let reader = index
@@ -60,6 +301,28 @@ impl TantivyIndex {
             path_field,
             filename_field,
             content_field,
+            title_field,
+            description_field,
+            headings_field,
+            tags_field,
+            author_field,
+            from_field,
+            to_field,
+            subject_field,
+            date_field,
+            camera_field,
+            lens_field,
+            taken_field,
+            year_field,
+            width_field,
+            height_field,
+            orientation_field,
+            lat_field,
+            lon_field,
+            sym_field,
+            ident_field,
+            comment_field,
+            key_field,
         })
     }
 
@@ -77,25 +340,150 @@ impl TantivyIndex {
         filename: &str,
         content: &str,
     ) -> Result<()> {
-        // Delete existing document with this file_id
-        let term = Term::from_field_i64(self.file_id_field, file_id);
-        self.writer.delete_term(term);
+        let doc = self.document(file_id, path, filename, content);
+        self.replace_document(file_id, doc)
+    }
+
+    /// Add or update a document in the index, with the properties found by
+    /// its extractor
+    ///
+    /// The `title`, `description`, `headings`, `tags`, `author`, `from`, `to`
+    /// and `subject` entries of the content's metadata are indexed in fields of
+    /// their own, as is `date`, or else `created`, if it is an RFC 3339 date.
+    /// The last editor, `modified_by`, goes into `author` as well. The symbols of source
+    /// code go into `sym`, and its `identifiers` and `comments` into
+    /// `ident` and `comment`. The key values of structured data go into
+    /// `key`, as their key paths, the paths above them and `path=value`.
+    ///
+    /// # Arguments
+    /// * `file_id` - File ID
+    /// * `path` - File path
+    /// * `filename` - Filename
+    /// * `content` - Extracted content of the file
+    pub fn upsert_extracted_document(
+        &mut self,
+        file_id: FileId,
+        path: &str,
+        filename: &str,
+        content: &ExtractedContent,
+    ) -> Result<()> {
+        let mut doc = self.document(file_id, path, filename, &content.text);
+        for (field, name) in [
+            (self.title_field, "title"),
+            (self.description_field, "description"),
+            (self.headings_field, "headings"),
+            (self.tags_field, "tags"),
+            (self.author_field, "author"),
+            (self.author_field, "modified_by"),
+            (self.from_field, "from"),
+            (self.to_field, "to"),
+            (self.subject_field, "subject"),
+            (self.ident_field, "identifiers"),
+            (self.comment_field, "comments"),
+        ] {
+            if let Some(value) = content.metadata.get(name) {
+                doc.add_text(field, value);
+            }
+        }
+        for symbol in &content.symbols {
+            doc.add_text(self.sym_field, &symbol.name);
+        }
+        let mut key_paths = HashSet::new();
+        for entry in &content.key_values {
+            doc.add_text(self.key_field, format!("{}={}", entry.path, entry.value));
+            let mut path = entry.path.as_str();
+            while key_paths.insert(path) {
+                doc.add_text(self.key_field, path);
+                match path.rfind('.') {
+                    Some(end) => path = &path[..end],
+                    None => break,
+                }
+            }
+        }
+        if let Some(date) = content.metadata.get("date").or_else(|| content.metadata.get("created")) {
+            if let Ok(date) = chrono::DateTime::parse_from_rfc3339(date) {
+                doc.add_date(self.date_field, DateTime::from_timestamp_secs(date.timestamp()));
+            }
+        }
+        self.replace_document(file_id, doc)
+    }
 
-        // Add new document
-        let doc = doc!(
+    /// Add or update an image in the index, with its EXIF and XMP properties
+    ///
+    /// # Arguments
+    /// * `file_id` - File ID
+    /// * `path` - File path
+    /// * `filename` - Filename
+    /// * `content` - Text recognized in the image, or a description of it
+    /// * `metadata` - Properties read from the image
+    pub fn upsert_image_document(
+        &mut self,
+        file_id: FileId,
+        path: &str,
+        filename: &str,
+        content: &str,
+        metadata: &ImageMetadata,
+    ) -> Result<()> {
+        let mut doc = self.document(file_id, path, filename, content);
+        if let Some(camera) = metadata.camera() {
+            doc.add_text(self.camera_field, camera);
+        }
+        if let Some(lens) = &metadata.lens {
+            doc.add_text(self.lens_field, lens);
+        }
+        if let Some(taken_at) = metadata.taken_at {
+            doc.add_date(self.taken_field, DateTime::from_timestamp_secs(taken_at));
+            if let Some(taken_at) = chrono::DateTime::from_timestamp(taken_at, 0) {
+                doc.add_u64(self.year_field, taken_at.year() as u64);
+            }
+        }
+        for (field, value) in [
+            (self.width_field, metadata.width),
+            (self.height_field, metadata.height),
+            (self.orientation_field, metadata.orientation.map(u32::from)),
+        ] {
+            if let Some(value) = value {
+                doc.add_u64(field, value.into());
+            }
+        }
+        if let (Some(lat), Some(lon)) = (metadata.latitude, metadata.longitude) {
+            doc.add_f64(self.lat_field, lat);
+            doc.add_f64(self.lon_field, lon);
+        }
+        self.replace_document(file_id, doc)
+    }
+
+    /// Document with the fields every file has
+    fn document(&self, file_id: FileId, path: &str, filename: &str, content: &str) -> TantivyDocument {
+        doc!(
             self.file_id_field => file_id,
             self.path_field => path,
             self.filename_field => filename,
             self.content_field => content,
-        );
+        )
+    }
+
+    /// Writer of the index, unless it was opened read-only
+    fn writer(&mut self) -> Result<&mut IndexWriter> {
+        self.writer
+            .as_mut()
+            .ok_or_else(|| crate::Error::SearchIndex("Index is opened read-only".to_string()))
+    }
 
-        self.writer.add_document(doc)?;
+    /// Replace the document of a file
+    fn replace_document(&mut self, file_id: FileId, doc: TantivyDocument) -> Result<()> {
+        // Delete existing document with this file_id
+        let term = Term::from_field_i64(self.file_id_field, file_id);
+        let writer = self.writer()?;
+        writer.delete_term(term);
+
+        writer.add_document(doc)?;
         Ok(())
     }
 
     /// Commit changes to the index
     pub fn commit(&mut self) -> Result<()> {
-        self.writer.commit()?;
+        self.writer()?.commit()?;
         // Reload reader to see new documents
         self.reader.reload()?;
         Ok(())
@@ -110,18 +498,64 @@ impl TantivyIndex {
     /// # Returns
     /// List of search results with scores
     pub fn search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        self.search_sorted(query, limit, SortOrder::Relevance)
+    }
+
+    /// Search the index, ordering all matches before taking the best ones
+    ///
+    /// # Arguments
+    /// * `query` - Search query string
+    /// * `limit` - Maximum number of results
+    /// * `order` - Order of the results
+    ///
+    /// # Returns
+    /// List of search results with their BM25 scores
+    pub fn search_sorted(&self, query: &str, limit: usize, order: SortOrder) -> Result<Vec<SearchResult>> {
         let searcher = self.reader.searcher();
 
-        // Parse query (searches in filename and content fields)
-        let query_parser = QueryParser::for_index(
+        // Parse query (searches in filename, content and document properties)
+        let mut query_parser = QueryParser::for_index(
             &self.index,
-            vec![self.filename_field, self.content_field],
+            vec![
+                self.filename_field,
+                self.content_field,
+                self.title_field,
+                self.description_field,
+                self.headings_field,
+                self.subject_field,
+                self.sym_field,
+                self.ident_field,
+                self.key_field,
+            ],
         );
+        query_parser.set_field_boost(self.title_field, 3.0);
+        query_parser.set_field_boost(self.subject_field, 3.0);
+        query_parser.set_field_boost(self.headings_field, 1.5);
+        // Definitions rank above usages
+        query_parser.set_field_boost(self.sym_field, 2.0);
 
         let query = query_parser.parse_query(query)?;
 
-        // Execute search
-        let top_docs = searcher.search(&query, &TopDocs::with_limit(limit))?;
+        // Execute search, ranking by date taken first if requested
+        let top_docs: Vec<(Score, _)> = if order == SortOrder::Relevance {
+            searcher.search(&query, &TopDocs::with_limit(limit))?
+        } else {
+            let collector = TopDocs::with_limit(limit).tweak_score(move |segment: &SegmentReader| {
+                let taken = segment.fast_fields().date("taken").ok();
+                move |doc: DocId, score: Score| {
+                    let taken_at = taken
+                        .as_ref()
+                        .and_then(|column| column.first(doc))
+                        .map(|taken| taken.into_timestamp_secs());
+                    (order.key(taken_at), score)
+                }
+            });
+            searcher
+                .search(&query, &collector)?
+                .into_iter()
+                .map(|((_, score), doc_address)| (score, doc_address))
+                .collect()
+        };
 
         // Convert results
         let mut results = Vec::new();
@@ -151,6 +585,11 @@ impl TantivyIndex {
                 filename,
                 score,
                 snippet: None, // Will be added by search engine
+                chunk_index: None,
+                taken_at: doc
+                    .get_first(self.taken_field)
+                    .and_then(|v| v.as_datetime())
+                    .map(|taken| taken.into_timestamp_secs()),
             });
         }
 
@@ -163,12 +602,23 @@ impl TantivyIndex {
     /// * `file_id` - File ID to delete
     pub fn delete_document(&mut self, file_id: FileId) -> Result<()> {
         let term = Term::from_field_i64(self.file_id_field, file_id);
-        self.writer.delete_term(term);
-        self.writer.commit()?;
+        let writer = self.writer()?;
+        writer.delete_term(term);
+        writer.commit()?;
         self.reader.reload()?;
         Ok(())
     }
 
+    /// Delete a document on the next commit
+    ///
+    /// Unlike [`delete_document`](Self::delete_document) this does not
+    /// commit, so many deletions can be batched with other writes.
+    pub fn queue_delete(&mut self, file_id: FileId) -> Result<()> {
+        let term = Term::from_field_i64(self.file_id_field, file_id);
+        self.writer()?.delete_term(term);
+        Ok(())
+    }
+
     /// Get the number of documents in the index
     pub fn num_docs(&self) -> u64 {
         let searcher = self.reader.searcher();
@@ -299,6 +749,130 @@ mod tests {
         assert_eq!(results.len(), 1);
     }
 
+    #[test]
+    fn test_queue_delete() {
+        let (mut index, _temp_dir) = create_test_index();
+
+        index
+            .upsert_document(1, "/test/file1.txt", "file1.txt", "apple orange pear")
+            .unwrap();
+        index.commit().unwrap();
+
+        index.queue_delete(1).unwrap();
+        // Still visible until the next commit
+        assert_eq!(index.search("apple", 10).unwrap().len(), 1);
+
+        index.commit().unwrap();
+        assert_eq!(index.search("apple", 10).unwrap().len(), 0);
+    }
+
+    #[test]
+    fn test_document_properties() {
+        let (mut index, _temp_dir) = create_test_index();
+
+        let mut page = ExtractedContent::new("Steps to ship a release of the billing service".to_string());
+        page.metadata.insert("title".to_string(), "Deployment runbook".to_string());
+        page.metadata.insert("description".to_string(), "Shipping checklist".to_string());
+        page.metadata.insert("headings".to_string(), "Rollback\nMonitoring".to_string());
+        index.upsert_extracted_document(1, "/wiki/deploy.html", "deploy.html", &page).unwrap();
+        let notes = ExtractedContent::new("The deployment runbook needs an update".to_string());
+        index.upsert_extracted_document(2, "/notes.txt", "notes.txt", &notes).unwrap();
+        index.commit().unwrap();
+
+        // Searched by default, titles ranking first
+        let results = index.search("deployment runbook", 10).unwrap();
+        assert_eq!(results.iter().map(|r| r.file_id).collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(index.search("rollback", 10).unwrap()[0].file_id, 1);
+        assert_eq!(index.search("checklist", 10).unwrap()[0].file_id, 1);
+
+        // Or by field
+        assert_eq!(index.search("title:runbook", 10).unwrap().len(), 1);
+        assert!(index.search("headings:billing", 10).unwrap().is_empty());
+
+        let mut manual = ExtractedContent::new("Installation and maintenance".to_string());
+        manual.metadata.insert("author".to_string(), "Grace Hopper".to_string());
+        manual.metadata.insert("modified_by".to_string(), "Ada Lovelace".to_string());
+        manual.metadata.insert("created".to_string(), "2019-03-14T09:30:00+01:00".to_string());
+        index.upsert_extracted_document(3, "/manual.pdf", "manual.pdf", &manual).unwrap();
+        index.commit().unwrap();
+        assert_eq!(index.search("author:hopper", 10).unwrap()[0].file_id, 3);
+        assert_eq!(index.search("author:lovelace", 10).unwrap()[0].file_id, 3);
+        assert_eq!(index.search("date:[2019-01-01T00:00:00Z TO 2020-01-01T00:00:00Z]", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_email_fields() {
+        let (mut index, _temp_dir) = create_test_index();
+
+        let mut message = ExtractedContent::new("From: Alice\nSubject: Q3 budget\n\nPlease review".to_string());
+        for (name, value) in [
+            ("from", "Alice Martin <alice@example.com>"),
+            ("to", "bob@example.com"),
+            ("subject", "Q3 budget"),
+            ("date", "2024-05-14T09:30:00+02:00"),
+        ] {
+            message.metadata.insert(name.to_string(), value.to_string());
+        }
+        index.upsert_extracted_document(1, "/mail/1.eml", "1.eml", &message).unwrap();
+        index.commit().unwrap();
+
+        for query in [
+            "from:alice@example.com",
+            "to:bob",
+            "subject:budget",
+            "budget AND date:[2024-05-01T00:00:00Z TO 2024-06-01T00:00:00Z]",
+        ] {
+            assert_eq!(index.search(query, 10).unwrap().len(), 1, "{}", query);
+        }
+        assert!(index.search("from:bob", 10).unwrap().is_empty());
+        assert!(index.search("date:[2024-06-01T00:00:00Z TO *]", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_code_fields() {
+        let (mut index, _temp_dir) = create_test_index();
+
+        let source = |text: &str, symbols: &[&str], identifiers: &str, comments: &str| {
+            let mut content = ExtractedContent::new(text.to_string());
+            content.symbols = symbols
+                .iter()
+                .map(|name| crate::extractors::Symbol {
+                    name: name.to_string(),
+                    kind: crate::extractors::SymbolKind::Function,
+                    line: 1,
+                })
+                .collect();
+            content.metadata.insert("identifiers".to_string(), identifiers.to_string());
+            content.metadata.insert("comments".to_string(), comments.to_string());
+            content
+        };
+        let usage = source("let q = parse_query(text);", &["run"], "run q parse_query text", "");
+        let definition = source(
+            "// Parse a search query\nfn parseQuery(text: &str) {}",
+            &["parseQuery"],
+            "parseQuery text",
+            "Parse a search query",
+        );
+        index.upsert_extracted_document(1, "/src/main.rs", "main.rs", &usage).unwrap();
+        index.upsert_extracted_document(2, "/src/query.rs", "query.rs", &definition).unwrap();
+        index.commit().unwrap();
+
+        // Definitions only, whatever the case convention
+        for query in ["sym:parse_query", "sym:parseQuery", "sym:ParseQuery"] {
+            let results = index.search(query, 10).unwrap();
+            assert_eq!(results.iter().map(|r| r.file_id).collect::<Vec<_>>(), vec![2], "{}", query);
+        }
+
+        // Definitions first, then usages
+        let results = index.search("parse_query", 10).unwrap();
+        assert_eq!(results.iter().map(|r| r.file_id).collect::<Vec<_>>(), vec![2, 1]);
+
+        // Identifier parts and comments on their own
+        assert_eq!(index.search("ident:query", 10).unwrap().len(), 2);
+        assert_eq!(index.search("comment:search", 10).unwrap()[0].file_id, 2);
+        assert!(index.search("comment:text", 10).unwrap().is_empty());
+    }
+
     #[test]
     fn test_search_filename() {
         let (mut index, _temp_dir) = create_test_index();
@@ -344,4 +918,110 @@ mod tests {
         let results = index.search("anything", 10).unwrap();
         assert_eq!(results.len(), 0);
     }
-}
\ No newline at end of file
+
+    fn photo(camera_model: &str, taken_at: Option<i64>) -> ImageMetadata {
+        ImageMetadata {
+            camera_make: Some("FUJIFILM".to_string()),
+            camera_model: Some(camera_model.to_string()),
+            taken_at,
+            width: Some(6240),
+            height: Some(4160),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_image_fields() {
+        let (mut index, _temp_dir) = create_test_index();
+
+        // 2023-05-14, 2022-08-01 and 2023-11-30
+        let photos = [
+            (1, photo("X100V", Some(1684052521))),
+            (2, photo("X100V", Some(1659312000))),
+            (3, photo("X-T5", Some(1701302400))),
+            (4, photo("X100V", None)),
+        ];
+        for (id, metadata) in &photos {
+            let name = format!("DSCF{}.jpg", id);
+            index
+                .upsert_image_document(*id, &format!("/photos/{}", name), &name, "image file", metadata)
+                .unwrap();
+        }
+        index.upsert_document(5, "/notes/x100v.txt", "x100v.txt", "image file").unwrap();
+        index.commit().unwrap();
+
+        let ids = |results: Vec<SearchResult>| results.into_iter().map(|r| r.file_id).collect::<Vec<_>>();
+        assert_eq!(ids(index.search("camera:x100v AND year:2023", 10).unwrap()), vec![1]);
+        assert_eq!(
+            ids(index.search("taken:[2023-01-01T00:00:00Z TO 2024-01-01T00:00:00Z]", 10).unwrap()).len(),
+            2
+        );
+        assert_eq!(index.search("width:6240", 10).unwrap().len(), 4);
+
+        // Photos by date taken, then the other matches
+        let results = index.search_sorted("image", 10, SortOrder::TakenNewest).unwrap();
+        assert_eq!(results[0].taken_at, Some(1701302400));
+        assert_eq!(ids(results)[..3], [3, 1, 2]);
+        let results = index.search_sorted("image", 3, SortOrder::TakenOldest).unwrap();
+        assert_eq!(ids(results), vec![2, 1, 3]);
+    }
+
+    #[test]
+    fn test_read_only_while_writing() {
+        let (mut index, temp_dir) = create_test_index();
+        index.upsert_document(1, "/notes/a.txt", "a.txt", "hello world").unwrap();
+        index.commit().unwrap();
+
+        // Opens while the writer holds its lock
+        let mut reader = TantivyIndex::open_read_only(temp_dir.path().join("tantivy")).unwrap();
+        assert_eq!(reader.search("hello", 10).unwrap().len(), 1);
+        assert!(reader.upsert_document(2, "/notes/b.txt", "b.txt", "hello").is_err());
+        assert!(reader.queue_delete(1).is_err());
+    }
+
+    #[test]
+    fn test_rebuild_older_schema() {
+        let temp_dir = TempDir::new().unwrap();
+        let index_path = temp_dir.path().join("tantivy");
+        std::fs::create_dir_all(&index_path).unwrap();
+
+        let mut schema_builder = Schema::builder();
+        let content = schema_builder.add_text_field("content", TEXT);
+        let old = Index::create_in_dir(&index_path, schema_builder.build()).unwrap();
+        let mut writer: IndexWriter = old.writer(15_000_000).unwrap();
+        writer.add_document(doc!(content => "stale")).unwrap();
+        writer.commit().unwrap();
+        drop(writer);
+
+        // Only the writer rebuilds it
+        assert!(TantivyIndex::open_read_only(&index_path).is_err());
+        assert!(index_path.join("meta.json").exists());
+
+        let mut index = TantivyIndex::new(&index_path).unwrap();
+        assert_eq!(index.num_docs(), 0);
+        index
+            .upsert_image_document(1, "/a.jpg", "a.jpg", "image file", &photo("X100V", None))
+            .unwrap();
+        index.commit().unwrap();
+        assert_eq!(index.search("camera:x100v", 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_sort_order() {
+        assert_eq!("taken".parse::<SortOrder>().unwrap(), SortOrder::TakenNewest);
+        assert!("newest".parse::<SortOrder>().is_err());
+
+        let result = |file_id, taken_at| SearchResult {
+            file_id,
+            path: String::new(),
+            filename: String::new(),
+            score: 1.0,
+            snippet: None,
+            chunk_index: None,
+            taken_at,
+        };
+        let mut results = vec![result(1, None), result(2, Some(10)), result(3, Some(20)), result(4, None)];
+        SortOrder::TakenOldest.sort(&mut results);
+        assert_eq!(results.iter().map(|r| r.file_id).collect::<Vec<_>>(), vec![2, 3, 1, 4]);
+    }
+}
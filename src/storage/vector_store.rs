@@ -3,12 +3,21 @@
 //! Uses a flat index with exact nearest neighbor search.
 //! Perfect for offline operation with datasets up to ~100k vectors.
 //! For larger datasets, consider adding HNSW or other ANN algorithms.
+//!
+//! A file can have several vectors, one per chunk of its text; it matches
+//! with the score of its best chunk.
+//!
+//! On disk, the store is a JSON snapshot plus a log of the files changed
+//! since, one JSON line each, next to it. [`VectorStore::save_changes`]
+//! only appends to the log, so that frequent checkpoints don't rewrite the
+//! whole store, and folds the log into a new snapshot once it outgrows it.
 
 use crate::types::{Embedding, FileId};
 use crate::Result;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, RwLock};
 
 /// Simple flat vector store for offline semantic search
@@ -16,8 +25,10 @@ use std::sync::{Arc, RwLock};
 /// Uses exact nearest neighbor search with cosine similarity.
 /// All data stored in memory and can be persisted to disk.
 pub struct VectorStore {
-    /// Map from file ID to embedding vector
-    vectors: Arc<RwLock<HashMap<FileId, Vec<f32>>>>,
+    /// Map from file ID to its embedding vectors, by chunk index
+    vectors: Arc<RwLock<HashMap<FileId, Vec<Vec<f32>>>>>,
+    /// Files upserted or deleted since the store was last written to disk
+    changed: Arc<RwLock<HashSet<FileId>>>,
     /// Dimension of embeddings (e.g., 384 for all-MiniLM-L6-v2)
     dimension: usize,
 }
@@ -30,6 +41,7 @@ impl VectorStore {
     pub fn new(dimension: usize) -> Result<Self> {
         Ok(Self {
             vectors: Arc::new(RwLock::new(HashMap::new())),
+            changed: Arc::new(RwLock::new(HashSet::new())),
             dimension,
         })
     }
@@ -40,7 +52,18 @@ impl VectorStore {
     /// * `file_id` - File ID
     /// * `embedding` - Embedding vector (must be normalized)
     pub fn upsert(&self, file_id: FileId, embedding: &Embedding) -> Result<()> {
-        if embedding.len() != self.dimension {
+        self.upsert_chunks(file_id, std::slice::from_ref(embedding))
+    }
+
+    /// Insert or update the vectors of a chunked file
+    ///
+    /// Replaces all previous vectors of the file.
+    ///
+    /// # Arguments
+    /// * `file_id` - File ID
+    /// * `embeddings` - One embedding per chunk, in chunk order (must be normalized)
+    pub fn upsert_chunks(&self, file_id: FileId, embeddings: &[Embedding]) -> Result<()> {
+        if let Some(embedding) = embeddings.iter().find(|e| e.len() != self.dimension) {
             return Err(crate::Error::Embedding(format!(
                 "Embedding dimension mismatch: expected {}, got {}",
                 self.dimension,
@@ -49,7 +72,12 @@ impl VectorStore {
         }
 
         let mut vectors = self.vectors.write().unwrap();
-        vectors.insert(file_id, embedding.clone());
+        if embeddings.is_empty() {
+            vectors.remove(&file_id);
+        } else {
+            vectors.insert(file_id, embeddings.to_vec());
+        }
+        self.changed.write().unwrap().insert(file_id);
 
         Ok(())
     }
@@ -63,6 +91,22 @@ impl VectorStore {
     /// # Returns
     /// List of (file_id, similarity_score) tuples, sorted by score descending
     pub fn search(&self, query_embedding: &Embedding, limit: usize) -> Result<Vec<(FileId, f32)>> {
+        Ok(self
+            .search_chunks(query_embedding, limit)?
+            .into_iter()
+            .map(|(file_id, _, similarity)| (file_id, similarity))
+            .collect())
+    }
+
+    /// Search for similar vectors, reporting the best matching chunk of each file
+    ///
+    /// # Arguments
+    /// * `query_embedding` - Query embedding vector (should be normalized)
+    /// * `limit` - Number of files to return
+    ///
+    /// # Returns
+    /// List of (file_id, chunk_index, similarity_score) tuples, sorted by score descending
+    pub fn search_chunks(&self, query_embedding: &Embedding, limit: usize) -> Result<Vec<(FileId, usize, f32)>> {
         if query_embedding.len() != self.dimension {
             return Err(crate::Error::Embedding(format!(
                 "Query embedding dimension mismatch: expected {}, got {}",
@@ -73,17 +117,21 @@ impl VectorStore {
 
         let vectors = self.vectors.read().unwrap();
 
-        // Calculate cosine similarity for all vectors
-        let mut scores: Vec<(FileId, f32)> = vectors
+        // Score every file by its most similar chunk
+        let mut scores: Vec<(FileId, usize, f32)> = vectors
             .iter()
-            .map(|(&file_id, embedding)| {
-                let similarity = cosine_similarity(query_embedding, embedding);
-                (file_id, similarity)
+            .filter_map(|(&file_id, chunks)| {
+                chunks
+                    .iter()
+                    .map(|embedding| cosine_similarity(query_embedding, embedding))
+                    .enumerate()
+                    .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+                    .map(|(chunk_index, similarity)| (file_id, chunk_index, similarity))
             })
             .collect();
 
         // Sort by similarity descending
-        scores.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        scores.sort_by(|a, b| b.2.partial_cmp(&a.2).unwrap_or(std::cmp::Ordering::Equal));
 
         // Take top k results
         scores.truncate(limit);
@@ -97,11 +145,13 @@ impl VectorStore {
     /// * `file_id` - File ID to delete
     pub fn delete(&self, file_id: FileId) -> Result<()> {
         let mut vectors = self.vectors.write().unwrap();
-        vectors.remove(&file_id);
+        if vectors.remove(&file_id).is_some() {
+            self.changed.write().unwrap().insert(file_id);
+        }
         Ok(())
     }
 
-    /// Get the number of vectors in the store
+    /// Get the number of files with vectors in the store
     pub fn len(&self) -> usize {
         self.vectors.read().unwrap().len()
     }
@@ -113,10 +163,13 @@ impl VectorStore {
 
     /// Save the vector store to disk
     ///
+    /// Writes a new snapshot and drops the log of changes.
+    ///
     /// # Arguments
     /// * `path` - Path to save the vector store
     pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         let vectors = self.vectors.read().unwrap();
+        let mut changed = self.changed.write().unwrap();
 
         // Convert to a serializable format
         let data = VectorStoreData {
@@ -124,9 +177,67 @@ impl VectorStore {
             vectors: vectors.clone(),
         };
 
+        // Write to a temporary file first so a crash never leaves a
+        // truncated store behind
+        let path = path.as_ref();
+        let tmp_path = path.with_extension("json.tmp");
         let json = serde_json::to_string(&data)?;
-        fs::write(path, json)?;
+        fs::write(&tmp_path, json)?;
+        fs::rename(&tmp_path, path)?;
 
+        let log_path = log_path(path);
+        if log_path.exists() {
+            fs::remove_file(log_path)?;
+        }
+        changed.clear();
+
+        Ok(())
+    }
+
+    /// Save the files changed since the store was last written to disk
+    ///
+    /// Appends them to the log next to the snapshot at `path`. Writes a
+    /// full snapshot instead when there is none yet or the log grew larger
+    /// than it, so the I/O stays proportional to the changes.
+    ///
+    /// # Arguments
+    /// * `path` - Path of the snapshot
+    pub fn save_changes<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        let log_path = log_path(path);
+        let snapshot_len = match fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(_) => return self.save(path),
+        };
+
+        {
+            let vectors = self.vectors.read().unwrap();
+            let mut changed = self.changed.write().unwrap();
+            if changed.is_empty() {
+                return Ok(());
+            }
+
+            let mut lines = String::new();
+            for file_id in changed.iter() {
+                // No vectors mark a deleted file
+                let entry = LogEntry {
+                    file_id: *file_id,
+                    vectors: vectors.get(file_id).map(Vec::as_slice).unwrap_or_default(),
+                };
+                lines.push_str(&serde_json::to_string(&entry)?);
+                lines.push('\n');
+            }
+
+            let mut log = fs::OpenOptions::new().read(true).create(true).append(true).open(&log_path)?;
+            drop_torn_line(&mut log)?;
+            log.write_all(lines.as_bytes())?;
+            log.sync_data()?;
+            changed.clear();
+        }
+
+        if fs::metadata(&log_path)?.len() > snapshot_len {
+            self.save(path)?;
+        }
         Ok(())
     }
 
@@ -135,21 +246,105 @@ impl VectorStore {
     /// # Arguments
     /// * `path` - Path to load the vector store from
     pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
         let json = fs::read_to_string(path)?;
-        let data: VectorStoreData = serde_json::from_str(&json)?;
+        let data: StoredVectorStoreData = serde_json::from_str(&json)?;
+
+        let mut vectors: HashMap<FileId, Vec<Vec<f32>>> = data
+            .vectors
+            .into_iter()
+            .map(|(file_id, vectors)| match vectors {
+                StoredVectors::Chunks(chunks) => (file_id, chunks),
+                StoredVectors::Single(vector) => (file_id, vec![vector]),
+            })
+            .collect();
+
+        // Replay the changes made since the snapshot
+        if let Ok(log) = fs::read_to_string(log_path(path)) {
+            for line in log.lines() {
+                // A line cut short by a crash belongs to an unfinished checkpoint
+                let Ok(entry) = serde_json::from_str::<StoredLogEntry>(line) else {
+                    tracing::warn!("Ignoring incomplete vector log entry in {}", path.display());
+                    continue;
+                };
+                if entry.vectors.is_empty() {
+                    vectors.remove(&entry.file_id);
+                } else {
+                    vectors.insert(entry.file_id, entry.vectors);
+                }
+            }
+        }
 
         Ok(Self {
-            vectors: Arc::new(RwLock::new(data.vectors)),
+            vectors: Arc::new(RwLock::new(vectors)),
+            changed: Arc::new(RwLock::new(HashSet::new())),
             dimension: data.dimension,
         })
     }
 }
 
+/// Log of the changes made since the snapshot at `path`
+fn log_path(path: &Path) -> PathBuf {
+    path.with_extension("json.log")
+}
+
+/// Cut a line left incomplete by a crash off the end of the log
+///
+/// Otherwise the next entry would be appended to it and be lost as well.
+/// Only the writer does this: readers may see an append in progress.
+fn drop_torn_line(log: &mut fs::File) -> Result<()> {
+    let len = log.metadata()?.len();
+    if len == 0 {
+        return Ok(());
+    }
+
+    let mut last = [0u8];
+    log.seek(SeekFrom::End(-1))?;
+    log.read_exact(&mut last)?;
+    if last[0] != b'\n' {
+        let mut content = Vec::with_capacity(len as usize);
+        log.seek(SeekFrom::Start(0))?;
+        log.read_to_end(&mut content)?;
+        let complete = content.iter().rposition(|&b| b == b'\n').map_or(0, |i| i + 1);
+        log.set_len(complete as u64)?;
+    }
+    Ok(())
+}
+
 /// Serializable vector store data
-#[derive(serde::Serialize, serde::Deserialize)]
+#[derive(serde::Serialize)]
 struct VectorStoreData {
     dimension: usize,
-    vectors: HashMap<FileId, Vec<f32>>,
+    vectors: HashMap<FileId, Vec<Vec<f32>>>,
+}
+
+/// Vectors of a changed file, as appended to the log
+#[derive(serde::Serialize)]
+struct LogEntry<'a> {
+    file_id: FileId,
+    vectors: &'a [Vec<f32>],
+}
+
+/// Log entry as found on disk
+#[derive(serde::Deserialize)]
+struct StoredLogEntry {
+    file_id: FileId,
+    vectors: Vec<Vec<f32>>,
+}
+
+/// Vector store data as found on disk
+#[derive(serde::Deserialize)]
+struct StoredVectorStoreData {
+    dimension: usize,
+    vectors: HashMap<FileId, StoredVectors>,
+}
+
+/// Vectors of one file, in the current or the pre-chunking format
+#[derive(serde::Deserialize)]
+#[serde(untagged)]
+enum StoredVectors {
+    Chunks(Vec<Vec<f32>>),
+    Single(Vec<f32>),
 }
 
 /// Compute cosine similarity between two vectors
@@ -275,13 +470,94 @@ mod tests {
         assert_eq!(results[0].0, 1);
     }
 
+    #[test]
+    fn test_save_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("vectors.json");
+        let log = temp_dir.path().join("vectors.json.log");
+        let embedding = |offset: usize| normalize(&(0..64).map(|i| (i + offset) as f32).collect::<Vec<_>>());
+
+        let store = VectorStore::new(64).unwrap();
+        for file_id in 1..=10 {
+            store.upsert(file_id, &embedding(file_id as usize)).unwrap();
+        }
+        // Without a snapshot, the whole store is written
+        store.save_changes(&path).unwrap();
+        assert!(path.exists() && !log.exists());
+        let snapshot = fs::read(&path).unwrap();
+
+        // Later changes are only appended to the log
+        store.upsert(11, &embedding(11)).unwrap();
+        store.delete(2).unwrap();
+        store.save_changes(&path).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), snapshot);
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 2);
+
+        // A line cut short by a crash is ignored
+        let mut file = fs::OpenOptions::new().append(true).open(&log).unwrap();
+        file.write_all(br#"{"file_id":12,"vec"#).unwrap();
+
+        let loaded = VectorStore::load(&path).unwrap();
+        assert_eq!(loaded.len(), 10);
+        assert_eq!(loaded.search(&embedding(11), 1).unwrap()[0].0, 11);
+        assert!(loaded.search(&embedding(2), 10).unwrap().iter().all(|(id, _)| *id != 2));
+
+        // and cut off before the next changes are appended
+        loaded.upsert(12, &embedding(12)).unwrap();
+        loaded.save_changes(&path).unwrap();
+        assert_eq!(fs::read_to_string(&log).unwrap().lines().count(), 3);
+
+        let reloaded = VectorStore::load(&path).unwrap();
+        assert_eq!(reloaded.len(), 11);
+        assert_eq!(reloaded.search(&embedding(12), 1).unwrap()[0].0, 12);
+
+        // Once the log outgrows the snapshot, it is folded into a new one
+        for file_id in 1..=11 {
+            store.upsert(file_id, &embedding(file_id as usize + 1)).unwrap();
+        }
+        store.save_changes(&path).unwrap();
+        assert!(!log.exists());
+        assert_eq!(VectorStore::load(&path).unwrap().len(), 11);
+    }
+
+    #[test]
+    fn test_chunks() {
+        let store = VectorStore::new(64).unwrap();
+        let chunks: Vec<Vec<f32>> = (0..3)
+            .map(|c| normalize(&(0..64).map(|i| ((i * (c + 1)) % 7) as f32).collect::<Vec<_>>()))
+            .collect();
+        store.upsert_chunks(1, &chunks).unwrap();
+        store.upsert(2, &normalize(&vec![1.0; 64])).unwrap();
+        assert_eq!(store.len(), 2);
+
+        // The file matches through its most similar chunk
+        let results = store.search_chunks(&chunks[2], 2).unwrap();
+        assert_eq!((results[0].0, results[0].1), (1, 2));
+        assert!(results[0].2 > 0.99);
+        assert_eq!(results.len(), 2);
+
+        store.upsert_chunks(1, &[]).unwrap();
+        assert_eq!(store.len(), 1);
+    }
+
+    #[test]
+    fn test_load_single_vector_format() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("vectors.json");
+        fs::write(&path, r#"{"dimension":2,"vectors":{"7":[0.6,0.8]}}"#).unwrap();
+
+        let store = VectorStore::load(&path).unwrap();
+        let results = store.search_chunks(&vec![0.6, 0.8], 1).unwrap();
+        assert_eq!((results[0].0, results[0].1), (7, 0));
+    }
+
     #[test]
     fn test_multiple_searches() {
         let store = VectorStore::new(128).unwrap();
 
         // Insert multiple embeddings
         for i in 0..10 {
-            let embedding: Vec<f32> = normalize(&(0..128).map(|j| ((j + i * 10) as f32)).collect::<Vec<_>>());
+            let embedding: Vec<f32> = normalize(&(0..128).map(|j| (j + i * 10) as f32).collect::<Vec<_>>());
             store.upsert(i as i64, &embedding).unwrap();
         }
 
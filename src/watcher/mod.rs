@@ -1,3 +1,277 @@
 //! File system watcher for real-time indexing
+//!
+//! [`Watcher`] subscribes to file system events below a set of roots and
+//! turns them into debounced [`ChangeBatch`]es: a burst of events such as an
+//! editor's save-rename dance or a `git checkout` is coalesced into one set
+//! of affected paths, which [`Indexer::index_paths`] then reconciles with
+//! what is on disk.
+//!
+//! When events are lost, because the kernel queue or our own queue
+//! overflowed, the affected directories (or all roots, if unknown) are
+//! scheduled for a full rescan.
+//!
+//! [`Indexer::index_paths`]: crate::indexer::Indexer::index_paths
 
-// TODO: Implement watcher
\ No newline at end of file
+use crate::Result;
+use notify::event::{EventKind, ModifyKind};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher as _};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Quiet period used by `khoj watch` unless configured otherwise
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// Upper bound for the delay of a batch, in multiples of the debounce period
+///
+/// Keeps a continuous stream of events from postponing indexing forever.
+const MAX_DELAY_FACTOR: u32 = 10;
+
+/// Number of raw events buffered between notify and the debouncer
+const EVENT_QUEUE_CAPACITY: usize = 4096;
+
+/// Paths affected by a burst of file system events
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChangeBatch {
+    /// Created, modified, renamed or removed paths, including directories
+    /// to rescan
+    pub paths: BTreeSet<PathBuf>,
+    /// Directories scheduled for a rescan because events were lost
+    pub rescans: BTreeSet<PathBuf>,
+}
+
+impl ChangeBatch {
+    /// Check if the batch contains no changes
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+
+    /// Record a path reported by an event
+    fn insert(&mut self, path: PathBuf) {
+        self.paths.insert(path);
+    }
+
+    /// Schedule the directory containing `path` for a rescan
+    fn rescan(&mut self, path: &Path) {
+        let dir = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(path)
+        };
+        self.rescans.insert(dir.to_path_buf());
+        self.paths.insert(dir.to_path_buf());
+    }
+
+    /// Drop paths that are covered by a directory in the same batch
+    fn coalesce(&mut self) {
+        let dirs: Vec<PathBuf> = self.paths.iter().filter(|p| p.is_dir()).cloned().collect();
+        self.paths
+            .retain(|path| !dirs.iter().any(|dir| path != dir && path.starts_with(dir)));
+        self.rescans
+            .retain(|path| !dirs.iter().any(|dir| path != dir && path.starts_with(dir)));
+    }
+}
+
+/// Debounced file system watcher over one or more roots
+pub struct Watcher {
+    roots: Vec<PathBuf>,
+    debounce: Duration,
+    events: mpsc::Receiver<notify::Result<Event>>,
+    overflowed: Arc<AtomicBool>,
+    _inner: RecommendedWatcher,
+}
+
+impl Watcher {
+    /// Start watching roots recursively
+    ///
+    /// # Arguments
+    /// * `roots` - Directories to watch
+    /// * `debounce` - Quiet period that ends a batch of events
+    pub fn new(roots: &[PathBuf], debounce: Duration) -> Result<Self> {
+        let (tx, events) = mpsc::channel(EVENT_QUEUE_CAPACITY);
+        let overflowed = Arc::new(AtomicBool::new(false));
+
+        let handler_overflowed = overflowed.clone();
+        let mut inner = notify::recommended_watcher(move |event| {
+            if let Err(mpsc::error::TrySendError::Full(_)) = tx.try_send(event) {
+                handler_overflowed.store(true, Ordering::Relaxed);
+            }
+        })?;
+
+        let mut canonical_roots = Vec::with_capacity(roots.len());
+        for root in roots {
+            // Canonical paths so that event paths match the indexed ones
+            let root = root
+                .canonicalize()
+                .map_err(|_| crate::Error::FileNotFound(root.display().to_string()))?;
+            inner.watch(&root, RecursiveMode::Recursive)?;
+            canonical_roots.push(root);
+        }
+
+        Ok(Self {
+            roots: canonical_roots,
+            debounce,
+            events,
+            overflowed,
+            _inner: inner,
+        })
+    }
+
+    /// Canonical roots being watched
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Wait for the next batch of changes
+    ///
+    /// Returns once no new event arrived for the debounce period, or the
+    /// batch has been collecting for ten debounce periods.
+    ///
+    /// # Returns
+    /// The coalesced batch, or `None` if the watcher stopped
+    pub async fn next_batch(&mut self) -> Option<ChangeBatch> {
+        loop {
+            let mut batch = ChangeBatch::default();
+
+            let first = self.events.recv().await?;
+            collect_event(&self.roots, first, &mut batch);
+
+            let deadline = Instant::now() + self.debounce * MAX_DELAY_FACTOR;
+            loop {
+                let quiet_until = (Instant::now() + self.debounce).min(deadline);
+                match tokio::time::timeout_at(quiet_until, self.events.recv()).await {
+                    Ok(Some(event)) => collect_event(&self.roots, event, &mut batch),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+
+            if self.overflowed.swap(false, Ordering::Relaxed) {
+                tracing::warn!("File event queue overflowed, rescanning all roots");
+                for root in &self.roots {
+                    batch.rescan(root);
+                }
+            }
+
+            batch.coalesce();
+            if !batch.is_empty() {
+                return Some(batch);
+            }
+        }
+    }
+}
+
+/// Add the paths affected by one notify event to a batch
+fn collect_event(roots: &[PathBuf], event: notify::Result<Event>, batch: &mut ChangeBatch) {
+    let event = match event {
+        Ok(event) => event,
+        Err(e) => {
+            tracing::warn!("File watcher error, rescanning: {}", e);
+            rescan_paths(roots, &e.paths, batch);
+            return;
+        }
+    };
+
+    // The backend dropped events, e.g. an inotify queue overflow
+    if event.need_rescan() {
+        tracing::warn!("File watcher lost events, rescanning");
+        rescan_paths(roots, &event.paths, batch);
+        return;
+    }
+
+    match event.kind {
+        // Reads and permission/timestamp changes don't affect content
+        EventKind::Access(_) | EventKind::Modify(ModifyKind::Metadata(_)) => {}
+        _ => {
+            for path in event.paths {
+                batch.insert(path);
+            }
+        }
+    }
+}
+
+/// Rescan the directories of `paths`, or every root if none are known
+fn rescan_paths(roots: &[PathBuf], paths: &[PathBuf], batch: &mut ChangeBatch) {
+    if paths.is_empty() {
+        for root in roots {
+            batch.rescan(root);
+        }
+    } else {
+        for path in paths {
+            batch.rescan(path);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use notify::event::{AccessKind, CreateKind, DataChange, Flag, MetadataKind};
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_collect_coalesces_events() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().to_path_buf();
+        let roots = vec![root.clone()];
+        fs::create_dir(root.join("dir")).unwrap();
+
+        let mut batch = ChangeBatch::default();
+        let file = root.join("notes.txt");
+        for kind in [
+            EventKind::Create(CreateKind::File),
+            EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+            EventKind::Modify(ModifyKind::Data(DataChange::Content)),
+        ] {
+            collect_event(&roots, Ok(Event::new(kind).add_path(file.clone())), &mut batch);
+        }
+        let nested = Event::new(EventKind::Create(CreateKind::File)).add_path(root.join("dir/a.txt"));
+        collect_event(&roots, Ok(nested), &mut batch);
+        let dir = Event::new(EventKind::Create(CreateKind::Folder)).add_path(root.join("dir"));
+        collect_event(&roots, Ok(dir), &mut batch);
+
+        // Events that don't change content are ignored
+        let access = Event::new(EventKind::Access(AccessKind::Any)).add_path(root.join("read.txt"));
+        collect_event(&roots, Ok(access), &mut batch);
+        let chmod = Event::new(EventKind::Modify(ModifyKind::Metadata(MetadataKind::Permissions)))
+            .add_path(root.join("chmod.txt"));
+        collect_event(&roots, Ok(chmod), &mut batch);
+
+        batch.coalesce();
+        assert_eq!(batch.paths, BTreeSet::from([file, root.join("dir")]));
+        assert!(batch.rescans.is_empty());
+    }
+
+    #[test]
+    fn test_overflow_rescans_roots() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path().to_path_buf();
+        let roots = vec![root.clone()];
+        let mut batch = ChangeBatch::default();
+
+        collect_event(&roots, Ok(Event::new(EventKind::Other).set_flag(Flag::Rescan)), &mut batch);
+
+        assert_eq!(batch.rescans, BTreeSet::from([root.clone()]));
+        assert!(batch.paths.contains(&root));
+    }
+
+    #[tokio::test]
+    async fn test_watcher_reports_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let mut watcher = Watcher::new(&[temp_dir.path().to_path_buf()], Duration::from_millis(100)).unwrap();
+        let root = watcher.roots()[0].clone();
+
+        fs::write(root.join("a.txt"), "hello").unwrap();
+        fs::write(root.join("a.txt"), "hello again").unwrap();
+
+        let batch = tokio::time::timeout(Duration::from_secs(10), watcher.next_batch())
+            .await
+            .expect("no events received")
+            .unwrap();
+        assert!(batch.paths.contains(&root.join("a.txt")));
+    }
+}
@@ -8,19 +8,26 @@ use axum::{
     Router,
 };
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::sync::Arc;
 use tower_http::cors::CorsLayer;
 
 use crate::{
+    config::ArchiveConfig,
     embedding::{EmbeddingModel, image::ClipTextEmbedding},
-    search::HybridSearch,
-    storage::{Database, TantivyIndex, VectorStore},
+    extractors::{match_offset, KeyTerm, Page},
+    indexer::archive,
+    search::{boost_linked, HybridSearch},
+    storage::{tantivy_index::SortOrder, Database, IndexPaths, TantivyIndex, VectorStore},
 };
 
 #[derive(Clone)]
 pub struct AppState {
-    pub index_dir: PathBuf,
+    /// Stores of the index to search
+    pub paths: IndexPaths,
+    /// Limits for reading the members of archives the index was built with
+    pub archives: ArchiveConfig,
 }
 
 #[derive(Deserialize)]
@@ -32,6 +39,9 @@ pub struct SearchParams {
     semantic: bool,
     #[serde(default = "default_keyword_weight")]
     keyword_weight: f32,
+    /// `relevance`, `taken` (newest photos first) or `taken-asc`
+    #[serde(default = "default_sort")]
+    sort: String,
 }
 
 fn default_limit() -> usize {
@@ -42,6 +52,10 @@ fn default_keyword_weight() -> f32 {
     0.7
 }
 
+fn default_sort() -> String {
+    "relevance".to_string()
+}
+
 #[derive(Serialize)]
 pub struct SearchResponse {
     pub query: String,
@@ -57,7 +71,15 @@ pub struct SearchResult {
     pub path: String,
     pub score: f32,
     pub snippet: Option<String>,
+    /// Passage that matched a semantic search
+    pub passage: Option<String>,
+    /// Page of the match, for documents with pages such as PDFs
+    pub page: Option<usize>,
+    /// What pages are called in the file, `Page` or `Cell` for notebooks
+    pub page_label: &'static str,
     pub file_type: String,
+    /// Date a photo was taken, as a Unix timestamp
+    pub taken_at: Option<i64>,
 }
 
 #[derive(Serialize)]
@@ -74,9 +96,15 @@ pub struct ErrorResponse {
 }
 
 /// Start the web server
-pub async fn serve(index_dir: PathBuf, port: u16) -> Result<(), Box<dyn std::error::Error>> {
+///
+/// # Arguments
+/// * `index_dir` - Directory of the index to search
+/// * `port` - Port to listen on
+/// * `archives` - Limits for reading the members of archives
+pub async fn serve(index_dir: PathBuf, port: u16, archives: ArchiveConfig) -> Result<(), Box<dyn std::error::Error>> {
     let state = AppState {
-        index_dir: index_dir.clone(),
+        paths: IndexPaths::new(&index_dir),
+        archives,
     };
 
     let app = Router::new()
@@ -113,8 +141,7 @@ async fn handle_search(
     let start = std::time::Instant::now();
 
     // Check if index exists
-    let tantivy_path = state.index_dir.join("tantivy");
-    if !tantivy_path.exists() {
+    if !state.paths.tantivy.exists() {
         return (
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
@@ -126,10 +153,7 @@ async fn handle_search(
     }
 
     // Initialize search components
-    let db_path = state.index_dir.join("db.sqlite");
-    let vector_path = state.index_dir.join("vectors.json");
-
-    let db = match Database::new(&db_path).await {
+    let db = match Database::new(&state.paths.db).await {
         Ok(db) => db,
         Err(e) => {
             return (
@@ -143,7 +167,18 @@ async fn handle_search(
         }
     };
 
-    let tantivy_index = match TantivyIndex::new(&tantivy_path) {
+    let sort: SortOrder = match params.sort.parse() {
+        Ok(sort) => sort,
+        Err(e) => {
+            return (
+                StatusCode::BAD_REQUEST,
+                Json(ErrorResponse { error: e.to_string() }).into_response(),
+            )
+                .into_response()
+        }
+    };
+
+    let tantivy_index = match TantivyIndex::open_read_only(&state.paths.tantivy) {
         Ok(idx) => idx,
         Err(e) => {
             return (
@@ -157,8 +192,8 @@ async fn handle_search(
         }
     };
 
-    let vector_store = if params.semantic && vector_path.exists() {
-        match VectorStore::load(&vector_path) {
+    let vector_store = if params.semantic && state.paths.vectors.exists() {
+        match VectorStore::load(&state.paths.vectors) {
             Ok(vs) => vs,
             Err(_) => VectorStore::new(384).unwrap(),
         }
@@ -169,7 +204,7 @@ async fn handle_search(
     let search_engine = HybridSearch::new(tantivy_index, vector_store);
 
     // Perform search
-    let results = if params.semantic {
+    let mut results = if params.semantic {
         // Load embedding model
         let model_path = PathBuf::from("models/model.onnx");
         let tokenizer_path = PathBuf::from("models/tokenizer.json");
@@ -220,7 +255,10 @@ async fn handle_search(
             params.limit,
             params.keyword_weight,
         ) {
-            Ok(r) => r,
+            Ok(mut r) => {
+                sort.sort(&mut r);
+                r
+            }
             Err(e) => {
                 return (
                     StatusCode::INTERNAL_SERVER_ERROR,
@@ -233,7 +271,7 @@ async fn handle_search(
             }
         }
     } else {
-        match search_engine.keyword_search(&params.q, params.limit) {
+        match search_engine.keyword_search_sorted(&params.q, params.limit, sort) {
             Ok(r) => r,
             Err(e) => {
                 return (
@@ -248,13 +286,21 @@ async fn handle_search(
         }
     };
 
+    // Notes that others link to rank higher
+    if sort == SortOrder::Relevance {
+        let mut backlinks = HashMap::new();
+        for result in &results {
+            backlinks.insert(result.file_id, db.count_backlinks(&result.path).await.unwrap_or(0));
+        }
+        boost_linked(&mut results, &backlinks);
+    }
+
     // Also search images if semantic search is enabled
-    let image_vector_path = state.index_dir.join("image_vectors.json");
     let mut image_results = Vec::new();
 
-    if params.semantic && image_vector_path.exists() {
+    if params.semantic && state.paths.image_vectors.exists() {
         // Load image vector store
-        let image_vector_store = match VectorStore::load(&image_vector_path) {
+        let image_vector_store = match VectorStore::load(&state.paths.image_vectors) {
             Ok(vs) => vs,
             Err(_) => VectorStore::new(512).unwrap(),
         };
@@ -275,6 +321,7 @@ async fn handle_search(
     }
 
     // Get snippets and file info for results
+    let key_terms = KeyTerm::parse_query(&params.q);
     let mut search_results = Vec::new();
     for result in results {
         // Get file metadata to determine type
@@ -284,32 +331,75 @@ async fn handle_search(
             .map(|m| m.file_type.as_str().to_string())
             .unwrap_or_else(|| "unknown".to_string());
 
-        let snippet = if let Ok(Some(content)) = db.get_content(result.file_id).await {
-            crate::extractors::text::extract_snippet(&content.text, &params.q, 100)
+        let content = db.get_content(result.file_id).await.ok().flatten();
+        // Values a `key:` query found stand in for the snippet
+        let key_values: Vec<String> = if key_terms.is_empty() {
+            Vec::new()
+        } else {
+            db.get_key_values(result.file_id)
+                .await
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|entry| key_terms.iter().any(|term| term.matches(entry)))
+                .map(|entry| format!("{} = {}", entry.path, entry.value))
+                .collect()
+        };
+        let snippet = if key_values.is_empty() {
+            content
+                .as_ref()
+                .and_then(|content| crate::extractors::text::extract_snippet(&content.text, &params.q, 100))
         } else {
-            None
+            Some(key_values.join("; "))
         };
 
+        let chunk = match result.chunk_index {
+            Some(chunk_index) => db.get_text_chunk(result.file_id, chunk_index).await.ok().flatten(),
+            None => None,
+        };
+        let passage = content
+            .as_ref()
+            .zip(chunk.as_ref())
+            .and_then(|(content, chunk)| content.text.get(chunk.start..chunk.end))
+            .map(str::to_string);
+
+        // The page of the keyword match, or else of the passage
+        let pages = db.get_pages(result.file_id).await.unwrap_or_default();
+        let page = content
+            .as_ref()
+            .and_then(|content| match_offset(&content.text, &params.q))
+            .or(chunk.map(|chunk| chunk.start))
+            .and_then(|offset| Page::number_at(&pages, offset));
+
+        let page_label = Page::label(&result.path);
         search_results.push(SearchResult {
             file_id: result.file_id,
             filename: result.filename,
             path: result.path,
             score: result.score,
             snippet,
+            passage,
+            page,
+            page_label,
             file_type,
+            taken_at: result.taken_at,
         });
     }
 
     // Add image results
     for (file_id, similarity) in image_results {
         if let Ok(Some(metadata)) = db.get_file(file_id).await {
+            let properties = db.get_image_metadata(file_id).await.ok().flatten();
             search_results.push(SearchResult {
                 file_id,
                 filename: metadata.filename,
                 path: metadata.path,
                 score: similarity,
                 snippet: None,
+                passage: None,
+                page: None,
+                page_label: "Page",
                 file_type: "image".to_string(),
+                taken_at: properties.and_then(|p| p.taken_at),
             });
         }
     }
@@ -330,6 +420,12 @@ async fn handle_search(
     // Sort by score descending (highest similarity/relevance first)
     documents.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
     images.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(std::cmp::Ordering::Equal));
+    if sort != SortOrder::Relevance {
+        // Then by date taken, keeping the order of results taken at the same time
+        for results in [&mut documents, &mut images] {
+            results.sort_by_key(|r| std::cmp::Reverse(sort.key(r.taken_at)));
+        }
+    }
 
     let took_ms = start.elapsed().as_millis() as u64;
 
@@ -348,11 +444,7 @@ async fn handle_search(
 
 /// Handle stats requests
 async fn handle_stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let db_path = state.index_dir.join("db.sqlite");
-    let tantivy_path = state.index_dir.join("tantivy");
-    let vector_path = state.index_dir.join("vectors.json");
-
-    if !db_path.exists() {
+    if !state.paths.db.exists() {
         return (
             StatusCode::NOT_FOUND,
             Json(ErrorResponse {
@@ -363,7 +455,7 @@ async fn handle_stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
             .into_response();
     }
 
-    let db = match Database::new(&db_path).await {
+    let db = match Database::new(&state.paths.db).await {
         Ok(db) => db,
         Err(e) => {
             return (
@@ -395,9 +487,9 @@ async fn handle_stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
         StatusCode::OK,
         Json(StatsResponse {
             total_files: stats.total_files,
-            index_location: state.index_dir.display().to_string(),
-            has_keyword_index: tantivy_path.exists(),
-            has_semantic_index: vector_path.exists(),
+            index_location: state.paths.root.display().to_string(),
+            has_keyword_index: state.paths.tantivy.exists(),
+            has_semantic_index: state.paths.vectors.exists(),
         })
         .into_response(),
     )
@@ -405,13 +497,13 @@ async fn handle_stats(State(state): State<Arc<AppState>>) -> impl IntoResponse {
 }
 
 /// Serve a file by ID
+///
+/// Members of archives are read out of their archive.
 async fn handle_file(
     State(state): State<Arc<AppState>>,
     AxumPath(file_id): AxumPath<i64>,
 ) -> impl IntoResponse {
-    let db_path = state.index_dir.join("db.sqlite");
-
-    let db = match Database::new(&db_path).await {
+    let db = match Database::new(&state.paths.db).await {
         Ok(db) => db,
         Err(e) => {
             return (
@@ -450,9 +542,18 @@ async fn handle_file(
         }
     };
 
-    // Read the file
-    let file_path = std::path::Path::new(&file_metadata.path);
-    let file_bytes = match tokio::fs::read(file_path).await {
+    // Read the file, or the member out of its archive
+    let file_path = file_metadata.path.clone();
+    let read = if archive::is_member_path(&file_path) {
+        let limits = state.archives.clone();
+        tokio::task::spawn_blocking(move || archive::read_member(&file_path, &limits))
+            .await
+            .map_err(|e| e.to_string())
+            .and_then(|read| read.map_err(|e| e.to_string()))
+    } else {
+        tokio::fs::read(&file_path).await.map_err(|e| e.to_string())
+    };
+    let file_bytes = match read {
         Ok(bytes) => bytes,
         Err(e) => {
             return (